@@ -0,0 +1,32 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use vssh::parser;
+use vssh::shell::Shell;
+
+const RUNS: u32 = 200;
+const BALLAST: usize = 256 << 20;
+
+fn measure(shell: &mut Shell, spawn: bool) -> Duration {
+    let list = parser::parse("/bin/true", 1).expect("parse");
+    shell.options.set("spawn", spawn);
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        shell.run_list(&list).expect("run");
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let mut shell = Shell::new();
+    for (label, size) in [("small", 0), ("256 MiB resident", BALLAST)] {
+        let ballast = vec![1u8; size];
+        black_box(&ballast);
+        let fork = measure(&mut shell, false);
+        let spawn = measure(&mut shell, true);
+        println!(
+            "{:<18} fork+exec {:>9.1?}  posix_spawn {:>9.1?}",
+            label, fork, spawn
+        );
+    }
+}
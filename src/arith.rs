@@ -0,0 +1,422 @@
+use crate::error::{Result, ShellError};
+use crate::exec;
+use crate::shell::Shell;
+use crate::word::is_name;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Num(i64),
+    Var(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Assign(String, &'static str, Box<Expr>),
+    Step {
+        name: String,
+        delta: i64,
+        prefix: bool,
+    },
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+const OPS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=",
+    "*=", "/=", "%=", "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "=", "!", "~", "&", "^",
+    "|", "?", ":", ",", "(", ")",
+];
+
+const MAX_DEPTH: usize = 64;
+
+fn error(src: &str, message: &str) -> ShellError {
+    ShellError::Expansion(format!("{}: {}", src.trim(), message))
+}
+
+fn number(text: &str) -> Option<i64> {
+    if let Some((base, digits)) = text.split_once('#') {
+        let base: u32 = base.parse().ok()?;
+        if !(2..=36).contains(&base) {
+            return None;
+        }
+        return i64::from_str_radix(digits, base).ok();
+    }
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return i64::from_str_radix(hex, 16).ok();
+    }
+    if text.len() > 1 && text.starts_with('0') {
+        return i64::from_str_radix(&text[1..], 8).ok();
+    }
+    text.parse().ok()
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = src;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        if c.is_ascii_alphanumeric() || c == '_' {
+            let digit = c.is_ascii_digit();
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_' && !(digit && c == '#'))
+                .unwrap_or(rest.len());
+            let text = &rest[..end];
+            if digit {
+                let Some(n) = number(text) else {
+                    return Err(error(src, &format!("invalid number `{}'", text)));
+                };
+                tokens.push(Token::Num(n));
+            } else {
+                tokens.push(Token::Name(text.to_string()));
+            }
+            rest = &rest[end..];
+            continue;
+        }
+        let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) else {
+            return Err(error(
+                src,
+                &format!("syntax error: invalid arithmetic operator `{}'", c),
+            ));
+        };
+        tokens.push(Token::Op(op));
+        rest = &rest[op.len()..];
+    }
+    Ok(tokens)
+}
+
+fn binary_precedence(op: &str) -> Option<u8> {
+    let precedence = match op {
+        "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" => 6,
+        "<" | ">" | "<=" | ">=" => 7,
+        "<<" | ">>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        "**" => 11,
+        _ => return None,
+    };
+    Some(precedence)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<()> {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            return Ok(());
+        }
+        Err(self.unexpected())
+    }
+
+    fn unexpected(&self) -> ShellError {
+        let token = match self.peek() {
+            Some(Token::Num(n)) => n.to_string(),
+            Some(Token::Name(name)) => name.clone(),
+            Some(Token::Op(op)) => op.to_string(),
+            None => return error(self.src, "syntax error: operand expected"),
+        };
+        error(
+            self.src,
+            &format!("syntax error in expression (error token is \"{}\")", token),
+        )
+    }
+
+    fn comma(&mut self) -> Result<Expr> {
+        let mut expr = self.assignment()?;
+        while self.peek_op() == Some(",") {
+            self.pos += 1;
+            let right = self.assignment()?;
+            expr = Expr::Binary(",", Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn assignment(&mut self) -> Result<Expr> {
+        if let Some(Token::Name(name)) = self.peek()
+            && let Some(Token::Op(op)) = self.tokens.get(self.pos + 1)
+            && matches!(
+                *op,
+                "=" | "+=" | "-=" | "*=" | "/=" | "%=" | "<<=" | ">>=" | "&=" | "^=" | "|="
+            )
+        {
+            let name = name.clone();
+            let op = *op;
+            self.pos += 2;
+            let value = self.assignment()?;
+            return Ok(Expr::Assign(name, op, Box::new(value)));
+        }
+        self.conditional()
+    }
+
+    fn conditional(&mut self) -> Result<Expr> {
+        let condition = self.binary(1)?;
+        if self.peek_op() != Some("?") {
+            return Ok(condition);
+        }
+        self.pos += 1;
+        let then = self.assignment()?;
+        self.expect(":")?;
+        let otherwise = self.assignment()?;
+        Ok(Expr::Cond(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn binary(&mut self, min: u8) -> Result<Expr> {
+        let mut left = self.unary()?;
+        while let Some(op) = self.peek_op() {
+            let Some(precedence) = binary_precedence(op) else {
+                break;
+            };
+            if precedence < min {
+                break;
+            }
+            self.pos += 1;
+            let next = if op == "**" {
+                precedence
+            } else {
+                precedence + 1
+            };
+            let right = self.binary(next)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if exec::stack_exhausted() {
+            return Err(error(self.src, "expression nested too deeply"));
+        }
+        match self.peek_op() {
+            Some(op @ ("++" | "--")) => {
+                self.pos += 1;
+                let Some(Token::Name(name)) = self.peek().cloned() else {
+                    return Err(self.unexpected());
+                };
+                self.pos += 1;
+                Ok(Expr::Step {
+                    name,
+                    delta: if op == "++" { 1 } else { -1 },
+                    prefix: true,
+                })
+            }
+            Some(op @ ("+" | "-" | "!" | "~")) => {
+                self.pos += 1;
+                Ok(Expr::Unary(op, Box::new(self.unary()?)))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr> {
+        let expr = self.primary()?;
+        if let Expr::Var(name) = &expr
+            && let Some(op @ ("++" | "--")) = self.peek_op()
+        {
+            self.pos += 1;
+            return Ok(Expr::Step {
+                name: name.clone(),
+                delta: if op == "++" { 1 } else { -1 },
+                prefix: false,
+            });
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.peek().cloned() {
+            Some(Token::Num(n)) => {
+                self.pos += 1;
+                Ok(Expr::Num(n))
+            }
+            Some(Token::Name(name)) => {
+                self.pos += 1;
+                Ok(Expr::Var(name))
+            }
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let expr = self.comma()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+}
+
+fn parse(src: &str) -> Result<Option<Expr>> {
+    let tokens = tokenize(src)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    let mut parser = Parser {
+        src,
+        tokens,
+        pos: 0,
+    };
+    let expr = parser.comma()?;
+    if parser.peek().is_some() {
+        return Err(parser.unexpected());
+    }
+    Ok(Some(expr))
+}
+
+fn apply(src: &str, op: &str, a: i64, b: i64) -> Result<i64> {
+    let value = match op {
+        "+" => a.wrapping_add(b),
+        "-" => a.wrapping_sub(b),
+        "*" => a.wrapping_mul(b),
+        "/" | "%" if b == 0 => return Err(error(src, "division by 0")),
+        "/" => a.wrapping_div(b),
+        "%" => a.wrapping_rem(b),
+        "**" if b < 0 => return Err(error(src, "exponent less than 0")),
+        "**" => a.wrapping_pow(b.min(u32::MAX as i64) as u32),
+        "<<" => a.wrapping_shl(b as u32),
+        ">>" => a.wrapping_shr(b as u32),
+        "<" => (a < b) as i64,
+        ">" => (a > b) as i64,
+        "<=" => (a <= b) as i64,
+        ">=" => (a >= b) as i64,
+        "==" => (a == b) as i64,
+        "!=" => (a != b) as i64,
+        "&" => a & b,
+        "^" => a ^ b,
+        "|" => a | b,
+        "," => b,
+        _ => return Err(error(src, &format!("unknown operator `{}'", op))),
+    };
+    Ok(value)
+}
+
+impl Shell {
+    pub fn eval_arith(&mut self, src: &str) -> Result<i64> {
+        self.eval_arith_depth(src, 0)
+    }
+
+    fn eval_arith_depth(&mut self, src: &str, depth: usize) -> Result<i64> {
+        if depth > MAX_DEPTH {
+            return Err(error(src, "expression recursion level exceeded"));
+        }
+        match parse(src)? {
+            Some(expr) => self.eval(src, &expr, depth),
+            None => Ok(0),
+        }
+    }
+
+    fn arith_var(&mut self, name: &str, depth: usize) -> Result<i64> {
+        let value = match self.dynamic(name) {
+            Some(value) => value,
+            None => self.vars.get(name).unwrap_or("").to_string(),
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(0);
+        }
+        if let Some(n) = number(value) {
+            return Ok(n);
+        }
+        self.eval_arith_depth(value, depth + 1)
+    }
+
+    fn arith_set(&mut self, name: &str, value: i64) -> Result<()> {
+        self.restrict_variable(name)?;
+        self.writable(name)?;
+        let value = value.to_string();
+        self.assign_dynamic(name, &value);
+        self.vars.set(name, value);
+        Ok(())
+    }
+
+    fn eval(&mut self, src: &str, expr: &Expr, depth: usize) -> Result<i64> {
+        let value = match expr {
+            Expr::Num(n) => *n,
+            Expr::Var(name) => self.arith_var(name, depth)?,
+            Expr::Unary(op, inner) => {
+                let v = self.eval(src, inner, depth)?;
+                match *op {
+                    "-" => v.wrapping_neg(),
+                    "!" => (v == 0) as i64,
+                    "~" => !v,
+                    _ => v,
+                }
+            }
+            Expr::Binary("&&", a, b) => {
+                (self.eval(src, a, depth)? != 0 && self.eval(src, b, depth)? != 0) as i64
+            }
+            Expr::Binary("||", a, b) => {
+                (self.eval(src, a, depth)? != 0 || self.eval(src, b, depth)? != 0) as i64
+            }
+            Expr::Binary(op, a, b) => {
+                let a = self.eval(src, a, depth)?;
+                let b = self.eval(src, b, depth)?;
+                apply(src, op, a, b)?
+            }
+            Expr::Assign(name, op, value) => {
+                if !is_name(name) {
+                    return Err(error(src, "attempted assignment to non-variable"));
+                }
+                let rhs = self.eval(src, value, depth)?;
+                let value = match op.strip_suffix('=').filter(|op| !op.is_empty()) {
+                    Some(op) => {
+                        let current = self.arith_var(name, depth)?;
+                        apply(src, op, current, rhs)?
+                    }
+                    None => rhs,
+                };
+                self.arith_set(name, value)?;
+                value
+            }
+            Expr::Step {
+                name,
+                delta,
+                prefix,
+            } => {
+                let current = self.arith_var(name, depth)?;
+                let next = current.wrapping_add(*delta);
+                self.arith_set(name, next)?;
+                if *prefix {
+                    next
+                } else {
+                    current
+                }
+            }
+            Expr::Cond(condition, then, otherwise) => {
+                if self.eval(src, condition, depth)? != 0 {
+                    self.eval(src, then, depth)?
+                } else {
+                    self.eval(src, otherwise, depth)?
+                }
+            }
+        };
+        Ok(value)
+    }
+}
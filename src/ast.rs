@@ -0,0 +1,161 @@
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WordPart {
+    Literal(String),
+    Quoted(String),
+    DoubleQuoted(Vec<WordPart>),
+    Tilde(String),
+    Param(String),
+    CommandSubst(String),
+    ProcessSubst(bool, String),
+    Arith(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedirectKind {
+    Input,
+    Output,
+    Append,
+    DupInput,
+    DupOutput,
+    OutputAll,
+}
+
+impl RedirectKind {
+    pub fn default_fd(&self) -> i32 {
+        match self {
+            RedirectKind::Input | RedirectKind::DupInput => 0,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    pub fd: Option<i32>,
+    pub kind: RedirectKind,
+    pub target: Word,
+    pub source: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssignValue {
+    Scalar(Word),
+    Array(Vec<Word>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assign {
+    pub name: String,
+    pub index: Option<Word>,
+    pub value: AssignValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimpleCommand {
+    pub assigns: Vec<Assign>,
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+    pub line: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForLoop {
+    pub var: String,
+    pub words: Option<Vec<Word>>,
+    pub body: List,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhileLoop {
+    pub until: bool,
+    pub condition: List,
+    pub body: List,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseItem {
+    pub patterns: Vec<Word>,
+    pub body: List,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseCommand {
+    pub word: Word,
+    pub items: Vec<CaseItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfCommand {
+    pub branches: Vec<(List, List)>,
+    pub otherwise: Option<List>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Compound {
+    If(IfCommand),
+    For(ForLoop),
+    While(WhileLoop),
+    Select(ForLoop),
+    Case(CaseCommand),
+    Group(List),
+    Subshell(List),
+    Arith(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDef {
+    pub name: String,
+    pub body: Rc<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coproc {
+    pub name: String,
+    pub body: Box<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    Compound(Compound, Vec<Redirect>),
+    Function(FunctionDef),
+    Coproc(Coproc),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pipeline {
+    pub negated: bool,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    pub text: String,
+    pub and_or: AndOr,
+    pub background: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct List {
+    pub items: Vec<ListItem>,
+}
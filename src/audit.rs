@@ -0,0 +1,86 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::bytes;
+use crate::error::{describe, program, report};
+use crate::history;
+use crate::shell::Shell;
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\x{:02x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+impl Shell {
+    fn audit_path(&self) -> Option<String> {
+        if self.audit_disabled {
+            return None;
+        }
+        self.audit_log
+            .clone()
+            .or_else(|| self.vars.get("VSSH_AUDIT_LOG").map(str::to_string))
+            .filter(|path| !path.is_empty())
+    }
+
+    /// Notes a top-level command about to run, with the directory it
+    /// runs in, for `audit` to log.
+    pub fn begin_audit(&mut self, text: &str) {
+        if self.audit_quiet || self.audit_path().is_none() {
+            return;
+        }
+        let cwd = env::current_dir()
+            .map(|p| bytes::from_os(p.as_os_str()))
+            .unwrap_or_default();
+        self.audit_pending = Some((text.to_string(), cwd));
+    }
+
+    /// Logs the command `begin_audit` noted. Subshells inherit the note
+    /// but leave the logging to the shell that ran the command.
+    pub fn audit(&mut self, status: i32) {
+        if self.subshell {
+            return;
+        }
+        let Some((text, cwd)) = self.audit_pending.take() else {
+            return;
+        };
+        let Some(path) = self.audit_path() else {
+            return;
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        let line = format!(
+            "{} pid={} cwd={} status={} cmd={}\n",
+            history::format_time("%Y-%m-%dT%H:%M:%S%z", now),
+            std::process::id(),
+            escape(&cwd),
+            status,
+            escape(&text)
+        );
+        let result = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(bytes::to_os(&path))
+            .and_then(|mut file| file.write_all(&bytes::encode(&line)));
+        if let Err(e) = result {
+            report(format_args!(
+                "{}: audit log: {}: {}; logging disabled",
+                program(),
+                path,
+                describe(&e)
+            ));
+            self.audit_disabled = true;
+        }
+    }
+}
@@ -1,184 +1,495 @@
 use std::env;
-use std::ffi::CString;
-use std::fs::File;
-use std::io::{self, Write};
-use std::os::unix::io::AsRawFd;
-use std::path::Path;
+use std::fs;
+use std::io::{self, Read, Write};
 
 use anyhow::Result;
-use nix::sys::wait::{waitpid, WaitStatus};
-use nix::unistd::{chdir, execvp, fork, ForkResult};
+use nix::errno::Errno;
+use nix::unistd;
 
-fn main() -> Result<()> {
-    loop {
-        let cwd = env::current_dir()?;
-        print!("{}$ ", cwd.display());
-        io::stdout().flush()?;
+use vssh::builtins;
+use vssh::bytes;
+use vssh::color::style;
+use vssh::error::{self, describe, program, ShellError};
+use vssh::options;
+use vssh::parser::{self, ParseError};
+use vssh::shell::Shell;
+use vssh::signals;
+use vssh::startup::Profile;
+use vssh::status::Status;
+use vssh::terminal;
+use vssh::width;
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let input = input.trim();
+fn report(shell: &mut Shell, e: ShellError) {
+    error::report(&e);
+    shell.last_status = e.status();
+    shell.record_prompt_status();
+    if e.aborts() && !shell.interactive {
+        shell.run_exit_trap();
+        io::stdout().flush().ok();
+        Status::new(shell.last_status as i64).exit(false);
+    }
+}
 
-        if input.is_empty() {
-            continue;
-        }
+fn report_syntax(shell: &mut Shell, e: ParseError) {
+    if shell.interactive {
+        error::report(format_args!("{}: {}", program(), e));
+        shell.last_status = 2;
+        shell.record_prompt_status();
+    } else {
+        report(shell, e.into());
+    }
+}
 
-        if input == "exit" {
-            break;
+fn wait_for_input(seconds: u32) -> nix::Result<bool> {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = seconds.saturating_mul(1000).min(i32::MAX as u32) as i32;
+    loop {
+        match unsafe { libc::poll(&mut fd, 1, timeout) } {
+            -1 if Errno::last() == Errno::EINTR && !signals::interrupted() => continue,
+            -1 => return Err(Errno::last()),
+            ready => return Ok(ready > 0),
         }
+    }
+}
 
-        if input.starts_with("cd ") {
-            let dir = input[3..].trim();
-            if let Err(e) = chdir(Path::new(dir)) {
-                eprintln!("cd error: {}", e);
+fn check_syntax(name: &str, src: &str) -> ! {
+    let errors = parser::check(src);
+    for e in &errors {
+        let line = match e {
+            ParseError::Incomplete { line, .. } | ParseError::Syntax { line, .. } => line,
+        };
+        error::report(format_args!(
+            "{}: {}: line {}: {}",
+            program(),
+            name,
+            line,
+            e
+        ));
+    }
+    std::process::exit(if errors.is_empty() { 0 } else { 2 });
+}
+
+struct Invocation {
+    interactive: bool,
+    login: bool,
+    restricted: bool,
+    check: bool,
+    options: Vec<&'static str>,
+    command: Option<String>,
+    audit_log: Option<String>,
+    trace_json: Option<String>,
+    profile_startup: bool,
+    norc: bool,
+    noprofile: bool,
+    operands: Vec<String>,
+}
+
+fn usage(message: &str) -> ! {
+    error::report(format_args!("{}: {}", program(), message));
+    eprintln!("usage: vssh [-ilnruvx] [--audit-log file] [--trace-json file] [--profile-startup] [--norc] [--noprofile] [-c command | script] [args...]");
+    std::process::exit(2);
+}
+
+fn invoked_as(name: &str) -> bool {
+    env::args().next().is_some_and(|arg0| {
+        arg0.rsplit('/')
+            .next()
+            .unwrap_or(&arg0)
+            .trim_start_matches('-')
+            == name
+    })
+}
+
+fn parse_args() -> Invocation {
+    let mut invocation = Invocation {
+        interactive: false,
+        login: env::args().next().is_some_and(|arg0| arg0.starts_with('-')),
+        restricted: invoked_as("rvssh"),
+        check: false,
+        options: Vec::new(),
+        command: None,
+        audit_log: None,
+        trace_json: None,
+        profile_startup: false,
+        norc: false,
+        noprofile: false,
+        operands: Vec::new(),
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--login" => invocation.login = true,
+            "--audit-log" => match args.next() {
+                Some(path) => invocation.audit_log = Some(path),
+                None => usage("--audit-log: option requires an argument"),
+            },
+            "--trace-json" => match args.next() {
+                Some(path) => invocation.trace_json = Some(path),
+                None => usage("--trace-json: option requires an argument"),
+            },
+            "--profile-startup" => invocation.profile_startup = true,
+            "--norc" => invocation.norc = true,
+            "--noprofile" => invocation.noprofile = true,
+            "--" => break,
+            _ if arg.starts_with("--audit-log=") => {
+                invocation.audit_log = Some(arg["--audit-log=".len()..].to_string());
+            }
+            _ if arg.starts_with("--trace-json=") => {
+                invocation.trace_json = Some(arg["--trace-json=".len()..].to_string());
+            }
+            _ if arg.starts_with("--") => usage(&format!("{}: invalid option", arg)),
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                for flag in arg[1..].chars() {
+                    match flag {
+                        'i' => invocation.interactive = true,
+                        'l' => invocation.login = true,
+                        'r' => invocation.restricted = true,
+                        'n' => invocation.check = true,
+                        'c' => match args.next() {
+                            Some(command) => invocation.command = Some(command),
+                            None => usage("-c: option requires an argument"),
+                        },
+                        _ => match options::flag_name(flag) {
+                            Some(name) => invocation.options.push(name),
+                            None => usage(&format!("-{}: invalid option", flag)),
+                        },
+                    }
+                }
+            }
+            _ => {
+                invocation.operands.push(arg);
+                break;
             }
-            continue;
         }
+    }
+    invocation.operands.extend(args);
+    invocation
+}
 
-        let (input, background) = if input.ends_with('&') {
-            (input.trim_end_matches('&').trim(), true)
-        } else {
-            (input, false)
-        };
+fn read_script(path: &str) -> String {
+    match fs::read(bytes::to_os(path)) {
+        Ok(src) => bytes::decode(&src),
+        Err(e) => {
+            error::report(format_args!("{}: {}: {}", program(), path, describe(&e)));
+            std::process::exit(127);
+        }
+    }
+}
 
-        if let Err(e) = execute_line(input, background) {
-            eprintln!("Error: {}", e);
+fn load_rc(shell: &mut Shell) {
+    let Some(home) = shell.vars.get("HOME") else {
+        return;
+    };
+    let path = format!("{}/.vsshrc", home.trim_end_matches('/'));
+    let Ok(src) = fs::read(bytes::to_os(&path)) else {
+        return;
+    };
+    match parser::parse(&bytes::decode(&src), 1) {
+        Ok(list) => {
+            if let Err(e) = shell.run_list(&list) {
+                report(shell, e);
+            }
         }
+        Err(e) => error::report(format_args!("{}: {}: {}", program(), path, e)),
     }
-    Ok(())
 }
 
-fn execute_line(line: &str, background: bool) -> Result<()> {
-    let mut segments: Vec<&str> = line.split('|').map(str::trim).collect();
-    if segments.is_empty() {
-        return Ok(());
+fn run_program(shell: &mut Shell, src: &str, args: Vec<String>) -> ! {
+    shell.positional = args;
+    shell.echo_input(src);
+    if let Err(e) = shell.run_line(src) {
+        report(shell, e);
     }
+    shell.run_exit_trap();
+    io::stdout().flush().ok();
+    Status::new(shell.last_status as i64).exit(signals::interrupted());
+}
 
-    let mut input_file = None;
-    if segments[0].contains('<') {
-        let parts: Vec<&str> = segments[0].split('<').map(str::trim).collect();
-        segments[0] = parts[0];
-        input_file = Some(parts[1]);
+fn run_file(shell: &mut Shell, src: &str, args: Vec<String>) -> ! {
+    shell.positional = args;
+    if let Err(e) = shell.run_script(src.as_bytes()) {
+        report(shell, e);
     }
+    shell.run_exit_trap();
+    io::stdout().flush().ok();
+    Status::new(shell.last_status as i64).exit(signals::interrupted());
+}
 
-    let mut output_file = None;
-    let last = segments.len() - 1;
-    if segments[last].contains('>') {
-        let parts: Vec<&str> = segments[last].split('>').map(str::trim).collect();
-        segments[last] = parts[0];
-        output_file = Some(parts[1]);
+fn main() {
+    if let Err(e) = run() {
+        error::report(format_args!("{}: {}", program(), e));
+        std::process::exit(1);
     }
+}
 
-    let mut pipes = Vec::new();
-    for _ in 0..segments.len() - 1 {
-        let mut fds = [0, 0];
-        unsafe {
-            if libc::pipe(fds.as_mut_ptr()) == -1 {
-                return Err(anyhow::anyhow!("Failed to create pipe"));
+fn run() -> Result<()> {
+    let invocation = parse_args();
+    let mut profile = Profile::new(invocation.profile_startup);
+    let mut shell = profile.time("shell", Shell::new);
+    shell.audit_log = invocation.audit_log.clone();
+    shell.trace_file = invocation.trace_json.clone();
+    shell.login = invocation.login;
+    for name in &invocation.options {
+        shell.options.set(name, true);
+    }
+    if invocation.check {
+        let (name, src) = match (&invocation.command, invocation.operands.first()) {
+            (Some(command), _) => ("-c", command.clone()),
+            (None, Some(path)) => (path.as_str(), read_script(path)),
+            (None, None) => {
+                let mut src = Vec::new();
+                io::stdin().read_to_end(&mut src)?;
+                ("stdin", bytes::decode(&src))
             }
-            pipes.push((fds[0], fds[1]));
+        };
+        check_syntax(name, &src);
+    }
+    shell.name = match (&invocation.command, invocation.operands.first()) {
+        (_, Some(name)) => name.clone(),
+        (None, None) | (Some(_), None) => env::args().next().unwrap_or_else(|| "vssh".to_string()),
+    };
+    error::set_program(&shell.name);
+    let env_files = !invocation.norc && !invocation.noprofile;
+    if env_files && (invocation.command.is_some() || !invocation.operands.is_empty()) {
+        profile.time("env file", || shell.load_env_file("VSSH_ENV"));
+    }
+    if let Some(command) = &invocation.command {
+        shell.restricted = invocation.restricted;
+        let args = invocation.operands.iter().skip(1).cloned().collect();
+        profile.report();
+        run_program(&mut shell, command, args);
+    }
+    if let Some((path, args)) = invocation.operands.split_first() {
+        shell.restricted = invocation.restricted;
+        let src = read_script(path);
+        profile.report();
+        run_file(&mut shell, &src, args.to_vec());
+    }
+    shell.interactive =
+        invocation.interactive || unistd::isatty(libc::STDIN_FILENO).unwrap_or(false);
+    if shell.interactive {
+        profile.time("prompt setup", || {
+            signals::install();
+            if unistd::isatty(libc::STDIN_FILENO).unwrap_or(false) {
+                shell.options.set("monitor", true);
+                shell.take_terminal();
+            }
+            shell.update_window_size();
+            shell.save_terminal();
+        });
+        shell.history.deferred = true;
+        profile.defer("history");
+        if !invocation.norc {
+            profile.time("rc file", || load_rc(&mut shell));
+        }
+        if env_files && !shell.login {
+            profile.time("env file", || shell.load_env_file("ENV"));
         }
+        profile.defer("completion cache");
+    } else if env_files {
+        profile.time("env file", || shell.load_env_file("VSSH_ENV"));
     }
-
-    let mut child_pids = Vec::new();
-
-    for i in 0..segments.len() {
-        let args = externalize(segments[i]);
-        if args.is_empty() {
-            continue;
+    shell.restricted = invocation.restricted;
+    if shell.interactive {
+        profile.time("directory env", || shell.update_directory_envs());
+    }
+    profile.report();
+    let mut buffer = String::new();
+    let mut eofs = 0;
+    let mut warned_stopped = false;
+    let mut line = 1;
+    let mut start = line;
+    let mut prompt = String::new();
+    let mut verifying = String::new();
+    loop {
+        if buffer.is_empty() {
+            shell.notify_jobs();
         }
-
-        match unsafe { fork()? } {
-            ForkResult::Child => {
-                if i == 0 {
-                    if let Some(file) = input_file {
-                        let infile = File::open(file)?;
-                        unsafe {
-                            libc::dup2(infile.as_raw_fd(), libc::STDIN_FILENO);
-                        }
-                    }
+        if shell.interactive {
+            if signals::take_resized() {
+                shell.update_window_size();
+            }
+            if buffer.is_empty() {
+                shell.restore_terminal();
+            }
+            if buffer.is_empty() && shell.title_enabled() {
+                terminal::set_title(&shell.idle_title());
+            }
+            prompt = match (buffer.is_empty(), shell.vars.get("PS1")) {
+                (true, Some(ps1)) => shell.render_prompt(ps1),
+                (true, None) => {
+                    let status = match shell.status_segment() {
+                        segment if segment.is_empty() => segment,
+                        segment => format!(" {}", segment),
+                    };
+                    format!(
+                        "{}{}{} ",
+                        style("prompt_cwd", &shell.display_cwd()),
+                        status,
+                        style("prompt_symbol", "$")
+                    )
                 }
+                (false, _) => shell.render_prompt(shell.vars.get("PS2").unwrap_or("> ")),
+            };
+            print!("{}", width::strip_markers(&prompt));
+            io::stdout().flush()?;
+        }
 
-                if i == segments.len() - 1 {
-                    if let Some(file) = output_file {
-                        let outfile = File::create(file)?;
-                        unsafe {
-                            libc::dup2(outfile.as_raw_fd(), libc::STDOUT_FILENO);
-                        }
-                    }
+        if shell.interactive
+            && let Some(seconds) = shell
+                .vars
+                .get("TMOUT")
+                .and_then(|t| t.trim().parse::<u32>().ok())
+                .filter(|&t| t > 0)
+        {
+            match wait_for_input(seconds) {
+                Ok(true) => {}
+                Ok(false) => {
+                    println!();
+                    eprintln!("timed out waiting for input: auto-logout");
+                    break;
                 }
-
-                if i > 0 {
-                    unsafe {
-                        libc::dup2(pipes[i - 1].0, libc::STDIN_FILENO);
-                    }
+                Err(Errno::EINTR) => {
+                    signals::clear();
+                    println!();
+                    buffer.clear();
+                    continue;
                 }
+                Err(e) => return Err(e.into()),
+            }
+        }
 
-                if i < segments.len() - 1 {
-                    unsafe {
-                        libc::dup2(pipes[i].1, libc::STDOUT_FILENO);
-                    }
+        let read = if shell.editor_enabled() {
+            shell.edit_line(&prompt, &std::mem::take(&mut verifying))
+        } else {
+            builtins::read_line(libc::STDIN_FILENO, true)
+        };
+        let (mut input, complete) = match read {
+            Ok(Some(read)) => read,
+            Ok(None) => {
+                signals::clear();
+                println!();
+                buffer.clear();
+                continue;
+            }
+            Err(e @ ShellError::Input(_)) => {
+                report(&mut shell, e);
+                buffer.clear();
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if !complete && input.is_empty() {
+            if !buffer.is_empty()
+                && let Err(e) = parser::parse(&buffer, start)
+            {
+                report_syntax(&mut shell, e);
+            }
+            buffer.clear();
+            if shell.interactive && shell.options.get("ignoreeof") {
+                let limit = shell
+                    .vars
+                    .get("IGNOREEOF")
+                    .and_then(|n| n.trim().parse().ok())
+                    .unwrap_or(10);
+                eofs += 1;
+                if eofs <= limit {
+                    eprintln!("Use \"exit\" to leave the shell.");
+                    continue;
                 }
-
-                for &(read_fd, write_fd) in &pipes {
-                    unsafe {
-                        libc::close(read_fd);
-                        libc::close(write_fd);
+            }
+            if shell.interactive && !warned_stopped && shell.has_stopped_jobs() {
+                eprintln!("There are stopped jobs.");
+                warned_stopped = true;
+                continue;
+            }
+            break;
+        }
+        eofs = 0;
+        warned_stopped = false;
+        shell.echo_input(&input);
+        if shell.interactive {
+            if input.contains(['!', '^']) {
+                shell.load_deferred_history();
+            }
+            match shell.expand_history(&input) {
+                Ok(None) => {}
+                Ok(Some((expanded, false)))
+                    if shell.options.get("histverify") && shell.editor_enabled() =>
+                {
+                    verifying = expanded;
+                    continue;
+                }
+                Ok(Some((expanded, print_only))) => {
+                    eprintln!("{}", expanded);
+                    if print_only || shell.options.get("histverify") {
+                        shell.add_history(&format!("{}{}", buffer, expanded));
+                        shell.history.current = false;
+                        buffer.clear();
+                        continue;
                     }
+                    input = expanded;
                 }
-
-                execvp(&args[0], &args)?;
-                std::process::exit(1);
-            }
-            ForkResult::Parent { child } => {
-                child_pids.push(child);
-                if background {
-                    println!("Started background process with PID: {}", child);
+                Err(message) => {
+                    error::report(format_args!("{}: {}", program(), message));
+                    shell.last_status = 1;
+                    buffer.clear();
+                    continue;
                 }
             }
         }
-    }
+        input.push('\n');
 
-    for &(read_fd, write_fd) in &pipes {
-        unsafe {
-            libc::close(read_fd);
-            libc::close(write_fd);
+        line += 1;
+        if buffer.is_empty() {
+            start = line - 1;
+            if input.trim().is_empty() {
+                continue;
+            }
         }
-    }
 
-    if !background {
-        for pid in child_pids {
-            let status = waitpid(pid, None)?;
-            match status {
-                WaitStatus::Exited(pid, status) => {
-                    println!("Process {} exited with status {}", pid, status);
+        buffer.push_str(&input);
+        match parser::parse(&buffer, start) {
+            Ok(list) => {
+                if shell.interactive {
+                    shell.add_history(&buffer);
                 }
-                WaitStatus::Signaled(pid, signal, core_dumped) => {
-                    println!(
-                        "Process {} was killed by signal {:?}, core dumped: {}",
-                        pid, signal, core_dumped
-                    );
+                shell.audit_quiet = buffer.starts_with(' ')
+                    && shell
+                        .vars
+                        .get("HISTCONTROL")
+                        .unwrap_or("")
+                        .split(':')
+                        .any(|o| o == "ignorespace" || o == "ignoreboth");
+                buffer.clear();
+                signals::clear();
+                if signals::take_resized() {
+                    shell.update_window_size();
                 }
-                WaitStatus::Continued(pid) => {
-                    println!("Process {} continued", pid);
+                if let Err(e) = shell.run_list(&list) {
+                    report(&mut shell, e);
                 }
-                WaitStatus::Stopped(pid, signal) => {
-                    println!("Process {} stopped by signal {:?}", pid, signal);
-                }
-                WaitStatus::StillAlive => {
-                    println!("No state changes to report");
+            }
+            Err(ParseError::Incomplete { .. }) => {}
+            Err(e) => {
+                if shell.interactive {
+                    shell.add_history(&buffer);
                 }
-                _ => {}
+                buffer.clear();
+                report_syntax(&mut shell, e);
             }
         }
     }
-
-    Ok(())
+    if shell.interactive {
+        println!("exit");
+        shell.hang_up_stopped();
+    }
+    shell.run_exit_trap();
+    shell.save_history();
+    io::stdout().flush()?;
+    Status::new(shell.last_status as i64).exit(!shell.interactive && signals::interrupted());
 }
-
-fn externalize(command: &str) -> Vec<CString> {
-    command
-        .split_whitespace()
-        .map(|s| CString::new(s).unwrap())
-        .collect()
-}
\ No newline at end of file
@@ -0,0 +1,2372 @@
+use std::fs;
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+use nix::errno::Errno;
+use nix::sys::signal::{self, Signal};
+use nix::sys::stat::Mode;
+use nix::unistd;
+
+use crate::ast::{Word, WordPart};
+use crate::bytes;
+use crate::direnv;
+use crate::error::{describe, program, report, Result, ShellError};
+use crate::exec;
+use crate::history;
+use crate::jobs;
+use crate::keymap::{self, Action};
+use crate::lock::{self as flock, Outcome};
+use crate::options::{self, OPTIONS};
+use crate::parser;
+use crate::policy as exec_policy;
+use crate::printf as format;
+use crate::scope::Scope;
+use crate::shell::{Control, Shell};
+use crate::signals;
+use crate::status::Status;
+use crate::traps;
+use crate::vars::Value;
+use crate::word::{self, is_name};
+
+// Like println!, but through the stdout handle, which always reaches
+// fd 1 (println! is diverted by the test harness's output capture) and
+// doesn't panic on a closed pipe.
+macro_rules! outln {
+    ($($arg:tt)*) => {{
+        let _ = writeln!(io::stdout(), $($arg)*);
+    }};
+}
+
+const MAX_LINE: usize = 1 << 22;
+const MAX_SOURCE_DEPTH: usize = 100;
+
+pub type Builtin = fn(&mut Shell, &[String]) -> Result<i32>;
+
+pub const NAMES: &[&str] = &[
+    ":",
+    ".",
+    "bg",
+    "bind",
+    "break",
+    "cd",
+    "continue",
+    "disown",
+    "echo",
+    "exec",
+    "env",
+    "exit",
+    "expand-debug",
+    "export",
+    "false",
+    "fc",
+    "fg",
+    "hash",
+    "history",
+    "jobs",
+    "kill",
+    "let",
+    "local",
+    "lock",
+    "mapfile",
+    "policy",
+    "printf",
+    "pwd",
+    "read",
+    "readarray",
+    "readonly",
+    "repeat",
+    "return",
+    "set",
+    "shopt",
+    "source",
+    "suspend",
+    "times",
+    "trap",
+    "true",
+    "unset",
+    "vssh-env",
+    "wait",
+    "with",
+];
+
+pub fn lookup(name: &str) -> Option<Builtin> {
+    match name {
+        ":" | "true" => Some(true_builtin),
+        "." | "source" => Some(source),
+        "bg" => Some(bg),
+        "bind" => Some(bind),
+        "env" => Some(env),
+        "export" => Some(export),
+        "false" => Some(false_builtin),
+        "fc" => Some(fc),
+        "break" => Some(break_builtin),
+        "cd" => Some(cd),
+        "continue" => Some(continue_builtin),
+        "disown" => Some(disown),
+        "echo" => Some(echo),
+        "exec" => Some(exec_builtin),
+        "exit" => Some(exit),
+        "expand-debug" => Some(expand_debug),
+        "fg" => Some(fg),
+        "hash" => Some(hash),
+        "history" => Some(history),
+        "jobs" => Some(jobs),
+        "kill" => Some(kill),
+        "let" => Some(let_builtin),
+        "local" => Some(local),
+        "lock" => Some(lock),
+        "mapfile" | "readarray" => Some(mapfile),
+        "readonly" => Some(readonly),
+        "policy" => Some(policy),
+        "printf" => Some(printf),
+        "pwd" => Some(pwd),
+        "read" => Some(read),
+        "repeat" => Some(repeat),
+        "return" => Some(return_builtin),
+        "set" => Some(set),
+        "shopt" => Some(shopt),
+        "suspend" => Some(suspend),
+        "times" => Some(times),
+        "trap" => Some(trap),
+        "unset" => Some(unset),
+        "vssh-env" => Some(vssh_env),
+        "wait" => Some(wait),
+        "with" => Some(with),
+        _ => None,
+    }
+}
+
+pub fn is_declaration(word: &Word) -> bool {
+    matches!(word.parts.as_slice(), [WordPart::Literal(name)] if name == "local")
+}
+
+fn true_builtin(_: &mut Shell, _: &[String]) -> Result<i32> {
+    Ok(0)
+}
+
+fn false_builtin(_: &mut Shell, _: &[String]) -> Result<i32> {
+    Ok(1)
+}
+
+fn loop_count(args: &[String]) -> Option<usize> {
+    let Some(arg) = args.get(1) else {
+        return Some(1);
+    };
+    match arg.parse::<usize>() {
+        Ok(n) if n > 0 => Some(n),
+        _ => {
+            report(format_args!(
+                "{}: {}: {}: loop count out of range",
+                program(),
+                args[0],
+                arg
+            ));
+            None
+        }
+    }
+}
+
+fn break_builtin(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    if shell.loop_depth == 0 {
+        report(format_args!(
+            "{}: break: only meaningful in a `for', `while', or `until' loop",
+            program()
+        ));
+        return Ok(0);
+    }
+    let Some(n) = loop_count(args) else {
+        return Ok(1);
+    };
+    shell.control = Some(Control::Break(n.min(shell.loop_depth)));
+    Ok(0)
+}
+
+fn continue_builtin(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    if shell.loop_depth == 0 {
+        report(format_args!(
+            "{}: continue: only meaningful in a `for', `while', or `until' loop",
+            program()
+        ));
+        return Ok(0);
+    }
+    let Some(n) = loop_count(args) else {
+        return Ok(1);
+    };
+    shell.control = Some(Control::Continue(n.min(shell.loop_depth)));
+    Ok(0)
+}
+
+fn cd(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    shell.restrict_command("cd")?;
+    let dir = match args {
+        [_] => match shell.vars.get("HOME") {
+            Some(home) => home,
+            None => {
+                report(format_args!("{}: cd: HOME not set", program()));
+                return Ok(1);
+            }
+        },
+        [_, dir] => dir.as_str(),
+        _ => {
+            report(format_args!("{}: cd: too many arguments", program()));
+            return Ok(1);
+        }
+    };
+    let searched = match dir.starts_with(['/', '.']) {
+        true => None,
+        false => shell.vars.get("CDPATH").and_then(|cdpath| {
+            cdpath
+                .split(':')
+                .filter(|base| !base.is_empty())
+                .map(|base| format!("{}/{}", base.trim_end_matches('/'), dir))
+                .find(|candidate| Path::new(&bytes::to_os(candidate)).is_dir())
+        }),
+    };
+    let recovered = match std::env::current_dir() {
+        Err(_) if !dir.starts_with('/') && searched.is_none() => Some(nearest_dir(shell, dir)),
+        _ => None,
+    };
+    let target = recovered.as_deref().or(searched.as_deref()).unwrap_or(dir);
+    if let Err(e) = unistd::chdir(Path::new(&bytes::to_os(target))) {
+        report(format_args!("{}: cd: {}: {}", program(), dir, e.desc()));
+        return Ok(1);
+    }
+    if let Some(target) = &searched {
+        outln!("{}", target);
+    }
+    if let Some(old) = shell.vars.get("PWD").map(str::to_string) {
+        shell.vars.set("OLDPWD", old);
+    }
+    if let Ok(cwd) = std::env::current_dir() {
+        shell.vars.set("PWD", bytes::from_os(cwd.as_os_str()));
+    }
+    shell.update_directory_envs();
+    Ok(0)
+}
+
+fn nearest_dir(shell: &Shell, dir: &str) -> String {
+    let mut parts: Vec<&str> = shell
+        .vars
+        .get("PWD")
+        .unwrap_or("")
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .collect();
+    for part in dir.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    while !parts.is_empty() {
+        let path = format!("/{}", parts.join("/"));
+        if Path::new(&bytes::to_os(&path)).is_dir() {
+            return path;
+        }
+        parts.pop();
+    }
+    shell.vars.get("HOME").unwrap_or("/").to_string()
+}
+
+fn vssh_env(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let (allow, dir) = match &args[1..] {
+        [command] | [command, _] if command == "allow" || command == "deny" => {
+            (command == "allow", args.get(2).map_or(".", String::as_str))
+        }
+        _ => {
+            report(format_args!(
+                "{}: vssh-env: usage: vssh-env allow|deny [dir]",
+                program()
+            ));
+            return Ok(2);
+        }
+    };
+    let file = match fs::canonicalize(bytes::to_os(dir)) {
+        Ok(dir) => dir.join(direnv::ENV_FILE),
+        Err(e) => {
+            report(format_args!(
+                "{}: vssh-env: {}: {}",
+                program(),
+                dir,
+                describe(&e)
+            ));
+            return Ok(1);
+        }
+    };
+    if !file.is_file() {
+        report(format_args!(
+            "{}: vssh-env: {}: no {} file",
+            program(),
+            dir,
+            direnv::ENV_FILE
+        ));
+        return Ok(1);
+    }
+    if let Err(e) = shell.set_trust(&file, allow) {
+        report(format_args!("{}: vssh-env: {}", program(), describe(&e)));
+        return Ok(1);
+    }
+    if allow {
+        shell.update_directory_envs();
+    }
+    Ok(0)
+}
+
+fn echo(_: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut newline = true;
+    let mut escapes = false;
+    let mut words = &args[1..];
+    while let Some(flag) = words.first().and_then(|w| w.strip_prefix('-')) {
+        if flag.is_empty() || !flag.chars().all(|c| matches!(c, 'n' | 'e' | 'E')) {
+            break;
+        }
+        for c in flag.chars() {
+            match c {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        words = &words[1..];
+    }
+    let mut out = String::new();
+    for (i, w) in words.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        if !escapes {
+            out.push_str(w);
+        } else if !interpret_escapes(w, &mut out) {
+            newline = false;
+            break;
+        }
+    }
+    if newline {
+        out.push('\n');
+    }
+    let mut stdout = io::stdout();
+    if stdout
+        .write_all(&bytes::encode(&out))
+        .and_then(|_| stdout.flush())
+        .is_err()
+    {
+        return Ok(1);
+    }
+    Ok(0)
+}
+
+pub fn interpret_escapes(word: &str, out: &mut String) -> bool {
+    let mut chars = word.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('a') => out.push('\x07'),
+            Some('b') => out.push('\x08'),
+            Some('c') => return false,
+            Some('e') | Some('E') => out.push('\x1b'),
+            Some('f') => out.push('\x0c'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('v') => out.push('\x0b'),
+            Some('\\') => out.push('\\'),
+            Some(radix @ ('0' | 'x')) => {
+                let (base, max) = if radix == '0' { (8, 3) } else { (16, 2) };
+                let mut value = 0;
+                let mut digits = 0;
+                while digits < max
+                    && let Some(d) = chars.peek().and_then(|c| c.to_digit(base))
+                {
+                    value = value * base + d;
+                    digits += 1;
+                    chars.next();
+                }
+                if radix == 'x' && digits == 0 {
+                    out.push_str("\\x");
+                } else {
+                    out.push(char::from_u32(value).unwrap_or('\u{fffd}'));
+                }
+            }
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    true
+}
+
+fn policy(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    if args.get(1).is_some_and(|a| a == "off") {
+        if args.len() < 3 {
+            report(format_args!(
+                "{}: policy: usage: policy [off command [args...] | name...]",
+                program()
+            ));
+            return Ok(2);
+        }
+        let saved = std::mem::replace(&mut shell.policy_off, true);
+        let status = shell.run_words(&args[2..]);
+        shell.policy_off = saved;
+        return status;
+    }
+    let spec = shell.vars.get("VSSH_EXEC_POLICY").unwrap_or("");
+    let mut out = io::stdout().lock();
+    if args.len() > 1 {
+        for name in &args[1..] {
+            let adjustments: Vec<String> = exec_policy::lookup(spec, name)
+                .iter()
+                .map(|a| a.to_string())
+                .collect();
+            match adjustments.is_empty() {
+                true => writeln!(out, "{}: none", name)?,
+                false => writeln!(out, "{}: {}", name, adjustments.join(","))?,
+            }
+        }
+        return Ok(0);
+    }
+    let (rules, errors) = exec_policy::parse(spec);
+    for rule in rules {
+        writeln!(out, "{}", rule)?;
+    }
+    for error in &errors {
+        report(format_args!("{}: policy: {}", program(), error));
+    }
+    Ok(if errors.is_empty() { 0 } else { 1 })
+}
+
+fn printf(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut rest = &args[1..];
+    let mut target = None;
+    while let Some(flag) = rest.first() {
+        match flag.as_str() {
+            "-v" => {
+                let Some(name) = rest.get(1) else {
+                    report(format_args!(
+                        "{}: printf: -v: option requires an argument",
+                        program()
+                    ));
+                    return Ok(2);
+                };
+                target = Some(name.clone());
+                rest = &rest[2..];
+            }
+            "--" => {
+                rest = &rest[1..];
+                break;
+            }
+            _ => break,
+        }
+    }
+    let Some((spec, values)) = rest.split_first() else {
+        report(format_args!(
+            "{}: printf: usage: printf [-v var] format [arguments]",
+            program()
+        ));
+        return Ok(2);
+    };
+    let formatted = format::format(spec, values);
+    for error in &formatted.errors {
+        report(format_args!("{}: printf: {}", program(), error));
+    }
+    let status = formatted.failed as i32;
+    if let Some(target) = target {
+        return match assign(shell, "printf", &target, formatted.text)? {
+            true => Ok(status),
+            false => Ok(1),
+        };
+    }
+    let mut stdout = io::stdout();
+    if stdout
+        .write_all(&bytes::encode(&formatted.text))
+        .and_then(|_| stdout.flush())
+        .is_err()
+    {
+        return Ok(1);
+    }
+    Ok(status)
+}
+
+fn assign(shell: &mut Shell, builtin: &str, target: &str, value: String) -> Result<bool> {
+    match shell.assign_target(target, value) {
+        Ok(()) => Ok(true),
+        Err(ShellError::Expansion(message)) => {
+            report(format_args!("{}: {}: {}", program(), builtin, message));
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn pwd(_: &mut Shell, _: &[String]) -> Result<i32> {
+    match std::env::current_dir() {
+        Ok(dir) => {
+            outln!("{}", dir.display());
+            Ok(0)
+        }
+        Err(e) => {
+            report(format_args!("{}: pwd: {}", program(), describe(&e)));
+            Ok(1)
+        }
+    }
+}
+
+fn exec_builtin(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut argv0 = None;
+    let mut login = false;
+    let mut clear_env = false;
+    let mut rest = &args[1..];
+    while let Some(flag) = rest.first().and_then(|a| a.strip_prefix('-')) {
+        match flag {
+            "" => break,
+            "-" => {
+                rest = &rest[1..];
+                break;
+            }
+            "a" => match rest.get(1) {
+                Some(name) => {
+                    argv0 = Some(name.clone());
+                    rest = &rest[1..];
+                }
+                None => {
+                    report(format_args!(
+                        "{}: exec: -a: option requires an argument",
+                        program()
+                    ));
+                    return Ok(2);
+                }
+            },
+            _ if flag.chars().all(|c| matches!(c, 'c' | 'l')) => {
+                login |= flag.contains('l');
+                clear_env |= flag.contains('c');
+            }
+            _ => {
+                report(format_args!(
+                    "{}: exec: -{}: invalid option\n{}: usage: exec [-cl] [-a name] [command [args...]]",
+                    program(),
+                    flag,
+                    program()
+                ));
+                return Ok(2);
+            }
+        }
+        rest = &rest[1..];
+    }
+    if rest.is_empty() {
+        return Ok(0);
+    }
+    let name = argv0.unwrap_or_else(|| rest[0].clone());
+    let name = match login {
+        true => format!("-{}", name),
+        false => name,
+    };
+    shell.exec_command(rest, Some(&name), clear_env)
+}
+
+fn exit(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let status = match args.get(1) {
+        Some(arg) => match arg.trim().parse::<i64>() {
+            Ok(status) => Status::new(status),
+            Err(_) => {
+                report(format_args!(
+                    "{}: exit: {}: numeric argument required",
+                    program(),
+                    arg
+                ));
+                Status::new(2)
+            }
+        },
+        None => Status::new(shell.last_status as i64),
+    };
+    io::stdout().flush().ok();
+    if shell.embedded && !shell.subshell {
+        shell.control = Some(Control::Exit);
+        return Ok(status.code());
+    }
+    shell.last_status = status.code();
+    shell.audit(status.code());
+    shell.run_exit_trap();
+    io::stdout().flush().ok();
+    shell.save_history();
+    status.exit(false);
+}
+
+fn hash(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut names = &args[1..];
+    if names.first().is_some_and(|a| a == "-r") {
+        shell.rehash();
+        names = &names[1..];
+    }
+    if names.is_empty() {
+        if args.len() > 1 {
+            return Ok(0);
+        }
+        let hashed = shell.command_hash();
+        if hashed.is_empty() {
+            report(format_args!("{}: hash: hash table empty", program()));
+            return Ok(0);
+        }
+        outln!("hits\tcommand");
+        for (path, hits) in hashed.values() {
+            outln!("{:>4}\t{}", hits, path);
+        }
+        return Ok(0);
+    }
+    let mut status = 0;
+    for name in names {
+        if lookup(name).is_some() || shell.functions.contains_key(name) {
+            continue;
+        }
+        shell.command_hash().remove(name);
+        if shell.hash_command(name, false).is_none() {
+            report(format_args!("{}: hash: {}: not found", program(), name));
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+fn history(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    shell.load_deferred_history();
+    let mut count = None;
+    for arg in &args[1..] {
+        let result = match arg.as_str() {
+            "-c" => {
+                shell.history.clear();
+                Ok(())
+            }
+            "-a" => shell.append_history(),
+            "-r" => shell.read_history(),
+            "-w" => shell.write_history(),
+            n if n.parse::<usize>().is_ok() && count.is_none() => {
+                count = n.parse().ok();
+                Ok(())
+            }
+            n if !n.starts_with('-') => {
+                report(format_args!(
+                    "{}: history: {}: numeric argument required",
+                    program(),
+                    n
+                ));
+                return Ok(1);
+            }
+            flag => {
+                report(format_args!(
+                    "{}: history: {}: invalid option",
+                    program(),
+                    flag
+                ));
+                report(format_args!(
+                    "{}: history: usage: history [-c] [-a|-r|-w] [n]",
+                    program()
+                ));
+                return Ok(2);
+            }
+        };
+        if let Err(e) = result {
+            let file = shell.history_file().unwrap_or_default();
+            report(format_args!(
+                "{}: history: {}: {}",
+                program(),
+                file,
+                describe(&e)
+            ));
+            return Ok(1);
+        }
+        if arg.starts_with('-') {
+            return Ok(0);
+        }
+    }
+    let entries = &shell.history.entries;
+    let skip = entries.len() - count.unwrap_or(entries.len()).min(entries.len());
+    let format = shell.vars.get("HISTTIMEFORMAT");
+    for (index, entry) in entries.iter().enumerate().skip(skip) {
+        let stamp = match (format, entry.time) {
+            (Some(format), Some(time)) => history::format_time(format, time),
+            _ => String::new(),
+        };
+        outln!(
+            "{:>5}  {}{}",
+            shell.history.number(index),
+            stamp,
+            entry.line
+        );
+    }
+    Ok(0)
+}
+
+fn fc_range(
+    shell: &Shell,
+    first: Option<&String>,
+    last: Option<&String>,
+    list: bool,
+) -> Option<(usize, usize)> {
+    let count = shell.history.previous().len();
+    if count == 0 {
+        return None;
+    }
+    let first = match first {
+        Some(spec) => shell.history.find(spec)?,
+        None if list => count.saturating_sub(16),
+        None => count - 1,
+    };
+    let last = match last {
+        Some(spec) => shell.history.find(spec)?,
+        None if list => count - 1,
+        None => first,
+    };
+    Some((first, last))
+}
+
+fn fc_run(shell: &mut Shell, text: &str) -> Result<i32> {
+    eprintln!("{}", text.trim_end_matches('\n'));
+    shell.replace_current_history(text);
+    let list = parser::parse(text, shell.lineno)?;
+    shell.run_list(&list)
+}
+
+fn fc(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    shell.load_deferred_history();
+    let mut list = false;
+    let mut numbers = true;
+    let mut reverse = false;
+    let mut substitute = false;
+    let mut editor = None;
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        match arg.as_str() {
+            "-l" => list = true,
+            "-n" => numbers = false,
+            "-r" => reverse = true,
+            "-s" => substitute = true,
+            "-ln" | "-nl" => {
+                list = true;
+                numbers = false;
+            }
+            "-e" => {
+                i += 1;
+                editor = args.get(i).cloned();
+            }
+            "--" => {
+                i += 1;
+                break;
+            }
+            flag if flag.starts_with('-') && flag.parse::<i64>().is_err() => {
+                report(format_args!("{}: fc: {}: invalid option", program(), flag));
+                report(format_args!(
+                    "{}: fc: usage: fc [-e ename] [-lnr] [first] [last] or fc -s [pat=rep] [command]", program()
+                ));
+                return Ok(2);
+            }
+            _ => break,
+        }
+        i += 1;
+    }
+    let mut operands = &args[i.min(args.len())..];
+
+    if substitute {
+        let mut replacements = Vec::new();
+        while let Some((old, new)) = operands.first().and_then(|a| a.split_once('=')) {
+            replacements.push((old.to_string(), new.to_string()));
+            operands = &operands[1..];
+        }
+        let Some((index, _)) = fc_range(shell, operands.first(), None, false) else {
+            report(format_args!("{}: fc: no command found", program()));
+            return Ok(1);
+        };
+        let mut text = shell.history.entries[index].line.clone();
+        for (old, new) in replacements {
+            text = text.replace(&old, &new);
+        }
+        return fc_run(shell, &text);
+    }
+
+    let Some((first, last)) = fc_range(shell, operands.first(), operands.get(1), list) else {
+        report(format_args!(
+            "{}: fc: history specification out of range",
+            program()
+        ));
+        return Ok(1);
+    };
+    let mut indices: Vec<usize> = if first <= last {
+        (first..=last).collect()
+    } else {
+        (last..=first).rev().collect()
+    };
+    if reverse {
+        indices.reverse();
+    }
+
+    if list {
+        for index in indices {
+            let line = &shell.history.entries[index].line;
+            if numbers {
+                outln!("{}\t {}", shell.history.number(index), line);
+            } else {
+                outln!("\t {}", line);
+            }
+        }
+        return Ok(0);
+    }
+
+    let text: String = indices
+        .iter()
+        .map(|&index| format!("{}\n", shell.history.entries[index].line))
+        .collect();
+    let path = std::env::temp_dir().join(format!("vssh-fc-{}", std::process::id()));
+    if let Err(e) = fs::write(&path, bytes::encode(&text)) {
+        report(format_args!(
+            "{}: fc: {}: {}",
+            program(),
+            path.display(),
+            describe(&e)
+        ));
+        return Ok(1);
+    }
+    let editor = editor
+        .or_else(|| shell.vars.get("FCEDIT").map(str::to_string))
+        .or_else(|| shell.vars.get("EDITOR").map(str::to_string))
+        .unwrap_or_else(|| "vi".to_string());
+    let command = format!("{} '{}'", editor, bytes::from_os(path.as_os_str()));
+    let status = shell.run_list(&parser::parse(&command, shell.lineno)?)?;
+    let edited = fs::read(&path).map(|text| bytes::decode(&text));
+    let _ = fs::remove_file(&path);
+    if status != 0 {
+        return Ok(status);
+    }
+    match edited {
+        Ok(text) if !text.trim().is_empty() => fc_run(shell, &text),
+        Ok(_) => Ok(0),
+        Err(e) => {
+            report(format_args!(
+                "{}: fc: {}: {}",
+                program(),
+                path.display(),
+                describe(&e)
+            ));
+            Ok(1)
+        }
+    }
+}
+
+fn jobs(shell: &mut Shell, _: &[String]) -> Result<i32> {
+    shell.update_jobs();
+    for index in 0..shell.jobs.len() {
+        outln!("{}", shell.format_job(index));
+    }
+    shell.jobs.retain(|job| job.status().is_none());
+    Ok(0)
+}
+
+fn job_arg(shell: &mut Shell, name: &str, spec: Option<&String>) -> Option<usize> {
+    shell.update_jobs();
+    let spec = spec.map_or("%+", String::as_str);
+    match shell.resolve_job(spec) {
+        Ok(index) => Some(index),
+        Err(e) => {
+            let shown = if spec == "%+" { "current" } else { spec };
+            report(format_args!(
+                "{}: {}: {}: {}",
+                program(),
+                name,
+                shown,
+                e.message()
+            ));
+            None
+        }
+    }
+}
+
+fn fg(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let Some(index) = job_arg(shell, "fg", args.get(1)) else {
+        return Ok(1);
+    };
+    if let Some(pgid) = shell.jobs[index].pgid
+        && shell.job_group(false) == Some(true)
+    {
+        jobs::join_group(pgid, pgid, true);
+    }
+    shell.continue_job(index);
+    let job = shell.jobs.remove(index);
+    outln!("{}", job.command);
+    let pending: Vec<unistd::Pid> = job
+        .processes
+        .iter()
+        .filter(|(_, status)| status.is_none())
+        .map(|(pid, _)| *pid)
+        .collect();
+    if pending.is_empty() {
+        shell.reclaim_terminal();
+        return Ok(job.status().unwrap_or(0));
+    }
+    shell.wait_pipeline(pending, &job.command, job.pgid)
+}
+
+fn bg(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let specs: Vec<Option<&String>> = match args.len() {
+        1 => vec![None],
+        _ => args[1..].iter().map(Some).collect(),
+    };
+    let mut status = 0;
+    for spec in specs {
+        let Some(index) = job_arg(shell, "bg", spec) else {
+            status = 1;
+            continue;
+        };
+        shell.continue_job(index);
+        let job = &shell.jobs[index];
+        outln!("[{}] {} &", job.id, job.command);
+    }
+    Ok(status)
+}
+
+fn disown(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    if args.get(1).is_some_and(|a| a == "-a") {
+        shell.jobs.clear();
+        return Ok(0);
+    }
+    let specs: Vec<Option<&String>> = match args.len() {
+        1 => vec![None],
+        _ => args[1..].iter().map(Some).collect(),
+    };
+    let mut status = 0;
+    for spec in specs {
+        match job_arg(shell, "disown", spec) {
+            Some(index) => {
+                shell.jobs.remove(index);
+            }
+            None => status = 1,
+        }
+    }
+    Ok(status)
+}
+
+fn kill(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut rest = &args[1..];
+    let mut number = libc::SIGTERM;
+    match rest.first().map(String::as_str) {
+        Some("-l" | "-L") => return kill_list(&rest[1..]),
+        Some("-s" | "-n") => {
+            let Some(name) = rest.get(1) else {
+                report(format_args!(
+                    "{}: kill: {}: option requires an argument",
+                    program(),
+                    rest[0]
+                ));
+                return Ok(2);
+            };
+            let Some(n) = signals::number(name) else {
+                report(format_args!(
+                    "{}: kill: {}: invalid signal specification",
+                    program(),
+                    name
+                ));
+                return Ok(1);
+            };
+            number = n;
+            rest = &rest[2..];
+        }
+        Some("--") => {}
+        Some(flag) if flag.len() > 1 && flag.starts_with('-') => {
+            let Some(n) = signals::number(&flag[1..]) else {
+                report(format_args!(
+                    "{}: kill: {}: invalid signal specification",
+                    program(),
+                    &flag[1..]
+                ));
+                return Ok(1);
+            };
+            number = n;
+            rest = &rest[1..];
+        }
+        _ => {}
+    }
+    if rest.first().is_some_and(|a| a == "--") {
+        rest = &rest[1..];
+    }
+    if rest.is_empty() {
+        report(format_args!(
+            "{}: kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ...",
+            program()
+        ));
+        return Ok(2);
+    }
+    let mut status = 0;
+    for target in rest {
+        let pids = if target.starts_with('%') {
+            let Some(index) = job_arg(shell, "kill", Some(target)) else {
+                status = 1;
+                continue;
+            };
+            shell.jobs[index]
+                .processes
+                .iter()
+                .filter(|(_, status)| status.is_none())
+                .map(|(pid, _)| pid.as_raw())
+                .collect()
+        } else {
+            match target.parse::<i32>() {
+                Ok(pid) => vec![pid],
+                Err(_) => {
+                    report(format_args!(
+                        "{}: kill: {}: arguments must be process or job IDs",
+                        program(),
+                        target
+                    ));
+                    status = 1;
+                    continue;
+                }
+            }
+        };
+        for pid in pids {
+            if unsafe { libc::kill(pid, number) } != 0 {
+                report(format_args!(
+                    "{}: kill: ({}) - {}",
+                    program(),
+                    pid,
+                    Errno::last().desc()
+                ));
+                status = 1;
+            }
+        }
+    }
+    shell.update_jobs();
+    Ok(status)
+}
+
+fn kill_list(args: &[String]) -> Result<i32> {
+    let mut out = io::stdout().lock();
+    if args.is_empty() {
+        let names: Vec<&str> = (1..65).filter_map(signals::name).collect();
+        writeln!(out, "{}", names.join(" "))?;
+        return Ok(0);
+    }
+    let mut status = 0;
+    for arg in args {
+        let shown = match arg.parse::<i32>() {
+            Ok(n) => signals::name(if n > 128 { n - 128 } else { n }).map(str::to_string),
+            Err(_) => signals::number(arg).map(|n| n.to_string()),
+        };
+        match shown {
+            Some(shown) => writeln!(out, "{}", shown)?,
+            None => {
+                report(format_args!(
+                    "{}: kill: {}: invalid signal specification",
+                    program(),
+                    arg
+                ));
+                status = 1;
+            }
+        }
+    }
+    Ok(status)
+}
+
+fn wait_job(shell: &mut Shell, index: usize) -> Result<i32> {
+    let mut processes = std::mem::take(&mut shell.jobs[index].processes);
+    for (pid, status) in &mut processes {
+        if status.is_none() {
+            *status = Some(Status::from(&shell.wait_raw(*pid)?).code());
+        }
+    }
+    let code = processes
+        .last()
+        .and_then(|(_, status)| *status)
+        .unwrap_or(0);
+    shell.jobs.remove(index);
+    Ok(code)
+}
+
+fn wait_any(shell: &mut Shell, targets: &[String]) -> Result<i32> {
+    let mut ids = Vec::new();
+    for target in targets {
+        let index = match target.parse::<i32>() {
+            Ok(pid) => shell
+                .jobs
+                .iter()
+                .position(|job| job.processes.iter().any(|(p, _)| p.as_raw() == pid)),
+            Err(_) => job_arg(shell, "wait", Some(target)),
+        };
+        match index {
+            Some(index) => ids.push(shell.jobs[index].id),
+            None if target.starts_with('%') => {}
+            None => report(format_args!(
+                "{}: wait: pid {} is not a child of this shell",
+                program(),
+                target
+            )),
+        }
+    }
+    let watched = |id: usize| ids.is_empty() || ids.contains(&id);
+    loop {
+        shell.update_jobs();
+        if let Some(index) = shell
+            .jobs
+            .iter()
+            .position(|job| watched(job.id) && job.status().is_some())
+        {
+            let job = shell.jobs.remove(index);
+            return Ok(job.status().unwrap_or(0));
+        }
+        if !shell.jobs.iter().any(|job| watched(job.id)) {
+            return Ok(127);
+        }
+        if signals::interrupted() {
+            return Ok(130);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn wait(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    shell.update_jobs();
+    if args.get(1).is_some_and(|a| a == "-n") {
+        return wait_any(shell, &args[2..]);
+    }
+    if args.len() < 2 {
+        while !shell.jobs.is_empty() {
+            wait_job(shell, 0)?;
+        }
+        return Ok(0);
+    }
+    let mut status = 0;
+    for target in &args[1..] {
+        if target.starts_with('%') {
+            status = match job_arg(shell, "wait", Some(target)) {
+                Some(index) => wait_job(shell, index)?,
+                None => 127,
+            };
+            continue;
+        }
+        let Ok(pid) = target.parse::<i32>() else {
+            report(format_args!(
+                "{}: wait: `{}': not a pid or valid job spec",
+                program(),
+                target
+            ));
+            status = 2;
+            continue;
+        };
+        let pid = unistd::Pid::from_raw(pid);
+        let owner = shell
+            .jobs
+            .iter()
+            .position(|job| job.processes.iter().any(|(p, _)| *p == pid));
+        status = match owner {
+            Some(index) => {
+                let recorded = shell.jobs[index]
+                    .processes
+                    .iter()
+                    .find(|(p, _)| *p == pid)
+                    .and_then(|(_, status)| *status);
+                let code = match recorded {
+                    Some(code) => code,
+                    None => Status::from(&shell.wait_raw(pid)?).code(),
+                };
+                let job = &mut shell.jobs[index];
+                if let Some(entry) = job.processes.iter_mut().find(|(p, _)| *p == pid) {
+                    entry.1 = Some(code);
+                }
+                if job.status().is_some() {
+                    shell.jobs.remove(index);
+                }
+                code
+            }
+            None => {
+                report(format_args!(
+                    "{}: wait: pid {} is not a child of this shell",
+                    program(),
+                    pid
+                ));
+                127
+            }
+        };
+    }
+    Ok(status)
+}
+
+fn with(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let usage = || {
+        report(format_args!(
+            "{}: with: usage: with [-C dir] [-u mask] [-e name=value]... [--] command [args...]",
+            program()
+        ));
+        Ok(2)
+    };
+    let mut scope = Scope::new(shell);
+    let mut rest = &args[1..];
+    while let Some(flag) = rest.first() {
+        let value = rest.get(1);
+        match (flag.as_str(), value) {
+            ("--", _) => {
+                rest = &rest[1..];
+                break;
+            }
+            ("-C", Some(dir)) => {
+                scope.restrict_command("cd")?;
+                if let Err(message) = scope.chdir(dir) {
+                    report(format_args!("{}: with: {}", program(), message));
+                    return Ok(1);
+                }
+            }
+            ("-u", Some(mask)) => match u32::from_str_radix(mask, 8) {
+                Ok(bits) if bits <= 0o777 => {
+                    scope.umask(Mode::from_bits_truncate(bits as libc::mode_t))
+                }
+                _ => {
+                    report(format_args!(
+                        "{}: with: {}: octal mask expected",
+                        program(),
+                        mask
+                    ));
+                    return Ok(1);
+                }
+            },
+            ("-e", Some(pair)) => match pair.split_once('=') {
+                Some((name, value)) if is_name(name) => scope.set(name, value)?,
+                _ => {
+                    report(format_args!(
+                        "{}: with: `{}': not a valid assignment",
+                        program(),
+                        pair
+                    ));
+                    return Ok(1);
+                }
+            },
+            ("-C" | "-u" | "-e", None) => return usage(),
+            (flag, _) if flag.starts_with('-') => return usage(),
+            _ => break,
+        }
+        rest = &rest[2..];
+    }
+    if rest.is_empty() {
+        return usage();
+    }
+    scope.run_words(rest)
+}
+
+fn lock(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let usage = || {
+        report(format_args!(
+            "{}: lock: usage: lock [-s|-x|-u] [-n] [-w seconds] (fd | file command [args...])",
+            program()
+        ));
+        Ok(2)
+    };
+    let mut mode = flock::Mode::Exclusive;
+    let mut timeout = None;
+    let mut rest = &args[1..];
+    while let Some(flag) = rest.first() {
+        match flag.as_str() {
+            "-s" => mode = flock::Mode::Shared,
+            "-x" => mode = flock::Mode::Exclusive,
+            "-u" => mode = flock::Mode::Unlock,
+            "-n" => timeout = Some(Duration::ZERO),
+            "-w" => {
+                let Some(seconds) = rest.get(1) else {
+                    return usage();
+                };
+                match seconds.parse::<f64>() {
+                    Ok(n) if n >= 0.0 && n.is_finite() => {
+                        timeout = Some(Duration::from_secs_f64(n))
+                    }
+                    _ => {
+                        report(format_args!(
+                            "{}: lock: {}: invalid timeout",
+                            program(),
+                            seconds
+                        ));
+                        return Ok(1);
+                    }
+                }
+                rest = &rest[1..];
+            }
+            "--" => {
+                rest = &rest[1..];
+                break;
+            }
+            flag if flag.starts_with('-') => return usage(),
+            _ => break,
+        }
+        rest = &rest[1..];
+    }
+
+    let (target, command) = match rest {
+        [fd] if fd.bytes().all(|b| b.is_ascii_digit()) => (fd, None),
+        [file, command @ ..] if !command.is_empty() && mode != flock::Mode::Unlock => {
+            (file, Some(command))
+        }
+        _ => return usage(),
+    };
+    let file = match command {
+        Some(_) => match open_lock_file(shell, target) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                report(format_args!(
+                    "{}: lock: {}: {}",
+                    program(),
+                    target,
+                    describe(&e)
+                ));
+                return Ok(1);
+            }
+        },
+        None => None,
+    };
+    let fd = match &file {
+        Some(file) => file.as_raw_fd(),
+        None => match target.parse::<i32>() {
+            Ok(fd) => fd,
+            Err(_) => {
+                report(format_args!(
+                    "{}: lock: {}: Bad file descriptor",
+                    program(),
+                    target
+                ));
+                return Ok(1);
+            }
+        },
+    };
+    match flock::acquire(fd, mode, timeout) {
+        Ok(Outcome::Locked) => {}
+        Ok(Outcome::TimedOut) => return Ok(flock::TIMED_OUT),
+        Ok(Outcome::Interrupted) => return Ok(130),
+        Err(e) => {
+            report(format_args!(
+                "{}: lock: {}: {}",
+                program(),
+                target,
+                e.desc()
+            ));
+            return Ok(1);
+        }
+    }
+    match command {
+        Some(command) => shell.run_words(command),
+        None => Ok(0),
+    }
+}
+
+fn open_lock_file(shell: &Shell, path: &str) -> io::Result<fs::File> {
+    let path = bytes::to_os(path);
+    if !shell.restricted {
+        match fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+        {
+            Ok(file) => return Ok(file),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(e),
+            Err(_) => {}
+        }
+    }
+    fs::File::open(&path)
+}
+
+fn repeat(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut rest = &args[1..];
+    let errexit = rest.first().is_some_and(|a| a == "-e");
+    if errexit {
+        rest = &rest[1..];
+    }
+    let Some((count, command)) = rest.split_first() else {
+        report(format_args!(
+            "{}: repeat: usage: repeat [-e] count command [args...]",
+            program()
+        ));
+        return Ok(2);
+    };
+    let count = match count.parse::<usize>() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            report(format_args!(
+                "{}: repeat: {}: positive integer expected",
+                program(),
+                count
+            ));
+            return Ok(1);
+        }
+    };
+    if command.is_empty() {
+        report(format_args!("{}: repeat: command expected", program()));
+        return Ok(2);
+    }
+    // One word is a command line of its own; several are already split
+    // and expanded, so they run as a single command with their quoting.
+    let line = match command {
+        [line] => line.clone(),
+        words => words
+            .iter()
+            .map(|w| word::quote(w))
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    let list = parser::parse(&line, shell.lineno)?;
+    shell.in_loop(|shell| {
+        let mut status = 0;
+        for _ in 0..count {
+            if signals::interrupted() {
+                return Ok(130);
+            }
+            status = shell.run_list(&list)?;
+            if shell.loop_exit() || (errexit && status != 0) {
+                break;
+            }
+        }
+        Ok(status)
+    })
+}
+
+fn suspend(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let force = match &args[1..] {
+        [] => false,
+        [flag] if flag == "-f" => true,
+        _ => {
+            report(format_args!("{}: suspend: usage: suspend [-f]", program()));
+            return Ok(2);
+        }
+    };
+    if shell.login && !force {
+        report(format_args!(
+            "{}: suspend: cannot suspend a login shell",
+            program()
+        ));
+        return Ok(1);
+    }
+    io::stdout().flush().ok();
+    io::stderr().flush().ok();
+    shell.restore_terminal();
+    signal::killpg(unistd::getpgrp(), Signal::SIGSTOP)?;
+    shell.restore_terminal();
+    if shell.interactive {
+        shell.update_window_size();
+    }
+    Ok(0)
+}
+
+// Whole hundredths, so 59.999s shows as 0m59.99s rather than 0m60.00s.
+fn format_cpu(time: libc::timeval) -> String {
+    let hundredths = time.tv_sec * 100 + time.tv_usec / 10_000;
+    format!(
+        "{}m{:02}.{:02}s",
+        hundredths / 6000,
+        hundredths % 6000 / 100,
+        hundredths % 100
+    )
+}
+
+fn times(_: &mut Shell, _: &[String]) -> Result<i32> {
+    for who in [libc::RUSAGE_SELF, libc::RUSAGE_CHILDREN] {
+        let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+        if unsafe { libc::getrusage(who, &mut usage) } != 0 {
+            return Err(Errno::last().into());
+        }
+        outln!(
+            "{} {}",
+            format_cpu(usage.ru_utime),
+            format_cpu(usage.ru_stime)
+        );
+    }
+    Ok(0)
+}
+
+fn trap(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut rest = &args[1..];
+    if rest.first().is_some_and(|a| a == "--") {
+        rest = &rest[1..];
+    }
+    if rest.is_empty() || rest[0] == "-p" {
+        let names = match rest.get(1..) {
+            Some(names) if !names.is_empty() => names.to_vec(),
+            _ => shell.traps.keys().cloned().collect(),
+        };
+        for name in names {
+            let name = traps::key(&name).unwrap_or(name);
+            if let Some(handler) = shell.traps.get(&name) {
+                outln!("trap -- {} {}", word::quote(handler), name);
+            }
+        }
+        return Ok(0);
+    }
+    let (handler, names) = match rest {
+        [name] if traps::key(name).is_some() => (None, rest),
+        [handler, names @ ..] if handler == "-" => (None, names),
+        [handler, names @ ..] => (Some(handler.clone()), names),
+        [] => unreachable!(),
+    };
+    let mut status = 0;
+    for name in names {
+        match traps::lookup(name) {
+            Some(trap) => match &handler {
+                Some(handler) => {
+                    shell.traps.insert(trap.to_string(), handler.clone());
+                }
+                None => {
+                    shell.traps.remove(trap);
+                }
+            },
+            None => match (traps::signal(name), &handler) {
+                (Some(signal), Some(handler)) if handler.is_empty() => {
+                    let _ = signals::set_caught(signal, false);
+                    signals::set_ignored(signal, true);
+                    shell
+                        .traps
+                        .insert(signal.as_str()[3..].to_string(), String::new());
+                }
+                (Some(signal), None) => {
+                    let _ = signals::set_caught(signal, false);
+                    signals::set_ignored(signal, false);
+                    shell.traps.remove(&signal.as_str()[3..]);
+                }
+                (Some(signal), Some(handler)) => {
+                    signals::set_ignored(signal, false);
+                    if let Err(e) = signals::set_caught(signal, true) {
+                        report(format_args!("{}: trap: {}: {}", program(), name, e.desc()));
+                        status = 1;
+                        continue;
+                    }
+                    shell
+                        .traps
+                        .insert(signal.as_str()[3..].to_string(), handler.clone());
+                }
+                (None, _) => {
+                    report(format_args!(
+                        "{}: trap: {}: invalid signal specification",
+                        program(),
+                        name
+                    ));
+                    status = 1;
+                }
+            },
+        }
+    }
+    Ok(status)
+}
+
+fn return_builtin(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    if shell.function_depth == 0 && shell.source_depth == 0 {
+        report(format_args!(
+            "{}: return: can only `return' from a function or sourced script",
+            program()
+        ));
+        return Ok(1);
+    }
+    let status = match args.get(1) {
+        Some(arg) => match arg.trim().parse::<i64>() {
+            Ok(status) => Status::new(status).code(),
+            Err(_) => {
+                report(format_args!(
+                    "{}: return: {}: numeric argument required",
+                    program(),
+                    arg
+                ));
+                2
+            }
+        },
+        None => shell.last_status,
+    };
+    shell.control = Some(Control::Return);
+    Ok(status)
+}
+
+fn set(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        let on = match arg.as_str() {
+            "--" => {
+                i += 1;
+                break;
+            }
+            "-o" => true,
+            "+o" => false,
+            _ if arg.starts_with(['-', '+']) && arg.len() > 1 => {
+                let on = arg.starts_with('-');
+                for flag in arg[1..].chars() {
+                    match options::flag_name(flag) {
+                        Some(name) => {
+                            shell.options.set(name, on);
+                        }
+                        None => {
+                            report(format_args!(
+                                "{}: set: {}{}: invalid option",
+                                program(),
+                                &arg[..1],
+                                flag
+                            ));
+                            return Ok(2);
+                        }
+                    }
+                }
+                i += 1;
+                continue;
+            }
+            _ => break,
+        };
+        i += 1;
+        let Some(name) = args.get(i) else {
+            for spec in OPTIONS.iter().filter(|spec| spec.in_set()) {
+                let (name, enabled) = (spec.name, shell.options.get(spec.name));
+                if on {
+                    outln!("{:<15}\t{}", name, if enabled { "on" } else { "off" });
+                } else {
+                    outln!("set {}o {}", if enabled { '-' } else { '+' }, name);
+                }
+            }
+            return Ok(0);
+        };
+        if !options::lookup(name).is_some_and(|spec| spec.in_set()) {
+            report(format_args!(
+                "{}: set: {}: invalid option name",
+                program(),
+                name
+            ));
+            return Ok(1);
+        }
+        shell.options.set(name, on);
+        i += 1;
+    }
+    if i < args.len() || args.get(i - 1).is_some_and(|a| a == "--") {
+        shell.positional = args[i..].to_vec();
+    }
+    Ok(0)
+}
+
+fn shopt(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let (mut mode, mut print, mut quiet, mut set_options) = (None, false, false, false);
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        if arg == "--" {
+            i += 1;
+            break;
+        }
+        if !arg.starts_with('-') || arg.len() == 1 {
+            break;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                's' => mode = Some(true),
+                'u' => mode = Some(false),
+                'p' => print = true,
+                'q' => quiet = true,
+                'o' => set_options = true,
+                _ => {
+                    report(format_args!(
+                        "{}: shopt: -{}: invalid option",
+                        program(),
+                        flag
+                    ));
+                    report(format_args!(
+                        "{}: shopt: usage: shopt [-pqsu] [-o] [optname ...]",
+                        program()
+                    ));
+                    return Ok(2);
+                }
+            }
+        }
+        i += 1;
+    }
+    let visible = |spec: &options::Spec| match set_options {
+        true => spec.in_set(),
+        false => spec.in_shopt(),
+    };
+    let mut specs = Vec::new();
+    for name in &args[i..] {
+        match options::lookup(name).filter(|spec| visible(spec)) {
+            Some(spec) => specs.push(spec),
+            None => {
+                report(format_args!(
+                    "{}: shopt: {}: invalid shell option name",
+                    program(),
+                    name
+                ));
+                return Ok(1);
+            }
+        }
+    }
+    if let Some(on) = mode
+        && !specs.is_empty()
+    {
+        for spec in specs {
+            shell.options.set(spec.name, on);
+        }
+        return Ok(0);
+    }
+    let listed = specs.is_empty();
+    if listed {
+        specs = OPTIONS
+            .iter()
+            .filter(|spec| visible(spec))
+            .filter(|spec| mode.is_none_or(|on| shell.options.get(spec.name) == on))
+            .collect();
+    }
+    let mut status = 0;
+    for spec in specs {
+        let enabled = shell.options.get(spec.name);
+        if !enabled && !listed {
+            status = 1;
+        }
+        if quiet {
+            continue;
+        }
+        let state = if enabled { "on" } else { "off" };
+        match (print, set_options) {
+            (true, true) => outln!("set {}o {}", if enabled { '-' } else { '+' }, spec.name),
+            (true, false) => outln!("shopt {} {}", if enabled { "-s" } else { "-u" }, spec.name),
+            (false, _) => outln!("{:<15}\t{}", spec.name, state),
+        }
+    }
+    Ok(status)
+}
+
+fn source(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let Some(path) = args.get(1) else {
+        report(format_args!(
+            "{}: {}: filename argument required",
+            program(),
+            args[0]
+        ));
+        return Ok(2);
+    };
+    shell.restrict_command(path)?;
+    let src = match fs::read(bytes::to_os(path)) {
+        Ok(src) => bytes::decode(&src),
+        Err(e) => {
+            report(format_args!(
+                "{}: {}: {}: {}",
+                program(),
+                args[0],
+                path,
+                describe(&e)
+            ));
+            return Ok(1);
+        }
+    };
+    if shell.source_depth >= MAX_SOURCE_DEPTH || exec::calls_exhausted() {
+        return Err(ShellError::Internal(format!(
+            "{}: {}: maximum source nesting level exceeded ({})",
+            args[0], path, shell.source_depth
+        )));
+    }
+    let list = parser::parse(&src, 1)?;
+    let saved = if args.len() > 2 {
+        Some(std::mem::replace(&mut shell.positional, args[2..].to_vec()))
+    } else {
+        None
+    };
+    shell.source_depth += 1;
+    let result = shell.run_list(&list);
+    shell.source_depth -= 1;
+    if shell.control == Some(Control::Return) {
+        shell.control = None;
+    }
+    if let Some(saved) = saved {
+        shell.positional = saved;
+    }
+    result
+}
+
+fn unset(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut status = 0;
+    let mut functions = false;
+    let mut variables = false;
+    let mut names = &args[1..];
+    while let Some(flag) = names.first() {
+        match flag.as_str() {
+            "-f" => functions = true,
+            "-v" => variables = true,
+            _ => break,
+        }
+        names = &names[1..];
+    }
+    for name in names {
+        if !functions {
+            shell.restrict_variable(name)?;
+            let base = name.split_once('[').map_or(name.as_str(), |(base, _)| base);
+            if shell.vars.is_readonly(base) {
+                report(format_args!(
+                    "{}: unset: {}: cannot unset: readonly variable",
+                    program(),
+                    base
+                ));
+                status = 1;
+                continue;
+            }
+        }
+        if let Some((array, index)) = name.strip_suffix(']').and_then(|n| n.split_once('['))
+            && is_name(array)
+        {
+            let index = shell.subscript(array, &word::parse(index))?;
+            shell.vars.unset_element(array, index);
+        } else if functions {
+            shell.functions.remove(name);
+        } else if variables || shell.vars.get(name).is_some() {
+            shell.vars.unset(name);
+        } else {
+            shell.functions.remove(name);
+        }
+    }
+    shell.refresh_colors();
+    Ok(status)
+}
+
+fn let_builtin(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    if args.len() < 2 {
+        report(format_args!("{}: let: expression expected", program()));
+        return Ok(1);
+    }
+    let mut value = 0;
+    for expr in &args[1..] {
+        value = shell.eval_arith(expr)?;
+    }
+    Ok((value == 0) as i32)
+}
+
+fn bind(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut command = false;
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        match arg.as_str() {
+            "--" => {
+                i += 1;
+                break;
+            }
+            "-l" => {
+                for (name, _) in keymap::FUNCTIONS {
+                    outln!("{}", name);
+                }
+            }
+            "-p" | "-X" => {
+                for (seq, action) in shell.keymap.bindings() {
+                    match action {
+                        Action::Function(function) if arg == "-p" => outln!(
+                            "\"{}\": {}",
+                            keymap::format_sequence(seq),
+                            keymap::function_name(*function)
+                        ),
+                        Action::Command(text) if arg == "-X" => outln!(
+                            "\"{}\": {}",
+                            keymap::format_sequence(seq),
+                            keymap::quote(text)
+                        ),
+                        _ => {}
+                    }
+                }
+            }
+            "-x" => command = true,
+            "-r" => {
+                i += 1;
+                let Some(seq) = args.get(i) else {
+                    report(format_args!(
+                        "{}: bind: -r: option requires an argument",
+                        program()
+                    ));
+                    return Ok(2);
+                };
+                match keymap::parse_sequence(seq.trim_matches('"')) {
+                    Ok(seq) => {
+                        shell.keymap.unbind(&seq);
+                    }
+                    Err(message) => {
+                        report(format_args!("{}: bind: {}", program(), message));
+                        return Ok(1);
+                    }
+                }
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                report(format_args!("{}: bind: {}: invalid option", program(), arg));
+                report(format_args!(
+                    "{}: bind: usage: bind [-lpX] [-r keyseq] [-x] [keyseq:function ...]",
+                    program()
+                ));
+                return Ok(2);
+            }
+            _ => break,
+        }
+        i += 1;
+    }
+    let mut status = 0;
+    for spec in &args[i..] {
+        let (seq, target) = match keymap::parse_binding(spec) {
+            Ok(binding) => binding,
+            Err(message) => {
+                report(format_args!("{}: bind: {}", program(), message));
+                status = 1;
+                continue;
+            }
+        };
+        let action = match command {
+            true => Action::Command(target),
+            false => match keymap::function(&target) {
+                Some(function) => Action::Function(function),
+                None => {
+                    report(format_args!(
+                        "{}: bind: {}: unknown function name",
+                        program(),
+                        target
+                    ));
+                    status = 1;
+                    continue;
+                }
+            },
+        };
+        shell.keymap.bind(seq, action);
+    }
+    Ok(status)
+}
+
+fn env(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    if args.len() > 1 {
+        io::stdout().flush()?;
+        let status = process::Command::new("env")
+            .args(&args[1..])
+            .env_clear()
+            .envs(shell.vars.exported())
+            .status();
+        return match status {
+            Ok(status) => Ok(status
+                .code()
+                .or(status.signal().map(|signal| 128 + signal))
+                .unwrap_or(1)),
+            Err(e) => {
+                report(format_args!("{}: env: {}", program(), describe(&e)));
+                Ok(127)
+            }
+        };
+    }
+    let mut out = io::stdout().lock();
+    for (name, value) in shell.vars.exported() {
+        writeln!(out, "{}={}", name, value)?;
+    }
+    Ok(0)
+}
+
+fn expand_debug(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    if args.len() < 2 {
+        report(format_args!(
+            "{}: expand-debug: usage: expand-debug word ...",
+            program()
+        ));
+        return Ok(2);
+    }
+    let mut lines = Vec::new();
+    for arg in &args[1..] {
+        lines.push(format!("word: {}", arg));
+        shell.expand_stages(&word::parse(arg), &mut |stage, fields| {
+            let shown: Vec<String> = fields.iter().map(|f| format!("<{}>", f)).collect();
+            lines.push(format!("  {}: {}", stage.name(), shown.join(" ")));
+        })?;
+    }
+    let mut out = io::stdout().lock();
+    for line in lines {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(0)
+}
+
+fn export(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let operands = match args.get(1).map(String::as_str) {
+        Some("-p") => &args[2..],
+        Some("--") => &args[2..],
+        _ => &args[1..],
+    };
+    if operands.is_empty() {
+        let mut out = io::stdout().lock();
+        for (name, value) in shell.vars.exported() {
+            // `_` changes with every command; reading it back means nothing.
+            if name == "_" {
+                continue;
+            }
+            writeln!(out, "export {}={}", name, word::quote(value))?;
+        }
+        return Ok(0);
+    }
+    let mut status = 0;
+    for arg in operands {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_name(name) {
+            report(format_args!(
+                "{}: export: `{}': not a valid identifier",
+                program(),
+                arg
+            ));
+            status = 1;
+            continue;
+        }
+        shell.restrict_variable(name)?;
+        if let Some(value) = value {
+            shell.writable(name)?;
+            shell.vars.set(name, value);
+            shell.assign_dynamic(name, value);
+        }
+        shell.vars.export(name);
+    }
+    Ok(status)
+}
+
+fn readonly(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let operands = match args.get(1).map(String::as_str) {
+        Some("-p") => &args[2..],
+        Some("--") => &args[2..],
+        _ => &args[1..],
+    };
+    if operands.is_empty() {
+        let mut out = io::stdout().lock();
+        for (name, value) in shell.vars.readonly() {
+            match value {
+                Value::Scalar(value) => writeln!(out, "readonly {}={}", name, word::quote(value))?,
+                Value::Array(elements) => {
+                    let elements: Vec<String> = elements
+                        .iter()
+                        .map(|(i, v)| format!("[{}]={}", i, word::quote(v)))
+                        .collect();
+                    writeln!(out, "readonly {}=({})", name, elements.join(" "))?
+                }
+            }
+        }
+        return Ok(0);
+    }
+    let mut status = 0;
+    for arg in operands {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_name(name) {
+            report(format_args!(
+                "{}: readonly: `{}': not a valid identifier",
+                program(),
+                arg
+            ));
+            status = 1;
+            continue;
+        }
+        shell.restrict_variable(name)?;
+        if let Some(value) = value {
+            if let Err(e) = shell.writable(name) {
+                report(&e);
+                status = 1;
+                continue;
+            }
+            shell.vars.set(name, value);
+            shell.assign_dynamic(name, value);
+        }
+        shell.vars.set_readonly(name);
+    }
+    Ok(status)
+}
+
+fn local(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    for arg in &args[1..] {
+        let name = arg.split_once('=').map_or(arg.as_str(), |(name, _)| name);
+        shell.restrict_variable(name)?;
+        shell.writable(name)?;
+    }
+    let Some(frame) = shell.locals.last_mut() else {
+        report(format_args!(
+            "{}: local: can only be used in a function",
+            program()
+        ));
+        return Ok(1);
+    };
+    let mut status = 0;
+    for arg in &args[1..] {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_name(name) {
+            report(format_args!(
+                "{}: local: `{}': not a valid identifier",
+                program(),
+                arg
+            ));
+            status = 1;
+            continue;
+        }
+        if !frame.iter().any(|(saved, _)| saved == name) {
+            frame.push((name.to_string(), shell.vars.variable(name).cloned()));
+        }
+        match value {
+            Some(value) => shell.vars.set(name, value),
+            None => shell.vars.unset(name),
+        }
+    }
+    Ok(status)
+}
+
+pub fn read_line(fd: i32, raw: bool) -> Result<Option<(String, bool)>> {
+    let mut line = Vec::new();
+    let mut escaped = false;
+    let mut complete = false;
+    let mut overflow = false;
+    loop {
+        let mut byte = [0u8];
+        match unistd::read(fd, &mut byte) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(Errno::EINTR) if signals::interrupted() => return Ok(None),
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(ShellError::Internal(format!("read: {}", e.desc()))),
+        }
+        match byte[0] {
+            b'\n' if escaped => escaped = false,
+            b'\n' => {
+                complete = true;
+                break;
+            }
+            b'\\' if !raw && !escaped => escaped = true,
+            _ if line.len() >= MAX_LINE => overflow = true,
+            b => {
+                escaped = false;
+                line.push(b);
+            }
+        }
+    }
+    if overflow {
+        return Err(ShellError::Input(format!(
+            "input line too long (limit is {} bytes)",
+            MAX_LINE
+        )));
+    }
+    Ok(Some((bytes::decode(&line), complete)))
+}
+
+fn mapfile(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut strip = false;
+    let mut limit = None;
+    let mut origin = None;
+    let mut skip = 0;
+    let mut fd = libc::STDIN_FILENO;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-t" => strip = true,
+            flag @ ("-n" | "-O" | "-s" | "-u") => {
+                i += 1;
+                let arg = args.get(i).map(String::as_str).unwrap_or("");
+                match (flag, arg.parse::<i32>()) {
+                    ("-n", Ok(n)) if n >= 0 => limit = (n > 0).then_some(n as usize),
+                    ("-O", Ok(n)) if n >= 0 => origin = Some(n as usize),
+                    ("-s", Ok(n)) if n >= 0 => skip = n as usize,
+                    ("-u", Ok(n)) if n >= 0 => fd = n,
+                    ("-n" | "-s", _) => {
+                        report(format_args!(
+                            "{}: {}: {}: invalid line count",
+                            program(),
+                            args[0],
+                            arg
+                        ));
+                        return Ok(1);
+                    }
+                    ("-O", _) => {
+                        report(format_args!(
+                            "{}: {}: {}: invalid array origin",
+                            program(),
+                            args[0],
+                            arg
+                        ));
+                        return Ok(1);
+                    }
+                    _ => {
+                        report(format_args!(
+                            "{}: {}: {}: invalid file descriptor specification",
+                            program(),
+                            args[0],
+                            arg
+                        ));
+                        return Ok(1);
+                    }
+                }
+            }
+            _ => break,
+        }
+        i += 1;
+    }
+    let name = args.get(i).map(String::as_str).unwrap_or("MAPFILE");
+    if !is_name(name) {
+        report(format_args!(
+            "{}: {}: `{}': not a valid identifier",
+            program(),
+            args[0],
+            name
+        ));
+        return Ok(1);
+    }
+    shell.restrict_variable(name)?;
+    shell.writable(name)?;
+
+    let mut lines = Vec::new();
+    let mut current = Vec::new();
+    let mut buf = [0u8; 4096];
+    'read: while limit.is_none_or(|n| lines.len() < n) {
+        let len = match unistd::read(fd, &mut buf) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(Errno::EINTR) if signals::interrupted() => return Ok(130),
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(ShellError::Internal(format!("{}: {}", args[0], e.desc()))),
+        };
+        for (pos, &byte) in buf[..len].iter().enumerate() {
+            if byte != b'\n' || !strip {
+                current.push(byte);
+            }
+            if byte == b'\n' && skip > 0 {
+                skip -= 1;
+                current.clear();
+            } else if byte == b'\n' {
+                lines.push(bytes::decode(&current));
+                current.clear();
+                if limit.is_some_and(|n| lines.len() >= n) {
+                    let unread = (len - pos - 1) as libc::off_t;
+                    if unread > 0 {
+                        unsafe {
+                            libc::lseek(fd, -unread, libc::SEEK_CUR);
+                        }
+                    }
+                    break 'read;
+                }
+            }
+        }
+    }
+    if !current.is_empty() && skip == 0 {
+        lines.push(bytes::decode(&current));
+    }
+    match origin {
+        Some(origin) => {
+            for (offset, line) in lines.into_iter().enumerate() {
+                shell.vars.set_element(name, origin + offset, line);
+            }
+        }
+        None => shell.vars.set_array(name, lines),
+    }
+    Ok(0)
+}
+
+fn read(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let mut raw = false;
+    let mut fd = libc::STDIN_FILENO;
+    let mut array = None;
+    let mut names = Vec::new();
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-r" => raw = true,
+            "-u" => {
+                i += 1;
+                let arg = args.get(i).map(String::as_str).unwrap_or("");
+                match arg.parse::<i32>() {
+                    Ok(n) if n >= 0 => fd = n,
+                    _ => {
+                        report(format_args!(
+                            "{}: read: {}: invalid file descriptor specification",
+                            program(),
+                            arg
+                        ));
+                        return Ok(1);
+                    }
+                }
+            }
+            "-p" => {
+                i += 1;
+                if let Some(prompt) = args.get(i) {
+                    eprint!("{}", prompt);
+                }
+            }
+            "-a" => {
+                i += 1;
+                let Some(name) = args.get(i) else {
+                    report(format_args!(
+                        "{}: read: -a: option requires an argument",
+                        program()
+                    ));
+                    return Ok(2);
+                };
+                array = Some(name.clone());
+            }
+            _ => {
+                names.extend_from_slice(&args[i..]);
+                break;
+            }
+        }
+        i += 1;
+    }
+
+    for name in array.iter().chain(&names) {
+        let base = name.split_once('[').map_or(name.as_str(), |(base, _)| base);
+        if !is_name(base) || (array.as_ref() == Some(name) && base != name) {
+            report(format_args!(
+                "{}: read: `{}': not a valid identifier",
+                program(),
+                name
+            ));
+            return Ok(1);
+        }
+        shell.restrict_variable(base)?;
+        shell.writable(base)?;
+    }
+    let Some((line, complete)) = read_line(fd, raw)? else {
+        return Ok(130);
+    };
+    let status = if complete { 0 } else { 1 };
+
+    if let Some(array) = array {
+        let words = line.split([' ', '\t', '\n']).filter(|w| !w.is_empty());
+        shell
+            .vars
+            .set_array(&array, words.map(str::to_string).collect());
+    } else if names.is_empty() {
+        shell.vars.set("REPLY", line);
+    } else {
+        let mut rest = line.trim_start_matches([' ', '\t', '\n']);
+        for (i, name) in names.iter().enumerate() {
+            let value = if i == names.len() - 1 {
+                rest.trim_end_matches([' ', '\t', '\n'])
+            } else {
+                &rest[..rest.find([' ', '\t', '\n']).unwrap_or(rest.len())]
+            };
+            if !assign(shell, "read", name, value.to_string())? {
+                return Ok(1);
+            }
+            rest = rest[value.len()..].trim_start_matches([' ', '\t', '\n']);
+        }
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_times() {
+        let time = |tv_sec, tv_usec| libc::timeval { tv_sec, tv_usec };
+        for (sec, usec, expected) in [
+            (0, 0, "0m00.00s"),
+            (0, 9_999, "0m00.00s"),
+            (0, 10_000, "0m00.01s"),
+            (1, 500_000, "0m01.50s"),
+            (59, 999_999, "0m59.99s"),
+            (60, 0, "1m00.00s"),
+            (754, 321_000, "12m34.32s"),
+            (6000, 0, "100m00.00s"),
+        ] {
+            assert_eq!(format_cpu(time(sec, usec)), expected, "{}.{}", sec, usec);
+        }
+    }
+}
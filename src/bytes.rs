@@ -0,0 +1,117 @@
+use std::ffi::{CString, NulError, OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+const ESCAPE_BASE: u32 = 0x10ff00;
+
+fn escape(b: u8) -> char {
+    char::from_u32(ESCAPE_BASE + b as u32).unwrap()
+}
+
+fn is_escape(c: char) -> bool {
+    matches!((c as u32).checked_sub(ESCAPE_BASE), Some(0x80..=0xff))
+}
+
+// Bytes that are not UTF-8 become U+10FF80..U+10FFFF. Genuine characters
+// in that range are escaped byte by byte as well, so that encode always
+// gives back the original bytes.
+pub fn decode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid();
+        if valid.contains(is_escape) {
+            for c in valid.chars() {
+                match is_escape(c) {
+                    true => c
+                        .encode_utf8(&mut [0; 4])
+                        .bytes()
+                        .for_each(|b| out.push(escape(b))),
+                    false => out.push(c),
+                }
+            }
+        } else {
+            out.push_str(valid);
+        }
+        for &b in chunk.invalid() {
+            out.push(escape(b));
+        }
+    }
+    out
+}
+
+pub fn encode(s: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        match is_escape(c) {
+            true => out.push((c as u32 - ESCAPE_BASE) as u8),
+            false => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    out
+}
+
+pub fn from_os(s: &OsStr) -> String {
+    decode(s.as_bytes())
+}
+
+pub fn to_os(s: &str) -> OsString {
+    OsString::from_vec(encode(s))
+}
+
+pub fn cstring(s: &str) -> Result<CString, NulError> {
+    CString::new(encode(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_bytes_round_trip() {
+        for bytes in [
+            &b"plain"[..],
+            b"\xff",
+            b"caf\xe9",
+            b"\xc3",
+            b"\xc3\xa9\x80",
+            b"\xed\xa0\x80",
+            b"\xf4\x90\x80\x80",
+        ] {
+            assert_eq!(encode(&decode(bytes)), bytes);
+        }
+        assert_eq!(decode(b"caf\xc3\xa9"), "caf\u{e9}");
+        assert_eq!(decode(b"\xff").chars().count(), 1);
+    }
+
+    #[test]
+    fn escape_range_characters_round_trip() {
+        for c in ['\u{10ff7f}', '\u{10ff80}', '\u{10ffc3}', '\u{10ffff}'] {
+            let text = format!("a{}b", c);
+            assert_eq!(encode(&decode(text.as_bytes())), text.as_bytes());
+        }
+        let raw = decode(b"\xff");
+        let genuine = decode("\u{10ffff}".as_bytes());
+        assert_ne!(raw, genuine);
+        assert_eq!(encode(&genuine), "\u{10ffff}".as_bytes());
+    }
+
+    #[test]
+    fn random_bytes_round_trip() {
+        let mut seed = 0x2545f491u32;
+        for _ in 0..2000 {
+            let len = (seed % 24) as usize;
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 17;
+                    seed ^= seed << 5;
+                    match seed % 4 {
+                        0 => 0xf4,
+                        1 => 0x8f,
+                        _ => (seed >> 8) as u8,
+                    }
+                })
+                .collect();
+            assert_eq!(encode(&decode(&bytes)), bytes, "{:x?}", bytes);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use nix::unistd;
+
+use crate::shell::Shell;
+use crate::width::{HIDE_END, HIDE_START};
+
+const DEFAULTS: &[(&str, &str)] = &[
+    ("error", "31"),
+    ("job", "33"),
+    ("prompt_status", "31"),
+    ("warning", "33"),
+];
+
+static PALETTE: RwLock<Option<Palette>> = RwLock::new(None);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Palette {
+    codes: BTreeMap<String, String>,
+    disabled: bool,
+}
+
+fn is_code(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit() || b == b';')
+}
+
+impl Palette {
+    pub fn parse(spec: Option<&str>, disabled: bool) -> Palette {
+        let mut codes: BTreeMap<String, String> = DEFAULTS
+            .iter()
+            .map(|(key, code)| (key.to_string(), code.to_string()))
+            .collect();
+        for pair in spec.unwrap_or("").split(':') {
+            match pair.split_once('=') {
+                Some((key, "")) => {
+                    codes.remove(key);
+                }
+                Some((key, code)) if is_code(code) => {
+                    codes.insert(key.to_string(), code.to_string());
+                }
+                _ => {}
+            }
+        }
+        Palette { codes, disabled }
+    }
+
+    pub fn code(&self, key: &str) -> Option<&str> {
+        match self.disabled {
+            true => None,
+            false => self.codes.get(key).map(String::as_str),
+        }
+    }
+
+    pub fn paint(&self, key: &str, text: &str, hidden: bool) -> String {
+        let Some(code) = self.code(key).filter(|_| !text.is_empty()) else {
+            return text.to_string();
+        };
+        match hidden {
+            true => format!(
+                "{}\x1b[{}m{}{}{}\x1b[0m{}",
+                HIDE_START, code, HIDE_END, text, HIDE_START, HIDE_END
+            ),
+            false => format!("\x1b[{}m{}\x1b[0m", code, text),
+        }
+    }
+}
+
+fn disabled(no_color: Option<&str>) -> bool {
+    no_color.is_some_and(|v| !v.is_empty())
+}
+
+pub fn configure(spec: Option<&str>, no_color: Option<&str>) {
+    if let Ok(mut palette) = PALETTE.write() {
+        *palette = Some(Palette::parse(spec, disabled(no_color)));
+    }
+}
+
+pub fn palette() -> Palette {
+    match PALETTE.read() {
+        Ok(palette) => palette
+            .clone()
+            .unwrap_or_else(|| Palette::parse(None, false)),
+        Err(_) => Palette::parse(None, true),
+    }
+}
+
+pub const RESET: &str = "\x1b[0m";
+
+// The escape that turns `key`'s color on, for messages a forked child
+// writes without allocating.
+pub fn start(key: &str) -> Option<String> {
+    palette().code(key).map(|code| format!("\x1b[{}m", code))
+}
+
+pub fn style(key: &str, text: &str) -> String {
+    let prompt = key.starts_with("prompt_");
+    let fd = match prompt {
+        true => libc::STDOUT_FILENO,
+        false => libc::STDERR_FILENO,
+    };
+    match unistd::isatty(fd) {
+        Ok(true) => palette().paint(key, text, prompt),
+        _ => text.to_string(),
+    }
+}
+
+impl Shell {
+    pub fn refresh_colors(&self) {
+        configure(self.vars.get("VSSH_COLORS"), self.vars.get("NO_COLOR"));
+    }
+
+    pub fn assign_colors(&self, name: &str, value: &str) {
+        match name {
+            "VSSH_COLORS" => configure(Some(value), self.vars.get("NO_COLOR")),
+            "NO_COLOR" => configure(self.vars.get("VSSH_COLORS"), Some(value)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::width;
+
+    #[test]
+    fn specs_override_and_remove_defaults() {
+        let palette = Palette::parse(None, false);
+        assert_eq!(palette.code("error"), Some("31"));
+        assert_eq!(palette.code("job"), Some("33"));
+        assert_eq!(palette.code("prompt_cwd"), None);
+        let palette = Palette::parse(Some("error=31;1:prompt_cwd=34:job=:bad=x1:noequals"), false);
+        assert_eq!(palette.code("error"), Some("31;1"));
+        assert_eq!(palette.code("prompt_cwd"), Some("34"));
+        assert_eq!(palette.code("job"), None);
+        assert_eq!(palette.code("bad"), None);
+        assert_eq!(palette.code("warning"), Some("33"));
+        let palette = Palette::parse(Some("error=35"), true);
+        assert_eq!(palette.code("error"), None);
+    }
+
+    #[test]
+    fn painting_emits_sgr_sequences() {
+        let palette = Palette::parse(Some("error=31;1:prompt_cwd=34"), false);
+        assert_eq!(
+            palette.paint("error", "vssh: oops", false).as_bytes(),
+            b"\x1b[31;1mvssh: oops\x1b[0m"
+        );
+        assert_eq!(
+            palette.paint("job", "[1]+  Done", false),
+            "\x1b[33m[1]+  Done\x1b[0m"
+        );
+        assert_eq!(palette.paint("prompt_user", "me", false), "me");
+        assert_eq!(palette.paint("error", "", false), "");
+        let plain = Palette::parse(Some("error=31"), true);
+        assert_eq!(plain.paint("error", "vssh: oops", false), "vssh: oops");
+    }
+
+    #[test]
+    fn prompt_colors_are_hidden_from_the_width() {
+        let palette = Palette::parse(Some("prompt_cwd=34"), false);
+        let painted = palette.paint("prompt_cwd", "~/src", true);
+        assert_eq!(painted, "\x01\x1b[34m\x02~/src\x01\x1b[0m\x02");
+        assert_eq!(width::str_width(&painted), 5);
+        assert_eq!(width::strip_markers(&painted), "\x1b[34m~/src\x1b[0m");
+    }
+}
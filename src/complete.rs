@@ -0,0 +1,405 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::builtins;
+use crate::bytes;
+use crate::shell::Shell;
+use crate::word::is_name;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Commands,
+    Files,
+    Directories,
+}
+
+pub const COMPLETIONS: &[(&str, Kind)] = &[
+    ("cd", Kind::Directories),
+    ("pushd", Kind::Directories),
+    ("rmdir", Kind::Directories),
+];
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub text: String,
+    pub display: String,
+    pub suffix: &'static str,
+}
+
+#[derive(Debug, Default)]
+pub struct Completion {
+    pub start: usize,
+    pub word: String,
+    pub candidates: Vec<Candidate>,
+}
+
+struct Context {
+    start: usize,
+    prefix: String,
+    quote: Option<char>,
+    command: Option<String>,
+    assignment: bool,
+}
+
+fn context(line: &[char]) -> Context {
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut start = line.len();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut assignment = false;
+    let mut value = 0;
+    let mut i = 0;
+    while i < line.len() {
+        let c = line[i];
+        i += 1;
+        if let Some(q) = quote {
+            match c {
+                c if c == q => quote = None,
+                '\\' if q == '"' && i < line.len() => {
+                    current.push(line[i]);
+                    i += 1;
+                }
+                c => current.push(c),
+            }
+            continue;
+        }
+        match c {
+            ' ' | '\t' | '\n' | '|' | '&' | ';' | '(' | ')' | '<' | '>' => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+                if !matches!(c, ' ' | '\t' | '<' | '>') {
+                    words.clear();
+                }
+                continue;
+            }
+            _ if !in_word => {
+                in_word = true;
+                assignment = false;
+                value = 0;
+                start = i - 1;
+            }
+            _ => {}
+        }
+        match c {
+            '=' if !assignment && is_name(&current) => {
+                current.push(c);
+                assignment = true;
+                value = current.len();
+                start = i;
+            }
+            ':' if assignment => {
+                current.push(c);
+                value = current.len();
+                start = i;
+            }
+            '\'' | '"' => quote = Some(c),
+            '\\' if i < line.len() => {
+                current.push(line[i]);
+                i += 1;
+            }
+            c => current.push(c),
+        }
+    }
+    if !in_word {
+        start = line.len();
+        assignment = false;
+        value = current.len();
+    }
+    let command = words
+        .into_iter()
+        .find(|w| !w.split_once('=').is_some_and(|(name, _)| is_name(name)));
+    Context {
+        start,
+        prefix: current[value..].to_string(),
+        quote,
+        command,
+        assignment,
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, c) in text.chars().enumerate() {
+        if " \t\n'\"\\$`&|;<>()*?[]#{}!".contains(c) || (c == '~' && i > 0) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn is_dir(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.is_dir())
+}
+
+impl Shell {
+    fn tilde_path(&self, prefix: &str) -> String {
+        match (prefix.strip_prefix("~/"), self.vars.get("HOME")) {
+            (Some(rest), Some(home)) => format!("{}/{}", home.trim_end_matches('/'), rest),
+            _ => prefix.to_string(),
+        }
+    }
+
+    fn complete_files(&self, prefix: &str, dirs_only: bool, out: &mut Vec<Candidate>) {
+        let (dir_part, base) = match prefix.rfind('/') {
+            Some(i) => (&prefix[..=i], &prefix[i + 1..]),
+            None => ("", prefix),
+        };
+        let dir = match dir_part {
+            "" => ".".to_string(),
+            dir => self.tilde_path(dir),
+        };
+        let Ok(entries) = fs::read_dir(bytes::to_os(&dir)) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = bytes::from_os(&entry.file_name());
+            if !name.starts_with(base) || (name.starts_with('.') && !base.starts_with('.')) {
+                continue;
+            }
+            let directory = is_dir(&entry.path());
+            if dirs_only && !directory {
+                continue;
+            }
+            let slash = if directory { "/" } else { "" };
+            out.push(Candidate {
+                text: format!("{}{}{}", dir_part, name, slash),
+                display: format!("{}{}", name, slash),
+                suffix: if directory { "" } else { " " },
+            });
+        }
+    }
+
+    fn complete_cdpath(&self, prefix: &str, out: &mut Vec<Candidate>) {
+        if prefix.starts_with(['/', '.', '~']) {
+            return;
+        }
+        let cdpath = self.vars.get("CDPATH").unwrap_or("").to_string();
+        for dir in cdpath.split(':').filter(|d| !d.is_empty() && *d != ".") {
+            let mut found = Vec::new();
+            self.complete_files(
+                &format!("{}/{}", dir.trim_end_matches('/'), prefix),
+                true,
+                &mut found,
+            );
+            for candidate in found {
+                out.push(Candidate {
+                    text: candidate.display.clone(),
+                    display: candidate.display,
+                    suffix: "",
+                });
+            }
+        }
+    }
+
+    fn complete_commands(&mut self, prefix: &str, out: &mut Vec<Candidate>) {
+        let functions: Vec<String> = self.functions.keys().cloned().collect();
+        let names: BTreeSet<String> = builtins::NAMES
+            .iter()
+            .map(|s| s.to_string())
+            .chain(functions)
+            .chain(self.path_commands().iter().cloned())
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        out.extend(names.into_iter().map(|name| Candidate {
+            text: name.clone(),
+            display: name,
+            suffix: " ",
+        }));
+    }
+
+    fn complete_variables(&self, line: &[char], out: &mut Vec<Candidate>) -> Option<usize> {
+        let mut start = line.len();
+        while start > 0 && (line[start - 1].is_ascii_alphanumeric() || line[start - 1] == '_') {
+            start -= 1;
+        }
+        let prefix: String = line[start..].iter().collect();
+        if prefix.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        let brace = start > 0 && line[start - 1] == '{';
+        let dollar = start - brace as usize;
+        if dollar == 0 || line[dollar - 1] != '$' || (dollar > 1 && line[dollar - 2] == '\\') {
+            return None;
+        }
+        let names: BTreeSet<&String> = self
+            .vars
+            .names()
+            .filter(|n| n.starts_with(&prefix))
+            .collect();
+        for name in names {
+            let (text, suffix) = match brace {
+                true => (format!("${{{}}}", name), ""),
+                false => (format!("${}", name), " "),
+            };
+            out.push(Candidate {
+                display: text.clone(),
+                text,
+                suffix,
+            });
+        }
+        Some(dollar - 1)
+    }
+
+    fn complete_users(&self, prefix: &str, out: &mut Vec<Candidate>) {
+        let mut names = BTreeSet::new();
+        unsafe {
+            libc::setpwent();
+            loop {
+                let entry = libc::getpwent();
+                if entry.is_null() {
+                    break;
+                }
+                let name = std::ffi::CStr::from_ptr((*entry).pw_name)
+                    .to_string_lossy()
+                    .into_owned();
+                if name.starts_with(prefix) {
+                    names.insert(name);
+                }
+            }
+            libc::endpwent();
+        }
+        out.extend(names.into_iter().map(|name| Candidate {
+            text: format!("~{}/", name),
+            display: format!("~{}/", name),
+            suffix: "",
+        }));
+    }
+
+    pub fn complete(&mut self, line: &[char]) -> Completion {
+        let ctx = context(line);
+        let mut candidates = Vec::new();
+        if ctx.quote != Some('\'')
+            && let Some(start) = self.complete_variables(line, &mut candidates)
+        {
+            if ctx.quote.is_some() {
+                candidates.iter_mut().for_each(|c| c.suffix = "");
+            }
+            return Completion {
+                start,
+                word: line[start..].iter().collect(),
+                candidates,
+            };
+        }
+        let word: String = line[ctx.start..].iter().collect();
+        if let Some(user) = word.strip_prefix('~')
+            && !user.contains('/')
+        {
+            self.complete_users(user, &mut candidates);
+            return Completion {
+                start: ctx.start,
+                word,
+                candidates,
+            };
+        }
+        let kind = match &ctx.command {
+            _ if ctx.assignment => Kind::Files,
+            None if !ctx.prefix.contains('/') => Kind::Commands,
+            None => Kind::Files,
+            Some(command) => COMPLETIONS
+                .iter()
+                .find(|(name, _)| name == command)
+                .map_or(Kind::Files, |(_, kind)| *kind),
+        };
+        match kind {
+            Kind::Commands => self.complete_commands(&ctx.prefix, &mut candidates),
+            Kind::Files => self.complete_files(&ctx.prefix, false, &mut candidates),
+            Kind::Directories => {
+                self.complete_files(&ctx.prefix, true, &mut candidates);
+                if ctx.command.as_deref() == Some("cd") {
+                    self.complete_cdpath(&ctx.prefix, &mut candidates);
+                }
+            }
+        }
+        let mut seen = BTreeSet::new();
+        candidates.retain(|c| seen.insert(c.text.clone()));
+        candidates.sort_by(|a, b| a.text.cmp(&b.text));
+        for candidate in &mut candidates {
+            candidate.text = match ctx.quote {
+                Some(quote) => format!("{}{}", quote, candidate.text),
+                None => escape(&candidate.text),
+            };
+        }
+        Completion {
+            start: ctx.start,
+            word,
+            candidates,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vars::Variables;
+    use std::fs;
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    #[test]
+    fn assignment_contexts() {
+        for (line, start, prefix, assignment, command) in [
+            ("PATH=$PATH:/opt/to", 11, "/opt/to", true, None),
+            ("make PREFIX=/usr/lo", 12, "/usr/lo", true, Some("make")),
+            ("export CDPATH=~:~/wo", 16, "~/wo", true, Some("export")),
+            ("x=a:b:", 6, "", true, None),
+            ("X=1 ca", 4, "ca", false, None),
+            ("echo a:b", 5, "a:b", false, Some("echo")),
+            ("echo 1x=/us", 5, "1x=/us", false, Some("echo")),
+            ("echo a=b=/us", 7, "b=/us", true, Some("echo")),
+        ] {
+            let ctx = context(&chars(line));
+            assert_eq!(ctx.start, start, "{}", line);
+            assert_eq!(ctx.prefix, prefix, "{}", line);
+            assert_eq!(ctx.assignment, assignment, "{}", line);
+            assert_eq!(ctx.command.as_deref(), command, "{}", line);
+        }
+    }
+
+    #[test]
+    fn values_complete_as_files() {
+        let root = std::env::temp_dir().join(format!("vssh-complete-{}", std::process::id()));
+        fs::create_dir_all(root.join("opt/tools")).unwrap();
+        fs::create_dir_all(root.join("home/work")).unwrap();
+        fs::write(root.join("opt/todo"), "").unwrap();
+        let root_text = root.display().to_string();
+        let home = format!("{}/home", root_text);
+        let mut shell = Shell::with_vars(Variables::from_pairs([
+            ("HOME".to_string(), home.clone()),
+            ("PATH".to_string(), "/nonexistent".to_string()),
+        ]));
+        for (line, texts) in [
+            (
+                format!("PATH=$PATH:{}/opt/to", root_text),
+                vec![
+                    format!("{}/opt/todo", root_text),
+                    format!("{}/opt/tools/", root_text),
+                ],
+            ),
+            (
+                format!("make PREFIX={}/opt/too", root_text),
+                vec![format!("{}/opt/tools/", root_text)],
+            ),
+            (
+                "export CDPATH=~:~/wo".to_string(),
+                vec!["~/work/".to_string()],
+            ),
+        ] {
+            let completion = shell.complete(&chars(&line));
+            let found: Vec<&str> = completion
+                .candidates
+                .iter()
+                .map(|c| c.text.as_str())
+                .collect();
+            assert_eq!(found, texts, "{}", line);
+            assert_eq!(line[completion.start..], completion.word, "{}", line);
+        }
+        fs::remove_dir_all(root).ok();
+    }
+}
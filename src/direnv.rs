@@ -0,0 +1,179 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::bytes;
+use crate::error::{describe, program, report, Result};
+use crate::parser;
+use crate::sha256;
+use crate::shell::Shell;
+use crate::vars::Variable;
+
+pub const ENV_FILE: &str = ".vssh_env";
+
+#[derive(Debug, Clone)]
+pub struct DirectoryEnv {
+    pub dir: PathBuf,
+    pub saved: Vec<(String, Option<Variable>)>,
+}
+
+enum Trust {
+    Allowed,
+    Changed,
+    Unknown,
+}
+
+fn env_dirs(cwd: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = cwd
+        .ancestors()
+        .filter(|dir| dir.join(ENV_FILE).is_file())
+        .map(Path::to_path_buf)
+        .collect();
+    dirs.reverse();
+    dirs
+}
+
+fn file_hash(path: &Path) -> Option<String> {
+    fs::read(path).ok().map(|data| sha256::hex_digest(&data))
+}
+
+impl Shell {
+    fn trust_file(&self) -> Option<PathBuf> {
+        let config = match self.vars.get("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+            Some(dir) => PathBuf::from(bytes::to_os(dir)),
+            None => PathBuf::from(bytes::to_os(self.vars.get("HOME")?)).join(".config"),
+        };
+        Some(config.join("vssh").join("trusted"))
+    }
+
+    fn trusted_entries(&self) -> Vec<(String, PathBuf)> {
+        let Some(path) = self.trust_file() else {
+            return Vec::new();
+        };
+        let text = fs::read(&path)
+            .map(|t| bytes::decode(&t))
+            .unwrap_or_default();
+        text.lines()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(hash, file)| (hash.to_string(), PathBuf::from(bytes::to_os(file))))
+            .collect()
+    }
+
+    fn trust(&self, file: &Path) -> Trust {
+        let Some(hash) = file_hash(file) else {
+            return Trust::Unknown;
+        };
+        let entries = self.trusted_entries();
+        match entries.iter().find(|(_, path)| path == file) {
+            Some((allowed, _)) if *allowed == hash => Trust::Allowed,
+            Some(_) => Trust::Changed,
+            None => Trust::Unknown,
+        }
+    }
+
+    pub fn set_trust(&self, file: &Path, allow: bool) -> std::io::Result<()> {
+        let Some(path) = self.trust_file() else {
+            return Err(std::io::Error::other("HOME not set"));
+        };
+        let mut entries = self.trusted_entries();
+        entries.retain(|(_, trusted)| trusted != file);
+        if allow {
+            let hash = sha256::hex_digest(&fs::read(file)?);
+            entries.push((hash, file.to_path_buf()));
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text: String = entries
+            .iter()
+            .map(|(hash, file)| format!("{} {}\n", hash, bytes::from_os(file.as_os_str())))
+            .collect();
+        fs::write(path, bytes::encode(&text))
+    }
+
+    fn load_directory_env(&mut self, dir: &Path) -> Result<Option<DirectoryEnv>> {
+        let file = dir.join(ENV_FILE);
+        let shown = file.display();
+        match self.trust(&file) {
+            Trust::Allowed => {}
+            Trust::Changed => {
+                report(format_args!(
+                    "{}: {} has changed since it was allowed; run `vssh-env allow' to load it",
+                    program(),
+                    shown
+                ));
+                return Ok(None);
+            }
+            Trust::Unknown => {
+                report(format_args!(
+                    "{}: {} is not trusted; run `vssh-env allow' to load it",
+                    program(),
+                    shown
+                ));
+                return Ok(None);
+            }
+        }
+        let src = match fs::read(&file) {
+            Ok(src) => bytes::decode(&src),
+            Err(e) => {
+                report(format_args!("{}: {}: {}", program(), shown, describe(&e)));
+                return Ok(None);
+            }
+        };
+        let list = parser::parse(&src, 1)?;
+        let before = self.vars.clone();
+        self.source_depth += 1;
+        let result = self.run_list(&list);
+        self.source_depth -= 1;
+        let names: BTreeSet<String> = before
+            .names()
+            .chain(self.vars.names())
+            .filter(|name| !matches!(name.as_str(), "_" | "PIPESTATUS"))
+            .cloned()
+            .collect();
+        let saved = names
+            .into_iter()
+            .filter(|name| before.variable(name) != self.vars.variable(name))
+            .map(|name| {
+                let old = before.variable(&name).cloned();
+                (name, old)
+            })
+            .collect();
+        result?;
+        Ok(Some(DirectoryEnv {
+            dir: dir.to_path_buf(),
+            saved,
+        }))
+    }
+
+    pub fn update_directory_envs(&mut self) {
+        if !self.interactive {
+            return;
+        }
+        let Ok(cwd) = env::current_dir() else {
+            return;
+        };
+        let dirs = env_dirs(&cwd);
+        while let Some(active) = self.directory_envs.last()
+            && !dirs.contains(&active.dir)
+        {
+            let Some(active) = self.directory_envs.pop() else {
+                break;
+            };
+            for (name, variable) in active.saved.into_iter().rev() {
+                self.vars.restore(&name, variable);
+            }
+        }
+        for dir in dirs {
+            if self.directory_envs.iter().any(|active| active.dir == dir) {
+                continue;
+            }
+            match self.load_directory_env(&dir) {
+                Ok(Some(active)) => self.directory_envs.push(active),
+                Ok(None) => {}
+                Err(e) => report(&e),
+            }
+        }
+    }
+}
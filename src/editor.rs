@@ -0,0 +1,1253 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+
+use nix::errno::Errno;
+use nix::unistd;
+
+use crate::bytes;
+use crate::error::{describe, program, report, Result, ShellError};
+use crate::history::Entry;
+use crate::keymap::{Action, Function, Keymap};
+use crate::parser::{self, ParseError};
+use crate::shell::Shell;
+use crate::signals;
+use crate::vars::Variable;
+use crate::width;
+use crate::word;
+
+const PASTE_END: &[u8] = b"\x1b[201~";
+const ESCAPE_TIMEOUT: i32 = 50;
+
+struct Editor {
+    prompt: String,
+    buffer: Vec<char>,
+    cursor: usize,
+    cursor_row: usize,
+    width: usize,
+    register: Vec<char>,
+}
+
+enum Vi {
+    Pending,
+    Done,
+    Insert,
+    Function(Function),
+    Invalid,
+}
+
+fn word_class(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+fn starts_with(line: &str, prefix: &[char]) -> bool {
+    let mut chars = line.chars();
+    prefix.iter().all(|&c| chars.next() == Some(c))
+}
+
+/// Where history browsing stands: the entry on show (one past the newest
+/// while composing), the line being composed, kept aside while entries are
+/// previewed, and the prefix a search is anchored to.
+struct Recall {
+    index: usize,
+    draft: Vec<char>,
+    prefix: Option<Vec<char>>,
+}
+
+impl Recall {
+    fn new(len: usize) -> Self {
+        Recall {
+            index: len,
+            draft: Vec::new(),
+            prefix: None,
+        }
+    }
+
+    fn show(&mut self, entries: &[Entry], index: usize, editor: &mut Editor) {
+        if self.index == entries.len() {
+            self.draft = std::mem::take(&mut editor.buffer);
+        }
+        self.index = index;
+        editor.buffer = match entries.get(index) {
+            Some(entry) => entry.line.chars().collect(),
+            None => std::mem::take(&mut self.draft),
+        };
+        editor.cursor = editor.buffer.len();
+    }
+
+    fn previous(&mut self, entries: &[Entry], editor: &mut Editor) -> bool {
+        if self.index == 0 {
+            return false;
+        }
+        self.show(entries, self.index - 1, editor);
+        true
+    }
+
+    fn next(&mut self, entries: &[Entry], editor: &mut Editor) -> bool {
+        if self.index >= entries.len() {
+            return false;
+        }
+        self.show(entries, self.index + 1, editor);
+        true
+    }
+
+    fn search_backward(&mut self, entries: &[Entry], editor: &mut Editor) -> bool {
+        let prefix = self
+            .prefix
+            .get_or_insert_with(|| editor.buffer[..editor.cursor].to_vec());
+        let found = entries[..self.index]
+            .iter()
+            .rposition(|entry| starts_with(&entry.line, prefix));
+        match found {
+            Some(index) => self.show(entries, index, editor),
+            None => return false,
+        }
+        true
+    }
+
+    fn search_forward(&mut self, entries: &[Entry], editor: &mut Editor) -> bool {
+        let prefix = self
+            .prefix
+            .get_or_insert_with(|| editor.buffer[..editor.cursor].to_vec());
+        let newer = (self.index + 1).min(entries.len());
+        let found = entries[newer..]
+            .iter()
+            .position(|entry| starts_with(&entry.line, prefix));
+        match found {
+            Some(offset) => self.show(entries, newer + offset, editor),
+            None if self.index < entries.len() => self.show(entries, entries.len(), editor),
+            None => return false,
+        }
+        true
+    }
+}
+
+fn printable(seq: &[u8]) -> Option<char> {
+    let mut chars = std::str::from_utf8(seq).ok()?.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_control() => Some(c),
+        _ => None,
+    }
+}
+
+fn cell_count(c: char) -> usize {
+    if c.is_control() {
+        2
+    } else {
+        width::char_width(c)
+    }
+}
+
+fn layout(width: usize, prompt: &str, text: &[char]) -> (usize, usize) {
+    let width = width.max(1);
+    let (mut row, mut col) = (0, 0);
+    let mut pending = false;
+    for c in width::visible(prompt).chain(text.iter().copied()) {
+        if c == '\n' {
+            row += 1;
+            col = 0;
+            pending = false;
+            continue;
+        }
+        let cells = cell_count(c);
+        let (step, count) = match c.is_control() {
+            true => (1, cells),
+            false => (cells, (cells > 0) as usize),
+        };
+        for _ in 0..count {
+            if pending {
+                row += 1;
+                col = 0;
+                pending = false;
+            }
+            if col + step > width && col > 0 {
+                row += 1;
+                col = 0;
+            }
+            col += step;
+            if col >= width {
+                col = width - 1;
+                pending = true;
+            }
+        }
+    }
+    if pending {
+        (row + 1, 0)
+    } else {
+        (row, col)
+    }
+}
+
+fn display(out: &mut String, c: char) {
+    match c {
+        '\n' => out.push('\n'),
+        c if c.is_control() => {
+            out.push('^');
+            out.push(char::from_u32((c as u32) ^ 0x40).unwrap_or('?'));
+        }
+        c => out.push(c),
+    }
+}
+
+fn write_out(text: &str) {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(text.as_bytes());
+    let _ = stdout.flush();
+}
+
+fn read_byte() -> Result<Option<u8>> {
+    let mut byte = [0u8];
+    match unistd::read(libc::STDIN_FILENO, &mut byte) {
+        Ok(0) => Ok(None),
+        Ok(_) => Ok(Some(byte[0])),
+        Err(e) => Err(ShellError::Internal(format!("read: {}", e.desc()))),
+    }
+}
+
+fn input_pending(timeout: i32) -> bool {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut fd, 1, timeout) > 0 }
+}
+
+fn read_sequence(keymap: &Keymap) -> Result<Option<Vec<u8>>> {
+    let Some(first) = read_byte()? else {
+        return Ok(None);
+    };
+    let mut seq = vec![first];
+    while keymap.is_prefix(&seq) {
+        if (keymap.get(&seq).is_some() || seq == [0x1b]) && !input_pending(ESCAPE_TIMEOUT) {
+            break;
+        }
+        match read_byte()? {
+            Some(b) => seq.push(b),
+            None => return Ok(Some(seq)),
+        }
+    }
+    if keymap.get(&seq).is_none() && first == 0x1b {
+        let complete = |seq: &[u8]| match seq.get(1) {
+            Some(b'[') => seq.len() > 2 && (0x40..=0x7e).contains(&seq[seq.len() - 1]),
+            Some(b'O') => seq.len() > 2,
+            _ => true,
+        };
+        while !complete(&seq) {
+            match read_byte()? {
+                Some(b) => seq.push(b),
+                None => break,
+            }
+        }
+    }
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    while seq.len() < len {
+        match read_byte()? {
+            Some(b) => seq.push(b),
+            None => break,
+        }
+    }
+    Ok(Some(seq))
+}
+
+fn wait_readable() -> std::result::Result<(), Errno> {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut fd, 1, -1) } {
+        -1 => Err(Errno::last()),
+        _ => Ok(()),
+    }
+}
+
+impl Editor {
+    fn render(&mut self) {
+        let mut out = String::new();
+        if self.cursor_row > 0 {
+            out.push_str(&format!("\x1b[{}A", self.cursor_row));
+        }
+        out.push_str("\r\x1b[J");
+        out.push_str(&width::strip_markers(&self.prompt));
+        for &c in &self.buffer {
+            display(&mut out, c);
+        }
+        let (end_row, end_col) = layout(self.width, &self.prompt, &self.buffer);
+        if end_row > 0 && end_col == 0 && self.buffer.last() != Some(&'\n') {
+            out.push_str("\r\n");
+        }
+        let (row, col) = layout(self.width, &self.prompt, &self.buffer[..self.cursor]);
+        if end_row > row {
+            out.push_str(&format!("\x1b[{}A", end_row - row));
+        }
+        out.push('\r');
+        if col > 0 {
+            out.push_str(&format!("\x1b[{}C", col));
+        }
+        self.cursor_row = row;
+        write_out(&out);
+    }
+
+    fn insert(&mut self, c: char) {
+        self.buffer.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    fn insert_text(&mut self, seq: &[u8]) {
+        let Ok(text) = std::str::from_utf8(seq) else {
+            return;
+        };
+        if text.starts_with('\x1b') {
+            return;
+        }
+        for c in text.chars().filter(|&c| c == '\t' || !c.is_control()) {
+            self.insert(c);
+        }
+    }
+
+    fn combining(&self, index: usize) -> bool {
+        self.buffer
+            .get(index)
+            .is_some_and(|&c| !c.is_control() && width::char_width(c) == 0)
+    }
+
+    fn backward_char(&mut self) {
+        self.cursor -= 1;
+        while self.cursor > 0 && self.combining(self.cursor) {
+            self.cursor -= 1;
+        }
+    }
+
+    fn forward_char(&mut self) {
+        self.cursor += 1;
+        while self.combining(self.cursor) {
+            self.cursor += 1;
+        }
+    }
+
+    fn forward_word(&mut self) {
+        while self.cursor < self.buffer.len() && !self.buffer[self.cursor].is_alphanumeric() {
+            self.cursor += 1;
+        }
+        while self.cursor < self.buffer.len() && self.buffer[self.cursor].is_alphanumeric() {
+            self.cursor += 1;
+        }
+    }
+
+    fn backward_word(&mut self) {
+        while self.cursor > 0 && !self.buffer[self.cursor - 1].is_alphanumeric() {
+            self.cursor -= 1;
+        }
+        while self.cursor > 0 && self.buffer[self.cursor - 1].is_alphanumeric() {
+            self.cursor -= 1;
+        }
+    }
+
+    fn finish(&mut self) {
+        self.abandon();
+        write_out("\n");
+    }
+
+    fn abandon(&mut self) {
+        self.cursor = self.buffer.len();
+        self.render();
+    }
+
+    fn text(&self) -> String {
+        self.buffer.iter().collect()
+    }
+
+    fn replace(&mut self, start: usize, text: &str) {
+        let replacement: Vec<char> = text.chars().collect();
+        let len = replacement.len();
+        self.buffer.splice(start..self.cursor, replacement);
+        self.cursor = start + len;
+    }
+
+    fn list(&mut self, items: &[String]) {
+        let end = self.buffer.len();
+        let cursor = std::mem::replace(&mut self.cursor, end);
+        self.render();
+        let column = items.iter().map(|i| width::str_width(i)).max().unwrap_or(0) + 2;
+        let columns = (self.width / column).max(1);
+        let mut out = String::from("\r\n");
+        for (i, item) in items.iter().enumerate() {
+            out.push_str(item);
+            out.push_str(&" ".repeat(column - width::str_width(item)));
+            if (i + 1) % columns == 0 || i + 1 == items.len() {
+                out.push_str("\r\n");
+            }
+        }
+        write_out(&out);
+        self.cursor = cursor;
+        self.cursor_row = 0;
+    }
+
+    fn move_vertical(&mut self, up: bool) -> bool {
+        let line_start = self.buffer[..self.cursor]
+            .iter()
+            .rposition(|&c| c == '\n')
+            .map_or(0, |i| i + 1);
+        let column = self.cursor - line_start;
+        let (start, end) = if up {
+            if line_start == 0 {
+                return false;
+            }
+            let end = line_start - 1;
+            let start = self.buffer[..end]
+                .iter()
+                .rposition(|&c| c == '\n')
+                .map_or(0, |i| i + 1);
+            (start, end)
+        } else {
+            let Some(offset) = self.buffer[self.cursor..].iter().position(|&c| c == '\n') else {
+                return false;
+            };
+            let start = self.cursor + offset + 1;
+            let end = self.buffer[start..]
+                .iter()
+                .position(|&c| c == '\n')
+                .map_or(self.buffer.len(), |i| start + i);
+            (start, end)
+        };
+        self.cursor = start + column.min(end - start);
+        true
+    }
+
+    fn first_nonblank(&self) -> usize {
+        self.buffer
+            .iter()
+            .position(|c| !c.is_whitespace())
+            .unwrap_or(self.buffer.len())
+    }
+
+    fn word_start(&self, big: bool) -> usize {
+        let b = &self.buffer;
+        let mut i = self.cursor;
+        if i < b.len() {
+            let class = word_class(b[i], big);
+            while class != 0 && i < b.len() && word_class(b[i], big) == class {
+                i += 1;
+            }
+        }
+        while i < b.len() && word_class(b[i], big) == 0 {
+            i += 1;
+        }
+        i
+    }
+
+    fn word_end(&self, big: bool) -> usize {
+        let b = &self.buffer;
+        let mut i = self.cursor + 1;
+        while i < b.len() && word_class(b[i], big) == 0 {
+            i += 1;
+        }
+        if i >= b.len() {
+            return b.len().saturating_sub(1);
+        }
+        let class = word_class(b[i], big);
+        while i + 1 < b.len() && word_class(b[i + 1], big) == class {
+            i += 1;
+        }
+        i
+    }
+
+    fn word_back(&self, big: bool) -> usize {
+        let b = &self.buffer;
+        let mut i = self.cursor;
+        while i > 0 && word_class(b[i - 1], big) == 0 {
+            i -= 1;
+        }
+        if i == 0 {
+            return 0;
+        }
+        let class = word_class(b[i - 1], big);
+        while i > 0 && word_class(b[i - 1], big) == class {
+            i -= 1;
+        }
+        i
+    }
+
+    fn motion(&self, c: char) -> Option<(usize, bool)> {
+        let len = self.buffer.len();
+        Some(match c {
+            'h' => (self.cursor.saturating_sub(1), false),
+            'l' | ' ' => ((self.cursor + 1).min(len), false),
+            '0' => (0, false),
+            '^' => (self.first_nonblank(), false),
+            '$' => (len, false),
+            'w' | 'W' => (self.word_start(c == 'W'), false),
+            'b' | 'B' => (self.word_back(c == 'B'), false),
+            'e' | 'E' => (self.word_end(c == 'E'), true),
+            _ => return None,
+        })
+    }
+
+    fn word_object(&self, around: bool, big: bool) -> Option<(usize, usize)> {
+        let b = &self.buffer;
+        let i = self.cursor.min(b.len().checked_sub(1)?);
+        let class = word_class(b[i], big);
+        let (mut start, mut end) = (i, i + 1);
+        while start > 0 && word_class(b[start - 1], big) == class {
+            start -= 1;
+        }
+        while end < b.len() && word_class(b[end], big) == class {
+            end += 1;
+        }
+        if around && class == 0 {
+            if let Some(&next) = b.get(end) {
+                let next = word_class(next, big);
+                while end < b.len() && word_class(b[end], big) == next {
+                    end += 1;
+                }
+            }
+        } else if around {
+            let word_end = end;
+            while end < b.len() && word_class(b[end], big) == 0 {
+                end += 1;
+            }
+            if end == word_end {
+                while start > 0 && word_class(b[start - 1], big) == 0 {
+                    start -= 1;
+                }
+            }
+        }
+        Some((start, end))
+    }
+
+    fn quote_object(&self, around: bool, quote: char) -> Option<(usize, usize)> {
+        let b = &self.buffer;
+        let line_start = b[..self.cursor.min(b.len())]
+            .iter()
+            .rposition(|&c| c == '\n')
+            .map_or(0, |i| i + 1);
+        let line_end = b[line_start..]
+            .iter()
+            .position(|&c| c == '\n')
+            .map_or(b.len(), |i| line_start + i);
+        let quotes: Vec<usize> = (line_start..line_end)
+            .filter(|&i| b[i] == quote && (i == 0 || b[i - 1] != '\\'))
+            .collect();
+        let (open, close) = quotes
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .find(|&(_, close)| close >= self.cursor)?;
+        match around {
+            true => Some((open, close + 1)),
+            false => Some((open + 1, close)),
+        }
+    }
+
+    fn bracket_object(&self, around: bool, open: char, close: char) -> Option<(usize, usize)> {
+        let b = &self.buffer;
+        let start = match b.get(self.cursor) {
+            Some(&c) if c == open => self.cursor,
+            _ => {
+                let mut depth = 0;
+                let mut found = None;
+                for j in (0..self.cursor.min(b.len())).rev() {
+                    if b[j] == close {
+                        depth += 1;
+                    } else if b[j] == open {
+                        if depth == 0 {
+                            found = Some(j);
+                            break;
+                        }
+                        depth -= 1;
+                    }
+                }
+                found?
+            }
+        };
+        let mut depth = 0;
+        let mut end = None;
+        for (k, &c) in b.iter().enumerate().skip(start + 1) {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                if depth == 0 {
+                    end = Some(k);
+                    break;
+                }
+                depth -= 1;
+            }
+        }
+        let end = end?;
+        match around {
+            true => Some((start, end + 1)),
+            false => Some((start + 1, end)),
+        }
+    }
+
+    fn text_object(&self, around: bool, c: char) -> Option<(usize, usize)> {
+        match c {
+            'w' | 'W' => self.word_object(around, c == 'W'),
+            '"' | '\'' | '`' => self.quote_object(around, c),
+            '(' | ')' | 'b' => self.bracket_object(around, '(', ')'),
+            '[' | ']' => self.bracket_object(around, '[', ']'),
+            '{' | '}' | 'B' => self.bracket_object(around, '{', '}'),
+            '<' | '>' => self.bracket_object(around, '<', '>'),
+            _ => None,
+        }
+    }
+
+    fn operate(&mut self, op: char, start: usize, end: usize) -> Vi {
+        let end = end.min(self.buffer.len());
+        let start = start.min(end);
+        self.register = self.buffer[start..end].to_vec();
+        if op != 'y' {
+            self.buffer.drain(start..end);
+        }
+        self.cursor = start;
+        match op {
+            'c' => Vi::Insert,
+            _ => Vi::Done,
+        }
+    }
+
+    fn vi_command(&mut self, keys: &[char]) -> Vi {
+        let len = self.buffer.len();
+        match *keys {
+            ['i'] => Vi::Insert,
+            ['a'] => {
+                self.cursor = (self.cursor + 1).min(len);
+                Vi::Insert
+            }
+            ['I'] => {
+                self.cursor = self.first_nonblank();
+                Vi::Insert
+            }
+            ['A'] => {
+                self.cursor = len;
+                Vi::Insert
+            }
+            ['x'] if len > 0 => self.operate('d', self.cursor, self.cursor + 1),
+            ['X'] if self.cursor > 0 => self.operate('d', self.cursor - 1, self.cursor),
+            ['s'] => self.operate('c', self.cursor, self.cursor + 1),
+            ['D'] => self.operate('d', self.cursor, len),
+            ['C'] => self.operate('c', self.cursor, len),
+            ['S'] | ['c', 'c'] => self.operate('c', 0, len),
+            ['d', 'd'] => self.operate('d', 0, len),
+            ['y', 'y'] => {
+                self.register = self.buffer.clone();
+                Vi::Done
+            }
+            ['p' | 'P'] if !self.register.is_empty() => {
+                let at = match keys[0] {
+                    'p' => (self.cursor + 1).min(len),
+                    _ => self.cursor,
+                };
+                let text = self.register.clone();
+                let count = text.len();
+                self.buffer.splice(at..at, text);
+                self.cursor = at + count - 1;
+                Vi::Done
+            }
+            ['r'] | ['d' | 'c' | 'y'] | ['d' | 'c' | 'y', 'i' | 'a'] => Vi::Pending,
+            ['r', c] if self.cursor < len => {
+                self.buffer[self.cursor] = c;
+                Vi::Done
+            }
+            ['~'] if self.cursor < len => {
+                let c = self.buffer[self.cursor];
+                let toggled: Vec<char> = match c.is_uppercase() {
+                    true => c.to_lowercase().collect(),
+                    false => c.to_uppercase().collect(),
+                };
+                if let [single] = toggled.as_slice() {
+                    self.buffer[self.cursor] = *single;
+                }
+                self.cursor += 1;
+                Vi::Done
+            }
+            ['k'] => Vi::Function(Function::PreviousHistory),
+            ['j'] => Vi::Function(Function::NextHistory),
+            ['v'] => Vi::Function(Function::EditAndExecuteCommand),
+            [op @ ('d' | 'c' | 'y'), scope @ ('i' | 'a'), object] => {
+                match self.text_object(scope == 'a', object) {
+                    Some((start, end)) => self.operate(op, start, end),
+                    None => Vi::Invalid,
+                }
+            }
+            ['c', 'w' | 'W']
+                if self
+                    .buffer
+                    .get(self.cursor)
+                    .is_some_and(|c| !c.is_whitespace()) =>
+            {
+                let end = self.word_end(keys[1] == 'W');
+                let end = match self.cursor < len && end == self.cursor {
+                    true => self.cursor + 1,
+                    false => end + 1,
+                };
+                self.operate('c', self.cursor, end)
+            }
+            [op @ ('d' | 'c' | 'y'), motion] => match self.motion(motion) {
+                Some((target, inclusive)) => {
+                    let (start, end) = match target < self.cursor {
+                        true => (target, self.cursor),
+                        false => (self.cursor, target + inclusive as usize),
+                    };
+                    self.operate(op, start, end)
+                }
+                None => Vi::Invalid,
+            },
+            [motion] => match self.motion(motion) {
+                Some((target, _)) => {
+                    self.cursor = target;
+                    Vi::Done
+                }
+                None => Vi::Invalid,
+            },
+            _ => Vi::Invalid,
+        }
+    }
+
+    fn kill_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.buffer[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !self.buffer[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        self.buffer.drain(start..self.cursor);
+        self.cursor = start;
+    }
+}
+
+impl Shell {
+    pub fn editor_enabled(&self) -> bool {
+        self.interactive
+            && unistd::isatty(libc::STDIN_FILENO).unwrap_or(false)
+            && unistd::isatty(libc::STDOUT_FILENO).unwrap_or(false)
+            && self.vars.get("TERM").is_some_and(|term| term != "dumb")
+    }
+
+    pub fn edit_line(&mut self, prompt: &str, initial: &str) -> Result<Option<(String, bool)>> {
+        let mut original = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return crate::builtins::read_line(libc::STDIN_FILENO, true);
+        }
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::IEXTEN);
+        raw.c_iflag &= !(libc::ICRNL | libc::IXON);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw);
+        }
+        write_out("\x1b[?2004h");
+        let result = self.edit(prompt, initial);
+        write_out("\x1b[?2004l");
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &original);
+        }
+        result
+    }
+
+    fn edit(&mut self, prompt: &str, initial: &str) -> Result<Option<(String, bool)>> {
+        let mut editor = Editor {
+            prompt: prompt.to_string(),
+            buffer: initial.chars().collect(),
+            cursor: initial.chars().count(),
+            cursor_row: 0,
+            width: self.columns,
+            register: Vec::new(),
+        };
+        let mut pasting = false;
+        let mut recall = Recall::new(self.history.entries.len());
+        let mut tabbed = false;
+        let mut normal = false;
+        let mut keys = Vec::new();
+        editor.render();
+        loop {
+            match wait_readable() {
+                Ok(()) => {}
+                Err(Errno::EINTR) if signals::interrupted() => {
+                    editor.abandon();
+                    return Ok(None);
+                }
+                Err(Errno::EINTR) => {
+                    if signals::take_resized() {
+                        self.update_window_size();
+                        editor.width = self.columns;
+                        editor.render();
+                    }
+                    continue;
+                }
+                Err(e) => return Err(ShellError::Internal(format!("poll: {}", e.desc()))),
+            }
+            let seq = match read_sequence(&self.keymap) {
+                Ok(Some(seq)) => seq,
+                Ok(None) => {
+                    editor.finish();
+                    return Ok(Some((editor.text(), false)));
+                }
+                Err(_) if signals::interrupted() => {
+                    editor.abandon();
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            };
+            if pasting {
+                match seq.as_slice() {
+                    PASTE_END => pasting = false,
+                    b"\r" | b"\n" => editor.insert('\n'),
+                    _ => editor.insert_text(&seq),
+                }
+                editor.render();
+                continue;
+            }
+            let vi = self.options.get("vi");
+            let mut seq = seq;
+            if vi
+                && !normal
+                && seq.first() == Some(&0x1b)
+                && self.keymap.get(&seq).is_none()
+                && !matches!(seq.get(1), Some(b'[' | b'O'))
+            {
+                normal = true;
+                editor.cursor = editor.cursor.saturating_sub(1);
+                seq.remove(0);
+                if seq.is_empty() {
+                    editor.render();
+                    continue;
+                }
+            }
+            let function = match printable(&seq) {
+                Some(c) if vi && normal => {
+                    keys.push(c);
+                    let outcome = editor.vi_command(&keys);
+                    if !matches!(outcome, Vi::Pending) {
+                        keys.clear();
+                    }
+                    match outcome {
+                        Vi::Pending | Vi::Done => None,
+                        Vi::Insert => {
+                            normal = false;
+                            None
+                        }
+                        Vi::Function(function) => Some(function),
+                        Vi::Invalid => {
+                            write_out("\x07");
+                            None
+                        }
+                    }
+                }
+                _ => match self.keymap.get(&seq).cloned() {
+                    Some(Action::Function(function)) => Some(function),
+                    Some(Action::Command(command)) => {
+                        self.run_bound(&mut editor, &command);
+                        None
+                    }
+                    None => {
+                        editor.insert_text(&seq);
+                        None
+                    }
+                },
+            };
+            if matches!(
+                function,
+                Some(Function::PreviousHistory | Function::HistorySearchBackward)
+            ) {
+                recall.index += self.load_deferred_history();
+            }
+            let searching = matches!(
+                function,
+                Some(Function::HistorySearchBackward | Function::HistorySearchForward)
+            );
+            if !searching {
+                recall.prefix = None;
+            }
+            let tab = function == Some(Function::Complete);
+            match function {
+                Some(Function::AcceptLine)
+                    if matches!(
+                        parser::parse(&editor.text(), 1),
+                        Err(ParseError::Incomplete { .. })
+                    ) =>
+                {
+                    editor.insert('\n')
+                }
+                Some(Function::AcceptLine) => {
+                    editor.finish();
+                    return Ok(Some((editor.text(), true)));
+                }
+                Some(Function::Complete) => self.complete_in(&mut editor, tabbed),
+                Some(Function::BracketedPasteBegin) => pasting = true,
+                Some(Function::BackwardDeleteChar) if editor.cursor > 0 => {
+                    editor.cursor -= 1;
+                    editor.buffer.remove(editor.cursor);
+                }
+                Some(Function::DeleteChar) if seq == [0x04] && editor.buffer.is_empty() => {
+                    editor.finish();
+                    return Ok(Some((String::new(), false)));
+                }
+                Some(Function::DeleteChar) if editor.cursor < editor.buffer.len() => {
+                    editor.buffer.remove(editor.cursor);
+                }
+                Some(Function::Abort) => {
+                    editor.abandon();
+                    return Ok(None);
+                }
+                Some(Function::BackwardChar) if editor.cursor > 0 => editor.backward_char(),
+                Some(Function::ForwardChar) if editor.cursor < editor.buffer.len() => {
+                    editor.forward_char()
+                }
+                Some(Function::BackwardWord) => editor.backward_word(),
+                Some(Function::ForwardWord) => editor.forward_word(),
+                Some(Function::BeginningOfLine) => editor.cursor = 0,
+                Some(Function::EndOfLine) => editor.cursor = editor.buffer.len(),
+                Some(Function::KillLine) => editor.buffer.truncate(editor.cursor),
+                Some(Function::UnixLineDiscard) => {
+                    editor.buffer.drain(..editor.cursor);
+                    editor.cursor = 0;
+                }
+                Some(Function::UnixWordRubout) => editor.kill_word(),
+                Some(Function::PreviousHistory) if editor.move_vertical(true) => {}
+                Some(Function::NextHistory) if editor.move_vertical(false) => {}
+                Some(Function::PreviousHistory)
+                    if recall.previous(&self.history.entries, &mut editor) => {}
+                Some(Function::NextHistory) if recall.next(&self.history.entries, &mut editor) => {}
+                Some(Function::HistorySearchBackward)
+                    if recall.prefix.is_none() && editor.move_vertical(true) => {}
+                Some(Function::HistorySearchForward)
+                    if recall.prefix.is_none() && editor.move_vertical(false) => {}
+                Some(Function::HistorySearchBackward)
+                    if !recall.search_backward(&self.history.entries, &mut editor) =>
+                {
+                    write_out("\x07")
+                }
+                Some(Function::HistorySearchForward)
+                    if !recall.search_forward(&self.history.entries, &mut editor) =>
+                {
+                    write_out("\x07")
+                }
+                Some(Function::ClearScreen) => {
+                    write_out("\x1b[H\x1b[2J");
+                    editor.cursor_row = 0;
+                }
+                Some(Function::EditAndExecuteCommand) => self.edit_externally(&mut editor),
+                _ => {}
+            }
+            if normal && editor.cursor > 0 && editor.cursor >= editor.buffer.len() {
+                editor.cursor = editor.buffer.len() - 1;
+            }
+            tabbed = tab;
+            editor.render();
+        }
+    }
+
+    fn run_cooked(&mut self, command: &str) -> i32 {
+        let mut raw = unsafe { std::mem::zeroed::<libc::termios>() };
+        let have_raw = unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut raw) } == 0;
+        write_out("\x1b[?2004l");
+        self.restore_terminal();
+        let status = match parser::parse(command, 1) {
+            Ok(list) => match self.run_list(&list) {
+                Ok(status) => status,
+                Err(e) => {
+                    report(&e);
+                    e.status()
+                }
+            },
+            Err(e) => {
+                let e = ShellError::from(e);
+                report(&e);
+                e.status()
+            }
+        };
+        io::stdout().flush().ok();
+        if have_raw {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw);
+            }
+        }
+        write_out("\x1b[?2004h");
+        status
+    }
+
+    fn run_bound(&mut self, editor: &mut Editor, command: &str) {
+        let cursor = editor.cursor;
+        editor.finish();
+        let saved: Vec<(&str, Option<Variable>)> = ["READLINE_LINE", "READLINE_POINT"]
+            .into_iter()
+            .map(|name| (name, self.vars.variable(name).cloned()))
+            .collect();
+        self.vars.set("READLINE_LINE", editor.text());
+        self.vars.set("READLINE_POINT", cursor.to_string());
+        self.run_cooked(command);
+        editor.buffer = self
+            .vars
+            .get("READLINE_LINE")
+            .unwrap_or("")
+            .chars()
+            .collect();
+        editor.cursor = self
+            .vars
+            .get("READLINE_POINT")
+            .and_then(|point| point.trim().parse().ok())
+            .map_or(editor.buffer.len(), |point: usize| {
+                point.min(editor.buffer.len())
+            });
+        for (name, variable) in saved {
+            self.vars.restore(name, variable);
+        }
+        editor.cursor_row = 0;
+    }
+
+    fn edit_externally(&mut self, editor: &mut Editor) {
+        let command = ["VISUAL", "EDITOR"]
+            .iter()
+            .filter_map(|name| self.vars.get(name))
+            .find(|value| !value.is_empty())
+            .unwrap_or("vi")
+            .to_string();
+        let dir = self
+            .vars
+            .get("TMPDIR")
+            .unwrap_or("/tmp")
+            .trim_end_matches('/');
+        let path = format!("{}/vssh-edit-{}.sh", dir, unistd::getpid());
+        let _ = fs::remove_file(bytes::to_os(&path));
+        let written = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(bytes::to_os(&path))
+            .and_then(|mut file| file.write_all(&bytes::encode(&format!("{}\n", editor.text()))));
+        if let Err(e) = written {
+            report(format_args!("{}: {}: {}", program(), path, describe(&e)));
+            return;
+        }
+        editor.finish();
+        let status = self.run_cooked(&format!("{} {}", command, word::quote(&path)));
+        if status == 0
+            && let Ok(text) = fs::read(bytes::to_os(&path))
+        {
+            let text = bytes::decode(&text);
+            editor.buffer = text.trim_end_matches('\n').chars().collect();
+            editor.cursor = editor.buffer.len();
+        }
+        let _ = fs::remove_file(bytes::to_os(&path));
+        editor.cursor_row = 0;
+    }
+
+    fn complete_in(&mut self, editor: &mut Editor, listing: bool) {
+        let completion = self.complete(&editor.buffer[..editor.cursor]);
+        let candidates = &completion.candidates;
+        match candidates.as_slice() {
+            [] => write_out("\x07"),
+            [only] => {
+                editor.replace(completion.start, &format!("{}{}", only.text, only.suffix));
+            }
+            [first, rest @ ..] => {
+                let mut common = first.text.clone();
+                for candidate in rest {
+                    let len = common
+                        .chars()
+                        .zip(candidate.text.chars())
+                        .take_while(|(a, b)| a == b)
+                        .map(|(a, _)| a.len_utf8())
+                        .sum();
+                    common.truncate(len);
+                }
+                if common.chars().count() > completion.word.chars().count() {
+                    editor.replace(completion.start, &common);
+                } else if listing {
+                    let items: Vec<String> = candidates.iter().map(|c| c.display.clone()).collect();
+                    editor.list(&items);
+                } else {
+                    write_out("\x07");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vars::Variables;
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    #[test]
+    fn cursor_columns() {
+        for (prompt, text, cursor) in [
+            ("$ ", "", (0, 2)),
+            ("$ ", "日本語", (0, 8)),
+            ("$ ", "cafe\u{301}", (0, 6)),
+            ("$ ", "😀!", (0, 5)),
+            ("日本語$ ", "e\u{301}😀", (0, 11)),
+            ("\x01\x1b[1;34m\x02日本語\x01\x1b[0m\x02 ", "x", (0, 8)),
+            ("$ ", "a\x07", (0, 5)),
+            ("line one\n> ", "日本", (1, 6)),
+        ] {
+            assert_eq!(
+                layout(80, prompt, &chars(text)),
+                cursor,
+                "{:?} {:?}",
+                prompt,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn wide_characters_wrap_whole() {
+        assert_eq!(layout(10, "", &chars("日本語日本")), (1, 0));
+        assert_eq!(layout(10, "$ ", &chars("日本語日本")), (1, 2));
+        assert_eq!(layout(10, "123456789", &chars("日")), (1, 2));
+        assert_eq!(layout(10, "12345678", &chars("😀")), (1, 0));
+        assert_eq!(layout(10, "12345678", &chars("😀e\u{301}")), (1, 1));
+        assert_eq!(layout(3, "", &chars("日本語")), (2, 2));
+    }
+
+    #[test]
+    fn rendered_prompts_measure_visible_cells() {
+        let vars = Variables::from_pairs([("USER".to_string(), "日本語".to_string())]);
+        let shell = Shell::with_vars(vars);
+        let prompt = shell.render_prompt("\\[\\e[1;32m\\]\\u\\[\\e[0m\\] é😀 \\$ ");
+        assert_eq!(width::str_width(&prompt), 13);
+        assert_eq!(layout(80, &prompt, &[]), (0, 13));
+        assert_eq!(layout(80, &prompt, &chars("日本")), (0, 17));
+        let plain = width::strip_markers(&prompt);
+        assert!(plain.starts_with("\x1b[1;32m日本語\x1b[0m"), "{:?}", plain);
+    }
+
+    fn entries(lines: &[&str]) -> Vec<Entry> {
+        lines
+            .iter()
+            .map(|line| Entry {
+                line: line.to_string(),
+                time: None,
+            })
+            .collect()
+    }
+
+    fn editor(text: &str) -> Editor {
+        Editor {
+            prompt: String::new(),
+            buffer: chars(text),
+            cursor: text.chars().count(),
+            cursor_row: 0,
+            width: 80,
+            register: Vec::new(),
+        }
+    }
+
+    fn line(editor: &Editor) -> String {
+        editor.buffer.iter().collect()
+    }
+
+    const HISTORY: &[&str] = &[
+        "git status",
+        "ls -l",
+        "git commit -m wip",
+        "make",
+        "git log",
+        "gitk",
+    ];
+
+    #[test]
+    fn prefix_search_cycles_matching_entries() {
+        let entries = entries(HISTORY);
+        let mut recall = Recall::new(entries.len());
+        let mut editor = editor("git ");
+        for expected in ["git log", "git commit -m wip", "git status"] {
+            assert!(recall.search_backward(&entries, &mut editor));
+            assert_eq!(line(&editor), expected);
+            assert_eq!(editor.cursor, expected.chars().count());
+        }
+        assert!(!recall.search_backward(&entries, &mut editor));
+        assert_eq!(line(&editor), "git status");
+        for expected in ["git commit -m wip", "git log", "git "] {
+            assert!(recall.search_forward(&entries, &mut editor));
+            assert_eq!(line(&editor), expected);
+            assert_eq!(editor.cursor, expected.chars().count());
+        }
+        assert!(!recall.search_forward(&entries, &mut editor));
+        assert_eq!(recall.index, entries.len());
+    }
+
+    #[test]
+    fn the_prefix_stays_anchored() {
+        let entries = entries(HISTORY);
+        let mut recall = Recall::new(entries.len());
+        let mut editor = editor("git");
+        assert!(recall.search_backward(&entries, &mut editor));
+        assert_eq!(line(&editor), "gitk");
+        assert!(recall.search_backward(&entries, &mut editor));
+        assert_eq!(line(&editor), "git log");
+        assert_eq!(recall.prefix, Some(chars("git")));
+        let mut editor = self::editor("git status --short");
+        editor.cursor = 4;
+        let mut recall = Recall::new(entries.len());
+        assert!(recall.search_backward(&entries, &mut editor));
+        assert_eq!(line(&editor), "git log");
+        assert_eq!(recall.draft, chars("git status --short"));
+        assert!(recall.search_forward(&entries, &mut editor));
+        assert_eq!(line(&editor), "git status --short");
+    }
+
+    #[test]
+    fn an_edited_prefix_starts_a_new_search() {
+        let entries = entries(HISTORY);
+        let mut recall = Recall::new(entries.len());
+        let mut editor = editor("git ");
+        assert!(recall.search_backward(&entries, &mut editor));
+        assert_eq!(line(&editor), "git log");
+        recall.prefix = None;
+        editor.buffer = chars("m");
+        editor.cursor = 1;
+        assert!(recall.search_backward(&entries, &mut editor));
+        assert_eq!(line(&editor), "make");
+        assert!(!recall.search_backward(&entries, &mut editor));
+        assert!(recall.search_forward(&entries, &mut editor));
+        assert_eq!(line(&editor), "git ");
+    }
+
+    #[test]
+    fn the_composed_line_survives_browsing() {
+        let entries = entries(HISTORY);
+        let mut recall = Recall::new(entries.len());
+        let mut editor = editor("echo unfinished");
+        assert!(recall.previous(&entries, &mut editor));
+        assert!(recall.previous(&entries, &mut editor));
+        assert_eq!(line(&editor), "git log");
+        assert_eq!(recall.draft, chars("echo unfinished"));
+        assert!(recall.next(&entries, &mut editor));
+        assert!(recall.next(&entries, &mut editor));
+        assert_eq!(line(&editor), "echo unfinished");
+        assert_eq!(editor.cursor, 15);
+        assert!(!recall.next(&entries, &mut editor));
+        let mut recall = Recall::new(entries.len());
+        assert!(!recall.search_backward(&entries, &mut editor));
+        assert_eq!(line(&editor), "echo unfinished");
+        assert_eq!(recall.index, entries.len());
+        let mut editor = self::editor("");
+        let mut recall = Recall::new(0);
+        for _ in 0..2 {
+            assert!(!recall.previous(&[], &mut editor));
+            assert!(!recall.search_backward(&[], &mut editor));
+            assert!(!recall.search_forward(&[], &mut editor));
+        }
+        assert_eq!(line(&editor), "");
+    }
+}
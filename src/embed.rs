@@ -0,0 +1,132 @@
+use std::io::{self, BufRead, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::PathBuf;
+
+use nix::fcntl::{fcntl, FcntlArg};
+use nix::unistd;
+
+use crate::ast::List;
+use crate::bytes;
+use crate::error::{Result, ShellError};
+use crate::exec;
+use crate::parser::{self, ParseError};
+use crate::shell::{Control, Shell};
+use crate::signals;
+use crate::vars::Variables;
+
+/// Settings for an embedded shell. `stdout` and `stderr`, when given,
+/// replace the process's fds 1 and 2 while a line or script runs, so
+/// children write straight into the caller's pipe or file.
+#[derive(Debug, Default)]
+pub struct Config {
+    pub env: Option<Vec<(String, String)>>,
+    pub cwd: Option<PathBuf>,
+    pub interactive: bool,
+    pub stdout: Option<OwnedFd>,
+    pub stderr: Option<OwnedFd>,
+}
+
+// An embedded shell acts on the whole process, not just on itself:
+// `with_config` and `cd` change the process's working directory, and
+// each run dup2()s the attached files over fds 1 and 2 until it
+// returns. Run one embedded shell at a time, on one thread; test
+// suites that create several must serialize them.
+impl Shell {
+    pub fn with_config(config: Config) -> Result<Shell> {
+        if let Some(cwd) = &config.cwd {
+            unistd::chdir(cwd)
+                .map_err(|e| ShellError::Internal(format!("{}: {}", cwd.display(), e.desc())))?;
+        }
+        let vars = match config.env {
+            Some(env) => Variables::from_pairs(env),
+            None => Variables::from_env(),
+        };
+        let mut shell = Shell::with_vars(vars);
+        if let Ok(cwd) = std::env::current_dir() {
+            shell.vars.set("PWD", bytes::from_os(cwd.as_os_str()));
+        }
+        shell.interactive = config.interactive;
+        shell.embedded = true;
+        shell.attached = [
+            (libc::STDOUT_FILENO, config.stdout),
+            (libc::STDERR_FILENO, config.stderr),
+        ]
+        .into_iter()
+        .filter_map(|(fd, file)| file.map(|file| (fd, file)))
+        .collect();
+        Ok(shell)
+    }
+
+    // Each call starts afresh, like a new line at the prompt: a ^C that
+    // ended the last one does not cancel this one.
+    pub fn run_line(&mut self, line: &str) -> Result<i32> {
+        signals::clear();
+        let list = parser::parse(line, 1)?;
+        let result = self.run_attached(&list);
+        self.finish_exit();
+        result
+    }
+
+    pub fn run_script(&mut self, mut reader: impl BufRead) -> Result<i32> {
+        signals::clear();
+        let mut buffer = String::new();
+        let mut line = 0;
+        let mut start = 1;
+        loop {
+            let mut input = Vec::new();
+            if reader.read_until(b'\n', &mut input)? == 0 {
+                break;
+            }
+            line += 1;
+            if buffer.is_empty() {
+                start = line;
+            }
+            let input = bytes::decode(&input);
+            self.echo_input(&input);
+            buffer.push_str(&input);
+            let list = match parser::parse(&buffer, start) {
+                Ok(list) => list,
+                Err(ParseError::Incomplete { .. }) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            buffer.clear();
+            if let Err(e) = self.run_attached(&list) {
+                self.finish_exit();
+                return Err(e);
+            }
+            if self.control == Some(Control::Exit) {
+                break;
+            }
+        }
+        self.finish_exit();
+        if !buffer.is_empty() {
+            parser::parse(&buffer, start)?;
+        }
+        Ok(self.last_status)
+    }
+
+    fn finish_exit(&mut self) {
+        if self.control == Some(Control::Exit) {
+            self.control = None;
+            self.run_exit_trap();
+            self.control = None;
+        }
+    }
+
+    fn run_attached(&mut self, list: &List) -> Result<i32> {
+        io::stdout().flush()?;
+        let mut saved = Vec::new();
+        for (fd, file) in &self.attached {
+            let copy = fcntl(*fd, FcntlArg::F_DUPFD_CLOEXEC(10)).ok();
+            saved.push((*fd, copy.map(|raw| unsafe { OwnedFd::from_raw_fd(raw) })));
+            if let Err(e) = unistd::dup2(file.as_raw_fd(), *fd) {
+                exec::restore(saved);
+                return Err(ShellError::Internal(format!("dup2: {}", e.desc())));
+            }
+        }
+        let result = self.run_list(list);
+        io::stdout().flush().ok();
+        exec::restore(saved);
+        result
+    }
+}
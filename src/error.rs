@@ -0,0 +1,174 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+
+use nix::errno::Errno;
+
+use crate::color::style;
+use crate::parser::ParseError;
+
+#[derive(Debug)]
+pub enum ShellError {
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Expansion(String),
+    Parameter(String),
+    Arithmetic(String),
+    Redirect {
+        path: String,
+        source: io::Error,
+    },
+    Exec {
+        command: String,
+        errno: Errno,
+    },
+    Input(String),
+    Restricted(String),
+    Runaway(String),
+    Internal(String),
+}
+
+pub type Result<T> = std::result::Result<T, ShellError>;
+
+static PROGRAM: OnceLock<String> = OnceLock::new();
+
+pub fn set_program(name: &str) {
+    let _ = PROGRAM.set(name.to_string());
+}
+
+pub fn program() -> &'static str {
+    PROGRAM.get().map_or("vssh", String::as_str)
+}
+
+// Written to the stderr handle rather than with eprintln!, so that in an
+// embedded shell (and under the test harness) messages land in the file
+// attached over fd 2 like every child's output.
+pub fn report(message: impl fmt::Display) {
+    let _ = writeln!(io::stderr(), "{}", style("error", &message.to_string()));
+}
+
+pub fn warn(message: impl fmt::Display) {
+    let _ = writeln!(io::stderr(), "{}", style("warning", &message.to_string()));
+}
+
+pub fn write_stderr(parts: &[&[u8]]) {
+    let mut buf = [0u8; 1024];
+    let mut len = 0;
+    for part in parts {
+        let n = part.len().min(buf.len() - len);
+        buf[len..len + n].copy_from_slice(&part[..n]);
+        len += n;
+    }
+    unsafe {
+        libc::write(libc::STDERR_FILENO, buf.as_ptr().cast(), len);
+    }
+}
+
+pub fn describe(e: &io::Error) -> String {
+    match e.raw_os_error() {
+        Some(code) => Errno::from_raw(code).desc().to_string(),
+        None => e.to_string(),
+    }
+}
+
+impl ShellError {
+    pub fn status(&self) -> i32 {
+        match self {
+            ShellError::Syntax { .. } => 2,
+            ShellError::Exec {
+                errno: Errno::ENOENT,
+                ..
+            } => 127,
+            ShellError::Exec { .. } => 126,
+            ShellError::Runaway(_) => 75,
+            ShellError::Expansion(_)
+            | ShellError::Parameter(_)
+            | ShellError::Arithmetic(_)
+            | ShellError::Redirect { .. }
+            | ShellError::Input(_)
+            | ShellError::Restricted(_)
+            | ShellError::Internal(_) => 1,
+        }
+    }
+}
+
+impl ShellError {
+    pub fn aborts(&self) -> bool {
+        matches!(self, ShellError::Parameter(_) | ShellError::Arithmetic(_))
+    }
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShellError::Syntax { line, message, .. } => {
+                write!(f, "{}: line {}: {}", program(), line, message)
+            }
+            ShellError::Expansion(message)
+            | ShellError::Parameter(message)
+            | ShellError::Arithmetic(message)
+            | ShellError::Input(message)
+            | ShellError::Internal(message) => {
+                write!(f, "{}: {}", program(), message)
+            }
+            ShellError::Restricted(thing) => write!(f, "{}: restricted: {}", program(), thing),
+            ShellError::Runaway(command) => {
+                write!(
+                    f,
+                    "{}: {}: halted runaway process creation",
+                    program(),
+                    command
+                )
+            }
+            ShellError::Redirect { path, source } => {
+                write!(f, "{}: {}: {}", program(), path, describe(source))
+            }
+            ShellError::Exec {
+                command,
+                errno: Errno::ENOENT,
+            } if !command.contains('/') => {
+                write!(f, "{}: {}: command not found", program(), command)
+            }
+            ShellError::Exec { command, errno } => {
+                write!(f, "{}: {}: {}", program(), command, errno.desc())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShellError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShellError::Redirect { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for ShellError {
+    fn from(e: ParseError) -> Self {
+        match e {
+            ParseError::Incomplete { line, column, .. }
+            | ParseError::Syntax { line, column, .. } => ShellError::Syntax {
+                line,
+                column,
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+impl From<Errno> for ShellError {
+    fn from(e: Errno) -> Self {
+        ShellError::Internal(e.desc().to_string())
+    }
+}
+
+impl From<io::Error> for ShellError {
+    fn from(e: io::Error) -> Self {
+        ShellError::Internal(describe(&e))
+    }
+}
@@ -0,0 +1,1800 @@
+use std::cell::Cell;
+use std::env;
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::ptr;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::sys::signal::{kill, killpg, Signal};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{self, pipe2, ForkResult, Pid};
+
+use crate::ast::{
+    AndOr, Assign, AssignValue, CaseCommand, Command, Compound, Connector, Coproc, ForLoop,
+    IfCommand, List, Pipeline, Redirect, RedirectKind, SimpleCommand, WhileLoop, Word, WordPart,
+};
+use crate::builtins;
+use crate::bytes;
+use crate::color;
+use crate::error::{program, report, write_stderr, Result, ShellError};
+use crate::jobs::{self, Coprocess};
+use crate::parser;
+use crate::policy::{self, Plan};
+use crate::runaway;
+use crate::shell::{Control, Shell};
+use crate::signals;
+use crate::status::Status;
+use crate::suggest;
+use crate::terminal;
+use crate::word;
+
+const MAX_FUNCTION_DEPTH: usize = 200;
+const MAX_FUNCNEST: usize = 100_000;
+const STACK_RESERVE: usize = 1 << 20;
+const CALL_MARGIN: usize = 128 << 10;
+
+thread_local! {
+    static STACK_LOW: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+const ABORT_GRACE: Duration = Duration::from_millis(500);
+
+fn stack_low() -> Option<usize> {
+    unsafe {
+        let mut attr = std::mem::zeroed::<libc::pthread_attr_t>();
+        if libc::pthread_getattr_np(libc::pthread_self(), &mut attr) != 0 {
+            return None;
+        }
+        let mut addr = ptr::null_mut();
+        let mut size = 0;
+        let found = libc::pthread_attr_getstack(&attr, &mut addr, &mut size) == 0;
+        libc::pthread_attr_destroy(&mut attr);
+        found.then_some(addr as usize)
+    }
+}
+
+pub fn stack_exhausted() -> bool {
+    stack_below(STACK_RESERVE)
+}
+
+// Function calls and `source` stop with some room to spare, so a
+// runaway recursion is reported as such and not as whatever expansion
+// inside it happens to run out first.
+pub fn calls_exhausted() -> bool {
+    stack_below(STACK_RESERVE + CALL_MARGIN)
+}
+
+fn stack_below(reserve: usize) -> bool {
+    let marker = 0u8;
+    let here = &marker as *const u8 as usize;
+    let low = STACK_LOW.with(|low| {
+        if low.get().is_none() {
+            low.set(stack_low());
+        }
+        low.get()
+    });
+    low.is_some_and(|low| here < low.saturating_add(reserve))
+}
+
+struct Prepared {
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    redirects: Vec<(i32, RedirectKind, String)>,
+}
+
+enum Action {
+    Open(OwnedFd),
+    Dup(i32),
+    Close,
+}
+
+enum Target {
+    Builtin(builtins::Builtin),
+    Function(Rc<Command>),
+    External(Exec),
+    NotFound(Handler),
+}
+
+enum Handler {
+    Function(Rc<Command>),
+    Command(String),
+    Suggest(String),
+}
+
+struct Exec {
+    path: Option<CString>,
+    args: Vec<CString>,
+    _env: Vec<CString>,
+    argv: Vec<*const libc::c_char>,
+    envp: Vec<*const libc::c_char>,
+    shell: Option<CString>,
+    script_argv: Vec<*const libc::c_char>,
+    redirects: Vec<(i32, Action)>,
+    policy: Plan,
+    error_color: Option<String>,
+}
+
+impl Exec {
+    // Returns only for a text file the kernel can't execute when there is
+    // no shell binary to hand it to; the caller runs it in-process.
+    fn run(&self) {
+        self.policy.apply(self.args[0].as_bytes());
+        for (fd, action) in &self.redirects {
+            let result = match action {
+                Action::Open(file) => unsafe { libc::dup2(file.as_raw_fd(), *fd) },
+                Action::Dup(source) if source == fd => unsafe {
+                    libc::fcntl(*fd, libc::F_SETFD, 0)
+                },
+                Action::Dup(source) => unsafe { libc::dup2(*source, *fd) },
+                Action::Close => unsafe { libc::close(*fd) },
+            };
+            if result == -1 && !matches!(action, Action::Close) {
+                self.fail(Errno::last(), 1);
+            }
+        }
+        let Some(path) = &self.path else {
+            self.fail(Errno::ENOENT, 127);
+        };
+        unsafe {
+            libc::execve(path.as_ptr(), self.argv.as_ptr(), self.envp.as_ptr());
+        }
+        let errno = Errno::last();
+        if errno == Errno::ENOEXEC {
+            if is_binary(path) {
+                self.report(b"cannot execute binary file: Exec format error");
+                unsafe { libc::_exit(126) }
+            }
+            let Some(shell) = &self.shell else {
+                return;
+            };
+            unsafe {
+                libc::execve(
+                    shell.as_ptr(),
+                    self.script_argv.as_ptr(),
+                    self.envp.as_ptr(),
+                );
+            }
+        }
+        self.fail(errno, if errno == Errno::ENOENT { 127 } else { 126 });
+    }
+
+    fn spawn(&self) -> Option<Pid> {
+        if !self.policy.is_empty() {
+            return None;
+        }
+        let path = self.path.as_ref()?;
+        let defaults = signals::spawn_defaults()?;
+        let mut pid = 0;
+        unsafe {
+            let mut actions = std::mem::zeroed::<libc::posix_spawn_file_actions_t>();
+            let mut attr = std::mem::zeroed::<libc::posix_spawnattr_t>();
+            libc::posix_spawn_file_actions_init(&mut actions);
+            libc::posix_spawnattr_init(&mut attr);
+            let mut supported = true;
+            for (fd, action) in &self.redirects {
+                match action {
+                    Action::Open(file) => {
+                        libc::posix_spawn_file_actions_adddup2(&mut actions, file.as_raw_fd(), *fd);
+                    }
+                    Action::Dup(source) if source != fd => {
+                        libc::posix_spawn_file_actions_adddup2(&mut actions, *source, *fd);
+                    }
+                    Action::Dup(_) | Action::Close => supported = false,
+                }
+            }
+            let mut reset = std::mem::zeroed::<libc::sigset_t>();
+            let mut mask = std::mem::zeroed::<libc::sigset_t>();
+            libc::sigemptyset(&mut reset);
+            libc::sigemptyset(&mut mask);
+            for signal in defaults {
+                libc::sigaddset(&mut reset, signal as libc::c_int);
+            }
+            libc::posix_spawnattr_setsigdefault(&mut attr, &reset);
+            libc::posix_spawnattr_setsigmask(&mut attr, &mask);
+            libc::posix_spawnattr_setflags(
+                &mut attr,
+                (libc::POSIX_SPAWN_SETSIGDEF | libc::POSIX_SPAWN_SETSIGMASK) as libc::c_short,
+            );
+            let result = match supported {
+                true => libc::posix_spawn(
+                    &mut pid,
+                    path.as_ptr(),
+                    &actions,
+                    &attr,
+                    self.argv.as_ptr().cast(),
+                    self.envp.as_ptr().cast(),
+                ),
+                false => -1,
+            };
+            libc::posix_spawn_file_actions_destroy(&mut actions);
+            libc::posix_spawnattr_destroy(&mut attr);
+            if result != 0 {
+                return None;
+            }
+        }
+        Some(Pid::from_raw(pid))
+    }
+
+    fn fail(&self, errno: Errno, status: i32) -> ! {
+        let message = if errno == Errno::ENOENT && !self.args[0].as_bytes().contains(&b'/') {
+            "command not found"
+        } else {
+            errno.desc()
+        };
+        self.report(message.as_bytes());
+        unsafe { libc::_exit(status) }
+    }
+
+    // Runs in the forked child, so the color was looked up beforehand and
+    // only the tty check happens here.
+    fn report(&self, message: &[u8]) {
+        let tty = unsafe { libc::isatty(libc::STDERR_FILENO) } == 1;
+        let (start, end) = match &self.error_color {
+            Some(start) if tty => (start.as_bytes(), color::RESET.as_bytes()),
+            _ => (&b""[..], &b""[..]),
+        };
+        write_stderr(&[
+            start,
+            program().as_bytes(),
+            b": ",
+            self.args[0].as_bytes(),
+            b": ",
+            message,
+            end,
+            b"\n",
+        ]);
+    }
+}
+
+fn is_binary(path: &CString) -> bool {
+    let mut buf = [0u8; 80];
+    let n = unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC);
+        if fd == -1 {
+            return false;
+        }
+        let n = libc::read(fd, buf.as_mut_ptr().cast(), buf.len());
+        libc::close(fd);
+        n
+    };
+    let head = &buf[..n.max(0) as usize];
+    let line = head.split(|&b| b == b'\n').next().unwrap_or(head);
+    line.contains(&0)
+}
+
+fn open_redirect(kind: RedirectKind, path: &str) -> Result<File> {
+    let os_path = bytes::to_os(path);
+    let file = match kind {
+        RedirectKind::Input | RedirectKind::DupInput => File::open(&os_path),
+        RedirectKind::Output | RedirectKind::DupOutput | RedirectKind::OutputAll => {
+            File::create(&os_path)
+        }
+        RedirectKind::Append => OpenOptions::new().append(true).create(true).open(&os_path),
+    };
+    file.map_err(|source| ShellError::Redirect {
+        path: path.to_string(),
+        source,
+    })
+}
+
+fn open_actions(targets: &[(i32, RedirectKind, String)]) -> Result<Vec<(i32, Action)>> {
+    let mut actions = Vec::new();
+    for (fd, kind, target) in targets {
+        let dup = matches!(kind, RedirectKind::DupInput | RedirectKind::DupOutput);
+        if dup && target == "-" {
+            actions.push((*fd, Action::Close));
+            continue;
+        }
+        if dup && !target.is_empty() && target.bytes().all(|b| b.is_ascii_digit()) {
+            let source = target
+                .parse()
+                .map_err(|_| ShellError::Expansion(format!("{}: ambiguous redirect", target)))?;
+            actions.push((*fd, Action::Dup(source)));
+            continue;
+        }
+        if *kind == RedirectKind::DupInput {
+            return Err(ShellError::Expansion(format!(
+                "{}: ambiguous redirect",
+                target
+            )));
+        }
+        let file = open_redirect(*kind, target)?;
+        let raw = fcntl(file.as_raw_fd(), FcntlArg::F_DUPFD_CLOEXEC(10))?;
+        actions.push((*fd, Action::Open(unsafe { OwnedFd::from_raw_fd(raw) })));
+        if *kind == RedirectKind::OutputAll || (*kind == RedirectKind::DupOutput && *fd == 1) {
+            actions.push((libc::STDERR_FILENO, Action::Dup(*fd)));
+        }
+    }
+    Ok(actions)
+}
+
+fn apply_actions(actions: Vec<(i32, Action)>) -> Result<Vec<(i32, Option<OwnedFd>)>> {
+    let mut saved = Vec::new();
+    for (fd, action) in actions {
+        let copy = fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(10)).ok();
+        saved.push((fd, copy.map(|raw| unsafe { OwnedFd::from_raw_fd(raw) })));
+        let result = match action {
+            Action::Open(file) => dup_onto(&file, fd),
+            Action::Dup(source) => unistd::dup2(source, fd)
+                .map(drop)
+                .map_err(|e| ShellError::Internal(format!("{}: {}", source, e.desc()))),
+            Action::Close => {
+                let _ = unistd::close(fd);
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            restore(saved);
+            return Err(e);
+        }
+    }
+    Ok(saved)
+}
+
+pub fn find_command(name: &str, path: &str) -> Option<String> {
+    if name.contains('/') {
+        return Some(name.to_string());
+    }
+    path.split(':').find_map(|dir| {
+        let dir = if dir.is_empty() { "." } else { dir };
+        let candidate = Path::new(&bytes::to_os(dir)).join(bytes::to_os(name));
+        let executable = fs::metadata(&candidate)
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+        executable.then(|| bytes::from_os(candidate.as_os_str()))
+    })
+}
+
+fn dup_onto(fd: &OwnedFd, target: i32) -> Result<()> {
+    unistd::dup2(fd.as_raw_fd(), target)
+        .map(drop)
+        .map_err(|e| ShellError::Internal(format!("dup2: {}", e.desc())))
+}
+
+fn connect(pipes: &[(OwnedFd, OwnedFd)], i: usize) -> Result<()> {
+    if i > 0 {
+        dup_onto(&pipes[i - 1].0, libc::STDIN_FILENO)?;
+    }
+    if i < pipes.len() {
+        dup_onto(&pipes[i].1, libc::STDOUT_FILENO)?;
+    }
+    Ok(())
+}
+
+fn abort_pipeline(children: &[Pid], pgid: Option<Pid>) {
+    let signal_all = |signal| match pgid {
+        Some(pgid) => {
+            let _ = killpg(pgid, signal);
+        }
+        None => {
+            for &pid in children {
+                let _ = kill(pid, signal);
+            }
+        }
+    };
+    signal_all(Signal::SIGTERM);
+    let deadline = Instant::now() + ABORT_GRACE;
+    let mut remaining = children.to_vec();
+    while !remaining.is_empty() && Instant::now() < deadline {
+        remaining.retain(|&pid| {
+            matches!(
+                waitpid(pid, Some(WaitPidFlag::WNOHANG)),
+                Ok(WaitStatus::StillAlive) | Err(Errno::EINTR)
+            )
+        });
+        thread::sleep(Duration::from_millis(10));
+    }
+    if remaining.is_empty() && pgid.is_none() {
+        return;
+    }
+    signal_all(Signal::SIGKILL);
+    for pid in remaining {
+        let _ = waitpid(pid, None);
+    }
+}
+
+pub fn restore(saved: Vec<(i32, Option<OwnedFd>)>) {
+    for (fd, copy) in saved.into_iter().rev() {
+        match copy {
+            Some(copy) => {
+                let _ = dup_onto(&copy, fd);
+            }
+            None => {
+                let _ = unistd::close(fd);
+            }
+        }
+    }
+}
+
+fn print_menu(items: &[String], columns: usize) {
+    let number_width = items.len().to_string().len();
+    let item_width = items.iter().map(|i| i.chars().count()).max().unwrap_or(0);
+    let entry_width = number_width + 2 + item_width;
+    let cols = (columns / (entry_width + 1)).max(1);
+    let mut rows = items.len().div_ceil(cols);
+    let mut cols = items.len().div_ceil(rows);
+    if rows == 1 {
+        rows = cols;
+        cols = 1;
+    }
+    for row in 0..rows {
+        let mut line = String::new();
+        for col in 0..cols {
+            let index = col * rows + row;
+            let Some(item) = items.get(index) else {
+                break;
+            };
+            let entry = format!("{:>width$}) {}", index + 1, item, width = number_width);
+            if col + 1 < cols && index + rows < items.len() {
+                line.push_str(&format!("{:<width$} ", entry, width = entry_width));
+            } else {
+                line.push_str(&entry);
+            }
+        }
+        eprintln!("{}", line);
+    }
+}
+
+impl Shell {
+    pub fn run_list(&mut self, list: &List) -> Result<i32> {
+        for item in &list.items {
+            self.run_pending_traps();
+            if signals::interrupted() {
+                return Ok(130);
+            }
+            if self.options.get("noexec") && !self.interactive {
+                break;
+            }
+            let top_level = !self.subshell
+                && self.function_depth == 0
+                && self.loop_depth == 0
+                && self.source_depth == 0;
+            // Lists run for this one, like a trap handler or `repeat`,
+            // are part of its entry.
+            let audited = top_level && self.audit_pending.is_none();
+            if audited {
+                self.begin_audit(&item.text);
+            }
+            let result = if item.background {
+                self.run_background(&item.and_or, &item.text)
+            } else {
+                if top_level && self.title_enabled() {
+                    terminal::set_title(&item.text);
+                }
+                self.run_and_or(&item.and_or)
+            };
+            self.last_status = match result {
+                Ok(status) => status,
+                Err(e) if e.aborts() => {
+                    self.last_status = e.status();
+                    if audited {
+                        self.audit(e.status());
+                    }
+                    return Err(e);
+                }
+                Err(e) => {
+                    report(&e);
+                    if let ShellError::Runaway(_) = e {
+                        self.halt_runaway(e.status());
+                    }
+                    if self.options.get("errexit") && self.condition_depth == 0 && !self.in_trap {
+                        self.exit_on_error(e.status());
+                    }
+                    e.status()
+                }
+            };
+            if audited {
+                self.audit(self.last_status);
+            }
+            if top_level && !self.in_trap {
+                self.record_prompt_status();
+            }
+            self.run_pending_traps();
+            if self.control.is_some() {
+                break;
+            }
+        }
+        Ok(self.last_status)
+    }
+
+    fn run_and_or(&mut self, and_or: &AndOr) -> Result<i32> {
+        let mut status = self.run_term(&and_or.first, !and_or.rest.is_empty())?;
+        let mut last = &and_or.first;
+        for (i, (connector, pipeline)) in and_or.rest.iter().enumerate() {
+            if self.control.is_some() {
+                break;
+            }
+            let run = match connector {
+                Connector::And => status == 0,
+                Connector::Or => status != 0,
+            };
+            if run {
+                self.last_status = status;
+                status = self.run_term(pipeline, i + 1 < and_or.rest.len())?;
+                last = pipeline;
+            }
+        }
+        let final_pipeline = and_or.rest.last().map_or(&and_or.first, |(_, p)| p);
+        let checked = !matches!(
+            last.commands.last(),
+            Some(Command::Compound(
+                Compound::If(_)
+                    | Compound::For(_)
+                    | Compound::While(_)
+                    | Compound::Select(_)
+                    | Compound::Case(_)
+                    | Compound::Group(_),
+                _
+            ))
+        );
+        let failed = status != 0
+            && checked
+            && std::ptr::eq(last, final_pipeline)
+            && !last.negated
+            && self.condition_depth == 0
+            && self.control.is_none();
+        if failed && (self.function_depth == 0 || self.options.get("errtrace")) {
+            self.run_trap("ERR", status);
+        }
+        if failed && self.options.get("errexit") && !self.in_trap {
+            self.exit_on_error(status);
+        }
+        Ok(status)
+    }
+
+    /// Runs one term of an and-or list. Terms whose status is tested, the
+    /// non-final ones and negated ones, count as conditions for ERR and
+    /// errexit all the way down, as in bash.
+    fn run_term(&mut self, pipeline: &Pipeline, tested: bool) -> Result<i32> {
+        let tested = tested || pipeline.negated;
+        self.condition_depth += tested as usize;
+        let status = self.run_pipeline(pipeline);
+        self.condition_depth -= tested as usize;
+        status
+    }
+
+    fn exit_on_error(&mut self, status: i32) {
+        self.last_status = status;
+        if self.embedded && !self.subshell {
+            self.control = Some(Control::Exit);
+            return;
+        }
+        self.audit(status);
+        self.run_exit_trap();
+        io::stdout().flush().ok();
+        self.save_history();
+        std::process::exit(status);
+    }
+
+    fn run_background(&mut self, and_or: &AndOr, text: &str) -> Result<i32> {
+        if and_or.rest.is_empty() {
+            let mark = self.substitutions.len();
+            let pids = self.spawn_pipeline(&and_or.first, true);
+            self.traced.clear();
+            let mut helpers = self.release_substitutions(mark, pids.is_err());
+            let pids = pids?.0;
+            let pgid = self.job_group(true).and(pids.first().copied());
+            helpers.extend(pids);
+            self.add_job(helpers, text, pgid);
+            return Ok(0);
+        }
+        io::stdout().flush()?;
+        let group = self.job_group(true);
+        match self.fork(text)? {
+            ForkResult::Child => {
+                if group.is_some() {
+                    jobs::join_group(Pid::from_raw(0), Pid::this(), false);
+                }
+                signals::reset_child();
+                self.subshell = true;
+                if group.is_none() {
+                    unsafe {
+                        libc::signal(libc::SIGINT, libc::SIG_IGN);
+                    }
+                }
+                let status = self.run_and_or(and_or);
+                io::stdout().flush().ok();
+                std::process::exit(status.unwrap_or_else(|e| {
+                    report(&e);
+                    e.status()
+                }));
+            }
+            ForkResult::Parent { child } => {
+                if group.is_some() {
+                    jobs::join_group(child, child, false);
+                }
+                self.add_job(vec![child], text, group.and(Some(child)));
+                Ok(0)
+            }
+        }
+    }
+
+    fn run_pipeline(&mut self, pipeline: &Pipeline) -> Result<i32> {
+        let mark = self.substitutions.len();
+        let result = self.run_segments(pipeline);
+        self.release_substitutions(mark, true);
+        if pipeline.negated {
+            return result.map(|status| (status == 0) as i32);
+        }
+        result
+    }
+
+    fn run_segments(&mut self, pipeline: &Pipeline) -> Result<i32> {
+        if let [command] = pipeline.commands.as_slice() {
+            let status = self.run_command(command)?;
+            self.vars.set_array("PIPESTATUS", vec![status.to_string()]);
+            return Ok(status);
+        }
+        let (pids, last) = self.spawn_pipeline(pipeline, false)?;
+        let text: Vec<String> = pipeline.commands.iter().map(runaway::describe).collect();
+        let pgid = self.job_group(false).and(pids.first().copied());
+        self.wait_segments(pids, last, &text.join(" | "), pgid)
+    }
+
+    fn spawn_pipeline(
+        &mut self,
+        pipeline: &Pipeline,
+        background: bool,
+    ) -> Result<(Vec<Pid>, Option<i32>)> {
+        let mut segments = Vec::new();
+        for command in &pipeline.commands {
+            // Bare assignments and redirections belong to the stage's
+            // process, so they run there rather than being prepared here.
+            let prepared = match command {
+                Command::Simple(simple) if !simple.words.is_empty() => {
+                    self.debug_trap(&simple.text);
+                    self.prepare(simple)?
+                }
+                _ => None,
+            };
+            segments.push(match prepared {
+                Some(prepared) => Some((self.resolve(&prepared)?, prepared)),
+                None => None,
+            });
+        }
+        let pipeline_id = self.next_pipeline();
+        self.traced = segments
+            .iter()
+            .map(|segment| {
+                segment.as_ref().and_then(|(_, prepared)| {
+                    self.trace_start(
+                        &prepared.args,
+                        &prepared.redirects,
+                        background,
+                        Some(pipeline_id),
+                    )
+                })
+            })
+            .collect();
+        // Stages run by the shell itself open their files here as well,
+        // so a bad target stops the pipeline before anything is forked.
+        let mut opened = Vec::new();
+        for segment in &mut segments {
+            opened.push(match segment {
+                Some((target, prepared)) if !matches!(target, Target::External(_)) => {
+                    let actions = open_actions(&prepared.redirects)?;
+                    prepared.redirects.clear();
+                    actions
+                }
+                _ => Vec::new(),
+            });
+        }
+
+        let lastpipe = !background
+            && self.options.get("lastpipe")
+            && !matches!(segments.last(), Some(Some((Target::External(_), _))));
+        let forked_count = pipeline.commands.len() - lastpipe as usize;
+
+        let mut pipes = Vec::new();
+        for _ in 0..pipeline.commands.len() - 1 {
+            let pipe = pipe2(OFlag::O_CLOEXEC)
+                .map_err(|e| ShellError::Internal(format!("pipe: {}", e.desc())))?;
+            pipes.push(pipe);
+        }
+
+        io::stdout().flush()?;
+        let group = self.job_group(background);
+        let mut child_pids: Vec<Pid> = Vec::new();
+        for (i, command) in pipeline.commands.iter().enumerate().take(forked_count) {
+            let forked = match self.fork(&runaway::describe(command)) {
+                Ok(forked) => forked,
+                Err(e) => {
+                    pipes.clear();
+                    abort_pipeline(&child_pids, group.and(child_pids.first().copied()));
+                    self.reclaim_terminal();
+                    return Err(e);
+                }
+            };
+            match forked {
+                ForkResult::Child => {
+                    if let Some(terminal) = group {
+                        let pgid = child_pids.first().copied().unwrap_or_else(Pid::this);
+                        jobs::join_group(Pid::from_raw(0), pgid, terminal);
+                    }
+                    signals::reset_child();
+                    let connected = connect(&pipes, i)
+                        .and_then(|()| apply_actions(std::mem::take(&mut opened[i])).map(drop));
+                    pipes.clear();
+                    self.subshell = true;
+                    if background && group.is_none() {
+                        unsafe {
+                            libc::signal(libc::SIGINT, libc::SIG_IGN);
+                        }
+                    }
+                    let status = connected.and_then(|()| match (command, &segments[i]) {
+                        (_, Some((target, prepared))) => self.run_target(target, prepared),
+                        (Command::Simple(simple), None) if !simple.words.is_empty() => Ok(0),
+                        (command, None) => self.run_command(command),
+                    });
+                    io::stdout().flush().ok();
+                    std::process::exit(status.unwrap_or_else(|e| {
+                        report(&e);
+                        e.status()
+                    }));
+                }
+                ForkResult::Parent { child } => {
+                    if let Some(terminal) = group {
+                        let pgid = child_pids.first().copied().unwrap_or(child);
+                        jobs::join_group(child, pgid, terminal);
+                    }
+                    child_pids.push(child);
+                }
+            }
+        }
+        if !lastpipe {
+            return Ok((child_pids, None));
+        }
+        let saved = fcntl(libc::STDIN_FILENO, FcntlArg::F_DUPFD_CLOEXEC(10))
+            .ok()
+            .map(|raw| unsafe { OwnedFd::from_raw_fd(raw) });
+        let last = pipeline.commands.len() - 1;
+        let connected = dup_onto(&pipes[pipes.len() - 1].0, libc::STDIN_FILENO)
+            .and_then(|()| apply_actions(std::mem::take(&mut opened[last])));
+        pipes.clear();
+        let status = connected.and_then(|redirected| {
+            let status = match (&pipeline.commands[last], &segments[last]) {
+                (_, Some((target, prepared))) => self.run_target(target, prepared),
+                (Command::Simple(simple), None) if !simple.words.is_empty() => Ok(0),
+                (command, None) => self.run_command(command),
+            };
+            io::stdout().flush().ok();
+            restore(redirected);
+            status
+        });
+        io::stdout().flush().ok();
+        restore(vec![(libc::STDIN_FILENO, saved)]);
+        let status = status.unwrap_or_else(|e| {
+            report(&e);
+            e.status()
+        });
+        Ok((child_pids, Some(status)))
+    }
+
+    fn run_command(&mut self, command: &Command) -> Result<i32> {
+        match command {
+            Command::Simple(simple) => self.run_simple(simple),
+            Command::Compound(compound, redirects) => self.run_redirected(compound, redirects),
+            Command::Function(def) => {
+                self.functions.insert(def.name.clone(), def.body.clone());
+                Ok(0)
+            }
+            Command::Coproc(coproc) => self.start_coproc(coproc),
+        }
+    }
+
+    fn start_coproc(&mut self, coproc: &Coproc) -> Result<i32> {
+        self.update_jobs();
+        if let Some(running) = &self.coproc {
+            report(format_args!(
+                "{}: coproc [{}:{}] still exists",
+                program(),
+                running.pid,
+                running.name
+            ));
+            return Ok(1);
+        }
+        let pipe = || {
+            pipe2(OFlag::O_CLOEXEC).map_err(|e| ShellError::Internal(format!("pipe: {}", e.desc())))
+        };
+        let (child_in, shell_out) = pipe()?;
+        let (shell_in, child_out) = pipe()?;
+        io::stdout().flush()?;
+        match self.fork(&format!("coproc {}", coproc.name))? {
+            ForkResult::Child => {
+                signals::reset_child();
+                self.subshell = true;
+                let connected = dup_onto(&child_in, libc::STDIN_FILENO)
+                    .and_then(|()| dup_onto(&child_out, libc::STDOUT_FILENO));
+                drop((child_in, child_out, shell_in, shell_out));
+                let status = connected.and_then(|()| self.run_command(&coproc.body));
+                io::stdout().flush().ok();
+                std::process::exit(status.unwrap_or_else(|e| {
+                    report(&e);
+                    e.status()
+                }));
+            }
+            ForkResult::Parent { child } => {
+                drop((child_in, child_out));
+                let high = |fd: OwnedFd| -> Result<OwnedFd> {
+                    let raw = fcntl(fd.as_raw_fd(), FcntlArg::F_DUPFD_CLOEXEC(10))?;
+                    Ok(unsafe { OwnedFd::from_raw_fd(raw) })
+                };
+                let fds = [high(shell_in)?, high(shell_out)?];
+                self.vars.set_array(
+                    &coproc.name,
+                    fds.iter().map(|fd| fd.as_raw_fd().to_string()).collect(),
+                );
+                self.vars
+                    .set(&format!("{}_PID", coproc.name), child.to_string());
+                self.add_job(vec![child], &format!("coproc {}", coproc.name), None);
+                self.coproc = Some(Coprocess {
+                    name: coproc.name.clone(),
+                    pid: child,
+                    fds,
+                });
+                Ok(0)
+            }
+        }
+    }
+
+    fn function_limit(&self) -> usize {
+        self.vars
+            .get("FUNCNEST")
+            .and_then(|n| n.trim().parse::<usize>().ok())
+            .filter(|&n| n > 0)
+            .map_or(MAX_FUNCTION_DEPTH, |n| n.min(MAX_FUNCNEST))
+    }
+
+    fn call_function(&mut self, body: &Command, args: &[String]) -> Result<i32> {
+        let limit = self.function_limit();
+        if self.function_depth >= limit || calls_exhausted() {
+            return Err(ShellError::Internal(format!(
+                "{}: maximum function nesting level exceeded ({})",
+                args[0],
+                self.function_depth.min(limit)
+            )));
+        }
+        let saved = std::mem::replace(&mut self.positional, args[1..].to_vec());
+        self.function_depth += 1;
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        self.locals.push(Vec::new());
+        let result = self.run_command(body);
+        if self.control == Some(Control::Return) {
+            self.control = None;
+        }
+        for (name, variable) in self.locals.pop().unwrap_or_default().into_iter().rev() {
+            self.vars.restore(&name, variable);
+        }
+        self.loop_depth = loop_depth;
+        self.function_depth -= 1;
+        self.positional = saved;
+        result
+    }
+
+    fn run_redirected(&mut self, compound: &Compound, redirects: &[Redirect]) -> Result<i32> {
+        let targets = self.redirect_targets(redirects)?;
+        let saved = self.redirect(&targets)?;
+        let result = self.run_compound(compound);
+        io::stdout().flush().ok();
+        restore(saved);
+        result
+    }
+
+    fn redirect_targets(
+        &mut self,
+        redirects: &[Redirect],
+    ) -> Result<Vec<(i32, RedirectKind, String)>> {
+        let mut targets = Vec::new();
+        for redirect in redirects {
+            let fd = redirect.fd.unwrap_or_else(|| redirect.kind.default_fd());
+            let target = self.expand_single(&redirect.target)?;
+            if target.is_empty() {
+                return Err(ShellError::Expansion(format!(
+                    "{}: ambiguous redirect",
+                    redirect.source
+                )));
+            }
+            self.restrict_redirect(redirect, &target)?;
+            targets.push((fd, redirect.kind, target));
+        }
+        Ok(targets)
+    }
+
+    fn redirect(
+        &mut self,
+        targets: &[(i32, RedirectKind, String)],
+    ) -> Result<Vec<(i32, Option<OwnedFd>)>> {
+        io::stdout().flush()?;
+        io::stderr().flush()?;
+        let actions = open_actions(targets)?;
+        apply_actions(actions)
+    }
+
+    fn run_compound(&mut self, compound: &Compound) -> Result<i32> {
+        match compound {
+            Compound::If(if_command) => self.run_if(if_command),
+            Compound::For(for_loop) => self.in_loop(|shell| shell.run_for(for_loop)),
+            Compound::While(while_loop) => self.in_loop(|shell| shell.run_while(while_loop)),
+            Compound::Select(select) => self.in_loop(|shell| shell.run_select(select)),
+            Compound::Case(case) => self.run_case(case),
+            Compound::Group(list) => self.run_list(list),
+            Compound::Subshell(list) => self.run_subshell(list),
+            Compound::Arith(expr) => {
+                let expr = self.expand_single(&word::parse(expr))?;
+                Ok((self.eval_arith(&expr)? == 0) as i32)
+            }
+        }
+    }
+
+    fn run_subshell(&mut self, list: &List) -> Result<i32> {
+        io::stdout().flush()?;
+        let text = format!("({})", runaway::list_text(list));
+        let group = self.job_group(false);
+        match self.fork(&text)? {
+            ForkResult::Child => {
+                if let Some(terminal) = group {
+                    jobs::join_group(Pid::from_raw(0), Pid::this(), terminal);
+                }
+                signals::reset_child();
+                self.subshell = true;
+                let status = self.run_list(list);
+                io::stdout().flush().ok();
+                std::process::exit(status.unwrap_or_else(|e| {
+                    report(&e);
+                    e.status()
+                }));
+            }
+            ForkResult::Parent { child } => self.wait_for(child, &text, group),
+        }
+    }
+
+    pub fn in_loop(&mut self, run: impl FnOnce(&mut Shell) -> Result<i32>) -> Result<i32> {
+        self.loop_depth += 1;
+        let result = run(self);
+        self.loop_depth -= 1;
+        result
+    }
+
+    fn run_for(&mut self, for_loop: &ForLoop) -> Result<i32> {
+        let items = match &for_loop.words {
+            Some(words) => self.expand_words(words)?,
+            None => self.positional.clone(),
+        };
+        let mut status = 0;
+        for item in items {
+            if signals::interrupted() {
+                return Ok(130);
+            }
+            self.restrict_variable(&for_loop.var)?;
+            self.writable(&for_loop.var)?;
+            self.vars.set(&for_loop.var, item);
+            status = self.run_list(&for_loop.body)?;
+            if self.loop_exit() {
+                break;
+            }
+        }
+        Ok(status)
+    }
+
+    fn run_condition(&mut self, condition: &List) -> Result<i32> {
+        self.condition_depth += 1;
+        let status = self.run_list(condition);
+        self.condition_depth -= 1;
+        status
+    }
+
+    fn run_if(&mut self, if_command: &IfCommand) -> Result<i32> {
+        for (condition, body) in &if_command.branches {
+            let status = self.run_condition(condition)?;
+            if self.control.is_some() {
+                return Ok(status);
+            }
+            if status == 0 {
+                return self.run_list(body);
+            }
+        }
+        match &if_command.otherwise {
+            Some(otherwise) => self.run_list(otherwise),
+            None => Ok(0),
+        }
+    }
+
+    fn run_while(&mut self, while_loop: &WhileLoop) -> Result<i32> {
+        let mut status = 0;
+        loop {
+            if signals::interrupted() {
+                return Ok(130);
+            }
+            let condition = self.run_condition(&while_loop.condition)?;
+            if signals::interrupted() {
+                return Ok(130);
+            }
+            if self.control.is_some() {
+                if self.loop_exit() {
+                    return Ok(condition);
+                }
+                continue;
+            }
+            if (condition == 0) == while_loop.until {
+                break;
+            }
+            status = self.run_list(&while_loop.body)?;
+            if self.loop_exit() {
+                break;
+            }
+        }
+        Ok(status)
+    }
+
+    fn run_select(&mut self, select: &ForLoop) -> Result<i32> {
+        let items = match &select.words {
+            Some(words) => self.expand_words(words)?,
+            None => self.positional.clone(),
+        };
+        if items.is_empty() {
+            return Ok(0);
+        }
+        let columns = self
+            .vars
+            .get("COLUMNS")
+            .and_then(|c| c.parse().ok())
+            .unwrap_or(80);
+        print_menu(&items, columns);
+        let mut status = 0;
+        loop {
+            eprint!("{}", self.vars.get("PS3").unwrap_or("#? "));
+            let Some((line, complete)) = builtins::read_line(libc::STDIN_FILENO, false)? else {
+                return Ok(130);
+            };
+            if !complete && line.is_empty() {
+                eprintln!();
+                break;
+            }
+            let reply = line.trim_matches([' ', '\t']);
+            if reply.is_empty() {
+                print_menu(&items, columns);
+                continue;
+            }
+            let choice = reply
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| items.get(n.wrapping_sub(1)))
+                .cloned()
+                .unwrap_or_default();
+            self.restrict_variable(&select.var)?;
+            self.writable(&select.var)?;
+            self.vars.set("REPLY", reply);
+            self.vars.set(&select.var, choice);
+            status = self.run_list(&select.body)?;
+            if self.loop_exit() || signals::interrupted() {
+                break;
+            }
+        }
+        Ok(status)
+    }
+
+    pub fn loop_exit(&mut self) -> bool {
+        match self.control {
+            Some(Control::Break(n)) => {
+                self.control = (n > 1).then_some(Control::Break(n - 1));
+                true
+            }
+            Some(Control::Continue(n)) if n > 1 => {
+                self.control = Some(Control::Continue(n - 1));
+                true
+            }
+            Some(Control::Continue(_)) => {
+                self.control = None;
+                false
+            }
+            Some(Control::Return | Control::Exit) => true,
+            None => false,
+        }
+    }
+
+    fn run_case(&mut self, case: &CaseCommand) -> Result<i32> {
+        let word = self.expand_single(&case.word)?;
+        for item in &case.items {
+            for pattern in &item.patterns {
+                if self.expand_pattern(pattern)?.matches(&word) {
+                    return self.run_list(&item.body);
+                }
+            }
+        }
+        Ok(0)
+    }
+
+    fn run_simple(&mut self, command: &SimpleCommand) -> Result<i32> {
+        self.debug_trap(&command.text);
+        let Some(prepared) = self.prepare(command)? else {
+            return Ok(self.subst_status.unwrap_or(0));
+        };
+        let traced = self.trace_start(&prepared.args, &prepared.redirects, false, None);
+        let result = self.run_prepared(&prepared);
+        if traced.is_some() {
+            let status = result
+                .as_ref()
+                .map_or_else(ShellError::status, |status| *status);
+            self.trace_end(traced, status);
+        }
+        result
+    }
+
+    pub fn run_words(&mut self, args: &[String]) -> Result<i32> {
+        self.run_prepared(&Prepared {
+            args: args.to_vec(),
+            env: Vec::new(),
+            redirects: Vec::new(),
+        })
+    }
+
+    fn run_prepared(&mut self, prepared: &Prepared) -> Result<i32> {
+        match self.resolve(prepared)? {
+            Target::External(exec) => {
+                io::stdout().flush()?;
+                let text = prepared.args.join(" ");
+                let group = self.job_group(false);
+                if self.options.get("spawn") && group.is_none() {
+                    self.admit_process(&text)?;
+                    if let Some(child) = exec.spawn() {
+                        return self.wait_for(child, &text, None);
+                    }
+                }
+                match self.fork(&text)? {
+                    ForkResult::Child => {
+                        if let Some(terminal) = group {
+                            jobs::join_group(Pid::from_raw(0), Pid::this(), terminal);
+                        }
+                        signals::reset_child();
+                        self.exec(&exec, &prepared.args)
+                    }
+                    ForkResult::Parent { child } => {
+                        if let Some(terminal) = group {
+                            jobs::join_group(child, child, terminal);
+                        }
+                        self.wait_for(child, &text, group)
+                    }
+                }
+            }
+            target => self.run_target(&target, prepared),
+        }
+    }
+
+    fn run_suggestion(&mut self, suggestion: &str, prepared: &Prepared) -> Result<i32> {
+        let name = &prepared.args[0];
+        let mut args = prepared.args.clone();
+        args[0] = suggestion.to_string();
+        let prompt = self.vars.get("VSSH_AUTOCORRECT") == Some("prompt")
+            && unistd::isatty(libc::STDIN_FILENO).unwrap_or(false);
+        if !prompt {
+            report(format_args!(
+                "{}: {}: command not found — did you mean '{}'?",
+                program(),
+                name,
+                suggestion
+            ));
+            return Ok(127);
+        }
+        eprint!(
+            "{}: {}: command not found — run '{}' instead? [y/N] ",
+            program(),
+            name,
+            args.join(" ")
+        );
+        let answer = builtins::read_line(libc::STDIN_FILENO, false)?;
+        if !answer.is_some_and(|(reply, _)| matches!(reply.trim(), "y" | "Y" | "yes")) {
+            return Ok(127);
+        }
+        self.run_prepared(&Prepared {
+            args,
+            env: prepared.env.clone(),
+            redirects: Vec::new(),
+        })
+    }
+
+    fn resolve(&mut self, prepared: &Prepared) -> Result<Target> {
+        let name = &prepared.args[0];
+        self.restrict_command(name)?;
+        if let Some(builtin) = builtins::lookup(name) {
+            return Ok(Target::Builtin(builtin));
+        }
+        if let Some(body) = self.functions.get(name) {
+            return Ok(Target::Function(Rc::clone(body)));
+        }
+        let exec = self.prepare_exec(prepared, None, false)?;
+        if exec.path.is_none()
+            && !name.contains('/')
+            && !self.handling_not_found
+            && let Some(handler) = self.not_found_handler(name)
+        {
+            return Ok(Target::NotFound(handler));
+        }
+        if std::env::current_dir().is_err() {
+            return Err(ShellError::Internal(
+                "cannot access current directory".to_string(),
+            ));
+        }
+        Ok(Target::External(exec))
+    }
+
+    fn not_found_handler(&mut self, name: &str) -> Option<Handler> {
+        if let Some(body) = self.functions.get("command_not_found_handle") {
+            return Some(Handler::Function(Rc::clone(body)));
+        }
+        if let Some(src) = self
+            .vars
+            .get("VSSH_COMMAND_NOT_FOUND")
+            .filter(|src| !src.trim().is_empty())
+        {
+            return Some(Handler::Command(src.to_string()));
+        }
+        if !self.interactive {
+            return None;
+        }
+        let functions: Vec<String> = self.functions.keys().cloned().collect();
+        let commands = self.path_commands();
+        let candidates = builtins::NAMES
+            .iter()
+            .copied()
+            .chain(functions.iter().map(String::as_str))
+            .chain(commands.iter().map(String::as_str));
+        suggest::closest(name, candidates).map(|s| Handler::Suggest(s.to_string()))
+    }
+
+    fn run_not_found(&mut self, handler: &Handler, prepared: &Prepared) -> Result<i32> {
+        let args = &prepared.args;
+        if let Handler::Suggest(suggestion) = handler {
+            return self.run_suggestion(suggestion, prepared);
+        }
+        self.handling_not_found = true;
+        let result = match handler {
+            Handler::Function(body) => {
+                let mut argv = vec!["command_not_found_handle".to_string()];
+                argv.extend_from_slice(args);
+                self.call_function(body, &argv)
+            }
+            Handler::Suggest(_) => Ok(127),
+            Handler::Command(src) => parser::parse(src, self.lineno)
+                .map_err(ShellError::from)
+                .and_then(|list| {
+                    let saved = std::mem::replace(&mut self.positional, args.to_vec());
+                    let result = self.run_list(&list);
+                    self.positional = saved;
+                    result
+                }),
+        };
+        self.handling_not_found = false;
+        result
+    }
+
+    fn run_target(&mut self, target: &Target, prepared: &Prepared) -> Result<i32> {
+        match target {
+            Target::Builtin(_) if prepared.args == ["exec"] => {
+                self.redirect(&prepared.redirects)?;
+                Ok(0)
+            }
+            Target::Builtin(builtin) => {
+                self.run_in_shell(prepared, |shell| builtin(shell, &prepared.args))
+            }
+            Target::Function(body) => {
+                self.run_in_shell(prepared, |shell| shell.call_function(body, &prepared.args))
+            }
+            Target::External(exec) => self.exec(exec, &prepared.args),
+            Target::NotFound(handler) => {
+                self.run_in_shell(prepared, |shell| shell.run_not_found(handler, prepared))
+            }
+        }
+    }
+
+    fn run_in_shell(
+        &mut self,
+        prepared: &Prepared,
+        run: impl FnOnce(&mut Shell) -> Result<i32>,
+    ) -> Result<i32> {
+        let mut previous = Vec::new();
+        for (name, value) in &prepared.env {
+            previous.push((name.clone(), self.vars.variable(name).cloned()));
+            self.vars.set(name, value.clone());
+        }
+        let result = self.redirect(&prepared.redirects).and_then(|saved| {
+            let result = run(self);
+            io::stdout().flush().ok();
+            restore(saved);
+            result
+        });
+        for (name, variable) in previous.into_iter().rev() {
+            self.vars.restore(&name, variable);
+        }
+        result
+    }
+
+    fn prepare(&mut self, command: &SimpleCommand) -> Result<Option<Prepared>> {
+        self.subst_status = None;
+        self.lineno = command.line;
+        let args = match command.words.first() {
+            Some(first) if builtins::is_declaration(first) => {
+                self.expand_declaration(&command.words)?
+            }
+            _ => self.expand_words(&command.words)?,
+        };
+        let redirects = self.redirect_targets(&command.redirects)?;
+        if args.is_empty() {
+            for assign in &command.assigns {
+                let traced = self.assign(assign)?;
+                self.xtrace(&[traced]);
+            }
+            open_actions(&redirects)?;
+            return Ok(None);
+        }
+        if self.interactive
+            && !self.subshell
+            && self.options.get("verify")
+            && expansion_differs(&command.words, &args)
+            && !confirm(&args)
+        {
+            return Err(ShellError::Internal(format!("{}: not executed", args[0])));
+        }
+        self.vars.set("_", args[args.len() - 1].clone());
+        let mut env = Vec::new();
+        for assign in &command.assigns {
+            if let (None, AssignValue::Scalar(value)) = (&assign.index, &assign.value) {
+                self.restrict_variable(&assign.name)?;
+                self.writable(&assign.name)?;
+                env.push((assign.name.clone(), self.expand_single(value)?));
+            }
+        }
+        if self.options.get("xtrace") {
+            let words: Vec<String> = env
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .chain(args.iter().cloned())
+                .collect();
+            self.xtrace(&words);
+        }
+        Ok(Some(Prepared {
+            args,
+            env,
+            redirects,
+        }))
+    }
+
+    fn assign(&mut self, assign: &Assign) -> Result<String> {
+        self.restrict_variable(&assign.name)?;
+        self.writable(&assign.name)?;
+        match (&assign.index, &assign.value) {
+            (None, AssignValue::Scalar(value)) => {
+                let value = self.expand_single(value)?;
+                self.assign_dynamic(&assign.name, &value);
+                self.vars.set(&assign.name, value.clone());
+                Ok(format!("{}={}", assign.name, value))
+            }
+            (None, AssignValue::Array(words)) => {
+                let values = self.expand_words(words)?;
+                let traced = format!("{}=({})", assign.name, values.join(" "));
+                self.vars.set_array(&assign.name, values);
+                Ok(traced)
+            }
+            (Some(index), AssignValue::Scalar(value)) => {
+                let index = self.subscript(&assign.name, index)?;
+                let value = self.expand_single(value)?;
+                let traced = format!("{}[{}]={}", assign.name, index, value);
+                self.vars.set_element(&assign.name, index, value);
+                Ok(traced)
+            }
+            (Some(_), AssignValue::Array(_)) => Err(ShellError::Expansion(format!(
+                "{}: cannot assign list to array member",
+                assign.name
+            ))),
+        }
+    }
+
+    pub fn xtrace(&mut self, words: &[String]) {
+        if !self.options.get("xtrace") {
+            return;
+        }
+        let ps4 = self.vars.get("PS4").unwrap_or("+ ").to_string();
+        let prefix = self.expand_single(&word::parse(&ps4)).unwrap_or(ps4);
+        let words: Vec<String> = words.iter().map(|w| word::quote(w)).collect();
+        eprintln!("{}{}", prefix, words.join(" "));
+    }
+
+    pub fn writable(&self, name: &str) -> Result<()> {
+        match self.vars.is_readonly(name) {
+            true => Err(ShellError::Expansion(format!(
+                "{}: readonly variable",
+                name
+            ))),
+            false => Ok(()),
+        }
+    }
+
+    pub fn assign_target(&mut self, target: &str, value: String) -> Result<()> {
+        let (name, index) = match target.strip_suffix(']').and_then(|t| t.split_once('[')) {
+            Some((name, index)) => (name, Some(index)),
+            None => (target, None),
+        };
+        if !word::is_name(name) {
+            return Err(ShellError::Expansion(format!(
+                "`{}': not a valid identifier",
+                target
+            )));
+        }
+        self.restrict_variable(name)?;
+        self.writable(name)?;
+        match index {
+            None => {
+                self.assign_dynamic(name, &value);
+                self.vars.set(name, value);
+            }
+            Some(text) => {
+                let n = self.eval_arith(text)?;
+                let index = self.element_index(name, text, n)?;
+                self.vars.set_element(name, index, value);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn hash_command(&mut self, name: &str, hit: bool) -> Option<String> {
+        if name.contains('/') {
+            return Some(name.to_string());
+        }
+        let hashed = self.command_hash();
+        if let Some((path, hits)) = hashed.get_mut(name) {
+            if Path::new(&bytes::to_os(path)).exists() {
+                *hits += hit as usize;
+                return Some(path.clone());
+            }
+            hashed.remove(name);
+        }
+        let path = find_command(name, self.vars.get("PATH").unwrap_or(""))?;
+        self.command_hash()
+            .insert(name.to_string(), (path.clone(), hit as usize));
+        Some(path)
+    }
+
+    pub fn exec_command(
+        &mut self,
+        args: &[String],
+        argv0: Option<&str>,
+        clear_env: bool,
+    ) -> Result<i32> {
+        let prepared = Prepared {
+            args: args.to_vec(),
+            env: Vec::new(),
+            redirects: Vec::new(),
+        };
+        let exec = self.prepare_exec(&prepared, argv0, clear_env)?;
+        if exec.path.is_none() {
+            return Err(ShellError::Exec {
+                command: args[0].clone(),
+                errno: Errno::ENOENT,
+            });
+        }
+        io::stdout().flush()?;
+        self.save_history();
+        self.exec(&exec, args)
+    }
+
+    // An embedded shell's executable is the host program, not vssh, so
+    // scripts without a #! line are read by this process instead.
+    fn exec(&mut self, exec: &Exec, args: &[String]) -> ! {
+        exec.run();
+        let path = bytes::decode(exec.path.as_ref().map_or(&[][..], |p| p.as_bytes()));
+        let status = File::open(&*path)
+            .map_err(ShellError::from)
+            .and_then(|file| {
+                self.subshell = true;
+                self.attached.clear();
+                self.name = path.clone();
+                self.positional = args[1..].to_vec();
+                self.run_script(io::BufReader::new(file))
+            });
+        io::stdout().flush().ok();
+        std::process::exit(status.unwrap_or_else(|e| {
+            report(&e);
+            e.status()
+        }));
+    }
+
+    fn prepare_exec(
+        &mut self,
+        command: &Prepared,
+        argv0: Option<&str>,
+        clear_env: bool,
+    ) -> Result<Exec> {
+        let redirects = open_actions(&command.redirects)?;
+        let name = &command.args[0];
+        let null_byte = |_| ShellError::Internal(format!("{}: argument contains null byte", name));
+        let mut args = command
+            .args
+            .iter()
+            .map(|s| bytes::cstring(s))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(null_byte)?;
+        if let Some(argv0) = argv0 {
+            args[0] = bytes::cstring(argv0).map_err(null_byte)?;
+        }
+        let path = match command.env.iter().rfind(|(k, _)| k == "PATH") {
+            Some((_, search)) => find_command(name, search),
+            None => self.hash_command(name, true),
+        };
+        let mut overrides = command.env.clone();
+        overrides.push((
+            "_".to_string(),
+            path.clone().unwrap_or_else(|| name.clone()),
+        ));
+        let env = match clear_env {
+            true => Vec::new(),
+            false => self.vars.environ(&overrides),
+        };
+        let path = path.and_then(|path| bytes::cstring(&path).ok());
+        let argv = args
+            .iter()
+            .map(|a| a.as_ptr())
+            .chain([ptr::null()])
+            .collect();
+        let envp = env
+            .iter()
+            .map(|e| e.as_ptr())
+            .chain([ptr::null()])
+            .collect();
+        let shell = env::current_exe()
+            .ok()
+            .filter(|_| !self.embedded)
+            .and_then(|exe| CString::new(exe.into_os_string().into_vec()).ok());
+        let script_argv = match (&shell, &path) {
+            (Some(shell), Some(path)) => [shell.as_ptr(), path.as_ptr()]
+                .into_iter()
+                .chain(args[1..].iter().map(|a| a.as_ptr()))
+                .chain([ptr::null()])
+                .collect(),
+            _ => Vec::new(),
+        };
+        let policy = Plan::new(match self.vars.get("VSSH_EXEC_POLICY") {
+            Some(spec) if !self.policy_off => policy::lookup(spec, name),
+            _ => Vec::new(),
+        });
+        Ok(Exec {
+            path,
+            args,
+            _env: env,
+            argv,
+            envp,
+            shell,
+            script_argv,
+            redirects,
+            policy,
+            error_color: color::start("error"),
+        })
+    }
+
+    pub fn wait_raw(&self, pid: Pid) -> Result<WaitStatus> {
+        reap(pid, None)
+    }
+
+    /// Waits for a foreground process. In an interactive or job-control
+    /// shell, one killed by SIGINT interrupts the shell too, as if the
+    /// user's ^C had reached it; a script only stops for a ^C it got itself.
+    fn wait_job(&self, pid: Pid) -> Result<WaitStatus> {
+        let flags = self.monitoring().then_some(WaitPidFlag::WUNTRACED);
+        let status = reap(pid, flags)?;
+        if let WaitStatus::Signaled(_, Signal::SIGINT, _) = status
+            && (self.interactive || self.monitoring())
+        {
+            match signals::caught(Signal::SIGINT) {
+                true => signals::raise_pending(Signal::SIGINT),
+                false => signals::interrupt(),
+            }
+        }
+        Ok(status)
+    }
+
+    fn wait_for(&mut self, pid: Pid, text: &str, group: Option<bool>) -> Result<i32> {
+        let status = self.wait_job(pid);
+        self.reclaim_terminal();
+        let status = status?;
+        self.last_signal = match status {
+            WaitStatus::Signaled(_, signal, _) => Some(signal),
+            _ => None,
+        };
+        if let WaitStatus::Stopped(..) = status {
+            self.suspend_job(vec![(pid, None)], text, group.and(Some(pid)));
+        }
+        if !self.subshell && self.interactive {
+            self.restore_terminal();
+            report_status(&status);
+        }
+        Ok(Status::from(&status).code())
+    }
+
+    pub fn wait_pipeline(&mut self, pids: Vec<Pid>, text: &str, pgid: Option<Pid>) -> Result<i32> {
+        self.wait_segments(pids, None, text, pgid)
+    }
+
+    fn wait_segments(
+        &mut self,
+        mut pids: Vec<Pid>,
+        last: Option<i32>,
+        text: &str,
+        pgid: Option<Pid>,
+    ) -> Result<i32> {
+        let mut statuses = Vec::new();
+        for pid in &pids {
+            match self.wait_job(*pid) {
+                Ok(status) => statuses.push(status),
+                Err(e) => {
+                    self.reclaim_terminal();
+                    return Err(e);
+                }
+            }
+        }
+        self.reclaim_terminal();
+        if statuses
+            .iter()
+            .any(|status| matches!(status, WaitStatus::Stopped(..)))
+        {
+            let processes = pids
+                .iter()
+                .zip(&statuses)
+                .map(|(pid, status)| (*pid, Status::terminated(status).map(Status::code)))
+                .collect();
+            self.suspend_job(processes, text, pgid);
+        }
+        if let Some(status) = last {
+            pids.push(Pid::this());
+            statuses.push(WaitStatus::Exited(Pid::this(), status));
+        }
+        self.last_signal = match statuses.last() {
+            Some(WaitStatus::Signaled(_, signal, _)) => Some(*signal),
+            _ => None,
+        };
+        let codes: Vec<i32> = statuses
+            .iter()
+            .map(|status| Status::from(status).code())
+            .collect();
+        for (traced, code) in std::mem::take(&mut self.traced).into_iter().zip(&codes) {
+            self.trace_end(traced, *code);
+        }
+        self.vars.set_array(
+            "PIPESTATUS",
+            codes.iter().map(|code| code.to_string()).collect(),
+        );
+        if !self.subshell && self.interactive {
+            self.restore_terminal();
+            if self.options.get("verbose-status") {
+                statuses.iter().for_each(report_status);
+            } else {
+                report_pipeline(&pids, &statuses);
+            }
+        }
+        Ok(codes.last().copied().unwrap_or(0))
+    }
+}
+
+fn reap(pid: Pid, flags: Option<WaitPidFlag>) -> Result<WaitStatus> {
+    loop {
+        match waitpid(pid, flags) {
+            Err(Errno::EINTR) => continue,
+            result => return Ok(result?),
+        }
+    }
+}
+
+fn literal(word: &Word) -> Option<String> {
+    let mut text = String::new();
+    for part in &word.parts {
+        match part {
+            WordPart::Literal(s) if !s.contains(['*', '?', '[']) => text.push_str(s),
+            WordPart::Quoted(s) => text.push_str(s),
+            WordPart::DoubleQuoted(parts) => {
+                for part in parts {
+                    match part {
+                        WordPart::Literal(s) => text.push_str(s),
+                        _ => return None,
+                    }
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(text)
+}
+
+fn expansion_differs(words: &[Word], args: &[String]) -> bool {
+    let typed: Option<Vec<String>> = words.iter().map(literal).collect();
+    typed.is_none_or(|typed| typed != args)
+}
+
+fn confirm(args: &[String]) -> bool {
+    let Ok(mut tty) = OpenOptions::new().read(true).write(true).open("/dev/tty") else {
+        return false;
+    };
+    let shown: Vec<String> = args.iter().map(|arg| word::quote(arg)).collect();
+    if write!(tty, "+ {}\nexecute? [Y/n] ", shown.join(" ")).is_err() {
+        return false;
+    }
+    let mut answer = Vec::new();
+    let mut byte = [0u8];
+    while let Ok(1) = tty.read(&mut byte) {
+        if byte[0] == b'\n' {
+            break;
+        }
+        answer.push(byte[0]);
+    }
+    matches!(
+        String::from_utf8_lossy(&answer).trim(),
+        "" | "y" | "Y" | "yes"
+    )
+}
+
+fn report_status(status: &WaitStatus) {
+    match *status {
+        WaitStatus::Exited(pid, status) => {
+            eprintln!("Process {} exited with status {}", pid, status);
+        }
+        WaitStatus::Signaled(pid, signal, core_dumped) => {
+            eprintln!(
+                "Process {} was killed by signal {:?}, core dumped: {}",
+                pid, signal, core_dumped
+            );
+        }
+        WaitStatus::Continued(pid) => {
+            eprintln!("Process {} continued", pid);
+        }
+        WaitStatus::Stopped(pid, signal) => {
+            eprintln!("Process {} stopped by signal {:?}", pid, signal);
+        }
+        WaitStatus::StillAlive => {
+            eprintln!("No state changes to report");
+        }
+        _ => {}
+    }
+}
+
+fn report_pipeline(pids: &[Pid], statuses: &[WaitStatus]) {
+    let Some((last, rest)) = statuses.split_last() else {
+        return;
+    };
+    let pids: Vec<String> = pids.iter().map(Pid::to_string).collect();
+    let mut line = match *last {
+        WaitStatus::Signaled(_, signal, core_dumped) => format!(
+            "Pipeline {} was killed by signal {:?}, core dumped: {}",
+            pids.join(" | "),
+            signal,
+            core_dumped
+        ),
+        WaitStatus::Stopped(_, signal) => {
+            format!(
+                "Pipeline {} stopped by signal {:?}",
+                pids.join(" | "),
+                signal
+            )
+        }
+        _ => format!(
+            "Pipeline {} exited with status {}",
+            pids.join(" | "),
+            Status::from(last).code()
+        ),
+    };
+    for (i, status) in rest.iter().enumerate() {
+        if let WaitStatus::Signaled(_, signal, _) = *status
+            && signal != Signal::SIGPIPE
+        {
+            line.push_str(&format!(
+                " (segment {} killed by signal {:?})",
+                i + 1,
+                signal
+            ));
+        }
+    }
+    eprintln!("{}", line);
+}
@@ -0,0 +1,1305 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
+use nix::sys::stat::Mode;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{self, dup2, mkfifo, pipe2, ForkResult, Pid, User};
+
+use crate::ast::{Command, List, Redirect, RedirectKind, Word, WordPart};
+use crate::bytes;
+use crate::error::{self, describe, report, Result, ShellError};
+use crate::exec;
+use crate::glob;
+use crate::parser;
+use crate::pattern::{has_meta, Pattern};
+use crate::shell::Shell;
+use crate::signals;
+use crate::word::{self, is_name};
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Quoted(String),
+    Expanded(String),
+}
+
+type Field = Vec<Segment>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Brace,
+    Tilde,
+    Substitution,
+    Splitting,
+    Pathname,
+    QuoteRemoval,
+}
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Brace => "brace",
+            Stage::Tilde => "tilde",
+            Stage::Substitution => "substitution",
+            Stage::Splitting => "splitting",
+            Stage::Pathname => "pathname",
+            Stage::QuoteRemoval => "quote removal",
+        }
+    }
+}
+
+pub struct Substitution {
+    pid: Pid,
+    fd: Option<OwnedFd>,
+    fifo: Option<PathBuf>,
+    output: bool,
+}
+
+enum Channel {
+    Pipe(OwnedFd, OwnedFd),
+    Fifo(PathBuf),
+}
+
+fn make_fifo() -> Result<PathBuf> {
+    let dir = std::env::temp_dir();
+    for i in 0.. {
+        let path = dir.join(format!("vssh-fifo-{}-{}", unistd::getpid(), i));
+        match mkfifo(&path, Mode::S_IRUSR | Mode::S_IWUSR) {
+            Ok(()) => return Ok(path),
+            Err(Errno::EEXIST) => continue,
+            Err(e) => return Err(ShellError::Internal(format!("mkfifo: {}", e.desc()))),
+        }
+    }
+    unreachable!()
+}
+
+fn open_fifo(path: &Path, read: bool, flags: i32) -> io::Result<File> {
+    OpenOptions::new()
+        .read(read)
+        .write(!read)
+        .custom_flags(flags)
+        .open(path)
+}
+
+fn push(fields: &mut Vec<Field>, segment: Segment) {
+    match fields.last_mut() {
+        Some(field) => field.push(segment),
+        None => fields.push(vec![segment]),
+    }
+}
+
+fn split(field: Field, ifs: &str) -> Vec<Field> {
+    if ifs.is_empty() {
+        // Nothing splits, but a field made only of empty expansions still
+        // disappears.
+        let empty = field
+            .iter()
+            .all(|segment| matches!(segment, Segment::Expanded(text) if text.is_empty()));
+        return match empty {
+            true => Vec::new(),
+            false => vec![field],
+        };
+    }
+    let is_space = |c: char| matches!(c, ' ' | '\t' | '\n') && ifs.contains(c);
+    let mut out = Vec::new();
+    let mut current: Field = Vec::new();
+    let mut keep = false;
+    let mut after_space = false;
+    for segment in field {
+        match segment {
+            Segment::Expanded(text) => {
+                let mut piece = String::new();
+                for c in text.chars() {
+                    if !ifs.contains(c) {
+                        piece.push(c);
+                        keep = true;
+                        after_space = false;
+                        continue;
+                    }
+                    if !piece.is_empty() {
+                        current.push(Segment::Expanded(std::mem::take(&mut piece)));
+                    }
+                    if keep {
+                        out.push(std::mem::take(&mut current));
+                        keep = false;
+                        after_space = is_space(c);
+                    } else if !is_space(c) {
+                        if !after_space {
+                            out.push(std::mem::take(&mut current));
+                        }
+                        after_space = false;
+                    }
+                }
+                if !piece.is_empty() {
+                    current.push(Segment::Expanded(piece));
+                }
+            }
+            other => {
+                current.push(other);
+                keep = true;
+                after_space = false;
+            }
+        }
+    }
+    if keep {
+        out.push(current);
+    }
+    out
+}
+
+fn push_list(fields: &mut Vec<Field>, values: Vec<String>, quoted: bool, at: bool, ifs: &str) {
+    if quoted && !at {
+        let sep = ifs.chars().next().map(String::from).unwrap_or_default();
+        push(fields, Segment::Quoted(values.join(&sep)));
+        return;
+    }
+    for (i, value) in values.into_iter().enumerate() {
+        if i > 0 {
+            fields.push(Vec::new());
+        }
+        push(
+            fields,
+            if quoted {
+                Segment::Quoted(value)
+            } else {
+                Segment::Expanded(value)
+            },
+        );
+    }
+}
+
+fn input_only(program: &List) -> Option<&Redirect> {
+    let [item] = program.items.as_slice() else {
+        return None;
+    };
+    if item.background || !item.and_or.rest.is_empty() || item.and_or.first.negated {
+        return None;
+    }
+    let [Command::Simple(simple)] = item.and_or.first.commands.as_slice() else {
+        return None;
+    };
+    match simple.redirects.as_slice() {
+        [redirect]
+            if simple.words.is_empty()
+                && simple.assigns.is_empty()
+                && redirect.kind == RedirectKind::Input
+                && redirect.fd.unwrap_or(0) == 0 =>
+        {
+            Some(redirect)
+        }
+        _ => None,
+    }
+}
+
+fn trim_newlines(mut output: String) -> String {
+    let kept = output.trim_end_matches('\n').len();
+    output.truncate(kept);
+    output
+}
+
+fn exit_code(status: WaitStatus) -> i32 {
+    match status {
+        WaitStatus::Exited(_, code) => code,
+        WaitStatus::Signaled(_, signal, _) => 128 + signal as i32,
+        _ => 0,
+    }
+}
+
+fn capture(read_end: OwnedFd, child: Pid) -> Result<(Vec<u8>, i32)> {
+    let mut reader = File::from(read_end);
+    let mut output = Vec::new();
+    let mut chunk = vec![0u8; 1 << 16];
+    let mut status = None;
+    loop {
+        let mut fd = libc::pollfd {
+            fd: reader.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut fd, 1, CAPTURE_POLL_MS) } > 0 {
+            match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => output.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+            continue;
+        }
+        if let Ok(finished @ (WaitStatus::Exited(..) | WaitStatus::Signaled(..))) =
+            waitpid(child, Some(WaitPidFlag::WNOHANG))
+        {
+            status = Some(exit_code(finished));
+            fcntl(reader.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK))?;
+            while let Ok(n @ 1..) = reader.read(&mut chunk) {
+                output.extend_from_slice(&chunk[..n]);
+            }
+            break;
+        }
+    }
+    let status = match status {
+        Some(status) => status,
+        None => loop {
+            match waitpid(child, None) {
+                Err(Errno::EINTR) => continue,
+                result => break exit_code(result?),
+            }
+        },
+    };
+    Ok((output, status))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Char(char),
+    Part(WordPart),
+}
+
+fn pieces(word: &Word) -> Vec<Piece> {
+    let mut out = Vec::new();
+    for part in &word.parts {
+        match part {
+            WordPart::Literal(s) => out.extend(s.chars().map(Piece::Char)),
+            WordPart::Tilde(user) => {
+                out.push(Piece::Char('~'));
+                out.extend(user.chars().map(Piece::Char));
+            }
+            other => out.push(Piece::Part(other.clone())),
+        }
+    }
+    out
+}
+
+fn assemble(pieces: Vec<Piece>) -> Word {
+    let mut parts = Vec::new();
+    for piece in pieces {
+        match (piece, parts.last_mut()) {
+            (Piece::Char(c), Some(WordPart::Literal(s))) => s.push(c),
+            (Piece::Char(c), _) => parts.push(WordPart::Literal(c.to_string())),
+            (Piece::Part(part), _) => parts.push(part),
+        }
+    }
+    let mut word = Word { parts };
+    if let Some(WordPart::Literal(text)) = word.parts.first()
+        && let Some(rest) = text.strip_prefix('~')
+    {
+        let end = rest.find('/').unwrap_or(rest.len());
+        let user = &rest[..end];
+        let whole = end < rest.len() || word.parts.len() == 1;
+        if whole
+            && user
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        {
+            let tail = rest[end..].to_string();
+            let mut parts = vec![WordPart::Tilde(user.to_string())];
+            if !tail.is_empty() {
+                parts.push(WordPart::Literal(tail));
+            }
+            word.parts.splice(..1, parts);
+        }
+    }
+    word
+}
+
+fn sequence(body: &[Piece]) -> Option<Vec<Vec<Piece>>> {
+    let mut text = String::new();
+    for piece in body {
+        match piece {
+            Piece::Char(c) => text.push(*c),
+            Piece::Part(_) => return None,
+        }
+    }
+    let bounds: Vec<&str> = text.split("..").collect();
+    let (start, end, step) = match bounds.as_slice() {
+        [start, end] => (*start, *end, None),
+        [start, end, step] => (*start, *end, Some(step.parse::<i64>().ok()?)),
+        _ => return None,
+    };
+    let step = step.map_or(1, i64::unsigned_abs).max(1);
+    let values: Vec<String> = if let (Ok(a), Ok(b)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let padded = |s: &str| {
+            let digits = s.trim_start_matches('-');
+            digits.len() > 1 && digits.starts_with('0')
+        };
+        let width = match padded(start) || padded(end) {
+            true => start.len().max(end.len()),
+            false => 0,
+        };
+        stepped(a, b, step)
+            .map(|n| match n < 0 {
+                true => format!("-{:0>1$}", -n, width.saturating_sub(1)),
+                false => format!("{:0>1$}", n, width),
+            })
+            .collect()
+    } else {
+        let (mut a, mut b) = (start.chars(), end.chars());
+        let (Some(a), None, Some(b), None) = (a.next(), a.next(), b.next(), b.next()) else {
+            return None;
+        };
+        if !a.is_ascii_alphabetic() || !b.is_ascii_alphabetic() {
+            return None;
+        }
+        stepped(a as i64, b as i64, step)
+            .filter_map(|n| char::from_u32(n as u32).map(String::from))
+            .collect()
+    };
+    Some(
+        values
+            .into_iter()
+            .map(|value| value.chars().map(Piece::Char).collect())
+            .collect(),
+    )
+}
+
+fn stepped(start: i64, end: i64, step: u64) -> impl Iterator<Item = i64> {
+    let count = start.abs_diff(end) / step + 1;
+    let step = if start <= end {
+        step as i64
+    } else {
+        -(step as i64)
+    };
+    (0..count as i64).map(move |i| start + i * step)
+}
+
+fn braces(pieces: &[Piece]) -> Vec<Vec<Piece>> {
+    let is = |i: usize, want: char| matches!(pieces.get(i), Some(Piece::Char(c)) if *c == want);
+    for open in (0..pieces.len()).filter(|&i| is(i, '{')) {
+        let mut depth = 0;
+        let mut commas = Vec::new();
+        let mut close = None;
+        for i in open + 1..pieces.len() {
+            if is(i, '{') {
+                depth += 1;
+            } else if is(i, '}') && depth == 0 {
+                close = Some(i);
+                break;
+            } else if is(i, '}') {
+                depth -= 1;
+            } else if is(i, ',') && depth == 0 {
+                commas.push(i);
+            }
+        }
+        let Some(close) = close else {
+            continue;
+        };
+        let alternatives = match commas.is_empty() {
+            true => match sequence(&pieces[open + 1..close]) {
+                Some(values) => values,
+                None => continue,
+            },
+            false => {
+                let mut bounds = vec![open];
+                bounds.extend(&commas);
+                bounds.push(close);
+                bounds
+                    .windows(2)
+                    .map(|w| pieces[w[0] + 1..w[1]].to_vec())
+                    .collect()
+            }
+        };
+        let (prefix, suffix) = (&pieces[..open], &pieces[close + 1..]);
+        return alternatives
+            .into_iter()
+            .flat_map(|alternative| braces(&[prefix, &alternative, suffix].concat()))
+            .collect();
+    }
+    vec![pieces.to_vec()]
+}
+
+pub fn brace_expand(word: &Word) -> Vec<Word> {
+    let braced = word
+        .parts
+        .iter()
+        .any(|part| matches!(part, WordPart::Literal(s) if s.contains('{')));
+    if !braced {
+        return vec![word.clone()];
+    }
+    let pieces = pieces(word);
+    let expanded = braces(&pieces);
+    match expanded.as_slice() {
+        [single] if *single == pieces => vec![word.clone()],
+        _ => expanded.into_iter().map(assemble).collect(),
+    }
+}
+
+fn show_parts(parts: &[WordPart]) -> String {
+    parts
+        .iter()
+        .map(|part| match part {
+            WordPart::Literal(s) => s.clone(),
+            WordPart::Quoted(s) => format!("'{}'", s.replace('\'', "'\\''")),
+            WordPart::DoubleQuoted(inner) => format!("\"{}\"", show_parts(inner)),
+            WordPart::Tilde(user) => format!("~{}", user),
+            WordPart::Param(name) => format!("${{{}}}", name),
+            WordPart::CommandSubst(src) => format!("$({})", src),
+            WordPart::ProcessSubst(output, src) => {
+                format!("{}({})", if *output { '>' } else { '<' }, src)
+            }
+            WordPart::Arith(expr) => format!("$(({}))", expr),
+        })
+        .collect()
+}
+
+fn glob_chars(field: &Field) -> Vec<(char, bool)> {
+    let mut chars = Vec::new();
+    for segment in field {
+        let (text, active) = match segment {
+            Segment::Literal(s) | Segment::Expanded(s) => (s, true),
+            Segment::Quoted(s) => (s, false),
+        };
+        chars.extend(text.chars().map(|c| (c, active)));
+    }
+    chars
+}
+
+fn show(fields: &[Field]) -> Vec<String> {
+    fields
+        .iter()
+        .map(|field| {
+            field
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Literal(s) | Segment::Expanded(s) => s.clone(),
+                    Segment::Quoted(s) => format!("'{}'", s.replace('\'', "'\\''")),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn join(field: &Field) -> String {
+    field
+        .iter()
+        .map(|segment| match segment {
+            Segment::Literal(s) | Segment::Quoted(s) | Segment::Expanded(s) => s.as_str(),
+        })
+        .collect()
+}
+
+const MAX_SUBSTITUTION_DEPTH: usize = 128;
+
+const CAPTURE_POLL_MS: i32 = 20;
+
+const DYNAMIC: &[&str] = &["RANDOM", "SECONDS", "LINENO", "EPOCHSECONDS"];
+
+const OPERATORS: &[&str] = &[
+    ":-", ":=", ":?", ":+", "-", "=", "?", "+", "##", "#", "%%", "%", "//", "/",
+];
+
+fn split_replacement(rhs: &str) -> (&str, &str) {
+    let mut chars = rhs.char_indices();
+    let mut quote = None;
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '\'' | '"' if quote.is_none() => quote = Some(c),
+            c if quote == Some(c) => quote = None,
+            '/' if quote.is_none() => return (&rhs[..i], &rhs[i + 1..]),
+            _ => {}
+        }
+    }
+    (rhs, "")
+}
+
+fn split_operator(body: &str) -> Option<(&str, &str, &str)> {
+    let first = body.chars().next()?;
+    let end = if first.is_ascii_alphabetic() || first == '_' {
+        let name_end = body
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(body.len());
+        match body[name_end..].strip_prefix('[') {
+            Some(rest) => name_end + 1 + rest.find(']')? + 1,
+            None => name_end,
+        }
+    } else if first.is_ascii_digit() {
+        body.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(body.len())
+    } else if "?$#@*!-".contains(first) {
+        1
+    } else {
+        return None;
+    };
+    let (param, rest) = body.split_at(end);
+    let op = OPERATORS.iter().find(|op| rest.starts_with(**op))?;
+    Some((param, &rest[..op.len()], &rest[op.len()..]))
+}
+
+fn array_subscript(body: &str) -> Option<(&str, &str)> {
+    let (name, rest) = body.split_once('[')?;
+    let sub = rest.strip_suffix(']')?;
+    is_name(name).then_some((name, sub))
+}
+
+impl Shell {
+    pub fn expand_words(&mut self, words: &[Word]) -> Result<Vec<String>> {
+        let mut out = Vec::new();
+        for word in words {
+            out.extend(self.expand_word(word)?);
+        }
+        Ok(out)
+    }
+
+    pub fn expand_word(&mut self, word: &Word) -> Result<Vec<String>> {
+        self.expand_stages(word, &mut |_, _| {})
+    }
+
+    pub fn expand_stages(
+        &mut self,
+        word: &Word,
+        observe: &mut dyn FnMut(Stage, &[String]),
+    ) -> Result<Vec<String>> {
+        let words = brace_expand(word);
+        let shown: Vec<String> = words.iter().map(|w| show_parts(&w.parts)).collect();
+        observe(Stage::Brace, &shown);
+        let words: Vec<Word> = words.iter().map(|w| self.expand_tilde(w)).collect();
+        let shown: Vec<String> = words.iter().map(|w| show_parts(&w.parts)).collect();
+        observe(Stage::Tilde, &shown);
+        let mut fields = Vec::new();
+        for word in &words {
+            let mut expanded = vec![Vec::new()];
+            self.expand_parts(&word.parts, false, &mut expanded)?;
+            fields.extend(expanded);
+        }
+        observe(Stage::Substitution, &show(&fields));
+        let ifs = self.ifs();
+        let fields: Vec<Field> = fields
+            .into_iter()
+            .flat_map(|field| split(field, &ifs))
+            .collect();
+        observe(Stage::Splitting, &show(&fields));
+        let nullglob = self.options.get("nullglob");
+        let mut globbed = Vec::new();
+        for field in fields {
+            let chars = glob_chars(&field);
+            if has_meta(&chars) {
+                let matches = glob::expand(&chars);
+                if !matches.is_empty() || nullglob {
+                    globbed.extend(matches.into_iter().map(|m| vec![Segment::Quoted(m)]));
+                    continue;
+                }
+            }
+            globbed.push(field);
+        }
+        observe(Stage::Pathname, &show(&globbed));
+        let out: Vec<String> = globbed.iter().map(join).collect();
+        observe(Stage::QuoteRemoval, &out);
+        Ok(out)
+    }
+
+    fn expand_tilde(&self, word: &Word) -> Word {
+        let parts = word
+            .parts
+            .iter()
+            .map(|part| match part {
+                WordPart::Tilde(user) => WordPart::Quoted(self.tilde(user)),
+                other => other.clone(),
+            })
+            .collect();
+        Word { parts }
+    }
+
+    fn ifs(&self) -> String {
+        self.vars.get("IFS").unwrap_or(" \t\n").to_string()
+    }
+
+    pub fn expand_declaration(&mut self, words: &[Word]) -> Result<Vec<String>> {
+        let mut out = Vec::new();
+        for word in words {
+            match word.parts.first() {
+                Some(WordPart::Literal(s))
+                    if s.split_once('=').is_some_and(|(name, _)| is_name(name)) =>
+                {
+                    out.push(self.expand_single(word)?);
+                }
+                _ => out.extend(self.expand_word(word)?),
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn expand_single(&mut self, word: &Word) -> Result<String> {
+        let mut fields = vec![Vec::new()];
+        self.expand_parts(&word.parts, true, &mut fields)?;
+        Ok(fields.iter().map(join).collect::<Vec<_>>().join(" "))
+    }
+
+    pub fn expand_pattern(&mut self, word: &Word) -> Result<Pattern> {
+        let mut fields = vec![Vec::new()];
+        self.expand_parts(&word.parts, false, &mut fields)?;
+        let mut chars = Vec::new();
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                chars.push((' ', false));
+            }
+            chars.extend(glob_chars(field));
+        }
+        Ok(Pattern::new(&chars))
+    }
+
+    fn expand_parts(
+        &mut self,
+        parts: &[WordPart],
+        quoted: bool,
+        fields: &mut Vec<Field>,
+    ) -> Result<()> {
+        if exec::stack_exhausted() {
+            return Err(ShellError::Expansion(
+                "expansion nested too deeply".to_string(),
+            ));
+        }
+        let wrap = |text: String| {
+            if quoted {
+                Segment::Quoted(text)
+            } else {
+                Segment::Expanded(text)
+            }
+        };
+        for part in parts {
+            match part {
+                WordPart::Literal(s) if quoted => push(fields, Segment::Quoted(s.clone())),
+                WordPart::Literal(s) => push(fields, Segment::Literal(s.clone())),
+                WordPart::Quoted(s) => push(fields, Segment::Quoted(s.clone())),
+                WordPart::DoubleQuoted(inner) => {
+                    if inner.is_empty() {
+                        push(fields, Segment::Quoted(String::new()));
+                    }
+                    self.expand_parts(inner, true, fields)?;
+                }
+                WordPart::Tilde(user) => push(fields, Segment::Quoted(self.tilde(user))),
+                WordPart::Param(name) if split_operator(name).is_some() => {
+                    let (param, op, rhs) = split_operator(name).unwrap();
+                    self.expand_operator(param, op, rhs, quoted, fields)?;
+                }
+                WordPart::Param(name) => match self.list_param(name) {
+                    Some((values, at)) => push_list(fields, values, quoted, at, &self.ifs()),
+                    None => {
+                        let value = self.bound_param(name)?;
+                        push(fields, wrap(value));
+                    }
+                },
+                WordPart::Arith(expr) => {
+                    let expr = self.expand_single(&word::parse(expr))?;
+                    // Unlike `((...))`, a bad `$((...))` abandons the
+                    // command line, as a bad `${x?}` does.
+                    let value = self.eval_arith(&expr).map_err(|e| match e {
+                        ShellError::Expansion(message) => ShellError::Arithmetic(message),
+                        e => e,
+                    })?;
+                    push(fields, wrap(value.to_string()));
+                }
+                WordPart::CommandSubst(src) => {
+                    let output = self.command_subst(src)?;
+                    push(fields, wrap(output));
+                }
+                WordPart::ProcessSubst(output, src) => {
+                    let path = self.process_subst(src, *output)?;
+                    push(fields, Segment::Quoted(path));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn tilde(&self, user: &str) -> String {
+        if user.is_empty() {
+            return self.vars.get("HOME").unwrap_or("~").to_string();
+        }
+        match User::from_name(user) {
+            Ok(Some(entry)) => entry.dir.to_string_lossy().into_owned(),
+            _ => format!("~{}", user),
+        }
+    }
+
+    pub fn subscript(&mut self, name: &str, index: &Word) -> Result<usize> {
+        let text = self.expand_single(index)?;
+        let Ok(n) = text.trim().parse::<i64>() else {
+            return Err(ShellError::Expansion(format!(
+                "{}[{}]: bad array subscript",
+                name, text
+            )));
+        };
+        self.element_index(name, &text, n)
+    }
+
+    pub fn element_index(&self, name: &str, text: &str, n: i64) -> Result<usize> {
+        if n >= 0 {
+            return Ok(n as usize);
+        }
+        let len = self
+            .vars
+            .elements(name)
+            .last()
+            .map_or(0, |(i, _)| *i as i64 + 1);
+        if len + n < 0 {
+            return Err(ShellError::Expansion(format!(
+                "{}[{}]: bad array subscript",
+                name, text
+            )));
+        }
+        Ok((len + n) as usize)
+    }
+
+    fn expand_operator(
+        &mut self,
+        param: &str,
+        op: &str,
+        rhs: &str,
+        quoted: bool,
+        fields: &mut Vec<Field>,
+    ) -> Result<()> {
+        let wrap = |text: String| {
+            if quoted {
+                Segment::Quoted(text)
+            } else {
+                Segment::Expanded(text)
+            }
+        };
+        if quoted {
+            push(fields, Segment::Quoted(String::new()));
+        }
+        if matches!(op, "#" | "##" | "%" | "%%" | "/" | "//") {
+            return self.expand_transform(param, op, rhs, quoted, fields);
+        }
+        let value = self.param_value(param)?;
+        let missing = match &value {
+            None => true,
+            Some(value) => op.starts_with(':') && value.is_empty(),
+        };
+        let word = word::parse(rhs);
+        match op.trim_start_matches(':') {
+            "-" if missing => self.expand_rhs(&word, quoted, fields)?,
+            "=" if missing => {
+                if !is_name(param) {
+                    return Err(ShellError::Expansion(format!(
+                        "${}: cannot assign in this way",
+                        param
+                    )));
+                }
+                self.restrict_variable(param)?;
+                self.writable(param)?;
+                let value = self.expand_single(&word)?;
+                self.vars.set(param, value.clone());
+                push(fields, wrap(value));
+            }
+            "?" if missing => {
+                let message = match rhs {
+                    "" => "parameter null or not set".to_string(),
+                    _ => self.expand_single(&word)?,
+                };
+                return Err(ShellError::Parameter(format!("{}: {}", param, message)));
+            }
+            "+" if missing => {}
+            "+" => self.expand_rhs(&word, quoted, fields)?,
+            _ => push(fields, wrap(value.unwrap_or_default())),
+        }
+        Ok(())
+    }
+
+    fn expand_transform(
+        &mut self,
+        param: &str,
+        op: &str,
+        rhs: &str,
+        quoted: bool,
+        fields: &mut Vec<Field>,
+    ) -> Result<()> {
+        let (pattern, with) = match op {
+            "/" | "//" => split_replacement(rhs),
+            _ => (rhs, ""),
+        };
+        let anchor = match pattern.chars().next() {
+            Some(c @ ('#' | '%')) if op == "/" => Some(c),
+            _ => None,
+        };
+        let pattern = match anchor {
+            Some(_) => &pattern[1..],
+            None => pattern,
+        };
+        let pattern = self.expand_pattern(&word::parse(pattern))?;
+        let with = self.expand_single(&word::parse(with))?;
+        let transform = |text: &str| match op {
+            "#" => pattern.remove_prefix(text, false),
+            "##" => pattern.remove_prefix(text, true),
+            "%" => pattern.remove_suffix(text, false),
+            "%%" => pattern.remove_suffix(text, true),
+            _ => pattern.replace(text, &with, op == "//", anchor),
+        };
+        let (values, at) = match self.list_param(param) {
+            Some(list) => list,
+            None => (vec![self.bound_param(param)?], false),
+        };
+        let values = values.iter().map(|v| transform(v)).collect();
+        push_list(fields, values, quoted, at, &self.ifs());
+        Ok(())
+    }
+
+    fn expand_rhs(&mut self, word: &Word, quoted: bool, fields: &mut Vec<Field>) -> Result<()> {
+        let mut rhs = vec![Vec::new()];
+        self.expand_parts(&word.parts, quoted, &mut rhs)?;
+        for (i, field) in rhs.into_iter().enumerate() {
+            if i > 0 {
+                fields.push(Vec::new());
+            }
+            for segment in field {
+                push(
+                    fields,
+                    match segment {
+                        Segment::Literal(s) => Segment::Expanded(s),
+                        other => other,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn param_value(&mut self, param: &str) -> Result<Option<String>> {
+        if let Some((values, _)) = self.list_param(param) {
+            return Ok((!values.is_empty()).then(|| values.join(" ")));
+        }
+        let set = if let Some((array, sub)) = array_subscript(param) {
+            let index = self.subscript(array, &word::parse(sub))?;
+            self.vars.elements(array).iter().any(|(i, _)| *i == index)
+        } else if is_name(param) {
+            DYNAMIC.contains(&param) || self.vars.variable(param).is_some()
+        } else if param != "0" && param.chars().all(|c| c.is_ascii_digit()) {
+            param
+                .parse::<usize>()
+                .is_ok_and(|n| n <= self.positional.len())
+        } else {
+            true
+        };
+        if !set {
+            return Ok(None);
+        }
+        self.param(param).map(Some)
+    }
+
+    fn bound_param(&mut self, name: &str) -> Result<String> {
+        let target = match name.strip_prefix('#') {
+            Some(inner) if !inner.is_empty() => inner,
+            _ => name,
+        };
+        if self.options.get("nounset")
+            && self.list_param(target).is_none()
+            && self.param_value(target)?.is_none()
+        {
+            return Err(ShellError::Parameter(format!(
+                "{}: unbound variable",
+                target
+            )));
+        }
+        self.param(name)
+    }
+
+    fn list_param(&self, name: &str) -> Option<(Vec<String>, bool)> {
+        match name {
+            "@" => return Some((self.positional.clone(), true)),
+            "*" => return Some((self.positional.clone(), false)),
+            _ => {}
+        }
+        let (array, sub) = array_subscript(name.strip_prefix('!').unwrap_or(name))?;
+        let at = match sub {
+            "@" => true,
+            "*" => false,
+            _ => return None,
+        };
+        let elements = self.vars.elements(array);
+        let values = if name.starts_with('!') {
+            elements.into_iter().map(|(i, _)| i.to_string()).collect()
+        } else {
+            elements.into_iter().map(|(_, v)| v).collect()
+        };
+        Some((values, at))
+    }
+
+    fn param(&mut self, name: &str) -> Result<String> {
+        if let Some(inner) = name.strip_prefix('#')
+            && !inner.is_empty()
+            && array_subscript(inner).is_none()
+        {
+            if inner == "@" || inner == "*" {
+                return Ok(self.positional.len().to_string());
+            }
+            return Ok(self.param(inner)?.chars().count().to_string());
+        }
+        if let Some(array) = name.strip_prefix('#')
+            && let Some((array, sub)) = array_subscript(array)
+        {
+            if sub == "@" || sub == "*" {
+                return Ok(self.vars.elements(array).len().to_string());
+            }
+            let index = self.subscript(array, &word::parse(sub))?;
+            return Ok(self.element(array, index).chars().count().to_string());
+        }
+        if let Some((array, sub)) = array_subscript(name) {
+            let index = self.subscript(array, &word::parse(sub))?;
+            return Ok(self.element(array, index));
+        }
+        let value = match name {
+            "?" => self.last_status.to_string(),
+            "$" => std::process::id().to_string(),
+            "#" => self.positional.len().to_string(),
+            "0" => self.name.clone(),
+            "!" => self
+                .last_background
+                .map(|pid| pid.to_string())
+                .unwrap_or_default(),
+            "-" => String::new(),
+            _ if name.chars().all(|c| c.is_ascii_digit()) => {
+                let index: usize = name.parse().unwrap_or(usize::MAX);
+                self.positional
+                    .get(index.wrapping_sub(1))
+                    .cloned()
+                    .unwrap_or_default()
+            }
+            _ if is_name(name) => match self.dynamic(name) {
+                Some(value) => value,
+                None => self.vars.get(name).unwrap_or("").to_string(),
+            },
+            _ => {
+                return Err(ShellError::Expansion(format!(
+                    "${{{}}}: bad substitution",
+                    name
+                )))
+            }
+        };
+        Ok(value)
+    }
+
+    fn element(&self, name: &str, index: usize) -> String {
+        self.vars
+            .elements(name)
+            .into_iter()
+            .find(|(i, _)| *i == index)
+            .map(|(_, v)| v)
+            .unwrap_or_default()
+    }
+
+    fn process_subst(&mut self, src: &str, output: bool) -> Result<String> {
+        let program = parser::parse(src, self.lineno)?;
+        let channel = if Path::new("/dev/fd").is_dir() {
+            let (read_end, write_end) = pipe2(OFlag::O_CLOEXEC)
+                .map_err(|e| ShellError::Internal(format!("pipe: {}", e.desc())))?;
+            Channel::Pipe(read_end, write_end)
+        } else {
+            Channel::Fifo(make_fifo()?)
+        };
+        io::stdout().flush()?;
+        let text = format!("{}({})", if output { '>' } else { '<' }, src.trim());
+        match self.fork(&text)? {
+            ForkResult::Child => {
+                signals::reset_child();
+                self.substitutions.clear();
+                let target = if output {
+                    libc::STDIN_FILENO
+                } else {
+                    libc::STDOUT_FILENO
+                };
+                let source = match channel {
+                    Channel::Pipe(read_end, write_end) if output => {
+                        drop(write_end);
+                        Ok(read_end)
+                    }
+                    Channel::Pipe(read_end, write_end) => {
+                        drop(read_end);
+                        Ok(write_end)
+                    }
+                    Channel::Fifo(path) => open_fifo(&path, output, 0).map(OwnedFd::from),
+                };
+                let result = source.and_then(|fd| {
+                    dup2(fd.as_raw_fd(), target)
+                        .map(drop)
+                        .map_err(io::Error::from)
+                });
+                if let Err(e) = result {
+                    report(format_args!("{}: {}", error::program(), describe(&e)));
+                    std::process::exit(1);
+                }
+                self.subshell = true;
+                let status = self.run_list(&program);
+                io::stdout().flush().ok();
+                std::process::exit(status.unwrap_or_else(|e| {
+                    report(&e);
+                    e.status()
+                }));
+            }
+            ForkResult::Parent { child } => {
+                let (path, fd, fifo) = match channel {
+                    Channel::Pipe(read_end, write_end) => {
+                        let end = if output { write_end } else { read_end };
+                        fcntl(end.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::empty()))?;
+                        (format!("/dev/fd/{}", end.as_raw_fd()), Some(end), None)
+                    }
+                    Channel::Fifo(path) => (bytes::from_os(path.as_os_str()), None, Some(path)),
+                };
+                self.substitutions.push(Substitution {
+                    pid: child,
+                    fd,
+                    fifo,
+                    output,
+                });
+                Ok(path)
+            }
+        }
+    }
+
+    pub fn release_substitutions(&mut self, mark: usize, wait: bool) -> Vec<Pid> {
+        let mut pids = Vec::new();
+        let substitutions: Vec<_> = self
+            .substitutions
+            .drain(mark.min(self.substitutions.len())..)
+            .collect();
+        for substitution in substitutions {
+            drop(substitution.fd);
+            if !wait {
+                pids.push(substitution.pid);
+                continue;
+            }
+            if let Some(path) = &substitution.fifo {
+                drop(open_fifo(path, !substitution.output, libc::O_NONBLOCK));
+                let _ = fs::remove_file(path);
+            }
+            while waitpid(substitution.pid, None) == Err(Errno::EINTR) {}
+        }
+        pids
+    }
+
+    fn command_subst(&mut self, src: &str) -> Result<String> {
+        if self.substitution_depth >= MAX_SUBSTITUTION_DEPTH {
+            return Err(ShellError::Internal(format!(
+                "maximum command substitution nesting level exceeded ({})",
+                MAX_SUBSTITUTION_DEPTH
+            )));
+        }
+        let program = parser::parse(src, self.lineno)?;
+        if let Some(redirect) = input_only(&program) {
+            let path = self.expand_single(&redirect.target)?;
+            let (contents, status) = match fs::read(bytes::to_os(&path)) {
+                Ok(contents) => (contents, 0),
+                Err(e) => {
+                    report(format_args!(
+                        "{}: {}: {}",
+                        error::program(),
+                        path,
+                        describe(&e)
+                    ));
+                    (Vec::new(), 1)
+                }
+            };
+            self.subst_status = Some(status);
+            return Ok(trim_newlines(bytes::decode(&contents)));
+        }
+        let (read_end, write_end) = pipe2(OFlag::O_CLOEXEC)
+            .map_err(|e| ShellError::Internal(format!("pipe: {}", e.desc())))?;
+        io::stdout().flush()?;
+        match self.fork(&format!("$({})", src.trim()))? {
+            ForkResult::Child => {
+                signals::reset_child();
+                drop(read_end);
+                if let Err(e) = dup2(write_end.as_raw_fd(), libc::STDOUT_FILENO) {
+                    report(format_args!("{}: dup2: {}", error::program(), e.desc()));
+                    std::process::exit(1);
+                }
+                drop(write_end);
+                self.subshell = true;
+                self.substitution_depth += 1;
+                let status = self.run_list(&program);
+                io::stdout().flush().ok();
+                std::process::exit(status.unwrap_or_else(|e| {
+                    report(&e);
+                    e.status()
+                }));
+            }
+            ForkResult::Parent { child } => {
+                drop(write_end);
+                let (output, status) = capture(read_end, child)?;
+                self.subst_status = Some(status);
+                Ok(trim_newlines(bytes::decode(&output)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vars::Variables;
+
+    fn expand(text: &str) -> String {
+        let vars = Variables::from_pairs([
+            ("file".to_string(), "dir/notes.tar.gz".to_string()),
+            ("word".to_string(), "日本語テキスト".to_string()),
+            ("accent".to_string(), "e\u{301}té".to_string()),
+            ("empty".to_string(), String::new()),
+            ("path".to_string(), "/usr/local/bin".to_string()),
+        ]);
+        let mut shell = Shell::with_vars(vars);
+        shell.expand_single(&word::parse(text)).unwrap()
+    }
+
+    fn split_text(text: &str, ifs: &str) -> Vec<String> {
+        split(vec![Segment::Expanded(text.to_string())], ifs)
+            .iter()
+            .map(join)
+            .collect()
+    }
+
+    // POSIX field splitting, as a reference for the splitter.
+    fn reference(text: &str, ifs: &str) -> Vec<String> {
+        let white = |c: char| matches!(c, ' ' | '\t' | '\n') && ifs.contains(c);
+        let text = text.trim_matches(white);
+        let mut fields = Vec::new();
+        let mut current = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if !ifs.contains(c) {
+                current.push(c);
+                continue;
+            }
+            let mut hard = !white(c);
+            while let Some(&next) = chars.peek() {
+                if white(next) || (!hard && ifs.contains(next)) {
+                    hard |= !white(next);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            fields.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            fields.push(current);
+        }
+        fields
+    }
+
+    fn random_text(seed: &mut u32, alphabet: &[char]) -> String {
+        let mut next = || {
+            *seed ^= *seed << 13;
+            *seed ^= *seed >> 17;
+            *seed ^= *seed << 5;
+            *seed
+        };
+        let len = next() % 12;
+        (0..len)
+            .map(|_| alphabet[next() as usize % alphabet.len()])
+            .collect()
+    }
+
+    #[test]
+    fn splitting_examples() {
+        let default = " \t\n";
+        for (text, ifs, expected) in [
+            ("", default, &[][..]),
+            ("   ", default, &[]),
+            ("a", default, &["a"]),
+            ("  a \t b\n\nc  ", default, &["a", "b", "c"]),
+            ("a:b", default, &["a:b"]),
+            ("a:b", ":", &["a", "b"]),
+            ("a::b", ":", &["a", "", "b"]),
+            (":a", ":", &["", "a"]),
+            ("a:", ":", &["a"]),
+            ("a::", ":", &["a", ""]),
+            (" a ", ":", &[" a "]),
+            ("a : b", " :", &["a", "b"]),
+            ("a  :  : b", " :", &["a", "", "b"]),
+            (" : a", " :", &["", "a"]),
+            ("a  b", "", &["a  b"]),
+            ("x日y本z", "日本", &["x", "y", "z"]),
+        ] {
+            assert_eq!(split_text(text, ifs), expected, "{:?} with {:?}", text, ifs);
+        }
+    }
+
+    #[test]
+    fn splitting_properties() {
+        let alphabet = ['a', 'b', ' ', '\t', '\n', ':', ',', 'é'];
+        let mut seed = 0x9e3779b9u32;
+        for ifs in [" \t\n", ":", " :", ":,", " \t\n,", "é", ""] {
+            for _ in 0..500 {
+                let text = random_text(&mut seed, &alphabet);
+                let fields = split_text(&text, ifs);
+                if ifs.is_empty() {
+                    let whole: &[String] = match text.is_empty() {
+                        true => &[],
+                        false => std::slice::from_ref(&text),
+                    };
+                    assert_eq!(fields, whole, "{:?}", text);
+                    continue;
+                }
+                assert_eq!(fields, reference(&text, ifs), "{:?} with {:?}", text, ifs);
+                for field in &fields {
+                    assert!(!field.contains(|c| ifs.contains(c)), "{:?}", field);
+                }
+                let kept: String = text.chars().filter(|c| !ifs.contains(*c)).collect();
+                assert_eq!(fields.concat(), kept);
+            }
+        }
+    }
+
+    #[test]
+    fn only_expanded_text_is_split() {
+        let field = vec![
+            Segment::Literal("x y".to_string()),
+            Segment::Expanded(" a b ".to_string()),
+            Segment::Quoted("c d".to_string()),
+            Segment::Expanded("e".to_string()),
+        ];
+        let fields: Vec<String> = split(field, " ").iter().map(join).collect();
+        assert_eq!(fields, ["x y", "a", "b", "c de"]);
+        let quoted = vec![Segment::Quoted(String::new())];
+        assert_eq!(split(quoted, " ").len(), 1);
+        let expanded = vec![Segment::Expanded("  ".to_string())];
+        assert!(split(expanded, " ").is_empty());
+        let empty = vec![Segment::Expanded(String::new())];
+        assert!(split(empty, "").is_empty());
+        let quoted = vec![Segment::Quoted(String::new())];
+        assert_eq!(split(quoted, "").len(), 1);
+    }
+
+    #[test]
+    fn string_operations() {
+        for (text, expected) in [
+            ("${#file}", "16"),
+            ("${#word}", "7"),
+            ("${#accent}", "4"),
+            ("${#empty}", "0"),
+            ("${#missing}", "0"),
+            ("${file#*/}", "notes.tar.gz"),
+            ("${file#*.}", "tar.gz"),
+            ("${file##*.}", "gz"),
+            ("${file%.*}", "dir/notes.tar"),
+            ("${file%%.*}", "dir/notes"),
+            ("${file%.txt}", "dir/notes.tar.gz"),
+            ("${path##*/}", "bin"),
+            ("${path%/*}", "/usr/local"),
+            ("${word#日本}", "語テキスト"),
+            ("${word%テキスト}", "日本語"),
+            ("${word%?}", "日本語テキス"),
+            ("${file/./-}", "dir/notes-tar.gz"),
+            ("${file//./-}", "dir/notes-tar-gz"),
+            ("${file/#dir/top}", "top/notes.tar.gz"),
+            ("${file/%gz/xz}", "dir/notes.tar.xz"),
+            ("${file//[a-e]}", "ir/nots.tr.gz"),
+            ("${word/本語/-}", "日-テキスト"),
+            ("${missing#x}", ""),
+        ] {
+            assert_eq!(expand(text), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn patterns_can_be_expanded_or_quoted() {
+        assert_eq!(expand("${file#\"dir/\"}"), "notes.tar.gz");
+        assert_eq!(expand("${file#'*/'}"), "dir/notes.tar.gz");
+        assert_eq!(expand("${path#${path%/*}/}"), "bin");
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::Path;
+
+use crate::bytes;
+use crate::pattern::{has_meta, Pattern};
+
+enum Component {
+    Literal(String),
+    Pattern(Pattern),
+}
+
+fn join(base: &str, name: &str) -> String {
+    if base.is_empty() {
+        name.to_string()
+    } else if base.ends_with('/') {
+        format!("{}{}", base, name)
+    } else {
+        format!("{}/{}", base, name)
+    }
+}
+
+pub fn expand(chars: &[(char, bool)]) -> Vec<String> {
+    let absolute = chars.first().map(|p| p.0) == Some('/');
+    let components: Vec<Component> = chars
+        .split(|p| p.0 == '/')
+        .enumerate()
+        .filter(|(i, part)| !part.is_empty() || *i > 0)
+        .map(|(_, part)| {
+            if has_meta(part) {
+                Component::Pattern(Pattern::new(part))
+            } else {
+                Component::Literal(part.iter().map(|p| p.0).collect())
+            }
+        })
+        .collect();
+
+    let mut paths = vec![if absolute {
+        "/".to_string()
+    } else {
+        String::new()
+    }];
+    let last = components.len().saturating_sub(1);
+    for (i, component) in components.iter().enumerate() {
+        let mut next = Vec::new();
+        for base in &paths {
+            match component {
+                Component::Literal(name) => next.push(join(base, name)),
+                Component::Pattern(pattern) => {
+                    let dir = if base.is_empty() { "." } else { base.as_str() };
+                    let Ok(entries) = fs::read_dir(bytes::to_os(dir)) else {
+                        continue;
+                    };
+                    let mut names: Vec<String> = entries
+                        .filter_map(|e| e.ok())
+                        .map(|e| bytes::from_os(&e.file_name()))
+                        .filter(|name| !name.starts_with('.') || pattern.starts_with_dot())
+                        .filter(|name| pattern.matches(name))
+                        .collect();
+                    names.sort_by_cached_key(|name| bytes::encode(name));
+                    for name in names {
+                        let path = join(base, &name);
+                        if i == last || Path::new(&bytes::to_os(&path)).is_dir() {
+                            next.push(path);
+                        }
+                    }
+                }
+            }
+        }
+        paths = next;
+    }
+    paths.retain(|p| {
+        let p = bytes::to_os(p);
+        fs::symlink_metadata(&p).is_ok() || Path::new(&p).exists()
+    });
+    paths.sort_by_cached_key(|path| bytes::encode(path));
+    paths
+}
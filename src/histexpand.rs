@@ -0,0 +1,268 @@
+use crate::shell::Shell;
+
+fn history_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => {
+                quote = None;
+                current.push(c);
+            }
+            (Some('"'), '\\') | (None, '\\') => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"' | '`') => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (None, c) if c.is_whitespace() => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            (None, '|' | '&' | ';' | '<' | '>' | '(' | ')') => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+                let mut op = c.to_string();
+                while let Some(&next) = chars.peek()
+                    && next == c
+                {
+                    op.push(next);
+                    chars.next();
+                }
+                words.push(op);
+            }
+            (None, c) => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn take_number(chars: &[char], pos: &mut usize) -> Option<usize> {
+    let start = *pos;
+    while *pos < chars.len() && chars[*pos].is_ascii_digit() {
+        *pos += 1;
+    }
+    chars[start..*pos].iter().collect::<String>().parse().ok()
+}
+
+fn select_words(
+    words: &[String],
+    chars: &[char],
+    pos: &mut usize,
+) -> std::result::Result<Option<String>, ()> {
+    let last = words.len().saturating_sub(1);
+    let explicit = chars.get(*pos) == Some(&':')
+        && chars
+            .get(*pos + 1)
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '^' | '$' | '*' | '-'));
+    if explicit {
+        *pos += 1;
+    } else if !matches!(chars.get(*pos), Some('^' | '$' | '*')) {
+        return Ok(None);
+    }
+    let range = match chars.get(*pos) {
+        Some('*') => {
+            *pos += 1;
+            if words.len() < 2 {
+                return Ok(Some(String::new()));
+            }
+            (1, last)
+        }
+        Some('$') => {
+            *pos += 1;
+            (last, last)
+        }
+        _ => {
+            let from = match chars.get(*pos) {
+                Some('^') => {
+                    *pos += 1;
+                    1
+                }
+                Some('-') => 0,
+                _ => take_number(chars, pos).ok_or(())?,
+            };
+            let to = match chars.get(*pos) {
+                Some('-') => {
+                    *pos += 1;
+                    match chars.get(*pos) {
+                        Some('$') => {
+                            *pos += 1;
+                            last
+                        }
+                        Some(c) if c.is_ascii_digit() => take_number(chars, pos).ok_or(())?,
+                        _ => last.checked_sub(1).ok_or(())?,
+                    }
+                }
+                Some('*') => {
+                    *pos += 1;
+                    last
+                }
+                _ => from,
+            };
+            (from, to)
+        }
+    };
+    match range {
+        (from, to) if from <= to && to < words.len() => Ok(Some(words[from..=to].join(" "))),
+        _ => Err(()),
+    }
+}
+
+fn apply_modifier(text: &str, modifier: char) -> String {
+    match modifier {
+        'h' => match text.rfind('/') {
+            Some(0) => "/".to_string(),
+            Some(i) => text[..i].to_string(),
+            None => text.to_string(),
+        },
+        't' => text.rsplit('/').next().unwrap_or(text).to_string(),
+        'r' => match text.rfind('.') {
+            Some(i) if !text[i..].contains('/') => text[..i].to_string(),
+            _ => text.to_string(),
+        },
+        _ => text.to_string(),
+    }
+}
+
+impl Shell {
+    fn find_event(&self, chars: &[char], pos: &mut usize) -> Option<Option<usize>> {
+        let entries = &self.history.entries;
+        let previous = entries.len().checked_sub(1);
+        let found = match chars.get(*pos)? {
+            '!' => {
+                *pos += 1;
+                previous
+            }
+            '^' | '$' | '*' | ':' => previous,
+            '-' if chars.get(*pos + 1).is_some_and(char::is_ascii_digit) => {
+                *pos += 1;
+                let n = take_number(chars, pos)?;
+                entries.len().checked_sub(n)
+            }
+            c if c.is_ascii_digit() => {
+                let n = take_number(chars, pos)?;
+                n.checked_sub(self.history.base + 1)
+                    .filter(|&i| i < entries.len())
+            }
+            '?' => {
+                let start = *pos + 1;
+                let end = chars[start..]
+                    .iter()
+                    .position(|&c| c == '?' || c == '\n')
+                    .map_or(chars.len(), |i| start + i);
+                let needle: String = chars[start..end].iter().collect();
+                *pos = if chars.get(end) == Some(&'?') {
+                    end + 1
+                } else {
+                    end
+                };
+                entries.iter().rposition(|e| e.line.contains(&needle))
+            }
+            _ => {
+                let start = *pos;
+                while *pos < chars.len()
+                    && !chars[*pos].is_whitespace()
+                    && !matches!(chars[*pos], ':' | ';' | '|' | '&' | '"' | '\'')
+                {
+                    *pos += 1;
+                }
+                let prefix: String = chars[start..*pos].iter().collect();
+                entries.iter().rposition(|e| e.line.starts_with(&prefix))
+            }
+        };
+        Some(found)
+    }
+
+    pub fn expand_history(
+        &self,
+        line: &str,
+    ) -> std::result::Result<Option<(String, bool)>, String> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut out = String::new();
+        let mut expanded = false;
+        let mut print_only = false;
+        let mut single = false;
+        let mut double = false;
+        let mut pos = 0;
+        while pos < chars.len() {
+            let c = chars[pos];
+            let literal = match c {
+                '\\' if !single => {
+                    out.push(c);
+                    if let Some(&next) = chars.get(pos + 1) {
+                        out.push(next);
+                    }
+                    pos += 2;
+                    continue;
+                }
+                '\'' if !double => {
+                    single = !single;
+                    true
+                }
+                '"' if !single => {
+                    double = !double;
+                    true
+                }
+                '!' => {
+                    single
+                        || matches!(
+                            chars.get(pos + 1),
+                            None | Some(' ' | '\t' | '\n' | '=' | '(')
+                        )
+                        || (double && chars.get(pos + 1) == Some(&'"'))
+                        || (pos > 0 && chars[pos - 1] == '$')
+                        || (pos > 1 && chars[pos - 1] == '{' && chars[pos - 2] == '$')
+                }
+                _ => true,
+            };
+            if literal {
+                out.push(c);
+                pos += 1;
+                continue;
+            }
+            let start = pos;
+            pos += 1;
+            let event = self.find_event(&chars, &mut pos).flatten();
+            let reference = |pos: usize| chars[start..pos].iter().collect::<String>();
+            let Some(index) = event else {
+                return Err(format!("{}: event not found", reference(pos)));
+            };
+            let entry = &self.history.entries[index].line;
+            let words = history_words(entry);
+            let mut text = match select_words(&words, &chars, &mut pos) {
+                Ok(Some(text)) => text,
+                Ok(None) => entry.clone(),
+                Err(()) => return Err(format!("{}: bad word specifier", reference(pos))),
+            };
+            while chars.get(pos) == Some(&':') {
+                match chars.get(pos + 1) {
+                    Some(&modifier @ ('h' | 't' | 'r')) => text = apply_modifier(&text, modifier),
+                    Some('p') => print_only = true,
+                    Some(_) => {
+                        return Err(format!(
+                            "{}: unrecognized history modifier",
+                            reference(pos + 2)
+                        ));
+                    }
+                    None => break,
+                }
+                pos += 2;
+            }
+            out.push_str(&text);
+            expanded = true;
+        }
+        Ok(expanded.then_some((out, print_only)))
+    }
+}
@@ -0,0 +1,333 @@
+use std::ffi::CString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::bytes;
+use crate::error::{describe, program, report};
+use crate::pattern::Pattern;
+use crate::shell::Shell;
+
+const DEFAULT_SIZE: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub line: String,
+    pub time: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    pub entries: Vec<Entry>,
+    pub base: usize,
+    pub current: bool,
+    pub deferred: bool,
+    saved: usize,
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+fn parse_file(text: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut time = None;
+    let mut pending: Option<String> = None;
+    for line in text.lines() {
+        if let Some(mut joined) = pending.take() {
+            joined.push('\n');
+            joined.push_str(line);
+            if continued(&joined) {
+                joined.pop();
+                pending = Some(joined);
+            } else {
+                entries.push(Entry {
+                    line: joined,
+                    time: time.take(),
+                });
+            }
+            continue;
+        }
+        if continued(line) {
+            pending = Some(line[..line.len() - 1].to_string());
+            continue;
+        }
+        if let Some(stamp) = line.strip_prefix('#')
+            && !stamp.is_empty()
+            && let Ok(stamp) = stamp.parse::<i64>()
+        {
+            time = Some(stamp);
+            continue;
+        }
+        entries.push(Entry {
+            line: line.to_string(),
+            time: time.take(),
+        });
+    }
+    if let Some(line) = pending {
+        entries.push(Entry {
+            line,
+            time: time.take(),
+        });
+    }
+    entries
+}
+
+fn continued(line: &str) -> bool {
+    line.bytes().rev().take_while(|&b| b == b'\\').count() % 2 == 1
+}
+
+fn format_entries(entries: &[Entry], stamps: bool) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        if stamps && let Some(time) = entry.time {
+            out.push_str(&format!("#{}\n", time));
+        }
+        out.push_str(&entry.line.replace('\n', "\\\n"));
+        out.push('\n');
+    }
+    out
+}
+
+pub fn format_time(format: &str, time: i64) -> String {
+    let Ok(format) = CString::new(format) else {
+        return String::new();
+    };
+    let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+    let mut buf = [0u8; 256];
+    let len = unsafe {
+        let time = time as libc::time_t;
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return String::new();
+        }
+        libc::strftime(buf.as_mut_ptr().cast(), buf.len(), format.as_ptr(), &tm)
+    };
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+impl History {
+    pub fn number(&self, index: usize) -> usize {
+        self.base + index + 1
+    }
+
+    fn push(&mut self, entry: Entry, limit: usize) {
+        self.entries.push(entry);
+        if self.entries.len() > limit {
+            let excess = self.entries.len() - limit;
+            self.entries.drain(..excess);
+            self.base += excess;
+            self.saved = self.saved.saturating_sub(excess);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.base += self.entries.len();
+        self.entries.clear();
+        self.saved = 0;
+        self.current = false;
+    }
+
+    pub fn previous(&self) -> &[Entry] {
+        match self.current {
+            true => &self.entries[..self.entries.len().saturating_sub(1)],
+            false => &self.entries,
+        }
+    }
+
+    pub fn find(&self, spec: &str) -> Option<usize> {
+        let entries = self.previous();
+        if let Ok(n) = spec.parse::<i64>() {
+            let index = if n < 0 {
+                entries.len() as i64 + n
+            } else {
+                n - self.base as i64 - 1
+            };
+            return (index >= 0 && (index as usize) < entries.len()).then_some(index as usize);
+        }
+        entries.iter().rposition(|e| e.line.starts_with(spec))
+    }
+}
+
+impl Shell {
+    pub fn history_file(&self) -> Option<String> {
+        match self.vars.get("HISTFILE") {
+            Some("") => None,
+            Some(path) => Some(path.to_string()),
+            None => self
+                .vars
+                .get("HOME")
+                .map(|home| format!("{}/.vssh_history", home.trim_end_matches('/'))),
+        }
+    }
+
+    fn history_limit(&self, name: &str) -> usize {
+        let fallback = match name {
+            "HISTSIZE" => DEFAULT_SIZE,
+            _ => self.history_limit("HISTSIZE"),
+        };
+        self.vars
+            .get(name)
+            .and_then(|n| n.trim().parse::<i64>().ok())
+            .map_or(fallback, |n| n.max(0) as usize)
+    }
+
+    fn history_ignored(&self, line: &str) -> bool {
+        let control = self.vars.get("HISTCONTROL").unwrap_or("");
+        let options: Vec<&str> = control.split(':').collect();
+        let both = options.contains(&"ignoreboth");
+        if (both || options.contains(&"ignorespace")) && line.starts_with(' ') {
+            return true;
+        }
+        if (both || options.contains(&"ignoredups"))
+            && self.history.entries.last().is_some_and(|e| e.line == line)
+        {
+            return true;
+        }
+        self.vars
+            .get("HISTIGNORE")
+            .unwrap_or("")
+            .split(':')
+            .filter(|p| !p.is_empty())
+            .any(|p| {
+                let chars: Vec<(char, bool)> = p.chars().map(|c| (c, true)).collect();
+                Pattern::new(&chars).matches(line)
+            })
+    }
+
+    pub fn add_history(&mut self, line: &str) {
+        let line = line.trim_end_matches('\n');
+        self.history.current = false;
+        if line.trim().is_empty() || self.history_ignored(line) {
+            return;
+        }
+        let control = self.vars.get("HISTCONTROL").unwrap_or("");
+        if control.split(':').any(|o| o == "erasedups") {
+            let before = self.history.entries.len();
+            self.history.entries.retain(|e| e.line != line);
+            let removed = before - self.history.entries.len();
+            self.history.saved = self.history.saved.saturating_sub(removed);
+        }
+        let limit = self.history_limit("HISTSIZE");
+        self.history.push(
+            Entry {
+                line: line.to_string(),
+                time: Some(now()),
+            },
+            limit,
+        );
+        self.history.current = true;
+    }
+
+    pub fn replace_current_history(&mut self, line: &str) {
+        if self.history.current {
+            self.history.entries.pop();
+            self.history.saved = self.history.saved.min(self.history.entries.len());
+        }
+        self.add_history(line);
+    }
+
+    pub fn load_history(&mut self) {
+        let Some(path) = self.history_file() else {
+            return;
+        };
+        if let Ok(text) = fs::read(bytes::to_os(&path)) {
+            self.read_entries(&bytes::decode(&text));
+        }
+        self.history.saved = self.history.entries.len();
+    }
+
+    pub fn load_deferred_history(&mut self) -> usize {
+        if !self.history.deferred {
+            return 0;
+        }
+        self.history.deferred = false;
+        let session = std::mem::take(&mut self.history.entries);
+        let before = session.len();
+        self.history.base = 0;
+        self.load_history();
+        let limit = self.history_limit("HISTSIZE");
+        for entry in session {
+            self.history.push(entry, limit);
+        }
+        self.history.entries.len() - before
+    }
+
+    fn read_entries(&mut self, text: &str) {
+        let limit = self.history_limit("HISTSIZE");
+        for entry in parse_file(text) {
+            self.history.push(entry, limit);
+        }
+    }
+
+    pub fn read_history(&mut self) -> io::Result<()> {
+        let Some(path) = self.history_file() else {
+            return Ok(());
+        };
+        let text = fs::read(bytes::to_os(&path))?;
+        self.read_entries(&bytes::decode(&text));
+        self.history.saved = self.history.entries.len();
+        Ok(())
+    }
+
+    pub fn append_history(&mut self) -> io::Result<()> {
+        let Some(path) = self.history_file() else {
+            return Ok(());
+        };
+        let stamps = self.vars.get("HISTTIMEFORMAT").is_some();
+        let pending = &self.history.entries[self.history.saved.min(self.history.entries.len())..];
+        if !pending.is_empty() {
+            let mut file = OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(bytes::to_os(&path))?;
+            file.write_all(&bytes::encode(&format_entries(pending, stamps)))?;
+        }
+        self.history.saved = self.history.entries.len();
+        self.truncate_history_file(&path)
+    }
+
+    pub fn write_history(&mut self) -> io::Result<()> {
+        let Some(path) = self.history_file() else {
+            return Ok(());
+        };
+        let stamps = self.vars.get("HISTTIMEFORMAT").is_some();
+        let text = format_entries(&self.history.entries, stamps);
+        fs::write(bytes::to_os(&path), bytes::encode(&text))?;
+        self.history.saved = self.history.entries.len();
+        self.truncate_history_file(&path)
+    }
+
+    fn truncate_history_file(&self, path: &str) -> io::Result<()> {
+        let limit = self.history_limit("HISTFILESIZE");
+        let text = bytes::decode(&fs::read(bytes::to_os(path))?);
+        let entries = parse_file(&text);
+        if entries.len() <= limit {
+            return Ok(());
+        }
+        let kept = format_entries(&entries[entries.len() - limit..], true);
+        fs::write(bytes::to_os(path), bytes::encode(&kept))
+    }
+
+    pub fn save_history(&mut self) {
+        if !self.interactive || self.subshell {
+            return;
+        }
+        let saved = match self.options.get("histappend") {
+            true => self.append_history(),
+            false => {
+                self.load_deferred_history();
+                self.write_history()
+            }
+        };
+        if let Err(e) = saved {
+            report(format_args!(
+                "{}: history: {}: {}",
+                program(),
+                self.history_file().unwrap_or_default(),
+                describe(&e)
+            ));
+        }
+    }
+}
@@ -0,0 +1,446 @@
+use std::os::fd::OwnedFd;
+
+use nix::errno::Errno;
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{isatty, Pid};
+
+use crate::color::style;
+use crate::shell::Shell;
+use crate::signals;
+use crate::status::Status;
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: usize,
+    pub command: String,
+    pub processes: Vec<(Pid, Option<i32>)>,
+    pub pgid: Option<Pid>,
+    pub stopped: bool,
+    pub touched: usize,
+    /// The signal that killed the last process, if one did.
+    pub signal: Option<Signal>,
+}
+
+impl Job {
+    pub fn status(&self) -> Option<i32> {
+        if self.processes.iter().any(|(_, status)| status.is_none()) {
+            return None;
+        }
+        self.processes.last().and_then(|(_, status)| *status)
+    }
+
+    pub fn pids(&self) -> Vec<Pid> {
+        self.processes.iter().map(|(pid, _)| *pid).collect()
+    }
+
+    fn state(&self) -> String {
+        match self.status() {
+            None if self.stopped => "Stopped".to_string(),
+            None => "Running".to_string(),
+            Some(0) => "Done".to_string(),
+            Some(code) => match self.signal {
+                Some(signal) if Status::from_signal(signal).code() == code => {
+                    signals::describe(signal)
+                }
+                _ => format!("Exit {}", code),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Coprocess {
+    pub name: String,
+    pub pid: Pid,
+    pub fds: [OwnedFd; 2],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobError {
+    NoSuchJob,
+    Ambiguous,
+}
+
+impl JobError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            JobError::NoSuchJob => "no such job",
+            JobError::Ambiguous => "ambiguous job spec",
+        }
+    }
+}
+
+/// Moves `pid` (0 for the calling process) into the job's group, and the
+/// group to the terminal's foreground when `terminal` is set. Both the
+/// shell and the new child call this so neither ordering races; it only
+/// makes system calls, so it is safe between fork and exec.
+pub fn join_group(pid: Pid, pgid: Pid, terminal: bool) {
+    unsafe {
+        libc::setpgid(pid.as_raw(), pgid.as_raw());
+        if terminal {
+            libc::tcsetpgrp(libc::STDIN_FILENO, pgid.as_raw());
+        }
+    }
+}
+
+pub fn ranked(jobs: &[Job]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..jobs.len()).collect();
+    order.sort_by_key(|&i| {
+        let job = &jobs[i];
+        (
+            !(job.stopped && job.status().is_none()),
+            std::cmp::Reverse(job.touched),
+        )
+    });
+    order
+}
+
+fn unique(mut found: impl Iterator<Item = usize>) -> Result<usize, JobError> {
+    let first = found.next().ok_or(JobError::NoSuchJob)?;
+    match found.next() {
+        Some(_) => Err(JobError::Ambiguous),
+        None => Ok(first),
+    }
+}
+
+pub fn resolve(jobs: &[Job], spec: &str) -> Result<usize, JobError> {
+    let spec = spec.strip_prefix('%').unwrap_or(spec);
+    let order = ranked(jobs);
+    match spec {
+        "" | "%" | "+" => order.first().copied().ok_or(JobError::NoSuchJob),
+        "-" => order
+            .get(1)
+            .or(order.first())
+            .copied()
+            .ok_or(JobError::NoSuchJob),
+        _ if spec.bytes().all(|b| b.is_ascii_digit()) => {
+            let id = spec.parse::<usize>().map_err(|_| JobError::NoSuchJob)?;
+            jobs.iter()
+                .position(|job| job.id == id)
+                .ok_or(JobError::NoSuchJob)
+        }
+        _ => match spec.strip_prefix('?') {
+            Some(text) => unique((0..jobs.len()).filter(|&i| jobs[i].command.contains(text))),
+            None => unique((0..jobs.len()).filter(|&i| jobs[i].command.starts_with(spec))),
+        },
+    }
+}
+
+impl Shell {
+    pub fn add_job(&mut self, pids: Vec<Pid>, command: &str, pgid: Option<Pid>) -> usize {
+        let id = self.jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1;
+        let touched = self.next_touch();
+        if let Some(&pid) = pids.last() {
+            self.last_background = Some(pid);
+            if self.interactive {
+                eprintln!("[{}] Started background process with PID: {}", id, pid);
+            }
+        }
+        self.jobs.push(Job {
+            id,
+            command: command.to_string(),
+            processes: pids.into_iter().map(|pid| (pid, None)).collect(),
+            pgid,
+            stopped: false,
+            touched,
+            signal: None,
+        });
+        id
+    }
+
+    pub fn suspend_job(
+        &mut self,
+        processes: Vec<(Pid, Option<i32>)>,
+        command: &str,
+        pgid: Option<Pid>,
+    ) {
+        let id = self.jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1;
+        let touched = self.next_touch();
+        self.jobs.push(Job {
+            id,
+            command: command.to_string(),
+            processes,
+            pgid,
+            stopped: true,
+            touched,
+            signal: None,
+        });
+        if self.interactive {
+            eprintln!("{}", style("job", &self.format_job(self.jobs.len() - 1)));
+        }
+    }
+
+    fn next_touch(&self) -> usize {
+        self.jobs.iter().map(|j| j.touched).max().unwrap_or(0) + 1
+    }
+
+    pub fn resolve_job(&self, spec: &str) -> Result<usize, JobError> {
+        resolve(&self.jobs, spec)
+    }
+
+    pub fn continue_job(&mut self, index: usize) {
+        let touched = self.next_touch();
+        let job = &mut self.jobs[index];
+        match job.pgid {
+            Some(pgid) => {
+                let _ = signal::killpg(pgid, Signal::SIGCONT);
+            }
+            None => {
+                for (pid, status) in &job.processes {
+                    if status.is_none() {
+                        let _ = signal::kill(*pid, Signal::SIGCONT);
+                    }
+                }
+            }
+        }
+        job.stopped = false;
+        job.touched = touched;
+    }
+
+    pub fn has_stopped_jobs(&mut self) -> bool {
+        self.update_jobs();
+        self.jobs
+            .iter()
+            .any(|job| job.stopped && job.status().is_none())
+    }
+
+    /// A stopped job would never run again once the shell is gone, so it
+    /// gets SIGHUP and then SIGCONT to act on it, as in bash.
+    pub fn hang_up_stopped(&mut self) {
+        for index in 0..self.jobs.len() {
+            if !self.jobs[index].stopped {
+                continue;
+            }
+            match self.jobs[index].pgid {
+                Some(pgid) => {
+                    let _ = signal::killpg(pgid, Signal::SIGHUP);
+                }
+                None => {
+                    for (pid, status) in &self.jobs[index].processes {
+                        if status.is_none() {
+                            let _ = signal::kill(*pid, Signal::SIGHUP);
+                        }
+                    }
+                }
+            }
+            self.continue_job(index);
+        }
+    }
+
+    /// Whether new jobs get a process group of their own (`set -m`).
+    /// Subshells never do job control; their jobs stay in the group
+    /// the outer shell made for them.
+    pub fn monitoring(&self) -> bool {
+        self.options.get("monitor") && !self.subshell
+    }
+
+    /// How a job about to be forked is grouped: `None` leaves it in the
+    /// shell's group, `Some(true)` also hands it the terminal.
+    pub fn job_group(&self, background: bool) -> Option<bool> {
+        self.monitoring()
+            .then(|| !background && self.interactive && isatty(libc::STDIN_FILENO).unwrap_or(false))
+    }
+
+    /// Puts an interactive shell in its own process group in the
+    /// foreground, so jobs can be moved in and out of it.
+    pub fn take_terminal(&self) {
+        let ignore = SigAction::new(SigHandler::SigIgn, SaFlags::empty(), SigSet::empty());
+        unsafe {
+            for signal in [Signal::SIGTSTP, Signal::SIGTTIN, Signal::SIGTTOU] {
+                let _ = signal::sigaction(signal, &ignore);
+            }
+            let pid = libc::getpid();
+            if libc::getpgrp() != pid {
+                libc::setpgid(0, pid);
+            }
+            libc::tcsetpgrp(libc::STDIN_FILENO, pid);
+        }
+    }
+
+    pub fn reclaim_terminal(&self) {
+        if self.job_group(false) == Some(true) {
+            unsafe {
+                libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+            }
+        }
+    }
+
+    pub fn update_jobs(&mut self) {
+        let mut touch = self.next_touch();
+        let flags = WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED;
+        let subshell = self.subshell;
+        for job in &mut self.jobs {
+            let last = job.processes.last().map(|(pid, _)| *pid);
+            for (pid, status) in &mut job.processes {
+                if status.is_some() {
+                    continue;
+                }
+                match waitpid(*pid, Some(flags)) {
+                    Ok(WaitStatus::StillAlive) | Err(Errno::EINTR) => {}
+                    Ok(WaitStatus::Stopped(..)) => {
+                        if !job.stopped {
+                            job.touched = touch;
+                            touch += 1;
+                        }
+                        job.stopped = true;
+                    }
+                    Ok(WaitStatus::Continued(_)) => job.stopped = false,
+                    Ok(result) => {
+                        if let WaitStatus::Signaled(_, signal, _) = result
+                            && Some(*pid) == last
+                        {
+                            job.signal = Some(signal);
+                        }
+                        *status = Status::terminated(&result).map(Status::code).or(*status)
+                    }
+                    // A subshell inherits its parent's table but not its children.
+                    Err(Errno::ECHILD) if subshell => {}
+                    Err(_) => *status = Some(127),
+                }
+            }
+        }
+        let finished = self.coproc.as_ref().is_some_and(|coproc| {
+            self.jobs.iter().any(|job| {
+                job.processes
+                    .iter()
+                    .any(|(pid, status)| *pid == coproc.pid && status.is_some())
+            })
+        });
+        if finished && let Some(coproc) = self.coproc.take() {
+            self.vars.unset(&coproc.name);
+            self.vars.unset(&format!("{}_PID", coproc.name));
+        }
+    }
+
+    pub fn format_job(&self, index: usize) -> String {
+        let job = &self.jobs[index];
+        let order = ranked(&self.jobs);
+        let mark = match order.iter().position(|&i| i == index) {
+            Some(0) => '+',
+            Some(1) => '-',
+            _ => ' ',
+        };
+        let suffix = match job.status() {
+            None if !job.stopped => " &",
+            _ => "",
+        };
+        format!(
+            "[{}]{}  {:<24}{}{}",
+            job.id,
+            mark,
+            job.state(),
+            job.command,
+            suffix
+        )
+    }
+
+    pub fn notify_jobs(&mut self) {
+        self.update_jobs();
+        let mut index = 0;
+        while index < self.jobs.len() {
+            if self.jobs[index].status().is_none() {
+                index += 1;
+                continue;
+            }
+            if self.interactive {
+                eprintln!("{}", style("job", &self.format_job(index)));
+            }
+            self.jobs.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: usize, command: &str, stopped: bool, touched: usize) -> Job {
+        Job {
+            id,
+            command: command.to_string(),
+            processes: vec![(Pid::from_raw(1000 + id as i32), None)],
+            pgid: None,
+            stopped,
+            touched,
+            signal: None,
+        }
+    }
+
+    // Job 2 was started last, job 3 was stopped before job 4, and job 5 has
+    // finished but is still in the table.
+    fn table() -> Vec<Job> {
+        let mut done = job(5, "make check", false, 1);
+        done.processes[0].1 = Some(0);
+        vec![
+            job(1, "sleep 100", false, 2),
+            job(2, "sleep 200 | cat", false, 6),
+            job(3, "vim notes.txt", true, 3),
+            job(4, "less README", true, 5),
+            done,
+        ]
+    }
+
+    fn ids(jobs: &[Job], spec: &str) -> Result<usize, JobError> {
+        resolve(jobs, spec).map(|index| jobs[index].id)
+    }
+
+    #[test]
+    fn stopped_jobs_rank_first() {
+        let jobs = table();
+        let order: Vec<usize> = ranked(&jobs).iter().map(|&i| jobs[i].id).collect();
+        assert_eq!(order, [4, 3, 2, 1, 5]);
+    }
+
+    #[test]
+    fn current_and_previous() {
+        let jobs = table();
+        for spec in ["%+", "%%", "%", "+", ""] {
+            assert_eq!(ids(&jobs, spec), Ok(4), "{}", spec);
+        }
+        assert_eq!(ids(&jobs, "%-"), Ok(3));
+        let running = &jobs[..2];
+        assert_eq!(ids(running, "%+"), Ok(2));
+        assert_eq!(ids(running, "%-"), Ok(1));
+        assert_eq!(ids(&jobs[..1], "%-"), Ok(1));
+        assert_eq!(ids(&[], "%+"), Err(JobError::NoSuchJob));
+        assert_eq!(ids(&[], "%-"), Err(JobError::NoSuchJob));
+    }
+
+    #[test]
+    fn numbers() {
+        let jobs = table();
+        for id in 1..=5 {
+            assert_eq!(ids(&jobs, &format!("%{}", id)), Ok(id));
+        }
+        assert_eq!(ids(&jobs, "3"), Ok(3));
+        for spec in ["%0", "%6", "%99999999999999999999999"] {
+            assert_eq!(ids(&jobs, spec), Err(JobError::NoSuchJob), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn prefixes_and_substrings() {
+        let jobs = table();
+        for (spec, found) in [
+            ("%vim", Ok(3)),
+            ("%sleep 2", Ok(2)),
+            ("%make", Ok(5)),
+            ("%sleep", Err(JobError::Ambiguous)),
+            ("%notes", Err(JobError::NoSuchJob)),
+            ("%?notes", Ok(3)),
+            ("%?| cat", Ok(2)),
+            ("%?100", Ok(1)),
+            ("%?e", Err(JobError::Ambiguous)),
+            ("%?missing", Err(JobError::NoSuchJob)),
+        ] {
+            assert_eq!(ids(&jobs, spec), found, "{}", spec);
+        }
+    }
+
+    #[test]
+    fn errors_have_messages() {
+        assert_eq!(JobError::NoSuchJob.message(), "no such job");
+        assert_eq!(JobError::Ambiguous.message(), "ambiguous job spec");
+    }
+}
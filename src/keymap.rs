@@ -0,0 +1,456 @@
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Abort,
+    AcceptLine,
+    BackwardChar,
+    BackwardDeleteChar,
+    BackwardWord,
+    BeginningOfLine,
+    BracketedPasteBegin,
+    ClearScreen,
+    Complete,
+    DeleteChar,
+    EditAndExecuteCommand,
+    EndOfLine,
+    ForwardChar,
+    ForwardWord,
+    HistorySearchBackward,
+    HistorySearchForward,
+    KillLine,
+    NextHistory,
+    PreviousHistory,
+    UnixLineDiscard,
+    UnixWordRubout,
+}
+
+pub const FUNCTIONS: &[(&str, Function)] = &[
+    ("abort", Function::Abort),
+    ("accept-line", Function::AcceptLine),
+    ("backward-char", Function::BackwardChar),
+    ("backward-delete-char", Function::BackwardDeleteChar),
+    ("backward-word", Function::BackwardWord),
+    ("beginning-of-line", Function::BeginningOfLine),
+    ("bracketed-paste-begin", Function::BracketedPasteBegin),
+    ("clear-screen", Function::ClearScreen),
+    ("complete", Function::Complete),
+    ("delete-char", Function::DeleteChar),
+    ("edit-and-execute-command", Function::EditAndExecuteCommand),
+    ("end-of-line", Function::EndOfLine),
+    ("forward-char", Function::ForwardChar),
+    ("forward-word", Function::ForwardWord),
+    ("history-search-backward", Function::HistorySearchBackward),
+    ("history-search-forward", Function::HistorySearchForward),
+    ("kill-line", Function::KillLine),
+    ("next-history", Function::NextHistory),
+    ("previous-history", Function::PreviousHistory),
+    ("unix-line-discard", Function::UnixLineDiscard),
+    ("unix-word-rubout", Function::UnixWordRubout),
+];
+
+const DEFAULTS: &[(&str, Function)] = &[
+    ("\\C-a", Function::BeginningOfLine),
+    ("\\C-b", Function::BackwardChar),
+    ("\\C-c", Function::Abort),
+    ("\\C-d", Function::DeleteChar),
+    ("\\C-e", Function::EndOfLine),
+    ("\\C-f", Function::ForwardChar),
+    ("\\C-h", Function::BackwardDeleteChar),
+    ("\\C-i", Function::Complete),
+    ("\\C-j", Function::AcceptLine),
+    ("\\C-k", Function::KillLine),
+    ("\\C-l", Function::ClearScreen),
+    ("\\C-m", Function::AcceptLine),
+    ("\\C-n", Function::NextHistory),
+    ("\\C-p", Function::PreviousHistory),
+    ("\\C-u", Function::UnixLineDiscard),
+    ("\\C-w", Function::UnixWordRubout),
+    ("\\C-x\\C-e", Function::EditAndExecuteCommand),
+    ("\\C-?", Function::BackwardDeleteChar),
+    ("\\eb", Function::BackwardWord),
+    ("\\ef", Function::ForwardWord),
+    ("\\e[A", Function::HistorySearchBackward),
+    ("\\e[B", Function::HistorySearchForward),
+    ("\\e[C", Function::ForwardChar),
+    ("\\e[D", Function::BackwardChar),
+    ("\\e[H", Function::BeginningOfLine),
+    ("\\e[F", Function::EndOfLine),
+    ("\\e[1~", Function::BeginningOfLine),
+    ("\\e[3~", Function::DeleteChar),
+    ("\\e[4~", Function::EndOfLine),
+    ("\\e[5~", Function::HistorySearchBackward),
+    ("\\e[6~", Function::HistorySearchForward),
+    ("\\e[7~", Function::BeginningOfLine),
+    ("\\e[8~", Function::EndOfLine),
+    ("\\e[1;5C", Function::ForwardWord),
+    ("\\e[1;5D", Function::BackwardWord),
+    ("\\e[200~", Function::BracketedPasteBegin),
+    ("\\eOA", Function::HistorySearchBackward),
+    ("\\eOB", Function::HistorySearchForward),
+    ("\\eOC", Function::ForwardChar),
+    ("\\eOD", Function::BackwardChar),
+    ("\\eOH", Function::BeginningOfLine),
+    ("\\eOF", Function::EndOfLine),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Function(Function),
+    Command(String),
+}
+
+#[derive(Debug)]
+pub struct Keymap {
+    bindings: BTreeMap<Vec<u8>, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            bindings: DEFAULTS
+                .iter()
+                .filter_map(|(seq, function)| {
+                    Some((parse_sequence(seq).ok()?, Action::Function(*function)))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn get(&self, seq: &[u8]) -> Option<&Action> {
+        self.bindings.get(seq)
+    }
+
+    pub fn is_prefix(&self, seq: &[u8]) -> bool {
+        self.bindings
+            .range(seq.to_vec()..)
+            .find(|(key, _)| key.as_slice() != seq)
+            .is_some_and(|(key, _)| key.starts_with(seq))
+    }
+
+    pub fn bind(&mut self, seq: Vec<u8>, action: Action) {
+        self.bindings.insert(seq, action);
+    }
+
+    pub fn unbind(&mut self, seq: &[u8]) -> bool {
+        self.bindings.remove(seq).is_some()
+    }
+
+    pub fn bindings(&self) -> impl Iterator<Item = (&Vec<u8>, &Action)> {
+        self.bindings.iter()
+    }
+}
+
+pub fn function(name: &str) -> Option<Function> {
+    FUNCTIONS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, function)| *function)
+}
+
+pub fn function_name(function: Function) -> &'static str {
+    FUNCTIONS
+        .iter()
+        .find(|(_, f)| *f == function)
+        .map_or("", |(name, _)| name)
+}
+
+fn control(c: char) -> Result<u8, String> {
+    match c {
+        '?' => Ok(0x7f),
+        c if c.is_ascii() => Ok(c.to_ascii_lowercase() as u8 & 0x1f),
+        c => Err(format!("\\C-{}: invalid control key", c)),
+    }
+}
+
+fn push_char(out: &mut Vec<u8>, c: char) {
+    let mut buf = [0u8; 4];
+    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+}
+
+pub fn parse_sequence(text: &str) -> Result<Vec<u8>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        if c != '\\' {
+            push_char(&mut out, c);
+            continue;
+        }
+        let Some(&escape) = chars.get(i) else {
+            out.push(b'\\');
+            break;
+        };
+        i += 1;
+        match escape {
+            'C' | 'M' if chars.get(i) == Some(&'-') => {
+                i += 1;
+                if escape == 'M' {
+                    out.push(0x1b);
+                    continue;
+                }
+                let Some(&key) = chars.get(i) else {
+                    return Err("\\C-: missing key".to_string());
+                };
+                i += 1;
+                // `\C-\\` is control-backslash, with the key escaped.
+                if key == '\\' && chars.get(i) == Some(&'\\') {
+                    i += 1;
+                }
+                out.push(control(key)?);
+            }
+            'e' => out.push(0x1b),
+            'a' => out.push(0x07),
+            'b' => out.push(0x08),
+            'd' => out.push(0x7f),
+            'f' => out.push(0x0c),
+            'n' => out.push(b'\n'),
+            'r' => out.push(b'\r'),
+            't' => out.push(b'\t'),
+            'v' => out.push(0x0b),
+            '0'..='7' => {
+                let mut value = escape.to_digit(8).unwrap_or(0);
+                for _ in 0..2 {
+                    match chars.get(i).and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            i += 1;
+                        }
+                        None => break,
+                    }
+                }
+                out.push(value as u8);
+            }
+            'x' => {
+                let mut value = 0;
+                let mut digits = 0;
+                while digits < 2
+                    && let Some(digit) = chars.get(i).and_then(|c| c.to_digit(16))
+                {
+                    value = value * 16 + digit;
+                    digits += 1;
+                    i += 1;
+                }
+                match digits {
+                    0 => out.extend_from_slice(b"\\x"),
+                    _ => out.push(value as u8),
+                }
+            }
+            other => push_char(&mut out, other),
+        }
+    }
+    match out.is_empty() {
+        true => Err("empty key sequence".to_string()),
+        false => Ok(out),
+    }
+}
+
+pub fn format_sequence(seq: &[u8]) -> String {
+    let mut out = String::new();
+    for c in String::from_utf8_lossy(seq).chars() {
+        match c {
+            '\x1b' => out.push_str("\\e"),
+            '\x7f' => out.push_str("\\C-?"),
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\x1c' => out.push_str("\\C-\\\\"),
+            c if (c as u32) < 0x20 => {
+                out.push_str("\\C-");
+                out.push(((c as u8) | 0x40).to_ascii_lowercase() as char);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+pub fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn unquote(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+pub fn parse_binding(spec: &str) -> Result<(Vec<u8>, String), String> {
+    let Some(rest) = spec.trim_start().strip_prefix('"') else {
+        return Err(format!("{}: key sequence must be quoted", spec));
+    };
+    let mut end = None;
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => {
+                end = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let Some(end) = end else {
+        return Err(format!("{}: unterminated key sequence", spec));
+    };
+    let seq = parse_sequence(&rest[..end])?;
+    let Some(target) = rest[end + 1..].trim_start().strip_prefix(':') else {
+        return Err(format!("{}: missing `:' after key sequence", spec));
+    };
+    let target = target.trim();
+    let target = match target.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+        Some(inner) => unquote(inner),
+        None => target.to_string(),
+    };
+    Ok((seq, target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequences() {
+        for (text, bytes) in [
+            ("a", &b"a"[..]),
+            ("\\C-a", b"\x01"),
+            ("\\C-A", b"\x01"),
+            ("\\C-@", b"\0"),
+            ("\\C-?", b"\x7f"),
+            ("\\C-\\\\", b"\x1c"),
+            ("\\C-\\x", b"\x1cx"),
+            ("\\C-x\\C-e", b"\x18\x05"),
+            ("\\e", b"\x1b"),
+            ("\\ef", b"\x1bf"),
+            ("\\M-f", b"\x1bf"),
+            ("\\M-\\C-h", b"\x1b\x08"),
+            ("\\e[A", b"\x1b[A"),
+            ("\\e[1;5C", b"\x1b[1;5C"),
+            ("\\e[200~", b"\x1b[200~"),
+            ("\\eOH", b"\x1bOH"),
+            ("\\t\\n\\r", b"\t\n\r"),
+            ("\\a\\b\\d\\f\\v", b"\x07\x08\x7f\x0c\x0b"),
+            ("\\033[", b"\x1b["),
+            ("\\0", b"\0"),
+            ("\\1234", b"\x534"),
+            ("\\x1b", b"\x1b"),
+            ("\\x7", b"\x07"),
+            ("\\xg", b"\\xg"),
+            ("\\\\", b"\\"),
+            ("\\\"", b"\""),
+            ("trailing\\", b"trailing\\"),
+            ("é", "é".as_bytes()),
+        ] {
+            assert_eq!(parse_sequence(text).as_deref(), Ok(bytes), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn bad_sequences() {
+        for (text, error) in [
+            ("", "empty key sequence"),
+            ("\\C-", "\\C-: missing key"),
+            ("\\C-é", "\\C-é: invalid control key"),
+        ] {
+            assert_eq!(parse_sequence(text), Err(error.to_string()), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn formatted_sequences_parse_back() {
+        for byte in 0..0x80u8 {
+            let text = format_sequence(&[byte]);
+            assert_eq!(parse_sequence(&text), Ok(vec![byte]), "{:?}", text);
+            let spec = format!("\"{}\": abort", text);
+            assert_eq!(
+                parse_binding(&spec),
+                Ok((vec![byte], "abort".into())),
+                "{}",
+                spec
+            );
+        }
+        for (seq, action) in Keymap::default().bindings() {
+            let text = format_sequence(seq);
+            assert_eq!(parse_sequence(&text).as_ref(), Ok(seq), "{:?}", action);
+        }
+        assert_eq!(format_sequence(b"\x1b[1;5C"), "\\e[1;5C");
+        assert_eq!(format_sequence(b"\x18\x05"), "\\C-x\\C-e");
+    }
+
+    #[test]
+    fn bindings() {
+        for (spec, seq, target) in [
+            ("\"\\C-f\": forward-word", &b"\x06"[..], "forward-word"),
+            ("  \"\\e[3~\"  :  delete-char  ", b"\x1b[3~", "delete-char"),
+            ("\"\\C-o\": \"echo hi\"", b"\x0f", "echo hi"),
+            ("\"\\C-o\": \"say \\\"hi\\\"\"", b"\x0f", "say \"hi\""),
+            ("\"\\\"\": abort", b"\"", "abort"),
+            ("\"x\":abort", b"x", "abort"),
+        ] {
+            assert_eq!(
+                parse_binding(spec),
+                Ok((seq.to_vec(), target.to_string())),
+                "{}",
+                spec
+            );
+        }
+        for (spec, error) in [
+            ("\\C-f: abort", "\\C-f: abort: key sequence must be quoted"),
+            (
+                "\"\\C-f: abort",
+                "\"\\C-f: abort: unterminated key sequence",
+            ),
+            (
+                "\"\\C-f\" abort",
+                "\"\\C-f\" abort: missing `:' after key sequence",
+            ),
+            ("\"\": abort", "empty key sequence"),
+        ] {
+            assert_eq!(parse_binding(spec), Err(error.to_string()), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn keymap_lookups() {
+        let mut keymap = Keymap::default();
+        assert_eq!(
+            keymap.get(b"\x01"),
+            Some(&Action::Function(Function::BeginningOfLine))
+        );
+        assert!(keymap.is_prefix(b"\x1b"));
+        assert!(keymap.is_prefix(b"\x1b["));
+        assert!(keymap.is_prefix(b"\x1b[1"));
+        assert!(!keymap.is_prefix(b"\x1b[A"));
+        assert!(!keymap.is_prefix(b"\x01"));
+        keymap.bind(b"\x06".to_vec(), Action::Function(Function::ForwardWord));
+        keymap.bind(b"\x0f".to_vec(), Action::Command("date".into()));
+        assert_eq!(
+            keymap.get(b"\x06"),
+            Some(&Action::Function(Function::ForwardWord))
+        );
+        assert_eq!(keymap.get(b"\x0f"), Some(&Action::Command("date".into())));
+        assert!(keymap.unbind(b"\x0f"));
+        assert!(!keymap.unbind(b"\x0f"));
+        assert_eq!(keymap.get(b"\x0f"), None);
+    }
+
+    #[test]
+    fn function_names() {
+        for &(name, named) in FUNCTIONS {
+            assert_eq!(function(name), Some(named));
+            assert_eq!(function_name(named), name);
+        }
+        assert_eq!(function("no-such-function"), None);
+    }
+}
@@ -0,0 +1,524 @@
+use crate::exec;
+use crate::parser::{ParseError, TOO_DEEP};
+use crate::word;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Pipe,
+    OrIf,
+    Amp,
+    AndIf,
+    Semi,
+    DSemi,
+    LParen,
+    RParen,
+    Less,
+    Great,
+    DGreat,
+    LessAnd,
+    GreatAnd,
+    AndGreat,
+}
+
+impl Op {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Op::Pipe => "|",
+            Op::OrIf => "||",
+            Op::Amp => "&",
+            Op::AndIf => "&&",
+            Op::Semi => ";",
+            Op::DSemi => ";;",
+            Op::LParen => "(",
+            Op::RParen => ")",
+            Op::Less => "<",
+            Op::Great => ">",
+            Op::DGreat => ">>",
+            Op::LessAnd => "<&",
+            Op::GreatAnd => ">&",
+            Op::AndGreat => "&>",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Word(String),
+    Arith(String),
+    IoNumber(i32),
+    Op(Op),
+    Newline,
+    Eof,
+}
+
+impl Token {
+    pub fn describe(&self) -> String {
+        match self {
+            Token::Word(w) => w.clone(),
+            Token::Arith(_) => "((".to_string(),
+            Token::IoNumber(n) => n.to_string(),
+            Token::Op(op) => op.as_str().to_string(),
+            Token::Newline => "newline".to_string(),
+            Token::Eof => "end of file".to_string(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    line_start: usize,
+    token: (usize, usize),
+    token_start: usize,
+}
+
+fn is_meta(c: char) -> bool {
+    matches!(
+        c,
+        ' ' | '\t' | '\n' | '|' | '&' | ';' | '<' | '>' | '(' | ')'
+    )
+}
+
+impl Lexer {
+    pub fn new(src: &str, line: usize) -> Self {
+        Lexer {
+            chars: src.chars().collect(),
+            pos: 0,
+            line,
+            line_start: 0,
+            token: (line, 1),
+            token_start: 0,
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn position(&self) -> (usize, usize) {
+        self.token
+    }
+
+    pub fn offset(&self) -> usize {
+        self.token_start
+    }
+
+    pub fn text(&self, start: usize, end: usize) -> String {
+        let text: String = self.chars[start..end.max(start)].iter().collect();
+        text.trim().to_string()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.get(self.pos).copied()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = self.pos;
+        }
+        Some(c)
+    }
+
+    fn skip_blanks(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ') | Some('\t') => {
+                    self.bump();
+                }
+                Some('\\') if self.peek_at(1) == Some('\n') => {
+                    self.bump();
+                    self.bump();
+                }
+                Some('#') => {
+                    while let Some(c) = self.peek() {
+                        if c == '\n' {
+                            break;
+                        }
+                        self.bump();
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    pub fn next_token(&mut self) -> Result<Token, ParseError> {
+        self.skip_blanks();
+        self.token = (self.line, self.pos - self.line_start + 1);
+        self.token_start = self.pos;
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Ok(Token::Eof),
+        };
+        let next = self.peek_at(1);
+        let (op, len) = match c {
+            '\n' => {
+                self.bump();
+                return Ok(Token::Newline);
+            }
+            '|' if next == Some('|') => (Op::OrIf, 2),
+            '|' => (Op::Pipe, 1),
+            '&' if next == Some('&') => (Op::AndIf, 2),
+            '&' if next == Some('>') => (Op::AndGreat, 2),
+            '&' => (Op::Amp, 1),
+            ';' if next == Some(';') => (Op::DSemi, 2),
+            ';' => (Op::Semi, 1),
+            '(' if next == Some('(') => match self.arith_body() {
+                Some(body) => return Ok(Token::Arith(body)),
+                None => (Op::LParen, 1),
+            },
+            '(' => (Op::LParen, 1),
+            ')' => (Op::RParen, 1),
+            '<' | '>' if next == Some('(') => return Ok(Token::Word(self.read_word()?)),
+            '<' if next == Some('&') => (Op::LessAnd, 2),
+            '<' => (Op::Less, 1),
+            '>' if next == Some('>') => (Op::DGreat, 2),
+            '>' if next == Some('&') => (Op::GreatAnd, 2),
+            '>' => (Op::Great, 1),
+            _ => {
+                let word = self.read_word()?;
+                if word.bytes().all(|b| b.is_ascii_digit())
+                    && matches!(self.peek(), Some('<' | '>'))
+                    && let Ok(fd) = word.parse()
+                {
+                    return Ok(Token::IoNumber(fd));
+                }
+                return Ok(Token::Word(word));
+            }
+        };
+        for _ in 0..len {
+            self.bump();
+        }
+        Ok(Token::Op(op))
+    }
+
+    fn arith_body(&mut self) -> Option<String> {
+        let mut depth = 0;
+        let mut i = self.pos + 2;
+        while let Some(&c) = self.chars.get(i) {
+            match c {
+                '(' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                ')' if self.chars.get(i + 1) == Some(&')') => {
+                    let body = self.chars[self.pos + 2..i].iter().collect();
+                    while self.pos < i + 2 {
+                        self.bump();
+                    }
+                    return Some(body);
+                }
+                ')' => return None,
+                _ => {}
+            }
+            i += 1;
+        }
+        None
+    }
+
+    fn read_word(&mut self) -> Result<String, ParseError> {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if matches!(c, '<' | '>') && self.peek_at(1) == Some('(') {
+                word.push(c);
+                word.push('(');
+                self.bump();
+                self.bump();
+                self.read_nested(&mut word, '(', ')')?;
+                continue;
+            }
+            if is_meta(c) {
+                break;
+            }
+            self.read_unit(&mut word)?;
+        }
+        Ok(word)
+    }
+
+    fn read_unit(&mut self, word: &mut String) -> Result<(), ParseError> {
+        match self.peek() {
+            Some('\\') => {
+                self.bump();
+                match self.bump() {
+                    Some('\n') => {}
+                    Some(c) => {
+                        word.push('\\');
+                        word.push(c);
+                    }
+                    None => word.push('\\'),
+                }
+            }
+            Some('\'') => self.read_single(word)?,
+            Some('$') if self.peek_at(1) == Some('\'') => self.read_ansi(word)?,
+            Some('"') => self.read_double(word)?,
+            Some('`') => self.read_backtick(word)?,
+            Some('$') => self.read_dollar(word)?,
+            Some(c) => {
+                word.push(c);
+                self.bump();
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn unterminated(&self, what: char) -> ParseError {
+        let (line, column) = self.token;
+        ParseError::Incomplete {
+            line,
+            column,
+            message: format!("unexpected EOF while looking for matching `{}'", what),
+        }
+    }
+
+    fn read_single(&mut self, word: &mut String) -> Result<(), ParseError> {
+        word.push('\'');
+        self.bump();
+        loop {
+            match self.bump() {
+                Some('\'') => {
+                    word.push('\'');
+                    return Ok(());
+                }
+                Some(c) => word.push(c),
+                None => return Err(self.unterminated('\'')),
+            }
+        }
+    }
+
+    fn read_ansi(&mut self, word: &mut String) -> Result<(), ParseError> {
+        self.bump();
+        self.bump();
+        let mut body = String::new();
+        loop {
+            match self.bump() {
+                Some('\'') => break,
+                Some('\\') => {
+                    body.push('\\');
+                    match self.bump() {
+                        Some(c) => body.push(c),
+                        None => return Err(self.unterminated('\'')),
+                    }
+                }
+                Some(c) => body.push(c),
+                None => return Err(self.unterminated('\'')),
+            }
+        }
+        if let Err(message) = word::ansi_c(&body) {
+            let (line, column) = self.token;
+            return Err(ParseError::Syntax {
+                line,
+                column,
+                message,
+            });
+        }
+        word.push_str(&format!("$'{}'", body));
+        Ok(())
+    }
+
+    fn read_double(&mut self, word: &mut String) -> Result<(), ParseError> {
+        word.push('"');
+        self.bump();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.bump();
+                    word.push('"');
+                    return Ok(());
+                }
+                Some('\\') => {
+                    self.bump();
+                    match self.bump() {
+                        Some('\n') => {}
+                        Some(c) => {
+                            word.push('\\');
+                            word.push(c);
+                        }
+                        None => return Err(self.unterminated('"')),
+                    }
+                }
+                Some('`') => self.read_backtick(word)?,
+                Some('$') => self.read_dollar(word)?,
+                Some(c) => {
+                    word.push(c);
+                    self.bump();
+                }
+                None => return Err(self.unterminated('"')),
+            }
+        }
+    }
+
+    fn read_backtick(&mut self, word: &mut String) -> Result<(), ParseError> {
+        word.push('`');
+        self.bump();
+        loop {
+            match self.bump() {
+                Some('`') => {
+                    word.push('`');
+                    return Ok(());
+                }
+                Some('\\') => {
+                    word.push('\\');
+                    match self.bump() {
+                        Some(c) => word.push(c),
+                        None => return Err(self.unterminated('`')),
+                    }
+                }
+                Some(c) => word.push(c),
+                None => return Err(self.unterminated('`')),
+            }
+        }
+    }
+
+    fn read_dollar(&mut self, word: &mut String) -> Result<(), ParseError> {
+        word.push('$');
+        self.bump();
+        match self.peek() {
+            Some('(') => {
+                word.push('(');
+                self.bump();
+                self.read_nested(word, '(', ')')
+            }
+            Some('{') => {
+                word.push('{');
+                self.bump();
+                self.read_nested(word, '{', '}')
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn read_nested(
+        &mut self,
+        word: &mut String,
+        open: char,
+        close: char,
+    ) -> Result<(), ParseError> {
+        if exec::stack_exhausted() {
+            let (line, column) = self.token;
+            return Err(ParseError::Syntax {
+                line,
+                column,
+                message: TOO_DEEP.to_string(),
+            });
+        }
+        let mut depth = 1;
+        loop {
+            match self.peek() {
+                Some(c) if c == close => {
+                    self.bump();
+                    word.push(c);
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                Some(c) if c == open => {
+                    self.bump();
+                    word.push(c);
+                    depth += 1;
+                }
+                Some(_) => self.read_unit(word)?,
+                None => return Err(self.unterminated(close)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(src: &str) -> Vec<Token> {
+        let mut lexer = Lexer::new(src, 1);
+        let mut tokens = Vec::new();
+        loop {
+            match lexer.next_token().unwrap() {
+                Token::Eof => return tokens,
+                token => tokens.push(token),
+            }
+        }
+    }
+
+    fn word(text: &str) -> Token {
+        Token::Word(text.to_string())
+    }
+
+    #[test]
+    fn words_and_operators() {
+        assert_eq!(
+            tokens("a|b||c&d&&e;f;;g"),
+            vec![
+                word("a"),
+                Token::Op(Op::Pipe),
+                word("b"),
+                Token::Op(Op::OrIf),
+                word("c"),
+                Token::Op(Op::Amp),
+                word("d"),
+                Token::Op(Op::AndIf),
+                word("e"),
+                Token::Op(Op::Semi),
+                word("f"),
+                Token::Op(Op::DSemi),
+                word("g"),
+            ]
+        );
+    }
+
+    #[test]
+    fn redirections() {
+        assert_eq!(
+            tokens("cat <in >out 2>>err >&2 &>all\n"),
+            vec![
+                word("cat"),
+                Token::Op(Op::Less),
+                word("in"),
+                Token::Op(Op::Great),
+                word("out"),
+                Token::IoNumber(2),
+                Token::Op(Op::DGreat),
+                word("err"),
+                Token::Op(Op::GreatAnd),
+                word("2"),
+                Token::Op(Op::AndGreat),
+                word("all"),
+                Token::Newline,
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_keep_operators_in_words() {
+        assert_eq!(
+            tokens("echo 'a | b' \"c && d\" e\\;f"),
+            vec![
+                word("echo"),
+                word("'a | b'"),
+                word("\"c && d\""),
+                word("e\\;f"),
+            ]
+        );
+    }
+
+    #[test]
+    fn ansi_c_quotes_are_one_word() {
+        assert_eq!(
+            tokens("grep $'a | b\\'; c' file"),
+            vec![word("grep"), word("$'a | b\\'; c'"), word("file")]
+        );
+        let mut lexer = Lexer::new("echo $'\\x'", 1);
+        lexer.next_token().unwrap();
+        assert!(matches!(
+            lexer.next_token(),
+            Err(ParseError::Syntax { message, .. }) if message == "`\\x': missing hexadecimal digits"
+        ));
+    }
+}
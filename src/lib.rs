@@ -0,0 +1,41 @@
+pub mod arith;
+pub mod ast;
+pub mod audit;
+pub mod builtins;
+pub mod bytes;
+pub mod color;
+pub mod complete;
+pub mod direnv;
+pub mod editor;
+pub mod embed;
+pub mod error;
+pub mod exec;
+pub mod expand;
+pub mod glob;
+pub mod histexpand;
+pub mod history;
+pub mod jobs;
+pub mod keymap;
+pub mod lexer;
+pub mod lock;
+pub mod options;
+pub mod parser;
+pub mod pattern;
+pub mod policy;
+pub mod printf;
+pub mod prompt;
+pub mod restricted;
+pub mod runaway;
+pub mod scope;
+pub mod sha256;
+pub mod shell;
+pub mod signals;
+pub mod startup;
+pub mod status;
+pub mod suggest;
+pub mod terminal;
+pub mod trace;
+pub mod traps;
+pub mod vars;
+pub mod width;
+pub mod word;
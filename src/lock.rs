@@ -0,0 +1,60 @@
+use std::os::fd::RawFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nix::errno::Errno;
+
+use crate::signals;
+
+const POLL: Duration = Duration::from_millis(20);
+
+pub const TIMED_OUT: i32 = 73;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Shared,
+    Exclusive,
+    Unlock,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Locked,
+    TimedOut,
+    Interrupted,
+}
+
+fn flock(fd: RawFd, operation: libc::c_int) -> Result<(), Errno> {
+    match unsafe { libc::flock(fd, operation) } {
+        0 => Ok(()),
+        _ => Err(Errno::last()),
+    }
+}
+
+pub fn acquire(fd: RawFd, mode: Mode, timeout: Option<Duration>) -> Result<Outcome, Errno> {
+    let operation = match mode {
+        Mode::Shared => libc::LOCK_SH,
+        Mode::Exclusive => libc::LOCK_EX,
+        Mode::Unlock => return flock(fd, libc::LOCK_UN).map(|_| Outcome::Locked),
+    };
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let operation = match deadline {
+        Some(_) => operation | libc::LOCK_NB,
+        None => operation,
+    };
+    loop {
+        match flock(fd, operation) {
+            Ok(()) => return Ok(Outcome::Locked),
+            Err(Errno::EINTR) if signals::interrupted() => return Ok(Outcome::Interrupted),
+            Err(Errno::EINTR) => {}
+            Err(Errno::EWOULDBLOCK) if deadline.is_some_and(|d| Instant::now() < d) => {
+                thread::sleep(POLL);
+                if signals::interrupted() {
+                    return Ok(Outcome::Interrupted);
+                }
+            }
+            Err(Errno::EWOULDBLOCK) => return Ok(Outcome::TimedOut),
+            Err(e) => return Err(e),
+        }
+    }
+}
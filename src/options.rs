@@ -0,0 +1,182 @@
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Set,
+    Shopt,
+    Both,
+}
+
+#[derive(Debug)]
+pub struct Spec {
+    pub name: &'static str,
+    pub default: bool,
+    pub scope: Scope,
+    pub flag: Option<char>,
+}
+
+impl Spec {
+    pub fn in_set(&self) -> bool {
+        self.scope != Scope::Shopt
+    }
+
+    pub fn in_shopt(&self) -> bool {
+        self.scope != Scope::Set
+    }
+}
+
+pub const OPTIONS: &[Spec] = &[
+    Spec {
+        name: "emacs",
+        default: true,
+        scope: Scope::Set,
+        flag: None,
+    },
+    Spec {
+        name: "errexit",
+        default: false,
+        scope: Scope::Set,
+        flag: Some('e'),
+    },
+    Spec {
+        name: "errtrace",
+        default: false,
+        scope: Scope::Set,
+        flag: Some('E'),
+    },
+    Spec {
+        name: "histappend",
+        default: true,
+        scope: Scope::Shopt,
+        flag: None,
+    },
+    Spec {
+        name: "histverify",
+        default: false,
+        scope: Scope::Set,
+        flag: None,
+    },
+    Spec {
+        name: "ignoreeof",
+        default: false,
+        scope: Scope::Set,
+        flag: None,
+    },
+    Spec {
+        name: "lastpipe",
+        default: false,
+        scope: Scope::Shopt,
+        flag: None,
+    },
+    Spec {
+        name: "monitor",
+        default: false,
+        scope: Scope::Set,
+        flag: Some('m'),
+    },
+    Spec {
+        name: "noexec",
+        default: false,
+        scope: Scope::Set,
+        flag: Some('n'),
+    },
+    Spec {
+        name: "nounset",
+        default: false,
+        scope: Scope::Set,
+        flag: Some('u'),
+    },
+    Spec {
+        name: "nullglob",
+        default: false,
+        scope: Scope::Shopt,
+        flag: None,
+    },
+    Spec {
+        name: "spawn",
+        default: false,
+        scope: Scope::Set,
+        flag: None,
+    },
+    Spec {
+        name: "verbose",
+        default: false,
+        scope: Scope::Set,
+        flag: Some('v'),
+    },
+    Spec {
+        name: "verbose-status",
+        default: false,
+        scope: Scope::Set,
+        flag: None,
+    },
+    Spec {
+        name: "verify",
+        default: false,
+        scope: Scope::Set,
+        flag: None,
+    },
+    Spec {
+        name: "vi",
+        default: false,
+        scope: Scope::Set,
+        flag: None,
+    },
+    Spec {
+        name: "xtrace",
+        default: false,
+        scope: Scope::Set,
+        flag: Some('x'),
+    },
+];
+
+pub fn lookup(name: &str) -> Option<&'static Spec> {
+    OPTIONS.iter().find(|spec| spec.name == name)
+}
+
+pub fn flag_name(flag: char) -> Option<&'static str> {
+    OPTIONS
+        .iter()
+        .find(|spec| spec.flag == Some(flag))
+        .map(|spec| spec.name)
+}
+
+#[derive(Debug)]
+pub struct Options {
+    enabled: BTreeSet<&'static str>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            enabled: OPTIONS
+                .iter()
+                .filter(|spec| spec.default)
+                .map(|spec| spec.name)
+                .collect(),
+        }
+    }
+}
+
+impl Options {
+    pub fn get(&self, name: &str) -> bool {
+        self.enabled.contains(name)
+    }
+
+    pub fn set(&mut self, name: &str, on: bool) -> bool {
+        let Some(spec) = lookup(name) else {
+            return false;
+        };
+        if on {
+            match spec.name {
+                "emacs" => self.enabled.remove("vi"),
+                "vi" => self.enabled.remove("emacs"),
+                _ => false,
+            };
+            self.enabled.insert(spec.name);
+        } else {
+            self.enabled.remove(spec.name);
+        }
+        true
+    }
+}
@@ -0,0 +1,1321 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{
+    AndOr, Assign, AssignValue, CaseCommand, CaseItem, Command, Compound, Connector, Coproc,
+    ForLoop, FunctionDef, IfCommand, List, ListItem, Pipeline, Redirect, RedirectKind,
+    SimpleCommand, WhileLoop, Word,
+};
+use crate::exec;
+use crate::lexer::{Lexer, Op, Token};
+use crate::word;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    Incomplete {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Incomplete { message, .. } | ParseError::Syntax { message, .. } => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+const RESERVED: &[&str] = &[
+    "do", "done", "in", "esac", "}", "then", "elif", "else", "fi",
+];
+
+pub const TOO_DEEP: &str = "syntax error: commands nested too deeply";
+
+const COMPOUND_WORDS: &[&str] = &["if", "for", "while", "until", "case", "select", "{"];
+
+pub fn parse(src: &str, line: usize) -> Result<List, ParseError> {
+    let mut parser = Parser {
+        lexer: Lexer::new(src, line),
+        peeked: None,
+    };
+    parser.parse_list(&[])
+}
+
+pub fn check(src: &str) -> Vec<ParseError> {
+    let mut errors = Vec::new();
+    let mut buffer = String::new();
+    let mut start = 1;
+    for (i, line) in src.split_inclusive('\n').enumerate() {
+        if buffer.is_empty() {
+            start = i + 1;
+        }
+        buffer.push_str(line);
+        match parse(&buffer, start) {
+            Ok(_) => buffer.clear(),
+            Err(ParseError::Incomplete { .. }) => {}
+            Err(e) => {
+                errors.push(e);
+                buffer.clear();
+            }
+        }
+    }
+    if !buffer.is_empty()
+        && let Err(e) = parse(&buffer, start)
+    {
+        errors.push(e);
+    }
+    errors
+}
+
+struct Parser {
+    lexer: Lexer,
+    peeked: Option<(Token, usize)>,
+}
+
+impl Parser {
+    fn error(&self, message: String) -> ParseError {
+        let (line, column) = self.lexer.position();
+        ParseError::Syntax {
+            line,
+            column,
+            message,
+        }
+    }
+
+    fn unexpected(&self, token: &Token) -> ParseError {
+        let (line, column) = self.lexer.position();
+        match token {
+            Token::Eof => ParseError::Incomplete {
+                line,
+                column,
+                message: "syntax error: unexpected end of file".to_string(),
+            },
+            _ => self.error(format!(
+                "syntax error near unexpected token `{}'",
+                token.describe()
+            )),
+        }
+    }
+
+    fn reject_next(&mut self) -> ParseError {
+        match self.next() {
+            Ok(token) => self.unexpected(&token),
+            Err(e) => e,
+        }
+    }
+
+    fn peek(&mut self) -> Result<&Token, ParseError> {
+        if self.peeked.is_none() {
+            let line = self.lexer.line();
+            let token = self.lexer.next_token()?;
+            self.peeked = Some((token, line));
+        }
+        Ok(&self.peeked.as_ref().unwrap().0)
+    }
+
+    fn peek_line(&mut self) -> Result<usize, ParseError> {
+        self.peek()?;
+        Ok(self.peeked.as_ref().unwrap().1)
+    }
+
+    fn next(&mut self) -> Result<Token, ParseError> {
+        self.peek()?;
+        Ok(self.peeked.take().unwrap().0)
+    }
+
+    fn peek_is_word(&mut self, words: &[&str]) -> Result<bool, ParseError> {
+        Ok(matches!(self.peek()?, Token::Word(w) if words.contains(&w.as_str())))
+    }
+
+    fn skip_newlines(&mut self) -> Result<(), ParseError> {
+        while *self.peek()? == Token::Newline {
+            self.next()?;
+        }
+        Ok(())
+    }
+
+    fn expect(&mut self, op: Op) -> Result<(), ParseError> {
+        match self.next()? {
+            Token::Op(o) if o == op => Ok(()),
+            token => Err(self.unexpected(&token)),
+        }
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), ParseError> {
+        match self.next()? {
+            Token::Word(w) if w == word => Ok(()),
+            token => Err(self.unexpected(&token)),
+        }
+    }
+
+    fn parse_list(&mut self, terminators: &[&str]) -> Result<List, ParseError> {
+        if exec::stack_exhausted() {
+            return Err(self.error(TOO_DEEP.to_string()));
+        }
+        let mut list = List::default();
+        loop {
+            self.skip_newlines()?;
+            if *self.peek()? == Token::Eof {
+                if terminators.is_empty() {
+                    break;
+                }
+                let (line, column) = self.lexer.position();
+                return Err(ParseError::Incomplete {
+                    line,
+                    column,
+                    message: format!(
+                        "syntax error: unexpected end of file while looking for `{}'",
+                        terminators[0]
+                    ),
+                });
+            }
+            if self.peek_is_word(terminators)? {
+                break;
+            }
+            let in_subshell = terminators.contains(&")");
+            if in_subshell && *self.peek()? == Token::Op(Op::RParen) {
+                break;
+            }
+            let in_case = terminators.contains(&"esac");
+            if in_case && *self.peek()? == Token::Op(Op::DSemi) {
+                break;
+            }
+            let start = self.lexer.offset();
+            let and_or = self.parse_and_or()?;
+            self.peek()?;
+            let text = self.lexer.text(start, self.lexer.offset());
+            let background = match self.peek()? {
+                Token::Op(Op::Semi) => {
+                    self.next()?;
+                    false
+                }
+                Token::Op(Op::Amp) => {
+                    self.next()?;
+                    true
+                }
+                Token::Newline | Token::Eof => false,
+                Token::Op(Op::RParen) if in_subshell => false,
+                Token::Op(Op::DSemi) if in_case => {
+                    list.items.push(ListItem {
+                        text,
+                        and_or,
+                        background: false,
+                    });
+                    break;
+                }
+                _ => return Err(self.reject_next()),
+            };
+            list.items.push(ListItem {
+                text,
+                and_or,
+                background,
+            });
+        }
+        Ok(list)
+    }
+
+    fn parse_and_or(&mut self) -> Result<AndOr, ParseError> {
+        let first = self.parse_pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let connector = match self.peek()? {
+                Token::Op(Op::AndIf) => Connector::And,
+                Token::Op(Op::OrIf) => Connector::Or,
+                _ => break,
+            };
+            self.next()?;
+            self.skip_newlines()?;
+            rest.push((connector, self.parse_pipeline()?));
+        }
+        Ok(AndOr { first, rest })
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        if self.peek_is_word(&["!"])? {
+            self.next()?;
+            let mut pipeline = self.parse_pipeline()?;
+            pipeline.negated = !pipeline.negated;
+            return Ok(pipeline);
+        }
+        if self.peek_is_word(&["coproc"])? {
+            self.next()?;
+            let named = match self.peek()? {
+                Token::Word(w) if word::is_name(w) && !COMPOUND_WORDS.contains(&w.as_str()) => {
+                    Some(w.clone())
+                }
+                _ => None,
+            };
+            let name = match named {
+                Some(name) if self.compound_follows() => {
+                    self.next()?;
+                    name
+                }
+                _ => "COPROC".to_string(),
+            };
+            let body = self.parse_segment()?;
+            return Ok(Pipeline {
+                negated: false,
+                commands: vec![Command::Coproc(Coproc {
+                    name,
+                    body: Box::new(body),
+                })],
+            });
+        }
+        if self.peek_is_word(&["function"])? {
+            self.next()?;
+            let name = match self.next()? {
+                Token::Word(w) if word::is_name(&w) => w,
+                token => return Err(self.unexpected(&token)),
+            };
+            if *self.peek()? == Token::Op(Op::LParen) {
+                self.next()?;
+                self.expect(Op::RParen)?;
+            }
+            return Ok(Pipeline {
+                negated: false,
+                commands: vec![self.parse_function(name)?],
+            });
+        }
+        let mut commands = vec![self.parse_segment()?];
+        while *self.peek()? == Token::Op(Op::Pipe) {
+            self.next()?;
+            self.skip_newlines()?;
+            commands.push(self.parse_segment()?);
+        }
+        Ok(Pipeline {
+            negated: false,
+            commands,
+        })
+    }
+
+    fn parse_segment(&mut self) -> Result<Command, ParseError> {
+        if self.peek_is_word(COMPOUND_WORDS)?
+            || matches!(self.peek()?, Token::Op(Op::LParen) | Token::Arith(_))
+        {
+            return self.parse_compound();
+        }
+        self.parse_simple()
+    }
+
+    fn compound_follows(&self) -> bool {
+        let mut lexer = self.lexer.clone();
+        match lexer.next_token() {
+            Ok(Token::Word(w)) => COMPOUND_WORDS.contains(&w.as_str()),
+            Ok(Token::Op(Op::LParen) | Token::Arith(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn parse_compound(&mut self) -> Result<Command, ParseError> {
+        let compound = match self.next()? {
+            Token::Word(w) if w == "if" => Compound::If(self.parse_if()?),
+            Token::Word(w) if w == "for" => Compound::For(self.parse_for()?),
+            Token::Word(w) if w == "while" => Compound::While(self.parse_while(false)?),
+            Token::Word(w) if w == "until" => Compound::While(self.parse_while(true)?),
+            Token::Word(w) if w == "select" => Compound::Select(self.parse_for()?),
+            Token::Word(w) if w == "case" => Compound::Case(self.parse_case()?),
+            Token::Word(w) if w == "{" => Compound::Group(self.parse_group()?),
+            Token::Op(Op::LParen) => Compound::Subshell(self.parse_subshell()?),
+            Token::Arith(expr) => Compound::Arith(expr),
+            token => return Err(self.unexpected(&token)),
+        };
+        let mut redirects = Vec::new();
+        while let Some(redirect) = self.parse_redirect()? {
+            redirects.push(redirect);
+        }
+        Ok(Command::Compound(compound, redirects))
+    }
+
+    fn parse_if(&mut self) -> Result<IfCommand, ParseError> {
+        let mut branches = Vec::new();
+        loop {
+            let condition = self.parse_list(&["then"])?;
+            if condition.items.is_empty() {
+                return Err(self.reject_next());
+            }
+            self.expect_word("then")?;
+            let body = self.parse_list(&["elif", "else", "fi"])?;
+            if body.items.is_empty() {
+                return Err(self.reject_next());
+            }
+            branches.push((condition, body));
+            match self.next()? {
+                Token::Word(w) if w == "elif" => continue,
+                Token::Word(w) if w == "else" => {
+                    let otherwise = self.parse_list(&["fi"])?;
+                    if otherwise.items.is_empty() {
+                        return Err(self.reject_next());
+                    }
+                    self.expect_word("fi")?;
+                    return Ok(IfCommand {
+                        branches,
+                        otherwise: Some(otherwise),
+                    });
+                }
+                Token::Word(w) if w == "fi" => {
+                    return Ok(IfCommand {
+                        branches,
+                        otherwise: None,
+                    });
+                }
+                token => return Err(self.unexpected(&token)),
+            }
+        }
+    }
+
+    fn parse_while(&mut self, until: bool) -> Result<WhileLoop, ParseError> {
+        let condition = self.parse_list(&["do"])?;
+        if condition.items.is_empty() {
+            return Err(self.reject_next());
+        }
+        let body = self.parse_body()?;
+        Ok(WhileLoop {
+            until,
+            condition,
+            body,
+        })
+    }
+
+    fn parse_redirect(&mut self) -> Result<Option<Redirect>, ParseError> {
+        let fd = match self.peek()? {
+            Token::IoNumber(fd) => Some(*fd),
+            _ => None,
+        };
+        if fd.is_some() {
+            self.next()?;
+        }
+        let kind = match self.peek()? {
+            Token::Op(Op::Less) => RedirectKind::Input,
+            Token::Op(Op::Great) => RedirectKind::Output,
+            Token::Op(Op::DGreat) => RedirectKind::Append,
+            Token::Op(Op::LessAnd) => RedirectKind::DupInput,
+            Token::Op(Op::GreatAnd) => RedirectKind::DupOutput,
+            Token::Op(Op::AndGreat) if fd.is_none() => RedirectKind::OutputAll,
+            _ if fd.is_some() => return Err(self.reject_next()),
+            _ => return Ok(None),
+        };
+        self.next()?;
+        let source = match self.next()? {
+            Token::Word(w) => w,
+            Token::Eof => return Err(self.unexpected(&Token::Newline)),
+            token => return Err(self.unexpected(&token)),
+        };
+        Ok(Some(Redirect {
+            fd,
+            kind,
+            target: word::parse(&source),
+            source,
+        }))
+    }
+
+    fn parse_body(&mut self) -> Result<List, ParseError> {
+        self.skip_newlines()?;
+        self.expect_word("do")?;
+        let body = self.parse_list(&["done"])?;
+        if body.items.is_empty() {
+            return Err(self.reject_next());
+        }
+        self.expect_word("done")?;
+        Ok(body)
+    }
+
+    fn parse_for(&mut self) -> Result<ForLoop, ParseError> {
+        let var = match self.next()? {
+            Token::Word(w) if word::is_name(&w) => w,
+            Token::Word(w) => {
+                return Err(self.error(format!("`{}': not a valid identifier", w)));
+            }
+            token => return Err(self.unexpected(&token)),
+        };
+        self.skip_newlines()?;
+        let mut words = None;
+        if self.peek_is_word(&["in"])? {
+            self.next()?;
+            let mut list = Vec::new();
+            loop {
+                match self.next()? {
+                    Token::Word(w) => list.push(word::parse(&w)),
+                    Token::Op(Op::Semi) | Token::Newline => break,
+                    token => return Err(self.unexpected(&token)),
+                }
+            }
+            words = Some(list);
+        } else if *self.peek()? == Token::Op(Op::Semi) {
+            self.next()?;
+        }
+        let body = self.parse_body()?;
+        Ok(ForLoop { var, words, body })
+    }
+
+    fn parse_case(&mut self) -> Result<CaseCommand, ParseError> {
+        let word = match self.next()? {
+            Token::Word(w) => word::parse(&w),
+            token => return Err(self.unexpected(&token)),
+        };
+        self.skip_newlines()?;
+        self.expect_word("in")?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_newlines()?;
+            if self.peek_is_word(&["esac"])? {
+                self.next()?;
+                break;
+            }
+            if *self.peek()? == Token::Op(Op::LParen) {
+                self.next()?;
+            }
+            let mut patterns = Vec::new();
+            loop {
+                match self.next()? {
+                    Token::Word(w) => patterns.push(word::parse(&w)),
+                    token => return Err(self.unexpected(&token)),
+                }
+                match self.next()? {
+                    Token::Op(Op::Pipe) => continue,
+                    Token::Op(Op::RParen) => break,
+                    token => return Err(self.unexpected(&token)),
+                }
+            }
+            let body = self.parse_list(&["esac"])?;
+            items.push(CaseItem { patterns, body });
+            if *self.peek()? == Token::Op(Op::DSemi) {
+                self.next()?;
+            } else {
+                self.expect_word("esac")?;
+                break;
+            }
+        }
+        Ok(CaseCommand { word, items })
+    }
+
+    fn parse_group(&mut self) -> Result<List, ParseError> {
+        let body = self.parse_list(&["}"])?;
+        if body.items.is_empty() {
+            return Err(self.reject_next());
+        }
+        self.expect_word("}")?;
+        Ok(body)
+    }
+
+    fn parse_subshell(&mut self) -> Result<List, ParseError> {
+        let body = self.parse_list(&[")"])?;
+        if body.items.is_empty() {
+            return Err(self.reject_next());
+        }
+        self.expect(Op::RParen)?;
+        Ok(body)
+    }
+
+    fn parse_function(&mut self, name: String) -> Result<Command, ParseError> {
+        self.skip_newlines()?;
+        if !self.peek_is_word(COMPOUND_WORDS)? && *self.peek()? != Token::Op(Op::LParen) {
+            return Err(self.reject_next());
+        }
+        let body = self.parse_compound()?;
+        Ok(Command::Function(FunctionDef {
+            name,
+            body: Rc::new(body),
+        }))
+    }
+
+    fn parse_array(&mut self) -> Result<Vec<Word>, ParseError> {
+        let mut words = Vec::new();
+        loop {
+            match self.next()? {
+                Token::Word(w) => words.push(word::parse(&w)),
+                Token::Newline => {}
+                Token::Op(Op::RParen) => return Ok(words),
+                token => return Err(self.unexpected(&token)),
+            }
+        }
+    }
+
+    fn parse_simple(&mut self) -> Result<Command, ParseError> {
+        let line = self.peek_line()?;
+        if self.peek_is_word(RESERVED)? {
+            return Err(self.reject_next());
+        }
+        let start = self.lexer.offset();
+        let mut command = SimpleCommand {
+            assigns: Vec::new(),
+            words: Vec::new(),
+            redirects: Vec::new(),
+            line,
+            text: String::new(),
+        };
+        loop {
+            if let Some(redirect) = self.parse_redirect()? {
+                command.redirects.push(redirect);
+                continue;
+            }
+            let Token::Word(_) = self.peek()? else {
+                break;
+            };
+            let Token::Word(raw) = self.next()? else {
+                unreachable!()
+            };
+            match assignment(&raw) {
+                Some(mut assign) if command.words.is_empty() => {
+                    if assign.index.is_none()
+                        && raw.ends_with('=')
+                        && *self.peek()? == Token::Op(Op::LParen)
+                    {
+                        self.next()?;
+                        assign.value = AssignValue::Array(self.parse_array()?);
+                    }
+                    command.assigns.push(assign);
+                }
+                _ => command.words.push(word::parse(&raw)),
+            }
+            if command.words.len() == 1
+                && command.assigns.is_empty()
+                && command.redirects.is_empty()
+                && word::is_name(&raw)
+                && *self.peek()? == Token::Op(Op::LParen)
+            {
+                self.next()?;
+                self.expect(Op::RParen)?;
+                return self.parse_function(raw);
+            }
+        }
+        if command.assigns.is_empty() && command.words.is_empty() && command.redirects.is_empty() {
+            return Err(self.reject_next());
+        }
+        self.peek()?;
+        command.text = self.lexer.text(start, self.lexer.offset());
+        Ok(Command::Simple(command))
+    }
+}
+
+fn assignment(raw: &str) -> Option<Assign> {
+    let (target, value) = raw.split_once('=')?;
+    let (name, index) = match target.split_once('[') {
+        Some((name, rest)) => (name, Some(word::parse(rest.strip_suffix(']')?))),
+        None => (target, None),
+    };
+    if !word::is_name(name) {
+        return None;
+    }
+    Some(Assign {
+        name: name.to_string(),
+        index,
+        value: AssignValue::Scalar(word::parse_value(value)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{
+        AndOr, AssignValue, Command, Compound, Connector, List, ListItem, Pipeline, Redirect,
+        RedirectKind, SimpleCommand, WordPart,
+    };
+
+    fn lit(text: &str) -> Word {
+        Word {
+            parts: vec![WordPart::Literal(text.to_string())],
+        }
+    }
+
+    fn simple(text: &str, words: &[&str]) -> Command {
+        with_redirects(text, words, Vec::new())
+    }
+
+    fn with_redirects(text: &str, words: &[&str], redirects: Vec<Redirect>) -> Command {
+        Command::Simple(SimpleCommand {
+            assigns: Vec::new(),
+            words: words.iter().map(|w| lit(w)).collect(),
+            redirects,
+            line: 1,
+            text: text.to_string(),
+        })
+    }
+
+    fn redirect(fd: Option<i32>, kind: RedirectKind, target: &str) -> Redirect {
+        Redirect {
+            fd,
+            kind,
+            target: lit(target),
+            source: target.to_string(),
+        }
+    }
+
+    fn pipeline(commands: Vec<Command>) -> Pipeline {
+        Pipeline {
+            negated: false,
+            commands,
+        }
+    }
+
+    fn item(text: &str, first: Pipeline, rest: Vec<(Connector, Pipeline)>) -> ListItem {
+        ListItem {
+            text: text.to_string(),
+            and_or: AndOr { first, rest },
+            background: false,
+        }
+    }
+
+    fn single(command: Command) -> List {
+        let text = match &command {
+            Command::Simple(simple) => simple.text.clone(),
+            _ => unreachable!(),
+        };
+        List {
+            items: vec![item(&text, pipeline(vec![command]), Vec::new())],
+        }
+    }
+
+    fn words_of(src: &str) -> Vec<Word> {
+        match parse(src, 1).unwrap().items[0]
+            .and_or
+            .first
+            .commands
+            .remove(0)
+        {
+            Command::Simple(simple) => simple.words,
+            other => panic!("not a simple command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn simple_command() {
+        assert_eq!(
+            parse("echo hello world", 1).unwrap(),
+            single(simple("echo hello world", &["echo", "hello", "world"]))
+        );
+    }
+
+    #[test]
+    fn pipeline_of_three() {
+        let expected = List {
+            items: vec![item(
+                "ls -l | grep foo | wc -l",
+                pipeline(vec![
+                    simple("ls -l", &["ls", "-l"]),
+                    simple("grep foo", &["grep", "foo"]),
+                    simple("wc -l", &["wc", "-l"]),
+                ]),
+                Vec::new(),
+            )],
+        };
+        assert_eq!(parse("ls -l | grep foo | wc -l", 1).unwrap(), expected);
+    }
+
+    #[test]
+    fn and_or_chain() {
+        let expected = List {
+            items: vec![item(
+                "make && make install || echo failed",
+                pipeline(vec![simple("make", &["make"])]),
+                vec![
+                    (
+                        Connector::And,
+                        pipeline(vec![simple("make install", &["make", "install"])]),
+                    ),
+                    (
+                        Connector::Or,
+                        pipeline(vec![simple("echo failed", &["echo", "failed"])]),
+                    ),
+                ],
+            )],
+        };
+        assert_eq!(
+            parse("make && make install || echo failed", 1).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn sequence_and_background() {
+        let mut expected = List {
+            items: vec![
+                item(
+                    "cd /tmp",
+                    pipeline(vec![simple("cd /tmp", &["cd", "/tmp"])]),
+                    Vec::new(),
+                ),
+                item(
+                    "sleep 10",
+                    pipeline(vec![simple("sleep 10", &["sleep", "10"])]),
+                    Vec::new(),
+                ),
+                item("ls", pipeline(vec![simple("ls", &["ls"])]), Vec::new()),
+            ],
+        };
+        expected.items[1].background = true;
+        assert_eq!(parse("cd /tmp; sleep 10 & ls\n", 1).unwrap(), expected);
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(
+            words_of("echo 'single quoted' \"double $HOME\""),
+            vec![
+                lit("echo"),
+                Word {
+                    parts: vec![WordPart::Quoted("single quoted".to_string())],
+                },
+                Word {
+                    parts: vec![WordPart::DoubleQuoted(vec![
+                        WordPart::Quoted("double ".to_string()),
+                        WordPart::Param("HOME".to_string()),
+                    ])],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_blank_stays_in_the_word() {
+        assert_eq!(
+            words_of("echo a\\ b"),
+            vec![
+                lit("echo"),
+                Word {
+                    parts: vec![
+                        WordPart::Literal("a".to_string()),
+                        WordPart::Quoted(" ".to_string()),
+                        WordPart::Literal("b".to_string()),
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn substitution_and_tilde() {
+        assert_eq!(
+            words_of("echo $(date) ~/x"),
+            vec![
+                lit("echo"),
+                Word {
+                    parts: vec![WordPart::CommandSubst("date".to_string())],
+                },
+                Word {
+                    parts: vec![
+                        WordPart::Tilde(String::new()),
+                        WordPart::Literal("/x".to_string()),
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn file_redirections() {
+        assert_eq!(
+            parse("cat < in > out 2>> err", 1).unwrap(),
+            single(with_redirects(
+                "cat < in > out 2>> err",
+                &["cat"],
+                vec![
+                    redirect(None, RedirectKind::Input, "in"),
+                    redirect(None, RedirectKind::Output, "out"),
+                    redirect(Some(2), RedirectKind::Append, "err"),
+                ],
+            ))
+        );
+    }
+
+    #[test]
+    fn descriptor_redirections() {
+        assert_eq!(
+            parse("sort file >&2 2>&1", 1).unwrap(),
+            single(with_redirects(
+                "sort file >&2 2>&1",
+                &["sort", "file"],
+                vec![
+                    redirect(None, RedirectKind::DupOutput, "2"),
+                    redirect(Some(2), RedirectKind::DupOutput, "1"),
+                ],
+            ))
+        );
+        assert_eq!(
+            parse("cmd &> all.log", 1).unwrap(),
+            single(with_redirects(
+                "cmd &> all.log",
+                &["cmd"],
+                vec![redirect(None, RedirectKind::OutputAll, "all.log")],
+            ))
+        );
+    }
+
+    #[test]
+    fn prefix_assignments() {
+        let Command::Simple(command) = parse("a=1 b=two env", 1).unwrap().items[0]
+            .and_or
+            .first
+            .commands[0]
+            .clone()
+        else {
+            panic!("not a simple command");
+        };
+        let assigns: Vec<(String, Option<Word>, AssignValue)> = command
+            .assigns
+            .into_iter()
+            .map(|a| (a.name, a.index, a.value))
+            .collect();
+        assert_eq!(
+            assigns,
+            vec![
+                ("a".to_string(), None, AssignValue::Scalar(lit("1"))),
+                ("b".to_string(), None, AssignValue::Scalar(lit("two"))),
+            ]
+        );
+        assert_eq!(command.words, vec![lit("env")]);
+    }
+
+    #[test]
+    fn operators_need_no_spaces() {
+        assert_eq!(
+            parse("a|b&&c", 1).unwrap().items[0].and_or,
+            parse("a | b && c", 1).unwrap().items[0].and_or
+        );
+    }
+
+    // Source text is kept verbatim, so it is the one thing spacing changes.
+    fn without_text(mut list: List) -> List {
+        for item in &mut list.items {
+            item.text.clear();
+            let and_or = &mut item.and_or;
+            let pipelines =
+                std::iter::once(&mut and_or.first).chain(and_or.rest.iter_mut().map(|(_, p)| p));
+            for pipeline in pipelines {
+                for command in &mut pipeline.commands {
+                    if let Command::Simple(simple) = command {
+                        simple.text.clear();
+                    }
+                }
+            }
+        }
+        list
+    }
+
+    // Every way of writing `tokens` with or without blanks around the
+    // operators. A word keeps its blank before another word or before
+    // an fd-numbered redirection, which would otherwise join it.
+    fn spacings(tokens: &[&str]) -> Vec<String> {
+        let is_word = |t: &str| !t.starts_with(|c| "<>|&;".contains(c)) && !t.ends_with(['<', '>']);
+        let gaps: Vec<bool> = tokens
+            .windows(2)
+            .map(|pair| {
+                let fd = pair[1].starts_with(|c: char| c.is_ascii_digit());
+                is_word(pair[0]) && (is_word(pair[1]) || fd)
+            })
+            .collect();
+        let optional = gaps.iter().filter(|&&required| !required).count();
+        (0..1u32 << optional)
+            .map(|mask| {
+                let mut text = tokens[0].to_string();
+                let mut bit = 0;
+                for (i, &required) in gaps.iter().enumerate() {
+                    let blank = required || {
+                        bit += 1;
+                        mask & (1 << (bit - 1)) != 0
+                    };
+                    if blank {
+                        text.push(' ');
+                    }
+                    text.push_str(tokens[i + 1]);
+                }
+                text
+            })
+            .collect()
+    }
+
+    #[test]
+    fn spacing_does_not_change_the_tree() {
+        let commands: &[&[&str]] = &[
+            &["echo", "hi", ">", "out.txt"],
+            &["wc", "-l", "<", "data"],
+            &["cmd", "2>", "err"],
+            &["cmd", ">>", "log", "2>&1"],
+            &["a", "|", "b", "|", "c"],
+            &["a", "&&", "b", "||", "c"],
+            &["a", ";", "b", "&", "c"],
+            &["sort", "<", "in", "|", "uniq", ">", "out", "&"],
+            &["a", "2>&1", "|", "b", ">", "f", ";", "c", "<", "g"],
+        ];
+        for tokens in commands {
+            let variants = spacings(tokens);
+            assert!(variants.len() > 1);
+            let expected = without_text(parse(&tokens.join(" "), 1).unwrap());
+            for variant in &variants {
+                let list = parse(variant, 1).unwrap_or_else(|e| panic!("{:?}: {}", variant, e));
+                assert_eq!(without_text(list), expected, "{:?}", variant);
+            }
+        }
+    }
+
+    #[test]
+    fn quoted_operators_are_words() {
+        for (src, words) in [
+            (
+                "echo 'a>b' \"c|d\" e\\;f",
+                &["echo", "a>b", "c|d", "e;f"][..],
+            ),
+            ("echo '&&' \"<\" \\>", &["echo", "&&", "<", ">"]),
+            ("echo 'x & y;z'", &["echo", "x & y;z"]),
+        ] {
+            let list = parse(src, 1).unwrap();
+            let Command::Simple(simple) = &list.items[0].and_or.first.commands[0] else {
+                panic!("{:?}", src);
+            };
+            assert!(simple.redirects.is_empty(), "{:?}", src);
+            let rendered: Vec<String> = simple.words.iter().map(word_text).collect();
+            assert_eq!(rendered, words, "{:?}", src);
+            assert_eq!(list.items.len(), 1, "{:?}", src);
+        }
+    }
+
+    #[test]
+    fn empty_commands_name_the_token() {
+        for (src, token) in [
+            ("ls | | wc", "|"),
+            ("| ls", "|"),
+            ("ls || | wc", "|"),
+            ("; ls", ";"),
+            ("ls; ; ls", ";"),
+            ("ls ;; ls", ";;"),
+            ("&& ls", "&&"),
+            ("ls && && ls", "&&"),
+            ("ls || ; x", ";"),
+            ("ls | && x", "&&"),
+            ("&", "&"),
+            ("ls & &", "&"),
+            ("ls & ; x", ";"),
+        ] {
+            let message = format!("syntax error near unexpected token `{}'", token);
+            match parse(src, 1) {
+                Err(ParseError::Syntax { message: got, .. }) => {
+                    assert_eq!(got, message, "{:?}", src)
+                }
+                other => panic!("{:?}: {:?}", src, other),
+            }
+        }
+    }
+
+    #[test]
+    fn dangling_operators_continue_on_the_next_line() {
+        for src in ["ls |", "ls &&", "ls ||", "ls |\n", "ls &&\n\n"] {
+            assert!(
+                matches!(parse(src, 1), Err(ParseError::Incomplete { .. })),
+                "{:?}",
+                src
+            );
+            let continued = format!("{}\ncat", src);
+            assert!(parse(&continued, 1).is_ok(), "{:?}", continued);
+        }
+    }
+
+    #[test]
+    fn incomplete_and_invalid_input() {
+        assert!(matches!(
+            parse("echo a |", 1),
+            Err(ParseError::Incomplete { .. })
+        ));
+        assert!(matches!(
+            parse("echo 'open", 1),
+            Err(ParseError::Incomplete { .. })
+        ));
+        assert!(matches!(parse("| echo", 1), Err(ParseError::Syntax { .. })));
+        assert!(matches!(
+            parse("a && && b", 1),
+            Err(ParseError::Syntax { .. })
+        ));
+    }
+
+    #[test]
+    fn check_reports_every_error_with_its_line() {
+        let lines = |src: &str| -> Vec<usize> {
+            check(src)
+                .iter()
+                .map(|e| match e {
+                    ParseError::Incomplete { line, .. } | ParseError::Syntax { line, .. } => *line,
+                })
+                .collect()
+        };
+        assert_eq!(lines(""), [0; 0]);
+        assert_eq!(lines("echo a\nif true; then\n  echo b\nfi\n"), [0; 0]);
+        assert_eq!(lines("echo a\n\n| b\necho c\nls )\n"), [3, 5]);
+        assert_eq!(lines("if true; then\n  echo a\nfi fi\n"), [3]);
+        assert_eq!(lines("echo a\nwhile true; do\n  echo b\n"), [4]);
+        assert_eq!(lines("echo 'open\nstill open\n"), [1]);
+    }
+
+    fn parts_text(parts: &[WordPart]) -> String {
+        parts
+            .iter()
+            .map(|part| match part {
+                WordPart::Literal(text) | WordPart::Quoted(text) | WordPart::Tilde(text) => {
+                    text.clone()
+                }
+                WordPart::DoubleQuoted(parts) => parts_text(parts),
+                WordPart::Param(name) => format!("${}", name),
+                other => format!("{:?}", other),
+            })
+            .collect()
+    }
+
+    fn word_text(word: &Word) -> String {
+        parts_text(&word.parts)
+    }
+
+    fn render(list: &List) -> String {
+        let items: Vec<String> = list
+            .items
+            .iter()
+            .map(|item| {
+                let mut text = render_pipeline(&item.and_or.first);
+                for (connector, pipeline) in &item.and_or.rest {
+                    text.push_str(match connector {
+                        Connector::And => " && ",
+                        Connector::Or => " || ",
+                    });
+                    text.push_str(&render_pipeline(pipeline));
+                }
+                if item.background {
+                    text.push_str(" &");
+                }
+                text
+            })
+            .collect();
+        items.join(" ; ")
+    }
+
+    fn render_pipeline(pipeline: &Pipeline) -> String {
+        let commands: Vec<String> = pipeline.commands.iter().map(render_command).collect();
+        let bang = if pipeline.negated { "!" } else { "" };
+        format!("{}[{}]", bang, commands.join(" | "))
+    }
+
+    fn render_command(command: &Command) -> String {
+        match command {
+            Command::Simple(simple) => {
+                let words: Vec<String> = simple.words.iter().map(word_text).collect();
+                words.join(" ")
+            }
+            Command::Compound(Compound::Group(list), _) => format!("{{ {} }}", render(list)),
+            Command::Compound(Compound::Subshell(list), _) => format!("( {} )", render(list)),
+            Command::Compound(Compound::If(if_command), _) => {
+                let (condition, body) = &if_command.branches[0];
+                format!("if {} then {} fi", render(condition), render(body))
+            }
+            other => format!("{:?}", other),
+        }
+    }
+
+    /// Runs a parsed list without forking: every simple command logs its
+    /// name and succeeds unless it is listed in `failing`.
+    struct Mock<'a> {
+        failing: Vec<&'a str>,
+        log: Vec<String>,
+    }
+
+    impl Mock<'_> {
+        fn list(&mut self, list: &List) -> i32 {
+            let mut status = 0;
+            for item in &list.items {
+                status = self.and_or(&item.and_or);
+                if item.background {
+                    status = 0;
+                }
+            }
+            status
+        }
+
+        fn and_or(&mut self, and_or: &AndOr) -> i32 {
+            let mut status = self.pipeline(&and_or.first);
+            for (connector, pipeline) in &and_or.rest {
+                let run = match connector {
+                    Connector::And => status == 0,
+                    Connector::Or => status != 0,
+                };
+                if run {
+                    status = self.pipeline(pipeline);
+                }
+            }
+            status
+        }
+
+        fn pipeline(&mut self, pipeline: &Pipeline) -> i32 {
+            let mut status = 0;
+            for command in &pipeline.commands {
+                status = self.command(command);
+            }
+            if pipeline.negated {
+                return (status == 0) as i32;
+            }
+            status
+        }
+
+        fn command(&mut self, command: &Command) -> i32 {
+            match command {
+                Command::Simple(simple) => {
+                    let name = word_text(&simple.words[0]);
+                    let status = self.failing.contains(&name.as_str()) as i32;
+                    self.log.push(name);
+                    status
+                }
+                Command::Compound(Compound::Group(list) | Compound::Subshell(list), _) => {
+                    self.list(list)
+                }
+                Command::Compound(Compound::If(if_command), _) => {
+                    for (condition, body) in &if_command.branches {
+                        if self.list(condition) == 0 {
+                            return self.list(body);
+                        }
+                    }
+                    if_command
+                        .otherwise
+                        .as_ref()
+                        .map_or(0, |otherwise| self.list(otherwise))
+                }
+                other => panic!("mock cannot run {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn operator_precedence() {
+        // (input, expected tree, failing commands, execution order, status)
+        let table: &[(&str, &str, &str, &str, i32)] = &[
+            (
+                "a | b && c | d; e || f &",
+                "[a | b] && [c | d] ; [e] || [f] &",
+                "",
+                "a b c d e",
+                0,
+            ),
+            ("a && b || c", "[a] && [b] || [c]", "a", "a c", 0),
+            ("a || b && c", "[a] || [b] && [c]", "a", "a b c", 0),
+            ("a || b && c", "[a] || [b] && [c]", "a b", "a b", 1),
+            (
+                "a || b || c && d",
+                "[a] || [b] || [c] && [d]",
+                "a b c",
+                "a b c",
+                1,
+            ),
+            ("a&&b||c", "[a] && [b] || [c]", "b", "a b c", 0),
+            ("a | b | c", "[a | b | c]", "a", "a b c", 0),
+            ("a | b", "[a | b]", "b", "a b", 1),
+            (
+                "a && b | c || d",
+                "[a] && [b | c] || [d]",
+                "c",
+                "a b c d",
+                0,
+            ),
+            ("a; b; c", "[a] ; [b] ; [c]", "a b", "a b c", 0),
+            ("a;b&c", "[a] ; [b] & ; [c]", "", "a b c", 0),
+            ("a\nb && c", "[a] ; [b] && [c]", "b", "a b", 1),
+            ("a && b &", "[a] && [b] &", "b", "a b", 0),
+            ("a & b && c &", "[a] & ; [b] && [c] &", "", "a b c", 0),
+            ("! a && b", "![a] && [b]", "a", "a b", 0),
+            ("! a | b", "![a | b]", "b", "a b", 0),
+            ("a && ! b || c", "[a] && ![b] || [c]", "", "a b c", 0),
+            (
+                "! (a || b) && c",
+                "![( [a] || [b] )] && [c]",
+                "a b",
+                "a b c",
+                0,
+            ),
+            (
+                "{ a || b; } && c",
+                "[{ [a] || [b] }] && [c]",
+                "a b",
+                "a b",
+                1,
+            ),
+            ("(a && b) | c", "[( [a] && [b] ) | c]", "a", "a c", 0),
+            (
+                "a || (b; c) && d",
+                "[a] || [( [b] ; [c] )] && [d]",
+                "a",
+                "a b c d",
+                0,
+            ),
+            (
+                "a | { b && c; } | d",
+                "[a | { [b] && [c] } | d]",
+                "",
+                "a b c d",
+                0,
+            ),
+            ("'a|b' && c", "[a|b] && [c]", "", "a|b c", 0),
+            ("a \"&&\" b || c", "[a && b] || [c]", "a", "a c", 0),
+            ("a '|' b; c", "[a | b] ; [c]", "", "a c", 0),
+            (
+                "if a; then b | c; fi && d",
+                "[if [a] then [b | c] fi] && [d]",
+                "c",
+                "a b c",
+                1,
+            ),
+        ];
+        assert!(table.len() >= 20);
+        for &(input, tree, failing, order, status) in table {
+            let list = parse(input, 1).unwrap_or_else(|e| panic!("{:?}: {}", input, e));
+            assert_eq!(render(&list), tree, "tree of {:?}", input);
+            let mut mock = Mock {
+                failing: failing.split_whitespace().collect(),
+                log: Vec::new(),
+            };
+            let result = mock.list(&list);
+            assert_eq!(mock.log.join(" "), order, "order of {:?}", input);
+            assert_eq!(result, status, "status of {:?}", input);
+        }
+    }
+
+    // Shell syntax is mostly punctuation, so the bytes lean on it.
+    const FUZZ_BYTES: &[u8] = b"abc $(){}[]|&;<>'\"\\`#~*?!=- \n\t0129";
+
+    #[test]
+    fn random_bytes_never_panic() {
+        let mut seed = 0x1234_5678u32;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed
+        };
+        for _ in 0..20_000 {
+            let len = next() % 40;
+            let input: Vec<u8> = (0..len)
+                .map(|_| match next() % 8 {
+                    0 => (next() >> 8) as u8,
+                    1 => 0,
+                    _ => FUZZ_BYTES[next() as usize % FUZZ_BYTES.len()],
+                })
+                .collect();
+            let text = crate::bytes::decode(&input);
+            let _ = parse(&text, 1);
+            let _ = check(&text);
+        }
+    }
+
+    #[test]
+    fn hostile_lines_are_results() {
+        let deep = "(".repeat(10_000);
+        assert!(parse(&deep, 1).is_err());
+        let nested = "$(".repeat(2_000);
+        assert!(parse(&nested, 1).is_err());
+        let long = format!("echo {}", "x".repeat(1 << 20));
+        assert!(parse(&long, 1).is_ok());
+        assert!(parse("echo a\0b", 1).is_ok());
+        assert!(parse(&crate::bytes::decode(b"echo \xff\xfe"), 1).is_ok());
+    }
+}
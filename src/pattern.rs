@@ -0,0 +1,335 @@
+#[derive(Debug, Clone, PartialEq)]
+enum ClassItem {
+    Range(char, char),
+    Named(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    Class {
+        negated: bool,
+        items: Vec<ClassItem>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+}
+
+pub fn has_meta(chars: &[(char, bool)]) -> bool {
+    chars
+        .iter()
+        .any(|&(c, active)| active && matches!(c, '*' | '?' | '['))
+}
+
+fn named_class(name: &str, c: char) -> bool {
+    match name {
+        "alpha" => c.is_alphabetic(),
+        "digit" => c.is_ascii_digit(),
+        "alnum" => c.is_alphanumeric(),
+        "upper" => c.is_uppercase(),
+        "lower" => c.is_lowercase(),
+        "space" => c.is_whitespace(),
+        "blank" => c == ' ' || c == '\t',
+        "punct" => c.is_ascii_punctuation(),
+        "xdigit" => c.is_ascii_hexdigit(),
+        "cntrl" => c.is_control(),
+        "print" => !c.is_control(),
+        "graph" => !c.is_control() && !c.is_whitespace(),
+        _ => false,
+    }
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Char(p) => *p == c,
+            Token::Any => true,
+            Token::Star => false,
+            Token::Class { negated, items } => {
+                let found = items.iter().any(|item| match item {
+                    ClassItem::Range(lo, hi) => *lo <= c && c <= *hi,
+                    ClassItem::Named(name) => named_class(name, c),
+                });
+                found != *negated
+            }
+        }
+    }
+}
+
+impl Pattern {
+    pub fn new(chars: &[(char, bool)]) -> Pattern {
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let (c, active) = chars[i];
+            i += 1;
+            if !active {
+                tokens.push(Token::Char(c));
+                continue;
+            }
+            match c {
+                '*' => {
+                    if tokens.last() != Some(&Token::Star) {
+                        tokens.push(Token::Star);
+                    }
+                }
+                '?' => tokens.push(Token::Any),
+                '[' => match parse_class(&chars[i..]) {
+                    Some((token, used)) => {
+                        tokens.push(token);
+                        i += used;
+                    }
+                    None => tokens.push(Token::Char('[')),
+                },
+                _ => tokens.push(Token::Char(c)),
+            }
+        }
+        Pattern { tokens }
+    }
+
+    pub fn starts_with_dot(&self) -> bool {
+        self.tokens.first() == Some(&Token::Char('.'))
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        self.matches_chars(&text)
+    }
+
+    pub fn remove_prefix(&self, text: &str, longest: bool) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut ends: Vec<usize> = (0..=chars.len()).collect();
+        if longest {
+            ends.reverse();
+        }
+        match ends
+            .into_iter()
+            .find(|&end| self.matches_chars(&chars[..end]))
+        {
+            Some(end) => chars[end..].iter().collect(),
+            None => text.to_string(),
+        }
+    }
+
+    pub fn remove_suffix(&self, text: &str, longest: bool) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut starts: Vec<usize> = (0..=chars.len()).collect();
+        if !longest {
+            starts.reverse();
+        }
+        match starts
+            .into_iter()
+            .find(|&start| self.matches_chars(&chars[start..]))
+        {
+            Some(start) => chars[..start].iter().collect(),
+            None => text.to_string(),
+        }
+    }
+
+    pub fn replace(&self, text: &str, with: &str, all: bool, anchor: Option<char>) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::new();
+        let mut i = 0;
+        let mut replaced = false;
+        while i <= chars.len() {
+            let found = if (replaced && !all) || (anchor == Some('#') && i > 0) {
+                None
+            } else if anchor == Some('%') {
+                self.matches_chars(&chars[i..]).then_some(chars.len())
+            } else {
+                (i + 1..=chars.len())
+                    .rev()
+                    .find(|&end| self.matches_chars(&chars[i..end]))
+            };
+            match found {
+                Some(end) if end > i => {
+                    out.push_str(with);
+                    replaced = true;
+                    i = end;
+                }
+                _ => {
+                    if let Some(&c) = chars.get(i) {
+                        out.push(c);
+                    }
+                    i += 1;
+                }
+            }
+        }
+        out
+    }
+
+    fn matches_chars(&self, text: &[char]) -> bool {
+        let tokens = &self.tokens;
+        let (mut pi, mut ti) = (0, 0);
+        let mut backtrack: Option<(usize, usize)> = None;
+        while ti < text.len() {
+            if pi < tokens.len() {
+                if tokens[pi] == Token::Star {
+                    backtrack = Some((pi, ti));
+                    pi += 1;
+                    continue;
+                }
+                if tokens[pi].matches(text[ti]) {
+                    pi += 1;
+                    ti += 1;
+                    continue;
+                }
+            }
+            match backtrack {
+                Some((star, start)) => {
+                    pi = star + 1;
+                    ti = start + 1;
+                    backtrack = Some((star, start + 1));
+                }
+                None => return false,
+            }
+        }
+        tokens[pi..].iter().all(|t| *t == Token::Star)
+    }
+}
+
+fn parse_class(chars: &[(char, bool)]) -> Option<(Token, usize)> {
+    let mut i = 0;
+    let mut negated = false;
+    if let Some(&(c, true)) = chars.first()
+        && (c == '!' || c == '^')
+    {
+        negated = true;
+        i += 1;
+    }
+    let mut items = Vec::new();
+    let mut first = true;
+    while i < chars.len() {
+        let (c, active) = chars[i];
+        if c == ']' && active && !first {
+            return Some((Token::Class { negated, items }, i + 1));
+        }
+        first = false;
+        if c == '[' && active && chars.get(i + 1).map(|p| p.0) == Some(':') {
+            let rest: String = chars[i + 2..].iter().map(|p| p.0).collect();
+            if let Some(end) = rest.find(":]") {
+                items.push(ClassItem::Named(rest[..end].to_string()));
+                i += 2 + rest[..end].chars().count() + 2;
+                continue;
+            }
+        }
+        if chars.get(i + 1).map(|p| p.0) == Some('-')
+            && chars.get(i + 2).is_some_and(|p| p.0 != ']')
+        {
+            items.push(ClassItem::Range(c, chars[i + 2].0));
+            i += 3;
+        } else {
+            items.push(ClassItem::Range(c, c));
+            i += 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(text: &str) -> Pattern {
+        let chars: Vec<(char, bool)> = text.chars().map(|c| (c, true)).collect();
+        Pattern::new(&chars)
+    }
+
+    #[test]
+    fn matching() {
+        for (pat, text, expected) in [
+            ("*", "", true),
+            ("*", "anything", true),
+            ("?", "", false),
+            ("?", "é", true),
+            ("a*c", "abbbc", true),
+            ("a*c", "abbbd", false),
+            ("*.txt", "notes.txt", true),
+            ("*.txt", "notes.txt.bak", false),
+            ("[abc]x", "bx", true),
+            ("[!abc]x", "bx", false),
+            ("[^abc]x", "dx", true),
+            ("[a-c]*", "cat", true),
+            ("[[:digit:]][[:alpha:]]", "1a", true),
+            ("[[:upper:]]", "a", false),
+            ("[]]", "]", true),
+            ("[", "[", true),
+            ("**a", "ba", true),
+            ("日*", "日本語", true),
+        ] {
+            assert_eq!(pattern(pat).matches(text), expected, "{} ~ {}", pat, text);
+        }
+    }
+
+    #[test]
+    fn quoted_characters_are_literal() {
+        let pat = Pattern::new(&[('*', false), ('.', true), ('*', true)]);
+        assert!(pat.matches("*.c"));
+        assert!(!pat.matches("a.c"));
+    }
+
+    #[test]
+    fn prefix_and_suffix_removal() {
+        for (pat, text, shortest, longest) in [
+            ("*/", "/usr/local/bin", "usr/local/bin", "bin"),
+            ("a", "abc", "bc", "bc"),
+            ("x", "abc", "abc", "abc"),
+            ("*", "abc", "abc", ""),
+            ("", "abc", "abc", "abc"),
+            ("?", "日本語", "本語", "本語"),
+            ("*本", "日本日本語", "日本語", "語"),
+        ] {
+            assert_eq!(
+                pattern(pat).remove_prefix(text, false),
+                shortest,
+                "#{}",
+                pat
+            );
+            assert_eq!(pattern(pat).remove_prefix(text, true), longest, "##{}", pat);
+        }
+        for (pat, text, shortest, longest) in [
+            (".*", "archive.tar.gz", "archive.tar", "archive"),
+            (".txt", "notes.txt", "notes", "notes"),
+            (".txt", "notes.md", "notes.md", "notes.md"),
+            ("*", "abc", "abc", ""),
+            ("/*", "/usr/local/bin", "/usr/local", ""),
+            ("語", "日本語", "日本", "日本"),
+        ] {
+            assert_eq!(
+                pattern(pat).remove_suffix(text, false),
+                shortest,
+                "%{}",
+                pat
+            );
+            assert_eq!(pattern(pat).remove_suffix(text, true), longest, "%%{}", pat);
+        }
+    }
+
+    #[test]
+    fn replacement() {
+        for (pat, text, with, all, anchor, expected) in [
+            ("o", "foo boo", "0", false, None, "f0o boo"),
+            ("o", "foo boo", "0", true, None, "f00 b00"),
+            ("o*", "foo boo", "X", false, None, "fX"),
+            ("b?", "abcbd", "", true, None, "a"),
+            ("f", "foof", "F", true, Some('#'), "Foof"),
+            ("f", "foof", "F", true, Some('%'), "fooF"),
+            ("x", "foo", "F", true, Some('#'), "foo"),
+            ("z", "foo", "F", true, None, "foo"),
+            ("本", "日本語日本語", "-", true, None, "日-語日-語"),
+        ] {
+            assert_eq!(
+                pattern(pat).replace(text, with, all, anchor),
+                expected,
+                "{} in {}",
+                pat,
+                text
+            );
+        }
+    }
+}
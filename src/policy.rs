@@ -0,0 +1,330 @@
+use std::ffi::CStr;
+use std::fmt;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+
+use nix::errno::Errno;
+
+use crate::error::{program, write_stderr};
+use crate::pattern::Pattern;
+
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+const OOM_SCORE_ADJ: &CStr = c"/proc/self/oom_score_adj";
+
+static WARNED: OnceLock<usize> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoClass {
+    Realtime = 1,
+    BestEffort = 2,
+    Idle = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adjustment {
+    Nice(i32),
+    Io(IoClass, u8),
+    Oom(i32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub pattern: String,
+    pub adjustments: Vec<Adjustment>,
+}
+
+impl fmt::Display for Adjustment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Adjustment::Nice(n) => write!(f, "nice:{}", n),
+            Adjustment::Io(IoClass::Idle, _) => write!(f, "ionice:idle"),
+            Adjustment::Io(IoClass::BestEffort, level) => write!(f, "ionice:best-effort:{}", level),
+            Adjustment::Io(IoClass::Realtime, level) => write!(f, "ionice:realtime:{}", level),
+            Adjustment::Oom(n) => write!(f, "oom:{}", n),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let adjustments: Vec<String> = self.adjustments.iter().map(|a| a.to_string()).collect();
+        write!(f, "{}={}", self.pattern, adjustments.join(","))
+    }
+}
+
+impl Adjustment {
+    fn bit(&self) -> u32 {
+        match self {
+            Adjustment::Nice(_) => 1,
+            Adjustment::Io(..) => 2,
+            Adjustment::Oom(_) => 4,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Adjustment::Nice(_) => "niceness",
+            Adjustment::Io(..) => "I/O priority",
+            Adjustment::Oom(_) => "OOM score adjustment",
+        }
+    }
+}
+
+fn ranged(text: &str, min: i32, max: i32) -> Option<i32> {
+    text.trim()
+        .parse::<i32>()
+        .ok()
+        .filter(|n| (min..=max).contains(n))
+}
+
+fn parse_adjustment(text: &str) -> Result<Adjustment, String> {
+    let invalid = || format!("`{}': invalid adjustment", text);
+    let (kind, value) = text.split_once(':').ok_or_else(invalid)?;
+    match kind {
+        "nice" => ranged(value, -20, 19)
+            .map(Adjustment::Nice)
+            .ok_or_else(invalid),
+        "oom" => ranged(value, -1000, 1000)
+            .map(Adjustment::Oom)
+            .ok_or_else(invalid),
+        "ionice" => {
+            let (class, level) = match value.split_once(':') {
+                Some((class, level)) => (class, Some(level)),
+                None => (value, None),
+            };
+            let class = match class {
+                "idle" => IoClass::Idle,
+                "best-effort" | "be" => IoClass::BestEffort,
+                "realtime" | "rt" => IoClass::Realtime,
+                _ => return Err(invalid()),
+            };
+            let level = match level {
+                Some(level) => ranged(level, 0, 7).ok_or_else(invalid)?,
+                None => 4,
+            };
+            let level = if class == IoClass::Idle { 0 } else { level };
+            Ok(Adjustment::Io(class, level as u8))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+pub fn parse(spec: &str) -> (Vec<Rule>, Vec<String>) {
+    let mut rules = Vec::new();
+    let mut errors = Vec::new();
+    for entry in spec.split([';', ' ', '\t', '\n']).filter(|e| !e.is_empty()) {
+        let Some((pattern, list)) = entry.split_once('=').filter(|(p, _)| !p.is_empty()) else {
+            errors.push(format!("`{}': missing command pattern", entry));
+            continue;
+        };
+        let mut adjustments = Vec::new();
+        for text in list.split(',').filter(|a| !a.is_empty()) {
+            match parse_adjustment(text) {
+                Ok(adjustment) => adjustments.push(adjustment),
+                Err(message) => errors.push(message),
+            }
+        }
+        rules.push(Rule {
+            pattern: pattern.to_string(),
+            adjustments,
+        });
+    }
+    (rules, errors)
+}
+
+pub fn lookup(spec: &str, name: &str) -> Vec<Adjustment> {
+    let name = name.rsplit('/').next().unwrap_or(name);
+    parse(spec)
+        .0
+        .into_iter()
+        .find(|rule| {
+            let chars: Vec<(char, bool)> = rule.pattern.chars().map(|c| (c, true)).collect();
+            Pattern::new(&chars).matches(name)
+        })
+        .map_or_else(Vec::new, |rule| rule.adjustments)
+}
+
+fn prepare() {
+    WARNED.get_or_init(|| {
+        let page = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                std::mem::size_of::<AtomicU32>(),
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        match page == libc::MAP_FAILED {
+            true => 0,
+            false => page as usize,
+        }
+    });
+}
+
+fn first_failure(bit: u32) -> bool {
+    match WARNED.get().copied().filter(|&page| page != 0) {
+        Some(page) => {
+            let warned = unsafe { &*(page as *const AtomicU32) };
+            warned.fetch_or(bit, Ordering::SeqCst) & bit == 0
+        }
+        None => true,
+    }
+}
+
+#[derive(Debug)]
+struct Step {
+    adjustment: Adjustment,
+    label: Vec<u8>,
+    value: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+pub struct Plan {
+    steps: Vec<Step>,
+}
+
+impl Plan {
+    pub fn new(adjustments: Vec<Adjustment>) -> Plan {
+        if !adjustments.is_empty() {
+            prepare();
+        }
+        let steps = adjustments
+            .into_iter()
+            .map(|adjustment| Step {
+                label: adjustment.to_string().into_bytes(),
+                value: match adjustment {
+                    Adjustment::Oom(n) => n.to_string().into_bytes(),
+                    _ => Vec::new(),
+                },
+                adjustment,
+            })
+            .collect();
+        Plan { steps }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn apply(&self, name: &[u8]) {
+        for step in &self.steps {
+            if let Err(errno) = set(step)
+                && first_failure(step.adjustment.bit())
+            {
+                write_stderr(&[
+                    program().as_bytes(),
+                    b": warning: ",
+                    name,
+                    b": cannot set ",
+                    step.adjustment.describe().as_bytes(),
+                    b" (",
+                    &step.label,
+                    b"): ",
+                    errno.desc().as_bytes(),
+                    b"\n",
+                ]);
+            }
+        }
+    }
+}
+
+fn set(step: &Step) -> Result<(), Errno> {
+    let result = match step.adjustment {
+        Adjustment::Nice(n) => unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, n) },
+        Adjustment::Io(class, level) => unsafe {
+            let value = ((class as libc::c_int) << IOPRIO_CLASS_SHIFT) | level as libc::c_int;
+            libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, value) as libc::c_int
+        },
+        Adjustment::Oom(_) => unsafe {
+            let fd = libc::open(OOM_SCORE_ADJ.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+            if fd == -1 {
+                return Err(Errno::last());
+            }
+            let written = libc::write(fd, step.value.as_ptr().cast(), step.value.len());
+            let errno = Errno::last();
+            libc::close(fd);
+            if written != step.value.len() as isize {
+                return Err(errno);
+            }
+            0
+        },
+    };
+    match result {
+        -1 => Err(Errno::last()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specs_parse_into_rules() {
+        let (rules, errors) =
+            parse("cargo=nice:10,ionice:idle ffmpeg=oom:500,nice:15;make*=ionice:be:6");
+        assert_eq!(errors, Vec::<String>::new());
+        assert_eq!(
+            rules,
+            vec![
+                Rule {
+                    pattern: "cargo".to_string(),
+                    adjustments: vec![Adjustment::Nice(10), Adjustment::Io(IoClass::Idle, 0)],
+                },
+                Rule {
+                    pattern: "ffmpeg".to_string(),
+                    adjustments: vec![Adjustment::Oom(500), Adjustment::Nice(15)],
+                },
+                Rule {
+                    pattern: "make*".to_string(),
+                    adjustments: vec![Adjustment::Io(IoClass::BestEffort, 6)],
+                },
+            ]
+        );
+        let shown: Vec<String> = rules.iter().map(|r| r.to_string()).collect();
+        assert_eq!(
+            shown,
+            [
+                "cargo=nice:10,ionice:idle",
+                "ffmpeg=oom:500,nice:15",
+                "make*=ionice:best-effort:6",
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_entries_are_reported() {
+        let (rules, errors) =
+            parse("=nice:1 a=nice:20,oom:-1001,ionice:rt:8,ionice:fast,speed:1,nice b=nice:-20");
+        assert_eq!(
+            errors,
+            [
+                "`=nice:1': missing command pattern",
+                "`nice:20': invalid adjustment",
+                "`oom:-1001': invalid adjustment",
+                "`ionice:rt:8': invalid adjustment",
+                "`ionice:fast': invalid adjustment",
+                "`speed:1': invalid adjustment",
+                "`nice': invalid adjustment",
+            ]
+        );
+        assert_eq!(rules.len(), 2);
+        assert!(rules[0].adjustments.is_empty());
+        assert_eq!(rules[1].adjustments, [Adjustment::Nice(-20)]);
+    }
+
+    #[test]
+    fn lookup_uses_the_first_matching_glob() {
+        let spec = "cargo=nice:10 car*=nice:5 [gm]cc=oom:100";
+        assert_eq!(lookup(spec, "cargo"), [Adjustment::Nice(10)]);
+        assert_eq!(lookup(spec, "/usr/bin/cargo"), [Adjustment::Nice(10)]);
+        assert_eq!(lookup(spec, "cart"), [Adjustment::Nice(5)]);
+        assert_eq!(lookup(spec, "gcc"), [Adjustment::Oom(100)]);
+        assert_eq!(lookup(spec, "cc"), []);
+        assert_eq!(lookup(spec, "xcargo"), []);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::builtins::interpret_escapes;
+use crate::word;
+
+#[derive(Debug, Default)]
+pub struct Formatted {
+    pub text: String,
+    pub errors: Vec<String>,
+    pub failed: bool,
+}
+
+#[derive(Debug, Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+struct Printer<'a> {
+    args: &'a [String],
+    next: usize,
+    out: Formatted,
+}
+
+pub fn format(format: &str, args: &[String]) -> Formatted {
+    let chars: Vec<char> = format.chars().collect();
+    let mut printer = Printer {
+        args,
+        next: 0,
+        out: Formatted::default(),
+    };
+    loop {
+        let start = printer.next;
+        if !printer.pass(&chars) || printer.next == start || printer.next >= args.len() {
+            break;
+        }
+    }
+    printer.out
+}
+
+fn escape(chars: &[char], mut i: usize, out: &mut String) -> Option<usize> {
+    let Some(&c) = chars.get(i) else {
+        out.push('\\');
+        return Some(i);
+    };
+    i += 1;
+    match c {
+        'a' => out.push('\x07'),
+        'b' => out.push('\x08'),
+        'c' => return None,
+        'e' | 'E' => out.push('\x1b'),
+        'f' => out.push('\x0c'),
+        'n' => out.push('\n'),
+        'r' => out.push('\r'),
+        't' => out.push('\t'),
+        'v' => out.push('\x0b'),
+        '\\' | '"' | '\'' => out.push(c),
+        '0'..='7' | 'x' => {
+            let (base, max, mut value, mut digits) = match c {
+                'x' => (16, 2, 0, 0),
+                _ => (8, 3, c.to_digit(8).unwrap_or(0), 1),
+            };
+            while digits < max
+                && let Some(d) = chars.get(i).and_then(|c| c.to_digit(base))
+            {
+                value = value * base + d;
+                digits += 1;
+                i += 1;
+            }
+            if digits == 0 {
+                out.push_str("\\x");
+            } else {
+                out.push(char::from_u32(value).unwrap_or('\u{fffd}'));
+            }
+        }
+        other => {
+            out.push('\\');
+            out.push(other);
+        }
+    }
+    Some(i)
+}
+
+fn pad(text: String, spec: &Spec) -> String {
+    let len = text.chars().count();
+    if len >= spec.width {
+        return text;
+    }
+    let fill = " ".repeat(spec.width - len);
+    match spec.left {
+        true => text + &fill,
+        false => fill + &text,
+    }
+}
+
+fn finish(spec: &Spec, negative: bool, prefix: &str, body: String) -> String {
+    let sign = match () {
+        _ if negative => "-",
+        _ if spec.plus => "+",
+        _ if spec.space => " ",
+        _ => "",
+    };
+    let len = sign.len() + prefix.len() + body.chars().count();
+    if spec.zero && !spec.left && len < spec.width {
+        return format!("{}{}{}{}", sign, prefix, "0".repeat(spec.width - len), body);
+    }
+    pad(format!("{}{}{}", sign, prefix, body), spec)
+}
+
+fn exponent(value: f64, precision: usize, upper: bool) -> String {
+    let text = format!("{:.*e}", precision, value);
+    let (mantissa, exp) = text.split_once('e').unwrap_or((&text, "0"));
+    let exp: i32 = exp.parse().unwrap_or(0);
+    let sign = if exp < 0 { '-' } else { '+' };
+    let e = if upper { 'E' } else { 'e' };
+    format!("{}{}{}{:02}", mantissa, e, sign, exp.abs())
+}
+
+fn general(value: f64, precision: usize, alternate: bool, upper: bool) -> String {
+    let precision = precision.max(1);
+    if value == 0.0 {
+        return match alternate {
+            true => format!("{:.*}", precision - 1, 0.0),
+            false => "0".to_string(),
+        };
+    }
+    let exp = value.abs().log10().floor() as i32;
+    let text = if exp < -4 || exp >= precision as i32 {
+        exponent(value, precision - 1, upper)
+    } else {
+        format!("{:.*}", (precision as i32 - 1 - exp).max(0) as usize, value)
+    };
+    if alternate {
+        return text;
+    }
+    match text.split_once(['e', 'E']) {
+        Some((mantissa, rest)) => {
+            let e = if upper { 'E' } else { 'e' };
+            format!("{}{}{}", trim_fraction(mantissa), e, rest)
+        }
+        None => trim_fraction(&text).to_string(),
+    }
+}
+
+fn trim_fraction(text: &str) -> &str {
+    match text.contains('.') {
+        true => text.trim_end_matches('0').trim_end_matches('.'),
+        false => text,
+    }
+}
+
+impl Printer<'_> {
+    fn arg(&mut self) -> Option<&str> {
+        let arg = self.args.get(self.next)?;
+        self.next += 1;
+        Some(arg)
+    }
+
+    fn integer(&mut self) -> i64 {
+        let Some(arg) = self.arg() else {
+            return 0;
+        };
+        let arg = arg.to_string();
+        if let Some(rest) = arg.strip_prefix(['\'', '"']) {
+            return rest.chars().next().map_or(0, |c| c as i64);
+        }
+        let text = arg.trim();
+        if text.is_empty() {
+            return 0;
+        }
+        let (negative, body) = match text.strip_prefix('-') {
+            Some(body) => (true, body),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (radix, digits) = match body.strip_prefix("0x").or(body.strip_prefix("0X")) {
+            Some(hex) => (16, hex),
+            None if body.len() > 1 && body.starts_with('0') => (8, &body[1..]),
+            None => (10, body),
+        };
+        match u64::from_str_radix(digits, radix) {
+            Ok(n) if negative => (n as i64).wrapping_neg(),
+            Ok(n) => n as i64,
+            Err(_) => {
+                self.out.errors.push(format!("{}: invalid number", arg));
+                self.out.failed = true;
+                0
+            }
+        }
+    }
+
+    fn float(&mut self) -> f64 {
+        let Some(arg) = self.arg() else {
+            return 0.0;
+        };
+        let arg = arg.to_string();
+        if let Some(rest) = arg.strip_prefix(['\'', '"']) {
+            return rest.chars().next().map_or(0.0, |c| c as u32 as f64);
+        }
+        match arg.trim() {
+            "" => 0.0,
+            text => text.parse().unwrap_or_else(|_| {
+                self.out.errors.push(format!("{}: invalid number", arg));
+                self.out.failed = true;
+                0.0
+            }),
+        }
+    }
+
+    fn count(&mut self, chars: &[char], i: &mut usize) -> Option<i64> {
+        if chars.get(*i) == Some(&'*') {
+            *i += 1;
+            return Some(self.integer());
+        }
+        let start = *i;
+        while chars.get(*i).is_some_and(char::is_ascii_digit) {
+            *i += 1;
+        }
+        match start == *i {
+            true => None,
+            false => chars[start..*i].iter().collect::<String>().parse().ok(),
+        }
+    }
+
+    fn pass(&mut self, chars: &[char]) -> bool {
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '\\' => match escape(chars, i + 1, &mut self.out.text) {
+                    Some(next) => i = next,
+                    None => return false,
+                },
+                '%' => match self.conversion(chars, i + 1) {
+                    Some(next) => i = next,
+                    None => return false,
+                },
+                c => {
+                    self.out.text.push(c);
+                    i += 1;
+                }
+            }
+        }
+        true
+    }
+
+    fn conversion(&mut self, chars: &[char], mut i: usize) -> Option<usize> {
+        let start = i - 1;
+        let mut spec = Spec::default();
+        while let Some(&c) = chars.get(i) {
+            match c {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '#' => spec.alternate = true,
+                '0' => spec.zero = true,
+                _ => break,
+            }
+            i += 1;
+        }
+        if let Some(width) = self.count(chars, &mut i) {
+            spec.left |= width < 0;
+            spec.width = width.unsigned_abs() as usize;
+        }
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            spec.precision = Some(self.count(chars, &mut i).unwrap_or(0).max(0) as usize);
+        }
+        while chars
+            .get(i)
+            .is_some_and(|c| matches!(c, 'h' | 'l' | 'L' | 'j'))
+        {
+            i += 1;
+        }
+        let Some(&c) = chars.get(i) else {
+            let text: String = chars[start..].iter().collect();
+            self.out
+                .errors
+                .push(format!("`{}': missing format character", text));
+            self.out.failed = true;
+            return None;
+        };
+        let text = match c {
+            '%' => "%".to_string(),
+            's' | 'q' => {
+                let arg = self.arg().unwrap_or("").to_string();
+                let mut text = if c == 'q' { word::quote(&arg) } else { arg };
+                if let Some(p) = spec.precision {
+                    text = text.chars().take(p).collect();
+                }
+                pad(text, &spec)
+            }
+            'b' => {
+                let arg = self.arg().unwrap_or("").to_string();
+                let mut text = String::new();
+                let more = interpret_escapes(&arg, &mut text);
+                if let Some(p) = spec.precision {
+                    text = text.chars().take(p).collect();
+                }
+                self.out.text.push_str(&pad(text, &spec));
+                return more.then_some(i + 1);
+            }
+            'c' => {
+                let arg = self.arg().unwrap_or("").to_string();
+                pad(arg.chars().take(1).collect(), &spec)
+            }
+            'd' | 'i' => {
+                let n = self.integer();
+                let body = digits(n.unsigned_abs().to_string(), &mut spec);
+                finish(&spec, n < 0, "", body)
+            }
+            'u' | 'o' | 'x' | 'X' => {
+                let n = self.integer() as u64;
+                let (text, prefix) = match c {
+                    'o' => (
+                        format!("{:o}", n),
+                        if spec.alternate && n != 0 { "0" } else { "" },
+                    ),
+                    'x' => (
+                        format!("{:x}", n),
+                        if spec.alternate && n != 0 { "0x" } else { "" },
+                    ),
+                    'X' => (
+                        format!("{:X}", n),
+                        if spec.alternate && n != 0 { "0X" } else { "" },
+                    ),
+                    _ => (n.to_string(), ""),
+                };
+                spec.plus = false;
+                spec.space = false;
+                let body = digits(text, &mut spec);
+                finish(&spec, false, prefix, body)
+            }
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+                let value = self.float();
+                let precision = spec.precision.unwrap_or(6);
+                let magnitude = value.abs();
+                let body = match c {
+                    'f' | 'F' => format!("{:.*}", precision, magnitude),
+                    'e' | 'E' => exponent(magnitude, precision, c == 'E'),
+                    _ => general(magnitude, precision, spec.alternate, c == 'G'),
+                };
+                let body = match c.is_ascii_uppercase() {
+                    true => body.to_uppercase(),
+                    false => body,
+                };
+                finish(&spec, value.is_sign_negative() && value != 0.0, "", body)
+            }
+            other => {
+                self.out
+                    .errors
+                    .push(format!("`{}': invalid format character", other));
+                self.out.failed = true;
+                return None;
+            }
+        };
+        self.out.text.push_str(&text);
+        Some(i + 1)
+    }
+}
+
+fn digits(text: String, spec: &mut Spec) -> String {
+    let Some(precision) = spec.precision else {
+        return text;
+    };
+    spec.zero = false;
+    match text.as_str() {
+        "0" if precision == 0 => String::new(),
+        _ if text.len() < precision => "0".repeat(precision - text.len()) + &text,
+        _ => text,
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nix::unistd;
+
+use crate::color::style;
+use crate::history;
+use crate::shell::Shell;
+use crate::status::Status;
+use crate::terminal;
+use crate::width::{HIDE_END, HIDE_START};
+
+impl Shell {
+    pub fn record_prompt_status(&mut self) {
+        let status = Status::new(self.last_status as i64);
+        self.prompt_status = match self.last_signal {
+            _ if status == Status::SUCCESS => None,
+            Some(signal) if Status::from_signal(signal) == status => {
+                Some(signal.as_str().to_string())
+            }
+            _ => Some(status.code().to_string()),
+        };
+    }
+
+    pub fn status_segment(&self) -> String {
+        match &self.prompt_status {
+            Some(label) => style("prompt_status", &format!("[{}]", label)),
+            None => String::new(),
+        }
+    }
+
+    pub fn render_prompt(&self, template: &str) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        let mut out = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('u') => {
+                    out.push_str(&style("prompt_user", self.vars.get("USER").unwrap_or("")))
+                }
+                Some('h') => {
+                    let host = terminal::hostname();
+                    out.push_str(&style(
+                        "prompt_host",
+                        host.split('.').next().unwrap_or(&host),
+                    ));
+                }
+                Some('H') => out.push_str(&style("prompt_host", &terminal::hostname())),
+                Some('w') => out.push_str(&style("prompt_cwd", &self.short_cwd())),
+                Some('W') => {
+                    let cwd = self.short_cwd();
+                    let base = match cwd.rsplit_once('/') {
+                        Some((_, "")) | None => &cwd,
+                        Some((_, base)) => base,
+                    };
+                    out.push_str(&style("prompt_cwd", base));
+                }
+                Some('$') => out.push_str(&style(
+                    "prompt_symbol",
+                    if unistd::geteuid().is_root() {
+                        "#"
+                    } else {
+                        "$"
+                    },
+                )),
+                Some('?') => out.push_str(&self.status_segment()),
+                Some('s') => out.push_str(self.name.rsplit('/').next().unwrap_or(&self.name)),
+                Some('j') => out.push_str(&self.jobs.len().to_string()),
+                Some('!') => {
+                    out.push_str(&(self.history.base + self.history.entries.len() + 1).to_string())
+                }
+                Some('t') => out.push_str(&history::format_time("%H:%M:%S", now)),
+                Some('T') => out.push_str(&history::format_time("%I:%M:%S", now)),
+                Some('A') => out.push_str(&history::format_time("%H:%M", now)),
+                Some('d') => out.push_str(&history::format_time("%a %b %d", now)),
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('a') => out.push('\x07'),
+                Some('e') => out.push('\x1b'),
+                Some('[') => out.push(HIDE_START),
+                Some(']') => out.push(HIDE_END),
+                Some('\\') => out.push('\\'),
+                Some(digit @ '0'..='7') => {
+                    let mut code = digit.to_digit(8).unwrap_or(0);
+                    for _ in 0..2 {
+                        match chars.peek().and_then(|c| c.to_digit(8)) {
+                            Some(d) => {
+                                code = code * 8 + d;
+                                chars.next();
+                            }
+                            None => break,
+                        }
+                    }
+                    out.extend(char::from_u32(code));
+                }
+                Some(other) => {
+                    out.push('\\');
+                    out.push(other);
+                }
+                None => out.push('\\'),
+            }
+        }
+        out
+    }
+}
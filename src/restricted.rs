@@ -0,0 +1,36 @@
+use crate::ast::{Redirect, RedirectKind};
+use crate::error::{Result, ShellError};
+use crate::shell::Shell;
+
+pub const PROTECTED: &[&str] = &["PATH", "SHELL", "ENV"];
+
+impl Shell {
+    pub fn restrict_variable(&self, name: &str) -> Result<()> {
+        match self.restricted && PROTECTED.contains(&name) {
+            true => Err(ShellError::Restricted(name.to_string())),
+            false => Ok(()),
+        }
+    }
+
+    pub fn restrict_command(&self, name: &str) -> Result<()> {
+        match self.restricted && (name.contains('/') || matches!(name, "cd" | "exec")) {
+            true => Err(ShellError::Restricted(name.to_string())),
+            false => Ok(()),
+        }
+    }
+
+    pub fn restrict_redirect(&self, redirect: &Redirect, target: &str) -> Result<()> {
+        let output = match redirect.kind {
+            RedirectKind::Output | RedirectKind::Append | RedirectKind::OutputAll => true,
+            RedirectKind::DupOutput => target != "-" && !target.bytes().all(|b| b.is_ascii_digit()),
+            RedirectKind::Input | RedirectKind::DupInput => false,
+        };
+        match self.restricted && output {
+            true => Err(ShellError::Restricted(format!(
+                "cannot redirect output to {}",
+                redirect.source
+            ))),
+            false => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nix::unistd::{self, fork, ForkResult};
+
+use crate::ast::{Command, Compound, List};
+use crate::builtins;
+use crate::error::{program, warn, Result, ShellError};
+use crate::shell::{Control, Shell};
+use crate::signals;
+
+const WINDOW: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_FORKS: usize = 5000;
+const DEFAULT_MAX_JOBS: usize = 1000;
+
+#[derive(Debug, Default)]
+pub struct Spawns {
+    recent: VecDeque<Instant>,
+    allowance: usize,
+}
+
+/// Why process creation looks runaway. `culprit` names the command to
+/// blame when it is not the one about to fork: once too many jobs pile
+/// up, that is the command that most recently started one. A job pile-up
+/// `settles` if some of them finish during the pause; a fork storm does
+/// not, since pausing only empties the rate window.
+struct Runaway {
+    reason: String,
+    culprit: Option<String>,
+    settles: bool,
+}
+
+pub fn list_text(list: &List) -> String {
+    let items: Vec<&str> = list.items.iter().map(|item| item.text.as_str()).collect();
+    items.join("; ")
+}
+
+pub fn describe(command: &Command) -> String {
+    match command {
+        Command::Simple(simple) => simple.text.clone(),
+        Command::Compound(compound, _) => match compound {
+            Compound::If(_) => "if ...".to_string(),
+            Compound::For(for_loop) => format!("for {} ...", for_loop.var),
+            Compound::While(_) => "while ...".to_string(),
+            Compound::Select(select) => format!("select {} ...", select.var),
+            Compound::Case(_) => "case ...".to_string(),
+            Compound::Group(list) => format!("{{ {}; }}", list_text(list)),
+            Compound::Subshell(list) => format!("({})", list_text(list)),
+            Compound::Arith(expr) => format!("(({}))", expr),
+        },
+        Command::Function(def) => format!("{} ()", def.name),
+        Command::Coproc(coproc) => format!("coproc {}", coproc.name),
+    }
+}
+
+impl Shell {
+    pub fn fork(&mut self, text: &str) -> Result<ForkResult> {
+        self.admit_process(text)?;
+        if let Some(hook) = &mut self.fork_hook {
+            hook(text)?;
+        }
+        unsafe { fork() }.map_err(|e| ShellError::Internal(format!("fork: {}", e.desc())))
+    }
+
+    pub fn admit_process(&mut self, text: &str) -> Result<()> {
+        if let Some(runaway) = self.runaway() {
+            let culprit = runaway.culprit.unwrap_or_else(|| text.to_string());
+            warn(format_args!(
+                "{}: warning: runaway process creation in `{}': {}",
+                program(),
+                culprit,
+                runaway.reason
+            ));
+            thread::sleep(WINDOW);
+            let persists = !runaway.settles || self.runaway().is_some();
+            if signals::interrupted() || (persists && !self.confirm_spawning()?) {
+                return Err(ShellError::Runaway(culprit));
+            }
+        }
+        self.spawns.recent.push_back(Instant::now());
+        Ok(())
+    }
+
+    pub fn halt_runaway(&mut self, status: i32) {
+        if self.interactive && !self.subshell {
+            signals::interrupt();
+        } else if self.embedded && !self.subshell {
+            self.control = Some(Control::Exit);
+        } else {
+            self.audit(status);
+            self.run_exit_trap();
+            io::stdout().flush().ok();
+            self.save_history();
+            std::process::exit(status);
+        }
+    }
+
+    fn spawn_limit(&self, name: &str, default: usize) -> usize {
+        match self.vars.get(name).map(|n| n.trim().parse::<usize>()) {
+            Some(Ok(0)) => usize::MAX,
+            Some(Ok(n)) => n,
+            _ => default,
+        }
+    }
+
+    fn unreaped(&self) -> usize {
+        self.jobs
+            .iter()
+            .flat_map(|job| &job.processes)
+            .filter(|(_, status)| status.is_none())
+            .count()
+    }
+
+    fn runaway(&mut self) -> Option<Runaway> {
+        let now = Instant::now();
+        while self
+            .spawns
+            .recent
+            .front()
+            .is_some_and(|&t| now.duration_since(t) >= WINDOW)
+        {
+            self.spawns.recent.pop_front();
+        }
+        let forks = self.spawns.recent.len();
+        let max_forks = self.spawn_limit("VSSH_MAX_FORKS", DEFAULT_MAX_FORKS);
+        if forks >= max_forks {
+            return Some(Runaway {
+                reason: format!(
+                    "{} processes started in the last second (VSSH_MAX_FORKS={})",
+                    forks, max_forks
+                ),
+                culprit: None,
+                settles: false,
+            });
+        }
+        let max_jobs = self
+            .spawn_limit("VSSH_MAX_JOBS", DEFAULT_MAX_JOBS)
+            .saturating_add(self.spawns.allowance);
+        if self.unreaped() < max_jobs {
+            return None;
+        }
+        self.update_jobs();
+        let jobs = self.unreaped();
+        (jobs >= max_jobs).then(|| Runaway {
+            reason: format!(
+                "{} background processes still running (VSSH_MAX_JOBS={})",
+                jobs,
+                max_jobs - self.spawns.allowance
+            ),
+            culprit: self
+                .jobs
+                .iter()
+                .filter(|job| job.status().is_none())
+                .max_by_key(|job| job.id)
+                .map(|job| job.command.clone()),
+            settles: true,
+        })
+    }
+
+    fn confirm_spawning(&mut self) -> Result<bool> {
+        if !self.interactive
+            || self.subshell
+            || !unistd::isatty(libc::STDIN_FILENO).unwrap_or(false)
+        {
+            return Ok(false);
+        }
+        eprint!("{}: keep starting processes? [y/N] ", program());
+        let answer = builtins::read_line(libc::STDIN_FILENO, false)?;
+        let confirmed = answer.is_some_and(|(reply, _)| matches!(reply.trim(), "y" | "Y" | "yes"));
+        if confirmed {
+            self.spawns.recent.clear();
+            self.spawns.allowance = self.unreaped();
+        }
+        Ok(confirmed)
+    }
+}
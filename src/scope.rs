@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::ops::{Deref, DerefMut};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+
+use nix::sys::stat::{umask, Mode};
+use nix::unistd;
+
+use crate::bytes;
+use crate::error::{describe, Result};
+use crate::shell::Shell;
+use crate::vars::Variable;
+
+pub struct Scope<'a> {
+    shell: &'a mut Shell,
+    dir: Option<File>,
+    mask: Option<Mode>,
+    saved: Vec<(String, Option<Variable>)>,
+}
+
+impl<'a> Scope<'a> {
+    pub fn new(shell: &'a mut Shell) -> Self {
+        Scope {
+            shell,
+            dir: None,
+            mask: None,
+            saved: Vec::new(),
+        }
+    }
+
+    fn save(&mut self, name: &str) {
+        if self.saved.iter().all(|(saved, _)| saved != name) {
+            let variable = self.shell.vars.variable(name).cloned();
+            self.saved.push((name.to_string(), variable));
+        }
+    }
+
+    pub fn chdir(&mut self, dir: &str) -> std::result::Result<(), String> {
+        if self.dir.is_none() {
+            let current = File::open(".")
+                .map_err(|e| format!("cannot access current directory: {}", describe(&e)))?;
+            self.dir = Some(current);
+        }
+        unistd::chdir(Path::new(&bytes::to_os(dir)))
+            .map_err(|e| format!("{}: {}", dir, e.desc()))?;
+        self.save("PWD");
+        self.save("OLDPWD");
+        if let Some(old) = self.shell.vars.get("PWD").map(str::to_string) {
+            self.shell.vars.set("OLDPWD", old);
+        }
+        if let Ok(cwd) = std::env::current_dir() {
+            self.shell.vars.set("PWD", bytes::from_os(cwd.as_os_str()));
+        }
+        self.shell.update_directory_envs();
+        Ok(())
+    }
+
+    pub fn umask(&mut self, mask: Mode) {
+        let previous = umask(mask);
+        self.mask.get_or_insert(previous);
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        self.shell.restrict_variable(name)?;
+        self.shell.writable(name)?;
+        self.save(name);
+        self.shell.vars.set(name, value);
+        self.shell.vars.export(name);
+        Ok(())
+    }
+}
+
+impl Deref for Scope<'_> {
+    type Target = Shell;
+
+    fn deref(&self) -> &Shell {
+        self.shell
+    }
+}
+
+impl DerefMut for Scope<'_> {
+    fn deref_mut(&mut self) -> &mut Shell {
+        self.shell
+    }
+}
+
+impl Drop for Scope<'_> {
+    fn drop(&mut self) {
+        for (name, variable) in self.saved.drain(..).rev() {
+            self.shell.vars.restore(&name, variable);
+        }
+        if let Some(mask) = self.mask.take() {
+            umask(mask);
+        }
+        if let Some(dir) = self.dir.take() {
+            let _ = unistd::fchdir(dir.as_raw_fd());
+            self.shell.update_directory_envs();
+        }
+    }
+}
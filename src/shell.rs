@@ -0,0 +1,282 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::os::fd::OwnedFd;
+use std::os::unix::fs::PermissionsExt;
+use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+
+use crate::ast::Command;
+use crate::bytes;
+use crate::color;
+use crate::direnv::DirectoryEnv;
+use crate::error::Result;
+use crate::expand::Substitution;
+use crate::history::History;
+use crate::jobs::{Coprocess, Job};
+use crate::keymap::Keymap;
+use crate::options::Options;
+use crate::runaway::Spawns;
+use crate::startup;
+use crate::trace::Traced;
+use crate::vars::{Variable, Variables};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    Break(usize),
+    Continue(usize),
+    Return,
+    Exit,
+}
+
+/// Runs before every fork with the text of the command being started;
+/// an error is returned in place of forking. Lets embedders and tests
+/// inject process-creation failures.
+pub type ForkHook = Box<dyn FnMut(&str) -> Result<()>>;
+
+pub struct Shell {
+    pub vars: Variables,
+    pub name: String,
+    pub positional: Vec<String>,
+    pub last_status: i32,
+    pub last_signal: Option<Signal>,
+    pub prompt_status: Option<String>,
+    pub last_background: Option<Pid>,
+    pub jobs: Vec<Job>,
+    pub coproc: Option<Coprocess>,
+    pub history: History,
+    pub subst_status: Option<i32>,
+    pub substitutions: Vec<Substitution>,
+    pub subshell: bool,
+    pub interactive: bool,
+    pub login: bool,
+    pub restricted: bool,
+    pub embedded: bool,
+    pub attached: Vec<(i32, OwnedFd)>,
+    pub options: Options,
+    pub keymap: Keymap,
+    pub functions: HashMap<String, Rc<Command>>,
+    pub function_depth: usize,
+    pub loop_depth: usize,
+    pub locals: Vec<Vec<(String, Option<Variable>)>>,
+    pub source_depth: usize,
+    pub substitution_depth: usize,
+    pub control: Option<Control>,
+    pub lineno: usize,
+    pub columns: usize,
+    pub terminal_modes: Option<libc::termios>,
+    pub handling_not_found: bool,
+    pub audit_log: Option<String>,
+    pub audit_quiet: bool,
+    pub audit_disabled: bool,
+    /// The text and starting directory of the top-level command running
+    /// now, logged once it finishes or the shell exits.
+    pub audit_pending: Option<(String, String)>,
+    pub trace_file: Option<String>,
+    pub trace_disabled: bool,
+    pub trace_id: u64,
+    pub trace_pipeline: u64,
+    pub traced: Vec<Option<Traced>>,
+    pub directory_envs: Vec<DirectoryEnv>,
+    pub traps: BTreeMap<String, String>,
+    pub in_trap: bool,
+    pub condition_depth: usize,
+    pub spawns: Spawns,
+    pub fork_hook: Option<ForkHook>,
+    pub policy_off: bool,
+    hashed: BTreeMap<String, (String, usize)>,
+    hashed_path: String,
+    path_commands: Option<BTreeSet<String>>,
+    random: u32,
+    seconds: (Instant, i64),
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shell {
+    pub fn new() -> Self {
+        let mut vars = Variables::from_env();
+        startup::populate_identity(&mut vars);
+        Self::with_vars(vars)
+    }
+
+    pub fn with_vars(mut vars: Variables) -> Self {
+        if vars.get("PS2").is_none() {
+            vars.set("PS2", "> ");
+        }
+        let level = vars
+            .get("SHLVL")
+            .and_then(|l| l.trim().parse::<i64>().ok())
+            .unwrap_or(0);
+        vars.set("SHLVL", (level + 1).max(0).to_string());
+        vars.export("SHLVL");
+        let invocation = std::env::current_exe()
+            .map(|p| p.to_string_lossy().into_owned())
+            .or_else(|_| std::env::args().next().ok_or(()))
+            .unwrap_or_default();
+        vars.set("_", invocation);
+        color::configure(vars.get("VSSH_COLORS"), vars.get("NO_COLOR"));
+        Shell {
+            vars,
+            name: "vssh".to_string(),
+            positional: Vec::new(),
+            last_status: 0,
+            last_signal: None,
+            prompt_status: None,
+            last_background: None,
+            jobs: Vec::new(),
+            coproc: None,
+            history: History::default(),
+            subst_status: None,
+            substitutions: Vec::new(),
+            subshell: false,
+            interactive: false,
+            login: false,
+            restricted: false,
+            embedded: false,
+            attached: Vec::new(),
+            options: Options::default(),
+            keymap: Keymap::default(),
+            functions: HashMap::new(),
+            function_depth: 0,
+            loop_depth: 0,
+            locals: Vec::new(),
+            source_depth: 0,
+            substitution_depth: 0,
+            control: None,
+            lineno: 0,
+            columns: 80,
+            terminal_modes: None,
+            handling_not_found: false,
+            audit_log: None,
+            audit_quiet: false,
+            audit_disabled: false,
+            audit_pending: None,
+            trace_file: None,
+            trace_disabled: false,
+            trace_id: 0,
+            trace_pipeline: 0,
+            traced: Vec::new(),
+            directory_envs: Vec::new(),
+            traps: BTreeMap::new(),
+            in_trap: false,
+            condition_depth: 0,
+            spawns: Spawns::default(),
+            fork_hook: None,
+            policy_off: false,
+            hashed: BTreeMap::new(),
+            hashed_path: String::new(),
+            path_commands: None,
+            random: std::process::id() ^ epoch_seconds() as u32,
+            seconds: (Instant::now(), 0),
+        }
+    }
+
+    pub fn dynamic(&mut self, name: &str) -> Option<String> {
+        let value = match name {
+            "RANDOM" => {
+                self.random = self.random.wrapping_mul(1103515245).wrapping_add(12345);
+                ((self.random >> 16) & 0x7fff) as i64
+            }
+            "SECONDS" => self.seconds.1 + self.seconds.0.elapsed().as_secs() as i64,
+            "LINENO" => self.lineno as i64,
+            "EPOCHSECONDS" => epoch_seconds() as i64,
+            _ => return None,
+        };
+        Some(value.to_string())
+    }
+
+    pub fn command_hash(&mut self) -> &mut BTreeMap<String, (String, usize)> {
+        let search = self.vars.get("PATH").unwrap_or("");
+        if search != self.hashed_path {
+            self.hashed_path = search.to_string();
+            self.hashed.clear();
+            self.path_commands = None;
+        }
+        &mut self.hashed
+    }
+
+    pub fn rehash(&mut self) {
+        self.command_hash().clear();
+        self.path_commands = None;
+    }
+
+    pub fn path_commands(&mut self) -> &BTreeSet<String> {
+        self.command_hash();
+        let search = &self.hashed_path;
+        self.path_commands.get_or_insert_with(|| {
+            let mut names = BTreeSet::new();
+            for dir in search.split(':') {
+                let dir = if dir.is_empty() { "." } else { dir };
+                let Ok(entries) = fs::read_dir(bytes::to_os(dir)) else {
+                    continue;
+                };
+                for entry in entries.flatten() {
+                    let executable = fs::metadata(entry.path())
+                        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+                    if executable {
+                        names.insert(bytes::from_os(&entry.file_name()));
+                    }
+                }
+            }
+            names
+        })
+    }
+
+    pub fn assign_dynamic(&mut self, name: &str, value: &str) {
+        self.assign_colors(name, value);
+        if name == "PATH" {
+            self.rehash();
+        }
+        let Ok(n) = value.trim().parse::<i64>() else {
+            return;
+        };
+        match name {
+            "RANDOM" => self.random = n as u32,
+            "SECONDS" => self.seconds = (Instant::now(), n),
+            _ => {}
+        }
+    }
+
+    pub fn echo_input(&self, text: &str) {
+        if !self.options.get("verbose") {
+            return;
+        }
+        match text.ends_with('\n') {
+            true => eprint!("{}", text),
+            false => eprintln!("{}", text),
+        }
+    }
+}
+
+fn epoch_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_a_command_does_not_scan_path() {
+        let path = std::env::var("PATH").unwrap_or_default();
+        let mut shell = Shell::with_vars(Variables::from_pairs([("PATH".to_string(), path)]));
+        assert!(shell.path_commands.is_none());
+        assert_eq!(shell.run_line("sh -c 'exit 3'").unwrap(), 3);
+        assert!(shell.path_commands.is_none());
+        let hashed: Vec<&String> = shell.hashed.keys().collect();
+        assert_eq!(hashed, ["sh"]);
+        assert!(shell.path_commands().contains("sh"));
+        shell.run_line("PATH=/nonexistent").unwrap();
+        assert!(shell.path_commands.is_none());
+        assert!(shell.hashed.is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use nix::sys::signal::{
+    sigaction, sigprocmask, SaFlags, SigAction, SigHandler, SigSet, SigmaskHow, Signal,
+};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static RESIZED: AtomicBool = AtomicBool::new(false);
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static IGNORED: AtomicU64 = AtomicU64::new(0);
+static CAUGHT: AtomicU64 = AtomicU64::new(0);
+static PENDING: AtomicU64 = AtomicU64::new(0);
+static SAVED: Mutex<Vec<(Signal, SigAction)>> = Mutex::new(Vec::new());
+
+fn bit(number: libc::c_int) -> u64 {
+    1 << number
+}
+
+extern "C" fn on_sigint(number: libc::c_int) {
+    match CAUGHT.load(Ordering::SeqCst) & bit(number) {
+        0 => INTERRUPTED.store(true, Ordering::SeqCst),
+        _ => {
+            PENDING.fetch_or(bit(number), Ordering::SeqCst);
+        }
+    }
+}
+
+extern "C" fn on_sigwinch(number: libc::c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
+    if CAUGHT.load(Ordering::SeqCst) & bit(number) != 0 {
+        PENDING.fetch_or(bit(number), Ordering::SeqCst);
+    }
+}
+
+extern "C" fn on_trapped(number: libc::c_int) {
+    PENDING.fetch_or(bit(number), Ordering::SeqCst);
+}
+
+pub fn install() {
+    let action = SigAction::new(
+        SigHandler::Handler(on_sigint),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    let resize = SigAction::new(
+        SigHandler::Handler(on_sigwinch),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    let ignore = SigAction::new(SigHandler::SigIgn, SaFlags::empty(), SigSet::empty());
+    unsafe {
+        let _ = sigaction(Signal::SIGINT, &action);
+        let _ = sigaction(Signal::SIGWINCH, &resize);
+        let _ = sigaction(Signal::SIGQUIT, &ignore);
+    }
+    INSTALLED.store(true, Ordering::SeqCst);
+}
+
+pub const HANDLED: &[Signal] = &[
+    Signal::SIGINT,
+    Signal::SIGQUIT,
+    Signal::SIGTSTP,
+    Signal::SIGTTIN,
+    Signal::SIGTTOU,
+    Signal::SIGCHLD,
+    Signal::SIGWINCH,
+    Signal::SIGPIPE,
+];
+
+pub fn reset_child() {
+    let default = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+    unsafe {
+        for &signal in HANDLED {
+            let _ = sigaction(signal, &default);
+        }
+    }
+    let ignored = IGNORED.load(Ordering::SeqCst);
+    let caught = CAUGHT.swap(0, Ordering::SeqCst);
+    for number in 1..64 {
+        let disposition = match (ignored & bit(number), caught & bit(number)) {
+            (0, 0) => continue,
+            (0, _) => libc::SIG_DFL,
+            _ => libc::SIG_IGN,
+        };
+        unsafe {
+            libc::signal(number, disposition);
+        }
+    }
+    PENDING.store(0, Ordering::SeqCst);
+    let _ = sigprocmask(SigmaskHow::SIG_SETMASK, Some(&SigSet::empty()), None);
+    clear();
+}
+
+/// Records a trap with a command: the signal is then only noted as
+/// pending, and the shell runs the command between commands. The
+/// disposition it had before comes back when the trap is removed.
+pub fn set_caught(signal: Signal, caught: bool) -> nix::Result<()> {
+    let number = signal as libc::c_int;
+    let was = CAUGHT.load(Ordering::SeqCst) & bit(number) != 0;
+    if caught == was {
+        return Ok(());
+    }
+    // The shell's own SIGINT and SIGWINCH handlers check for a trap.
+    let own =
+        INSTALLED.load(Ordering::SeqCst) && matches!(signal, Signal::SIGINT | Signal::SIGWINCH);
+    let mut saved = SAVED.lock().unwrap_or_else(|e| e.into_inner());
+    if caught {
+        if !own {
+            let action = SigAction::new(
+                SigHandler::Handler(on_trapped),
+                SaFlags::SA_RESTART,
+                SigSet::empty(),
+            );
+            let old = unsafe { sigaction(signal, &action)? };
+            saved.push((signal, old));
+        }
+        CAUGHT.fetch_or(bit(number), Ordering::SeqCst);
+    } else {
+        CAUGHT.fetch_and(!bit(number), Ordering::SeqCst);
+        PENDING.fetch_and(!bit(number), Ordering::SeqCst);
+        if let Some(index) = saved.iter().position(|(s, _)| *s == signal) {
+            let (_, old) = saved.remove(index);
+            unsafe {
+                let _ = sigaction(signal, &old);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn caught(signal: Signal) -> bool {
+    CAUGHT.load(Ordering::SeqCst) & bit(signal as libc::c_int) != 0
+}
+
+pub fn raise_pending(signal: Signal) {
+    PENDING.fetch_or(bit(signal as libc::c_int), Ordering::SeqCst);
+}
+
+/// The trapped signals that arrived since the last call, lowest first.
+pub fn take_pending() -> Vec<Signal> {
+    let pending = PENDING.swap(0, Ordering::SeqCst);
+    (1..64)
+        .filter(|&number| pending & bit(number) != 0)
+        .filter_map(|number| Signal::try_from(number).ok())
+        .collect()
+}
+
+/// Records a `trap ''` ignore, which every child inherits. The shell
+/// ignores the signal itself too, except for the ones an interactive
+/// shell handles for its own line editing and job control.
+pub fn set_ignored(signal: Signal, ignored: bool) {
+    let bit = bit(signal as i32);
+    match ignored {
+        true => IGNORED.fetch_or(bit, Ordering::SeqCst),
+        false => IGNORED.fetch_and(!bit, Ordering::SeqCst),
+    };
+    if INSTALLED.load(Ordering::SeqCst) && HANDLED.contains(&signal) {
+        return;
+    }
+    let handler = match ignored {
+        true => SigHandler::SigIgn,
+        false => SigHandler::SigDfl,
+    };
+    unsafe {
+        let _ = sigaction(
+            signal,
+            &SigAction::new(handler, SaFlags::empty(), SigSet::empty()),
+        );
+    }
+}
+
+/// The signals a `posix_spawn` child resets to their defaults: all the
+/// handled ones except those a `trap ''` ignores, which the child keeps.
+/// None when one of those ignores is caught by the shell's own handler
+/// instead, since only a forked child can be told to ignore it.
+pub fn spawn_defaults() -> Option<Vec<Signal>> {
+    let ignored = IGNORED.load(Ordering::SeqCst);
+    let is_ignored = |signal: &Signal| ignored & bit(*signal as i32) != 0;
+    if INSTALLED.load(Ordering::SeqCst) && HANDLED.iter().any(is_ignored) {
+        return None;
+    }
+    Some(HANDLED.iter().copied().filter(|s| !is_ignored(s)).collect())
+}
+
+pub fn take_resized() -> bool {
+    RESIZED.swap(false, Ordering::SeqCst)
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+pub fn clear() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+pub fn number(name: &str) -> Option<i32> {
+    if let Ok(n) = name.parse::<i32>() {
+        return (n == 0 || Signal::try_from(n).is_ok()).then_some(n);
+    }
+    let upper = name.to_ascii_uppercase();
+    let full = match upper.starts_with("SIG") {
+        true => upper,
+        false => format!("SIG{}", upper),
+    };
+    full.parse::<Signal>().ok().map(|signal| signal as i32)
+}
+
+pub fn name(number: i32) -> Option<&'static str> {
+    Signal::try_from(number)
+        .ok()
+        .map(|signal| &signal.as_str()[3..])
+}
+
+/// The C library's description of `signal`, as job listings show it.
+pub fn describe(signal: Signal) -> String {
+    let text = unsafe { libc::strsignal(signal as i32) };
+    match text.is_null() {
+        true => signal.as_str().to_string(),
+        false => unsafe { std::ffi::CStr::from_ptr(text) }
+            .to_string_lossy()
+            .into_owned(),
+    }
+}
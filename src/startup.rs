@@ -0,0 +1,202 @@
+use std::env;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use nix::unistd::{self, User};
+
+use crate::ast::WordPart;
+use crate::bytes;
+use crate::error::{describe, program, report, warn};
+use crate::parser;
+use crate::shell::Shell;
+use crate::terminal;
+use crate::vars::Variables;
+use crate::word;
+
+const LOADING: &str = "VSSH_LOADING_ENV";
+
+fn fill(vars: &mut Variables, name: &str, value: impl FnOnce() -> Option<String>) {
+    if vars.get(name).is_some_and(|v| !v.is_empty()) {
+        return;
+    }
+    if let Some(value) = value() {
+        vars.set(name, value);
+        vars.export(name);
+    }
+}
+
+pub fn populate_identity(vars: &mut Variables) {
+    let user = User::from_uid(unistd::getuid()).ok().flatten();
+    fill(vars, "HOME", || {
+        user.as_ref().map(|u| u.dir.to_string_lossy().into_owned())
+    });
+    fill(vars, "USER", || user.as_ref().map(|u| u.name.clone()));
+    fill(vars, "LOGNAME", || user.as_ref().map(|u| u.name.clone()));
+    fill(vars, "HOSTNAME", || Some(terminal::hostname()));
+    fill(vars, "SHELL", || {
+        env::current_exe()
+            .ok()
+            .map(|p| p.to_string_lossy().into_owned())
+    });
+}
+
+fn parameters_only(parts: &[WordPart]) -> bool {
+    parts.iter().all(|part| match part {
+        WordPart::DoubleQuoted(inner) => parameters_only(inner),
+        WordPart::CommandSubst(_) | WordPart::ProcessSubst(..) | WordPart::Arith(_) => false,
+        _ => true,
+    })
+}
+
+impl Shell {
+    pub fn load_env_file(&mut self, name: &str) {
+        if self.vars.get(LOADING).is_some() {
+            return;
+        }
+        let Some(raw) = self.vars.get(name).filter(|v| !v.is_empty()) else {
+            return;
+        };
+        let raw = word::parse(raw);
+        if !parameters_only(&raw.parts) {
+            warn(format_args!(
+                "{}: warning: {}: only parameter expansion is performed",
+                program(),
+                name
+            ));
+            return;
+        }
+        let path = match self.expand_single(&raw) {
+            Ok(path) if !path.is_empty() => path,
+            Ok(_) => return,
+            Err(e) => {
+                warn(format_args!("{}: warning: {}: {}", program(), name, e));
+                return;
+            }
+        };
+        let src = match fs::read(bytes::to_os(&path)) {
+            Ok(src) => bytes::decode(&src),
+            Err(e) => {
+                warn(format_args!(
+                    "{}: warning: {}: {}",
+                    program(),
+                    path,
+                    describe(&e)
+                ));
+                return;
+            }
+        };
+        let list = match parser::parse(&src, 1) {
+            Ok(list) => list,
+            Err(e) => {
+                warn(format_args!("{}: warning: {}: {}", program(), path, e));
+                return;
+            }
+        };
+        let previous = self.vars.variable(LOADING).cloned();
+        self.vars.set(LOADING, "1");
+        self.vars.export(LOADING);
+        self.source_depth += 1;
+        if let Err(e) = self.run_list(&list) {
+            report(&e);
+        }
+        self.source_depth -= 1;
+        self.control = None;
+        self.vars.restore(LOADING, previous);
+    }
+}
+
+pub struct Profile {
+    enabled: bool,
+    started: Instant,
+    phases: Vec<(&'static str, Option<Duration>)>,
+}
+
+impl Profile {
+    pub fn new(enabled: bool) -> Self {
+        Profile {
+            enabled,
+            started: Instant::now(),
+            phases: Vec::new(),
+        }
+    }
+
+    pub fn time<T>(&mut self, phase: &'static str, run: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = run();
+        self.phases.push((phase, Some(start.elapsed())));
+        result
+    }
+
+    pub fn defer(&mut self, phase: &'static str) {
+        self.phases.push((phase, None));
+    }
+
+    pub fn report(&self) {
+        if !self.enabled {
+            return;
+        }
+        eprintln!("startup profile:");
+        for (phase, elapsed) in &self.phases {
+            match elapsed {
+                Some(elapsed) => eprintln!("  {:<18}{:>10.3?}", phase, elapsed),
+                None => eprintln!("  {:<18}{:>10}", phase, "deferred"),
+            }
+        }
+        eprintln!("  {:<18}{:>10.3?}", "total", self.started.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrubbed_environment_gets_identity() {
+        let user = User::from_uid(unistd::getuid()).unwrap().unwrap();
+        let exe = env::current_exe().unwrap();
+        let mut vars = Variables::default();
+        populate_identity(&mut vars);
+        let home = user.dir.to_string_lossy();
+        let exe = exe.to_string_lossy();
+        let hostname = terminal::hostname();
+        for (name, expected) in [
+            ("HOME", &*home),
+            ("USER", &user.name),
+            ("LOGNAME", &user.name),
+            ("HOSTNAME", &hostname),
+            ("SHELL", &*exe),
+        ] {
+            assert_eq!(vars.get(name), Some(expected), "{}", name);
+            assert_eq!(vars.exported().get(name), Some(&expected), "{}", name);
+        }
+        assert!(!hostname.is_empty());
+    }
+
+    #[test]
+    fn inherited_identity_is_kept() {
+        let inherited = [
+            ("HOME", "/elsewhere"),
+            ("USER", "someone"),
+            ("LOGNAME", "someone-else"),
+            ("HOSTNAME", "box"),
+            ("SHELL", "/bin/sh"),
+        ];
+        let mut vars =
+            Variables::from_pairs(inherited.map(|(k, v)| (k.to_string(), v.to_string())));
+        populate_identity(&mut vars);
+        for (name, value) in inherited {
+            assert_eq!(vars.get(name), Some(value), "{}", name);
+        }
+    }
+
+    #[test]
+    fn empty_identity_is_filled() {
+        let mut vars = Variables::from_pairs([
+            ("HOME".to_string(), String::new()),
+            ("USER".to_string(), String::new()),
+        ]);
+        populate_identity(&mut vars);
+        assert!(vars.get("HOME").is_some_and(|home| !home.is_empty()));
+        assert!(vars.get("USER").is_some_and(|user| !user.is_empty()));
+    }
+}
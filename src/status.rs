@@ -0,0 +1,127 @@
+use nix::sys::signal::{self, SigHandler, SigSet, SigmaskHow, Signal};
+use nix::sys::wait::WaitStatus;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status(i32);
+
+impl Status {
+    pub const SUCCESS: Status = Status(0);
+
+    pub fn new(code: i64) -> Status {
+        Status((code & 0xff) as i32)
+    }
+
+    pub fn from_signal(signal: Signal) -> Status {
+        Status(128 + signal as i32)
+    }
+
+    pub fn terminated(status: &WaitStatus) -> Option<Status> {
+        match *status {
+            WaitStatus::Exited(_, code) => Some(Status::new(code as i64)),
+            WaitStatus::Signaled(_, signal, _) => Some(Status::from_signal(signal)),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> i32 {
+        self.0
+    }
+
+    pub fn signal(self) -> Option<Signal> {
+        match self.0 {
+            129..=255 => Signal::try_from(self.0 - 128).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn exit(self, signaled: bool) -> ! {
+        if signaled && let Some(signal) = self.signal() {
+            unsafe {
+                let _ = signal::signal(signal, SigHandler::SigDfl);
+            }
+            let mut set = SigSet::empty();
+            set.add(signal);
+            let _ = signal::sigprocmask(SigmaskHow::SIG_UNBLOCK, Some(&set), None);
+            let _ = signal::raise(signal);
+        }
+        std::process::exit(self.0)
+    }
+}
+
+impl From<&WaitStatus> for Status {
+    fn from(status: &WaitStatus) -> Status {
+        match *status {
+            WaitStatus::Stopped(_, signal) => Status::from_signal(signal),
+            _ => Status::terminated(status).unwrap_or(Status::SUCCESS),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::unistd::Pid;
+
+    #[test]
+    fn codes_wrap_to_a_byte() {
+        for (code, expected) in [
+            (0, 0),
+            (1, 1),
+            (255, 255),
+            (256, 0),
+            (257, 1),
+            (-1, 255),
+            (-256, 0),
+            (-255, 1),
+            (1 << 40, 0),
+            (i64::MIN, 0),
+        ] {
+            assert_eq!(Status::new(code).code(), expected, "{}", code);
+        }
+    }
+
+    #[test]
+    fn wait_statuses() {
+        let pid = Pid::from_raw(1);
+        for (status, expected, terminated) in [
+            (WaitStatus::Exited(pid, 0), 0, true),
+            (WaitStatus::Exited(pid, 3), 3, true),
+            (WaitStatus::Exited(pid, 255), 255, true),
+            (WaitStatus::Signaled(pid, Signal::SIGINT, false), 130, true),
+            (WaitStatus::Signaled(pid, Signal::SIGKILL, false), 137, true),
+            (WaitStatus::Signaled(pid, Signal::SIGSEGV, true), 139, true),
+            (WaitStatus::Signaled(pid, Signal::SIGPIPE, false), 141, true),
+            (WaitStatus::Stopped(pid, Signal::SIGTSTP), 148, false),
+            (WaitStatus::Stopped(pid, Signal::SIGTTIN), 149, false),
+            (WaitStatus::Continued(pid), 0, false),
+            (WaitStatus::StillAlive, 0, false),
+        ] {
+            assert_eq!(Status::from(&status).code(), expected, "{:?}", status);
+            assert_eq!(
+                Status::terminated(&status).map(Status::code),
+                terminated.then_some(expected),
+                "{:?}",
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn signals_from_codes() {
+        for (code, signal) in [
+            (0, None),
+            (1, None),
+            (128, None),
+            (129, Some(Signal::SIGHUP)),
+            (130, Some(Signal::SIGINT)),
+            (143, Some(Signal::SIGTERM)),
+            (200, None),
+            (255, None),
+        ] {
+            assert_eq!(Status::new(code).signal(), signal, "{}", code);
+        }
+        for signal in [Signal::SIGINT, Signal::SIGQUIT, Signal::SIGTERM] {
+            assert_eq!(Status::from_signal(signal).signal(), Some(signal));
+        }
+    }
+}
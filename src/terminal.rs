@@ -0,0 +1,110 @@
+use std::env;
+use std::io::{self, Write};
+
+use nix::unistd::{self, User};
+
+use crate::shell::Shell;
+
+pub fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return "localhost".to_string();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+pub fn window_size() -> Option<(usize, usize)> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == -1 {
+        return None;
+    }
+    (size.ws_row > 0 && size.ws_col > 0).then_some((size.ws_row as usize, size.ws_col as usize))
+}
+
+pub fn set_title(title: &str) {
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    let mut stdout = io::stdout();
+    let _ = write!(stdout, "\x1b]0;{}\x07", title);
+    let _ = stdout.flush();
+}
+
+impl Shell {
+    pub fn save_terminal(&mut self) {
+        let mut modes = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut modes) } == 0 {
+            self.terminal_modes = Some(modes);
+        }
+    }
+
+    pub fn restore_terminal(&self) {
+        if let Some(modes) = &self.terminal_modes {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, modes);
+            }
+        }
+    }
+
+    pub fn update_window_size(&mut self) {
+        if !unistd::isatty(libc::STDOUT_FILENO).unwrap_or(false) {
+            return;
+        }
+        if let Some((lines, columns)) = window_size() {
+            self.columns = columns;
+            self.vars.set("LINES", lines.to_string());
+            self.vars.set("COLUMNS", columns.to_string());
+        }
+    }
+
+    pub fn title_enabled(&self) -> bool {
+        self.interactive
+            && unistd::isatty(libc::STDOUT_FILENO).unwrap_or(false)
+            && self.vars.get("TERM").is_some_and(|term| term != "dumb")
+            && self.vars.get("VSSH_SET_TITLE") != Some("0")
+    }
+
+    pub fn idle_title(&self) -> String {
+        let user = match self.vars.get("USER") {
+            Some(user) => user.to_string(),
+            None => User::from_uid(unistd::getuid())
+                .ok()
+                .flatten()
+                .map_or_else(|| "?".to_string(), |u| u.name),
+        };
+        format!("{}@{}: {}", user, hostname(), self.short_cwd())
+    }
+
+    pub fn working_dir(&self) -> (String, bool) {
+        match env::current_dir() {
+            Ok(cwd) => (cwd.to_string_lossy().into_owned(), true),
+            Err(_) => (self.vars.get("PWD").unwrap_or("?").to_string(), false),
+        }
+    }
+
+    pub fn display_cwd(&self) -> String {
+        match self.working_dir() {
+            (cwd, true) => cwd,
+            (cwd, false) => format!("{} (deleted)", cwd),
+        }
+    }
+
+    pub fn short_cwd(&self) -> String {
+        let (cwd, exists) = self.working_dir();
+        let short = match self.vars.get("HOME").filter(|h| !h.is_empty() && *h != "/") {
+            Some(home) if cwd == home => "~".to_string(),
+            Some(home) if cwd.starts_with(&format!("{}/", home)) => {
+                format!("~{}", &cwd[home.len()..])
+            }
+            _ => cwd,
+        };
+        match exists {
+            true => short,
+            false => format!("{} (deleted)", short),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::ast::RedirectKind;
+use crate::bytes;
+use crate::error::{describe, program, report};
+use crate::shell::Shell;
+
+pub struct Traced {
+    pub id: u64,
+    pub started: Instant,
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn operator(kind: RedirectKind) -> &'static str {
+    match kind {
+        RedirectKind::Input => "<",
+        RedirectKind::Output => ">",
+        RedirectKind::Append => ">>",
+        RedirectKind::DupInput => "<&",
+        RedirectKind::DupOutput => ">&",
+        RedirectKind::OutputAll => "&>",
+    }
+}
+
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{}.{:03}", now.as_secs(), now.subsec_millis())
+}
+
+impl Shell {
+    fn trace_path(&self) -> Option<String> {
+        if self.trace_disabled {
+            return None;
+        }
+        self.trace_file
+            .clone()
+            .or_else(|| self.vars.get("VSSH_TRACE_FILE").map(str::to_string))
+            .filter(|path| !path.is_empty())
+    }
+
+    pub fn next_pipeline(&mut self) -> u64 {
+        self.trace_pipeline += 1;
+        self.trace_pipeline
+    }
+
+    pub fn trace_start(
+        &mut self,
+        args: &[String],
+        redirects: &[(i32, RedirectKind, String)],
+        background: bool,
+        pipeline: Option<u64>,
+    ) -> Option<Traced> {
+        let path = self.trace_path()?;
+        self.trace_id += 1;
+        let id = self.trace_id;
+        let pipeline = pipeline.unwrap_or_else(|| self.next_pipeline());
+        let cwd = env::current_dir()
+            .map(|p| bytes::from_os(p.as_os_str()))
+            .unwrap_or_default();
+        let argv: Vec<String> = args.iter().map(|arg| json_string(arg)).collect();
+        let redirects: Vec<String> = redirects
+            .iter()
+            .map(|(fd, kind, target)| {
+                format!(
+                    "{{\"fd\":{},\"op\":{},\"target\":{}}}",
+                    fd,
+                    json_string(operator(*kind)),
+                    json_string(target)
+                )
+            })
+            .collect();
+        let record = format!(
+            "{{\"event\":\"start\",\"id\":{},\"pid\":{},\"time\":{},\"cwd\":{},\"argv\":[{}],\"redirects\":[{}],\"background\":{},\"pipeline\":{}}}\n",
+            id,
+            std::process::id(),
+            timestamp(),
+            json_string(&cwd),
+            argv.join(","),
+            redirects.join(","),
+            background,
+            pipeline
+        );
+        self.trace_write(&path, &record);
+        Some(Traced {
+            id,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn trace_end(&mut self, traced: Option<Traced>, status: i32) {
+        let (Some(traced), Some(path)) = (traced, self.trace_path()) else {
+            return;
+        };
+        let record = format!(
+            "{{\"event\":\"end\",\"id\":{},\"pid\":{},\"time\":{},\"status\":{},\"duration\":{:.6}}}\n",
+            traced.id,
+            std::process::id(),
+            timestamp(),
+            status,
+            traced.started.elapsed().as_secs_f64()
+        );
+        self.trace_write(&path, &record);
+    }
+
+    fn trace_write(&mut self, path: &str, record: &str) {
+        let result = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(bytes::to_os(path))
+            .and_then(|mut file| file.write_all(&bytes::encode(record)));
+        if let Err(e) = result {
+            report(format_args!(
+                "{}: trace file: {}: {}; tracing disabled",
+                program(),
+                path,
+                describe(&e)
+            ));
+            self.trace_disabled = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_are_escaped() {
+        for (text, json) in [
+            ("plain", "\"plain\""),
+            ("a \"b\"", "\"a \\\"b\\\"\""),
+            ("back\\slash", "\"back\\\\slash\""),
+            ("line\nfeed\r\t", "\"line\\nfeed\\r\\t\""),
+            ("\x01\x1b", "\"\\u0001\\u001b\""),
+            ("é日", "\"é日\""),
+        ] {
+            assert_eq!(json_string(text), json, "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use nix::sys::signal::Signal;
+
+use crate::error::{program, report};
+use crate::parser;
+use crate::shell::Shell;
+use crate::signals;
+
+pub const TRAPS: &[&str] = &["DEBUG", "ERR", "EXIT"];
+
+pub fn lookup(name: &str) -> Option<&'static str> {
+    let name = match name {
+        "0" => "EXIT",
+        name => name,
+    };
+    TRAPS.iter().copied().find(|trap| *trap == name)
+}
+
+pub fn signal(name: &str) -> Option<Signal> {
+    signals::number(name)
+        .filter(|&number| number != 0)
+        .and_then(|number| Signal::try_from(number).ok())
+}
+
+/// The name a trap is stored under: `EXIT` for `0`, `INT` for `SIGINT` or `2`.
+pub fn key(name: &str) -> Option<String> {
+    lookup(name)
+        .map(str::to_string)
+        .or_else(|| signal(name).map(|signal| signal.as_str()[3..].to_string()))
+}
+
+impl Shell {
+    pub fn run_trap(&mut self, name: &str, status: i32) {
+        if self.in_trap || self.subshell {
+            return;
+        }
+        let Some(handler) = self.traps.get(name).cloned() else {
+            return;
+        };
+        let list = match parser::parse(&handler, self.lineno) {
+            Ok(list) => list,
+            Err(e) => {
+                report(format_args!("{}: trap: {}", program(), e));
+                return;
+            }
+        };
+        self.in_trap = true;
+        self.last_status = status;
+        if let Err(e) = self.run_list(&list) {
+            report(&e);
+        }
+        self.in_trap = false;
+        if self.control.is_none() {
+            self.last_status = status;
+        }
+    }
+
+    /// Runs the EXIT trap as the shell is about to exit; an `exit` inside
+    /// the handler finds `in_trap` set and does not run it again.
+    pub fn run_exit_trap(&mut self) {
+        if self.subshell || !self.traps.contains_key("EXIT") {
+            return;
+        }
+        let status = self.last_status;
+        self.run_trap("EXIT", status);
+        self.traps.remove("EXIT");
+    }
+
+    /// Runs the handlers of trapped signals that arrived since the last
+    /// check. A handler's own signals wait until it has finished.
+    pub fn run_pending_traps(&mut self) {
+        if self.in_trap || self.subshell {
+            return;
+        }
+        for signal in signals::take_pending() {
+            let status = self.last_status;
+            self.run_trap(&signal.as_str()[3..], status);
+        }
+    }
+
+    pub fn debug_trap(&mut self, text: &str) {
+        if self.in_trap {
+            return;
+        }
+        self.vars.set("BASH_COMMAND", text.to_string());
+        self.run_trap("DEBUG", self.last_status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_name_traps_and_signals() {
+        assert_eq!(key("EXIT").as_deref(), Some("EXIT"));
+        assert_eq!(key("0").as_deref(), Some("EXIT"));
+        assert_eq!(key("ERR").as_deref(), Some("ERR"));
+        assert_eq!(key("INT").as_deref(), Some("INT"));
+        assert_eq!(key("SIGINT").as_deref(), Some("INT"));
+        assert_eq!(key("2").as_deref(), Some("INT"));
+        assert_eq!(key("term").as_deref(), Some("TERM"));
+        assert_eq!(key("BOGUS"), None);
+        assert_eq!(key("99"), None);
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::ffi::CString;
+
+use crate::bytes;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Scalar(String),
+    Array(BTreeMap<usize, String>),
+}
+
+impl Value {
+    pub fn scalar(&self) -> Option<&str> {
+        match self {
+            Value::Scalar(s) => Some(s),
+            Value::Array(elements) => elements.get(&0).map(String::as_str),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub value: Value,
+    pub exported: bool,
+    pub readonly: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    map: HashMap<String, Variable>,
+}
+
+impl Variables {
+    pub fn from_env() -> Self {
+        let mut vars = Variables::default();
+        for (name, value) in env::vars_os() {
+            let (Ok(name), value) = (name.into_string(), bytes::from_os(&value)) else {
+                continue;
+            };
+            vars.map.insert(
+                name,
+                Variable {
+                    value: Value::Scalar(value),
+                    exported: true,
+                    readonly: false,
+                },
+            );
+        }
+        vars
+    }
+
+    pub fn from_pairs(pairs: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut vars = Variables::default();
+        for (name, value) in pairs {
+            vars.map.insert(
+                name,
+                Variable {
+                    value: Value::Scalar(value),
+                    exported: true,
+                    readonly: false,
+                },
+            );
+        }
+        vars
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.map.get(name).and_then(|v| v.value.scalar())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.map.keys()
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Variable> {
+        self.map.get(name)
+    }
+
+    pub fn restore(&mut self, name: &str, variable: Option<Variable>) {
+        match variable {
+            Some(variable) => {
+                self.map.insert(name.to_string(), variable);
+            }
+            None => {
+                self.map.remove(name);
+            }
+        }
+    }
+
+    fn entry(&mut self, name: &str) -> &mut Variable {
+        self.map.entry(name.to_string()).or_insert(Variable {
+            value: Value::Scalar(String::new()),
+            exported: false,
+            readonly: false,
+        })
+    }
+
+    pub fn set(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        let var = self.entry(name);
+        match &mut var.value {
+            Value::Array(elements) => {
+                elements.insert(0, value);
+            }
+            scalar => *scalar = Value::Scalar(value),
+        }
+    }
+
+    pub fn export(&mut self, name: &str) {
+        self.entry(name).exported = true;
+    }
+
+    pub fn set_readonly(&mut self, name: &str) {
+        self.entry(name).readonly = true;
+    }
+
+    pub fn is_readonly(&self, name: &str) -> bool {
+        self.map.get(name).is_some_and(|v| v.readonly)
+    }
+
+    pub fn readonly(&self) -> BTreeMap<&str, &Value> {
+        self.map
+            .iter()
+            .filter(|(_, v)| v.readonly)
+            .map(|(k, v)| (k.as_str(), &v.value))
+            .collect()
+    }
+
+    pub fn set_array(&mut self, name: &str, values: Vec<String>) {
+        self.entry(name).value = Value::Array(values.into_iter().enumerate().collect());
+    }
+
+    pub fn set_element(&mut self, name: &str, index: usize, value: impl Into<String>) {
+        let var = self.entry(name);
+        if let Value::Scalar(s) = &var.value {
+            let mut elements = BTreeMap::new();
+            if !s.is_empty() {
+                elements.insert(0, s.clone());
+            }
+            var.value = Value::Array(elements);
+        }
+        if let Value::Array(elements) = &mut var.value {
+            elements.insert(index, value.into());
+        }
+    }
+
+    pub fn elements(&self, name: &str) -> Vec<(usize, String)> {
+        match self.map.get(name).map(|v| &v.value) {
+            Some(Value::Array(elements)) => elements.iter().map(|(i, v)| (*i, v.clone())).collect(),
+            Some(Value::Scalar(s)) => vec![(0, s.clone())],
+            None => Vec::new(),
+        }
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        self.map.remove(name);
+    }
+
+    pub fn unset_element(&mut self, name: &str, index: usize) {
+        match self.map.get_mut(name).map(|v| &mut v.value) {
+            Some(Value::Array(elements)) => {
+                elements.remove(&index);
+            }
+            Some(Value::Scalar(_)) if index == 0 => {
+                self.map.remove(name);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn exported(&self) -> BTreeMap<&str, &str> {
+        self.map
+            .iter()
+            .filter(|(_, v)| v.exported)
+            .filter_map(|(k, v)| match &v.value {
+                Value::Scalar(s) => Some((k.as_str(), s.as_str())),
+                Value::Array(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn environ(&self, overrides: &[(String, String)]) -> Vec<CString> {
+        let mut env = self.exported();
+        for (name, value) in overrides {
+            env.insert(name, value);
+        }
+        env.into_iter()
+            .filter_map(|(k, v)| bytes::cstring(&format!("{}={}", k, v)).ok())
+            .collect()
+    }
+}
@@ -0,0 +1,285 @@
+pub const HIDE_START: char = '\x01';
+pub const HIDE_END: char = '\x02';
+
+const ZERO: &[(u32, u32)] = &[
+    (0x0300, 0x036f),
+    (0x0483, 0x0489),
+    (0x0591, 0x05bd),
+    (0x05bf, 0x05bf),
+    (0x05c1, 0x05c2),
+    (0x05c4, 0x05c5),
+    (0x05c7, 0x05c7),
+    (0x0610, 0x061a),
+    (0x064b, 0x065f),
+    (0x0670, 0x0670),
+    (0x06d6, 0x06dc),
+    (0x06df, 0x06e4),
+    (0x06e7, 0x06e8),
+    (0x06ea, 0x06ed),
+    (0x0711, 0x0711),
+    (0x0730, 0x074a),
+    (0x07a6, 0x07b0),
+    (0x07eb, 0x07f3),
+    (0x0816, 0x082d),
+    (0x0859, 0x085b),
+    (0x08d3, 0x0902),
+    (0x093a, 0x093a),
+    (0x093c, 0x093c),
+    (0x0941, 0x0948),
+    (0x094d, 0x094d),
+    (0x0951, 0x0957),
+    (0x0962, 0x0963),
+    (0x0981, 0x0981),
+    (0x09bc, 0x09bc),
+    (0x09c1, 0x09c4),
+    (0x09cd, 0x09cd),
+    (0x0a01, 0x0a02),
+    (0x0a3c, 0x0a3c),
+    (0x0a41, 0x0a51),
+    (0x0a81, 0x0a82),
+    (0x0abc, 0x0abc),
+    (0x0ac1, 0x0ac8),
+    (0x0acd, 0x0acd),
+    (0x0b01, 0x0b01),
+    (0x0b3c, 0x0b3c),
+    (0x0bcd, 0x0bcd),
+    (0x0c3e, 0x0c40),
+    (0x0c46, 0x0c56),
+    (0x0ccc, 0x0ccd),
+    (0x0d41, 0x0d44),
+    (0x0d4d, 0x0d4d),
+    (0x0e31, 0x0e31),
+    (0x0e34, 0x0e3a),
+    (0x0e47, 0x0e4e),
+    (0x0eb1, 0x0eb1),
+    (0x0eb4, 0x0ebc),
+    (0x0ec8, 0x0ecd),
+    (0x0f18, 0x0f19),
+    (0x0f71, 0x0f7e),
+    (0x0f80, 0x0f84),
+    (0x102d, 0x1030),
+    (0x1032, 0x1037),
+    (0x1160, 0x11ff),
+    (0x135d, 0x135f),
+    (0x1712, 0x1714),
+    (0x17b4, 0x17b5),
+    (0x17b7, 0x17bd),
+    (0x17c6, 0x17c6),
+    (0x17c9, 0x17d3),
+    (0x180b, 0x180f),
+    (0x1ab0, 0x1aff),
+    (0x1b00, 0x1b03),
+    (0x1dc0, 0x1dff),
+    (0x200b, 0x200f),
+    (0x202a, 0x202e),
+    (0x2060, 0x2064),
+    (0x20d0, 0x20ff),
+    (0x2cef, 0x2cf1),
+    (0x2de0, 0x2dff),
+    (0x302a, 0x302d),
+    (0x3099, 0x309a),
+    (0xa66f, 0xa672),
+    (0xa674, 0xa67d),
+    (0xa69e, 0xa69f),
+    (0xa6f0, 0xa6f1),
+    (0xa8e0, 0xa8f1),
+    (0xfb1e, 0xfb1e),
+    (0xfe00, 0xfe0f),
+    (0xfe20, 0xfe2f),
+    (0xfeff, 0xfeff),
+    (0x1d167, 0x1d169),
+    (0x1d173, 0x1d182),
+    (0x1f3fb, 0x1f3ff),
+    (0xe0000, 0xe0fff),
+];
+
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115f),
+    (0x231a, 0x231b),
+    (0x2329, 0x232a),
+    (0x23e9, 0x23ec),
+    (0x23f0, 0x23f0),
+    (0x23f3, 0x23f3),
+    (0x25fd, 0x25fe),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267f, 0x267f),
+    (0x2693, 0x2693),
+    (0x26a1, 0x26a1),
+    (0x26aa, 0x26ab),
+    (0x26bd, 0x26be),
+    (0x26c4, 0x26c5),
+    (0x26ce, 0x26ce),
+    (0x26d4, 0x26d4),
+    (0x26ea, 0x26ea),
+    (0x26f2, 0x26f3),
+    (0x26f5, 0x26f5),
+    (0x26fa, 0x26fa),
+    (0x26fd, 0x26fd),
+    (0x2705, 0x2705),
+    (0x270a, 0x270b),
+    (0x2728, 0x2728),
+    (0x274c, 0x274c),
+    (0x274e, 0x274e),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27b0, 0x27b0),
+    (0x27bf, 0x27bf),
+    (0x2b1b, 0x2b1c),
+    (0x2b50, 0x2b50),
+    (0x2b55, 0x2b55),
+    (0x2e80, 0x303e),
+    (0x3041, 0x33ff),
+    (0x3400, 0x4dbf),
+    (0x4e00, 0x9fff),
+    (0xa000, 0xa4cf),
+    (0xa960, 0xa97f),
+    (0xac00, 0xd7a3),
+    (0xf900, 0xfaff),
+    (0xfe10, 0xfe19),
+    (0xfe30, 0xfe6f),
+    (0xff00, 0xff60),
+    (0xffe0, 0xffe6),
+    (0x16fe0, 0x16fe4),
+    (0x17000, 0x18aff),
+    (0x1b000, 0x1b2ff),
+    (0x1f004, 0x1f004),
+    (0x1f0cf, 0x1f0cf),
+    (0x1f18e, 0x1f18e),
+    (0x1f191, 0x1f19a),
+    (0x1f200, 0x1f251),
+    (0x1f260, 0x1f265),
+    (0x1f300, 0x1f320),
+    (0x1f32d, 0x1f335),
+    (0x1f337, 0x1f37c),
+    (0x1f37e, 0x1f393),
+    (0x1f3a0, 0x1f3ca),
+    (0x1f3cf, 0x1f3d3),
+    (0x1f3e0, 0x1f3f0),
+    (0x1f3f4, 0x1f3f4),
+    (0x1f3f8, 0x1f43e),
+    (0x1f440, 0x1f440),
+    (0x1f442, 0x1f4fc),
+    (0x1f4ff, 0x1f53d),
+    (0x1f54b, 0x1f54e),
+    (0x1f550, 0x1f567),
+    (0x1f57a, 0x1f57a),
+    (0x1f595, 0x1f596),
+    (0x1f5a4, 0x1f5a4),
+    (0x1f5fb, 0x1f64f),
+    (0x1f680, 0x1f6c5),
+    (0x1f6cc, 0x1f6cc),
+    (0x1f6d0, 0x1f6d2),
+    (0x1f6d5, 0x1f6d7),
+    (0x1f6dc, 0x1f6df),
+    (0x1f6eb, 0x1f6ec),
+    (0x1f6f4, 0x1f6fc),
+    (0x1f7e0, 0x1f7eb),
+    (0x1f7f0, 0x1f7f0),
+    (0x1f90c, 0x1f93a),
+    (0x1f93c, 0x1f945),
+    (0x1f947, 0x1f9ff),
+    (0x1fa70, 0x1faff),
+    (0x20000, 0x2fffd),
+    (0x30000, 0x3fffd),
+];
+
+fn within(table: &[(u32, u32)], c: u32) -> bool {
+    table
+        .binary_search_by(|&(start, end)| {
+            if end < c {
+                std::cmp::Ordering::Less
+            } else if start > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+pub fn char_width(c: char) -> usize {
+    let code = c as u32;
+    if code < 0x300 {
+        return 1;
+    }
+    if within(ZERO, code) {
+        0
+    } else if within(WIDE, code) {
+        2
+    } else {
+        1
+    }
+}
+
+pub fn visible(text: &str) -> impl Iterator<Item = char> + '_ {
+    let mut hidden = false;
+    text.chars().filter(move |&c| match c {
+        HIDE_START => {
+            hidden = true;
+            false
+        }
+        HIDE_END => {
+            hidden = false;
+            false
+        }
+        _ => !hidden,
+    })
+}
+
+pub fn str_width(text: &str) -> usize {
+    visible(text).map(char_width).sum()
+}
+
+pub fn strip_markers(text: &str) -> String {
+    text.chars()
+        .filter(|&c| c != HIDE_START && c != HIDE_END)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn character_widths() {
+        for (c, width) in [
+            ('a', 1),
+            ('é', 1),
+            ('\u{301}', 0),
+            ('\u{200d}', 0),
+            ('日', 2),
+            ('語', 2),
+            ('ｱ', 1),
+            ('한', 2),
+            ('😀', 2),
+            ('🦀', 2),
+            ('\u{20000}', 2),
+        ] {
+            assert_eq!(char_width(c), width, "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn string_widths() {
+        for (text, width) in [
+            ("", 0),
+            ("plain", 5),
+            ("日本語", 6),
+            ("e\u{301}", 1),
+            ("cafe\u{301} 日本語 😀", 14),
+            ("\x01\x1b[1;32m\x02日本\x01\x1b[0m\x02$ ", 6),
+        ] {
+            assert_eq!(str_width(text), width, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn markers_hide_their_region() {
+        let prompt = "\x01\x1b[1m\x02~/日本語\x01\x1b[0m\x02$ ";
+        assert_eq!(visible(prompt).collect::<String>(), "~/日本語$ ");
+        assert_eq!(strip_markers(prompt), "\x1b[1m~/日本語\x1b[0m$ ");
+        assert_eq!(visible("\x01unterminated").count(), 0);
+    }
+}
@@ -0,0 +1,461 @@
+use crate::ast::{Word, WordPart};
+use crate::bytes;
+
+struct WordParser {
+    chars: Vec<char>,
+    pos: usize,
+    equals: Option<usize>,
+    colons: bool,
+}
+
+pub fn parse(raw: &str) -> Word {
+    let equals = raw
+        .split_once('=')
+        .filter(|(name, _)| is_name(name))
+        .map(|(name, _)| name.chars().count());
+    parse_with(raw, equals, equals.is_some())
+}
+
+pub fn parse_value(raw: &str) -> Word {
+    parse_with(raw, None, true)
+}
+
+fn parse_with(raw: &str, equals: Option<usize>, colons: bool) -> Word {
+    let mut parser = WordParser {
+        chars: raw.chars().collect(),
+        pos: 0,
+        equals,
+        colons,
+    };
+    let mut parts = Vec::new();
+    if parser.peek() == Some('~')
+        && let Some(user) = parser.tilde_prefix()
+    {
+        parts.push(WordPart::Tilde(user));
+    }
+    parser.parse_parts(false, &mut parts);
+    Word { parts }
+}
+
+pub fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Quotes `value` so the shell reads it back as the same word, using
+/// single quotes only when something in it is special.
+pub fn quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "%+,-./:=@_^".contains(c) || !c.is_ascii());
+    match plain {
+        true => value.to_string(),
+        false => format!("'{}'", value.replace('\'', "'\\''")),
+    }
+}
+
+pub fn ansi_c(body: &str) -> Result<String, String> {
+    let mut out = Vec::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.extend(bytes::encode(c.encode_utf8(&mut [0; 4])));
+            continue;
+        }
+        let Some(escape) = chars.next() else {
+            out.push(b'\\');
+            break;
+        };
+        match escape {
+            'a' => out.push(0x07),
+            'b' => out.push(0x08),
+            'e' | 'E' => out.push(0x1b),
+            'f' => out.push(0x0c),
+            'n' => out.push(b'\n'),
+            'r' => out.push(b'\r'),
+            't' => out.push(b'\t'),
+            'v' => out.push(0x0b),
+            '\\' | '\'' | '"' | '?' => out.push(escape as u8),
+            'c' => match chars.next() {
+                Some(c) if c.is_ascii() => out.push(c as u8 & 0x1f),
+                _ => return Err("`\\c': missing control character".to_string()),
+            },
+            '0'..='7' => {
+                let mut value = escape.to_digit(8).unwrap_or(0);
+                for _ in 0..2 {
+                    let Some(d) = chars.peek().and_then(|c| c.to_digit(8)) else {
+                        break;
+                    };
+                    value = value * 8 + d;
+                    chars.next();
+                }
+                out.push(value as u8);
+            }
+            'x' | 'u' | 'U' => {
+                let max = match escape {
+                    'x' => 2,
+                    'u' => 4,
+                    _ => 8,
+                };
+                let mut digits = String::new();
+                while digits.len() < max
+                    && let Some(&c) = chars.peek()
+                    && c.is_ascii_hexdigit()
+                {
+                    digits.push(c);
+                    chars.next();
+                }
+                let Ok(value) = u32::from_str_radix(&digits, 16) else {
+                    return Err(format!("`\\{}': missing hexadecimal digits", escape));
+                };
+                if escape == 'x' {
+                    out.push(value as u8);
+                    continue;
+                }
+                let Some(c) = char::from_u32(value) else {
+                    return Err(format!(
+                        "`\\{}{}': invalid Unicode code point",
+                        escape, digits
+                    ));
+                };
+                out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            other => {
+                out.push(b'\\');
+                out.extend(bytes::encode(other.encode_utf8(&mut [0; 4])));
+            }
+        }
+    }
+    Ok(bytes::decode(&out))
+}
+
+fn arith_body(body: &str) -> Option<&str> {
+    let inner = body.strip_prefix('(')?.strip_suffix(')')?;
+    let mut depth = 0;
+    for c in inner.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return None,
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    (depth == 0).then_some(inner)
+}
+
+fn push_text(parts: &mut Vec<WordPart>, text: &str, quoted: bool) {
+    match parts.last_mut() {
+        Some(WordPart::Literal(s)) if !quoted => s.push_str(text),
+        Some(WordPart::Quoted(s)) if quoted => s.push_str(text),
+        _ if quoted => parts.push(WordPart::Quoted(text.to_string())),
+        _ => parts.push(WordPart::Literal(text.to_string())),
+    }
+}
+
+impl WordParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn tilde_prefix(&mut self) -> Option<String> {
+        let end = self.chars[self.pos..]
+            .iter()
+            .position(|&c| c == '/' || (c == ':' && self.colons))
+            .map_or(self.chars.len(), |i| self.pos + i);
+        let user: String = self.chars[self.pos + 1..end].iter().collect();
+        if user
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        {
+            self.pos = end;
+            Some(user)
+        } else {
+            None
+        }
+    }
+
+    fn parse_parts(&mut self, in_double: bool, parts: &mut Vec<WordPart>) {
+        while let Some(c) = self.peek() {
+            match c {
+                '"' if in_double => {
+                    self.bump();
+                    return;
+                }
+                '"' => {
+                    self.bump();
+                    let mut inner = Vec::new();
+                    self.parse_parts(true, &mut inner);
+                    parts.push(WordPart::DoubleQuoted(inner));
+                }
+                '\'' if !in_double => {
+                    self.bump();
+                    let mut text = String::new();
+                    while let Some(c) = self.bump() {
+                        if c == '\'' {
+                            break;
+                        }
+                        text.push(c);
+                    }
+                    parts.push(WordPart::Quoted(text));
+                }
+                '\\' => {
+                    self.bump();
+                    match self.bump() {
+                        Some(c) if !in_double || matches!(c, '$' | '`' | '"' | '\\') => {
+                            push_text(parts, &c.to_string(), true)
+                        }
+                        Some(c) => push_text(parts, &format!("\\{}", c), true),
+                        None => push_text(parts, "\\", in_double),
+                    }
+                }
+                '`' => {
+                    self.bump();
+                    parts.push(WordPart::CommandSubst(self.backtick_body()));
+                }
+                '<' | '>' if !in_double && self.chars.get(self.pos + 1) == Some(&'(') => {
+                    self.pos += 2;
+                    let body = self.balanced('(', ')');
+                    parts.push(WordPart::ProcessSubst(c == '>', body));
+                }
+                '$' if !in_double && self.chars.get(self.pos + 1) == Some(&'\'') => {
+                    self.pos += 2;
+                    let body = self.ansi_body();
+                    parts.push(WordPart::Quoted(ansi_c(&body).unwrap_or(body)));
+                }
+                '$' => {
+                    self.bump();
+                    self.dollar(parts, in_double);
+                }
+                _ => {
+                    self.bump();
+                    push_text(parts, &c.to_string(), in_double);
+                    let separator = (c == '=' && self.equals == Some(self.pos - 1))
+                        || (c == ':' && self.colons);
+                    if separator
+                        && !in_double
+                        && self.peek() == Some('~')
+                        && let Some(user) = self.tilde_prefix()
+                    {
+                        parts.push(WordPart::Tilde(user));
+                    }
+                }
+            }
+        }
+    }
+
+    fn ansi_body(&mut self) -> String {
+        let mut body = String::new();
+        while let Some(c) = self.bump() {
+            match c {
+                '\'' => break,
+                '\\' => {
+                    body.push(c);
+                    if let Some(c) = self.bump() {
+                        body.push(c);
+                    }
+                }
+                _ => body.push(c),
+            }
+        }
+        body
+    }
+
+    fn backtick_body(&mut self) -> String {
+        let mut body = String::new();
+        while let Some(c) = self.bump() {
+            match c {
+                '`' => break,
+                '\\' => match self.bump() {
+                    Some(c @ ('$' | '`' | '\\')) => body.push(c),
+                    Some(c) => {
+                        body.push('\\');
+                        body.push(c);
+                    }
+                    None => body.push('\\'),
+                },
+                _ => body.push(c),
+            }
+        }
+        body
+    }
+
+    fn dollar(&mut self, parts: &mut Vec<WordPart>, in_double: bool) {
+        match self.peek() {
+            Some('{') => {
+                self.bump();
+                let body = self.balanced('{', '}');
+                parts.push(WordPart::Param(body));
+            }
+            Some('(') => {
+                self.bump();
+                let body = self.balanced('(', ')');
+                match arith_body(&body) {
+                    Some(expr) => parts.push(WordPart::Arith(expr.to_string())),
+                    None => parts.push(WordPart::CommandSubst(body)),
+                }
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = self.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    name.push(c);
+                    self.bump();
+                }
+                parts.push(WordPart::Param(name));
+            }
+            Some(c)
+                if c.is_ascii_digit() || matches!(c, '?' | '$' | '#' | '@' | '*' | '!' | '-') =>
+            {
+                self.bump();
+                parts.push(WordPart::Param(c.to_string()));
+            }
+            _ => push_text(parts, "$", in_double),
+        }
+    }
+
+    fn balanced(&mut self, open: char, close: char) -> String {
+        let mut body = String::new();
+        let mut depth = 1;
+        while let Some(c) = self.bump() {
+            match c {
+                '\\' => {
+                    body.push(c);
+                    if let Some(c) = self.bump() {
+                        body.push(c);
+                    }
+                    continue;
+                }
+                '$' if self.peek() == Some('\'') => {
+                    self.bump();
+                    body.push_str(&format!("$'{}'", self.ansi_body()));
+                    continue;
+                }
+                '\'' => {
+                    body.push(c);
+                    while let Some(c) = self.bump() {
+                        body.push(c);
+                        if c == '\'' {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                '"' => {
+                    body.push(c);
+                    while let Some(c) = self.bump() {
+                        body.push(c);
+                        if c == '\\' {
+                            if let Some(c) = self.bump() {
+                                body.push(c);
+                            }
+                        } else if c == '"' {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                _ if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ if c == open => depth += 1,
+                _ => {}
+            }
+            body.push(c);
+        }
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ansi_c_escapes_decode_to_bytes() {
+        for (body, expected) in [
+            ("plain", &b"plain"[..]),
+            ("a\\nb", b"a\nb"),
+            ("\\t\\r\\v\\f", b"\t\r\x0b\x0c"),
+            ("\\a\\b\\e\\E", b"\x07\x08\x1b\x1b"),
+            ("\\\\ \\' \\\" \\?", b"\\ ' \" ?"),
+            ("\\x41\\x4a", b"AJ"),
+            ("\\x7", b"\x07"),
+            ("\\x414", b"A4"),
+            ("\\xff\\x80", b"\xff\x80"),
+            ("\\101\\0\\12", b"A\0\n"),
+            ("\\1011", b"A1"),
+            ("\\cA\\c[", b"\x01\x1b"),
+            ("\\u00e9", "é".as_bytes()),
+            ("\\u65e5\\u672c", "日本".as_bytes()),
+            ("\\U0001F600", "😀".as_bytes()),
+            ("\\u41\\U42", b"AB"),
+            ("é\\n", "é\n".as_bytes()),
+            ("\\q\\$x", b"\\q\\$x"),
+            ("trailing\\", b"trailing\\"),
+        ] {
+            assert_eq!(bytes::encode(&ansi_c(body).unwrap()), expected, "{}", body);
+        }
+    }
+
+    #[test]
+    fn only_special_words_are_quoted() {
+        for (value, quoted) in [
+            ("plain", "plain"),
+            ("a-b/c.d:e=f", "a-b/c.d:e=f"),
+            ("é", "é"),
+            ("", "''"),
+            ("two words", "'two words'"),
+            ("it's", "'it'\\''s'"),
+            ("$HOME `x` \\", "'$HOME `x` \\'"),
+            ("~user", "'~user'"),
+            ("*", "'*'"),
+        ] {
+            assert_eq!(quote(value), quoted, "{}", value);
+        }
+    }
+
+    #[test]
+    fn malformed_escapes_are_errors() {
+        for (body, message) in [
+            ("\\x", "`\\x': missing hexadecimal digits"),
+            ("\\xZZ", "`\\x': missing hexadecimal digits"),
+            ("\\u", "`\\u': missing hexadecimal digits"),
+            ("\\Ug", "`\\U': missing hexadecimal digits"),
+            ("\\ud800", "`\\ud800': invalid Unicode code point"),
+            ("\\U00110000", "`\\U00110000': invalid Unicode code point"),
+            ("\\c", "`\\c': missing control character"),
+            ("\\cé", "`\\c': missing control character"),
+        ] {
+            assert_eq!(ansi_c(body), Err(message.to_string()), "{}", body);
+        }
+    }
+
+    #[test]
+    fn ansi_c_words_are_quoted_literals() {
+        let word = parse("a$'\\t$HOME *'b");
+        assert_eq!(
+            word.parts,
+            vec![
+                WordPart::Literal("a".to_string()),
+                WordPart::Quoted("\t$HOME *".to_string()),
+                WordPart::Literal("b".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::Sandbox;
+
+#[test]
+fn escapes_reach_commands_as_bytes() {
+    let mut sandbox = Sandbox::new("ansi-bytes");
+    for (word, bytes) in [
+        ("$'a\\tb\\n'", " 61 09 62 0a\n"),
+        ("$'\\x41\\xff'", " 41 ff\n"),
+        ("$'\\u00e9'", " c3 a9\n"),
+        ("$'\\u65e5'", " e6 97 a5\n"),
+        ("$'\\U0001F600'", " f0 9f 98 80\n"),
+        ("$'\\e[1m\\cA'", " 1b 5b 31 6d 01\n"),
+        ("$'it\\'s'", " 69 74 27 73\n"),
+        ("x$'\\n'\"y\"'z'", " 78 0a 79 7a\n"),
+    ] {
+        let line = format!("printf %s {} | od -An -tx1", word);
+        assert_eq!(sandbox.stdout(&line), bytes, "{}", word);
+    }
+}
+
+#[test]
+fn results_are_single_unexpanded_words() {
+    let mut sandbox = Sandbox::new("ansi-literal");
+    sandbox.write("a.x", "");
+    sandbox.write("b.x", "");
+    assert_eq!(
+        sandbox.stdout("set -- $'one two\\tthree\\nfour'; echo $#"),
+        "1\n"
+    );
+    assert_eq!(
+        sandbox.stdout("printf '<%s>' $'$HOME' $'*.x' $'`echo no`' $'$(echo no)' *.x"),
+        "<$HOME><*.x><`echo no`><$(echo no)><a.x><b.x>"
+    );
+    assert_eq!(
+        sandbox.stdout("x=$'a\\nb'; printf '<%s>' \"$x\" $x"),
+        "<a\nb><a><b>"
+    );
+    assert_eq!(sandbox.stdout("echo \"$'a\\n'\""), "$'a\\n'\n");
+    assert_eq!(
+        sandbox.stdout("case $'\\t' in $'\\t') echo tab;; esac"),
+        "tab\n"
+    );
+}
+
+#[test]
+fn malformed_escapes_are_syntax_errors() {
+    let mut sandbox = Sandbox::new("ansi-errors");
+    for (line, message) in [
+        (
+            "touch ran; echo $'\\xZZ'",
+            "`\\x': missing hexadecimal digits",
+        ),
+        (
+            "touch ran; echo $'\\u'",
+            "`\\u': missing hexadecimal digits",
+        ),
+        (
+            "touch ran; echo $'\\ud800'",
+            "`\\ud800': invalid Unicode code point",
+        ),
+        ("touch ran; echo $'\\c'", "`\\c': missing control character"),
+        (
+            "touch ran; echo $'open",
+            "unexpected EOF while looking for matching `''",
+        ),
+    ] {
+        let output = sandbox.run(line);
+        assert_eq!(output.status, 2, "{}", line);
+        assert!(
+            output.stderr.contains(message),
+            "{}: {}",
+            line,
+            output.stderr
+        );
+        assert!(!sandbox.dir.join("ran").exists(), "{}", line);
+    }
+}
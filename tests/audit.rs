@@ -0,0 +1,63 @@
+mod common;
+
+use std::fs;
+
+use common::vssh;
+
+// The fields after the timestamp and pid of each logged line.
+fn entries(log: &str) -> Vec<String> {
+    let text = fs::read_to_string(log).unwrap_or_default();
+    fs::remove_file(log).ok();
+    text.lines()
+        .map(|line| line.splitn(3, ' ').nth(2).unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn entries_show_where_each_command_started() {
+    let log = std::env::temp_dir().join(format!("vssh-test-audit-{}.log", std::process::id()));
+    let log = log.display().to_string();
+    let home = std::env::temp_dir().display().to_string();
+    let home = home.trim_end_matches('/');
+    let output = vssh(
+        &["--audit-log", &log],
+        &[],
+        "cd /\npwd\ncd /tmp; exit 3\necho gone\n",
+    );
+    assert_eq!(output.status, 3);
+    assert_eq!(
+        entries(&log),
+        [
+            format!("cwd={} status=0 cmd=cd /", home),
+            "cwd=/ status=0 cmd=pwd".to_string(),
+            "cwd=/ status=0 cmd=cd /tmp".to_string(),
+            "cwd=/tmp status=3 cmd=exit 3".to_string(),
+        ]
+    );
+    let output = vssh(
+        &["--audit-log", &log, "-c", "f() { exit 4; }; f; echo gone"],
+        &[],
+        "",
+    );
+    assert_eq!(output.status, 4);
+    assert_eq!(
+        entries(&log),
+        [
+            format!("cwd={} status=0 cmd=f() {{ exit 4; }}", home),
+            format!("cwd={} status=4 cmd=f", home),
+        ]
+    );
+    let output = vssh(
+        &["--audit-log", &log, "-c", "set -e; false; echo gone"],
+        &[],
+        "",
+    );
+    assert_eq!(output.status, 1);
+    assert_eq!(
+        entries(&log),
+        [
+            format!("cwd={} status=0 cmd=set -e", home),
+            format!("cwd={} status=1 cmd=false", home),
+        ]
+    );
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::Sandbox;
+
+#[test]
+fn commands_after_an_ampersand_run_immediately() {
+    let mut sandbox = Sandbox::new("bg-now");
+    let started = Instant::now();
+    let output = sandbox.run("sleep 1 & echo now");
+    assert!(started.elapsed() < Duration::from_millis(800));
+    assert_eq!(output.stdout, "now\n");
+    assert_eq!(sandbox.shell.jobs.len(), 1);
+    assert_eq!(sandbox.run("wait").status, 0);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+}
+
+#[test]
+fn every_ampersand_list_is_a_job() {
+    let mut sandbox = Sandbox::new("bg-jobs");
+    sandbox.run("sleep 5 & sleep 5& sleep 5 &");
+    assert_eq!(sandbox.shell.jobs.len(), 3);
+    let output = sandbox.run("jobs");
+    assert_eq!(output.stdout.lines().count(), 3);
+    sandbox.run("kill %1 %2 %3; wait");
+}
+
+#[test]
+fn ampersands_in_other_operators() {
+    let mut sandbox = Sandbox::new("bg-operators");
+    assert_eq!(sandbox.stdout("true && echo and"), "and\n");
+    assert_eq!(sandbox.stdout("sh -c 'echo err >&2' 2>&1 | cat"), "err\n");
+    assert_eq!(
+        sandbox.stdout("echo 'a & b' \"c&d\" e\\&f"),
+        "a & b c&d e&f\n"
+    );
+    assert!(sandbox.shell.jobs.is_empty());
+    let output = sandbox.run("true && sleep 1 & echo now; wait $!; echo $?");
+    assert_eq!(output.stdout, "now\n0\n");
+}
+
+#[test]
+fn a_lone_ampersand_is_a_syntax_error() {
+    let mut sandbox = Sandbox::new("bg-lone");
+    for line in ["&", "echo a & & echo b", "& echo a"] {
+        let output = sandbox.run(line);
+        assert_eq!(output.status, 2, "{}", line);
+        assert_eq!(output.stdout, "", "{}", line);
+        assert_eq!(
+            output.stderr, "vssh: line 1: syntax error near unexpected token `&'\n",
+            "{}",
+            line
+        );
+    }
+}
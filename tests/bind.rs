@@ -0,0 +1,86 @@
+mod common;
+
+use common::{vssh_pty, without_escapes, Sandbox};
+use vssh::keymap::FUNCTIONS;
+
+#[test]
+fn listings() {
+    let mut sandbox = Sandbox::new("bind-list");
+    let names: Vec<&str> = FUNCTIONS.iter().map(|(name, _)| *name).collect();
+    assert_eq!(sandbox.stdout("bind -l"), format!("{}\n", names.join("\n")));
+    sandbox.run("bind '\"\\C-f\": forward-word' '\"\\e[5~\": beginning-of-line'");
+    let bindings = sandbox.stdout("bind -p");
+    assert!(
+        bindings.contains("\"\\C-f\": forward-word\n"),
+        "{}",
+        bindings
+    );
+    assert!(
+        bindings.contains("\"\\e[5~\": beginning-of-line\n"),
+        "{}",
+        bindings
+    );
+    // The dump is input to bind itself.
+    let lines: Vec<String> = bindings
+        .lines()
+        .map(|line| format!("bind '{}'", line.replace('\'', "'\\''")))
+        .collect();
+    sandbox.run("bind -r '\\C-a'");
+    assert!(!sandbox.stdout("bind -p").contains("\"\\C-a\""));
+    sandbox.script(&lines.join("\n"));
+    assert_eq!(sandbox.stdout("bind -p"), bindings);
+}
+
+#[test]
+fn commands_are_listed_apart() {
+    let mut sandbox = Sandbox::new("bind-commands");
+    sandbox.run("bind -x '\"\\C-o\": \"echo hi\"'");
+    assert_eq!(sandbox.stdout("bind -X"), "\"\\C-o\": \"echo hi\"\n");
+    assert!(!sandbox.stdout("bind -p").contains("\\C-o"));
+    sandbox.run("bind -r '\\C-o'");
+    assert_eq!(sandbox.stdout("bind -X"), "");
+}
+
+#[test]
+fn bind_errors() {
+    let mut sandbox = Sandbox::new("bind-errors");
+    for (line, status, message) in [
+        (
+            "bind '\"\\C-f\": bogus'",
+            1,
+            "vssh: bind: bogus: unknown function name\n",
+        ),
+        (
+            "bind '\\C-f: abort'",
+            1,
+            "vssh: bind: \\C-f: abort: key sequence must be quoted\n",
+        ),
+        (
+            "bind -z",
+            2,
+            "vssh: bind: -z: invalid option\n\
+             vssh: bind: usage: bind [-lpX] [-r keyseq] [-x] [keyseq:function ...]\n",
+        ),
+    ] {
+        let output = sandbox.run(line);
+        assert_eq!(output.status, status, "{}", line);
+        assert_eq!(output.stderr, message, "{}", line);
+    }
+}
+
+#[test]
+fn bound_commands_see_and_replace_the_line() {
+    let output = vssh_pty(
+        &["-i"],
+        &[("PS1", "$ "), ("TERM", "xterm")],
+        "bind -x '\"\\C-o\": \"echo [$READLINE_LINE:$READLINE_POINT]; READLINE_LINE=\\\"echo replaced\\\"\"'\n\
+         abc\x0f\n\
+         echo after ${READLINE_LINE-unset}\n\
+         exit\n",
+    );
+    assert_eq!(output.status, 0, "{}", output.stdout);
+    let screen = without_escapes(&output.stdout);
+    assert!(screen.contains("[abc:3]\n"), "{}", screen);
+    assert!(screen.contains("\nreplaced\n"), "{}", screen);
+    assert!(screen.contains("\nafter unset\n"), "{}", screen);
+}
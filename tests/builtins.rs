@@ -0,0 +1,223 @@
+mod common;
+
+use common::{vssh, Sandbox};
+
+#[test]
+fn builtins_feed_pipelines() {
+    let mut sandbox = Sandbox::new("builtin-pipes");
+    assert_eq!(sandbox.stdout("echo hi | cat"), "hi\n");
+    assert_eq!(sandbox.stdout("printf '%s\\n' b a | sort | cat"), "a\nb\n");
+    assert_eq!(sandbox.stdout("jobs | wc -l").trim(), "0");
+    sandbox.run("sleep 5 &");
+    assert_eq!(sandbox.stdout("jobs | wc -l").trim(), "1");
+    sandbox.run("kill %1; wait");
+    sandbox.shell.add_history("make all");
+    sandbox.shell.add_history("ls");
+    assert!(sandbox.stdout("history | grep make").contains("make all"));
+}
+
+#[test]
+fn builtins_in_child_stages_do_not_touch_the_shell() {
+    let mut sandbox = Sandbox::new("builtin-children");
+    sandbox.write("sub/file", "");
+    let before = sandbox.stdout("pwd");
+    assert_eq!(sandbox.stdout("cd sub | cat; pwd"), before);
+    assert_eq!(sandbox.stdout("v=1; v=2 | cat; echo $v"), "1\n");
+}
+
+#[test]
+fn standalone_builtins_redirect_and_restore() {
+    let mut sandbox = Sandbox::new("builtin-redirect");
+    let output = sandbox.run("pwd > where.txt; echo after");
+    assert_eq!(output.stdout, "after\n");
+    assert_eq!(
+        sandbox.read("where.txt"),
+        format!("{}\n", sandbox.dir.display())
+    );
+    let output = sandbox.run("cd /tmp > /dev/null; pwd");
+    assert_eq!(output.stdout, "/tmp\n");
+    sandbox.run(&format!("cd {}", sandbox.dir.display()));
+    let output = sandbox.run("cd /nonexistent 2> err; echo \"status $?\"; echo visible");
+    assert_eq!(output.stdout, "status 1\nvisible\n");
+    assert_eq!(output.stderr, "");
+    assert!(sandbox.read("err").contains("No such file or directory"));
+    let output = sandbox.run("echo lost > gone/out; echo kept");
+    assert_eq!(output.stdout, "kept\n");
+}
+
+fn cwd() -> std::path::PathBuf {
+    std::env::current_dir().unwrap()
+}
+
+#[test]
+fn cd_arguments() {
+    let mut sandbox = Sandbox::new("builtin-cd");
+    sandbox.write("with space/file", "");
+    sandbox.write("plain/file", "");
+    let home = sandbox.dir.clone();
+    for (line, dir) in [
+        ("cd plain", "plain"),
+        ("cd", ""),
+        ("cd\tplain", "plain"),
+        ("cd  ..", ""),
+        ("cd 'with space'", "with space"),
+        ("cd \"$HOME\"", ""),
+        ("d=plain; cd $d", "plain"),
+        ("cd ~", ""),
+    ] {
+        let output = sandbox.run(line);
+        assert_eq!(output.status, 0, "{}: {:?}", line, output);
+        assert_eq!(cwd(), home.join(dir), "{}", line);
+        assert_eq!(
+            sandbox.stdout("echo $PWD"),
+            format!("{}\n", cwd().display())
+        );
+    }
+}
+
+#[test]
+fn cd_errors_leave_the_directory_alone() {
+    let mut sandbox = Sandbox::new("builtin-cd-errors");
+    sandbox.write("file", "");
+    let home = sandbox.dir.clone();
+    for (line, message) in [
+        ("cd /nope", "vssh: cd: /nope: No such file or directory\n"),
+        ("cd file", "vssh: cd: file: Not a directory\n"),
+        ("cd a b", "vssh: cd: too many arguments\n"),
+    ] {
+        let output = sandbox.run(line);
+        assert_eq!(output.status, 1, "{}", line);
+        assert_eq!(output.stderr, message, "{}", line);
+        assert_eq!(cwd(), home, "{}", line);
+    }
+    let output = sandbox.run("unset HOME; cd");
+    assert_eq!(output.status, 1);
+    assert_eq!(output.stderr, "vssh: cd: HOME not set\n");
+}
+
+fn seconds(time: &str) -> f64 {
+    let (minutes, seconds) = time.strip_suffix('s').unwrap().split_once('m').unwrap();
+    minutes.parse::<f64>().unwrap() * 60.0 + seconds.parse::<f64>().unwrap()
+}
+
+#[test]
+fn times_counts_reaped_children() {
+    let output = vssh(
+        &[],
+        &[],
+        "times\nawk 'BEGIN { for (i = 0; i < 6000000; i++) s += i }'\ntimes\n",
+    );
+    assert_eq!(output.status, 0);
+    let lines: Vec<Vec<f64>> = output
+        .stdout
+        .lines()
+        .map(|line| line.split(' ').map(seconds).collect())
+        .collect();
+    assert_eq!(lines.len(), 4, "{}", output.stdout);
+    assert!(lines.iter().all(|times| times.len() == 2));
+    let burned = lines[3][0] - lines[1][0];
+    assert!(burned >= 0.1, "{}", output.stdout);
+    assert!(
+        lines[2][0] < 0.1 + lines[0][0] + burned,
+        "{}",
+        output.stdout
+    );
+}
+
+#[test]
+fn repeat_runs_a_command_line() {
+    let mut sandbox = Sandbox::new("builtin-repeat");
+    assert_eq!(sandbox.stdout("repeat 3 echo hi"), "hi\nhi\nhi\n");
+    assert_eq!(
+        sandbox.stdout("repeat 2 'echo a | tr a b; echo c' >> out; cat out"),
+        "b\nc\nb\nc\n"
+    );
+    assert_eq!(
+        sandbox.stdout("n=0; repeat 4 'n=$((n + 1))'; echo $n"),
+        "4\n"
+    );
+    assert_eq!(
+        sandbox.stdout("for i in 1 2; do repeat 5 'echo $i; break'; done"),
+        "1\n2\n"
+    );
+}
+
+#[test]
+fn repeat_status() {
+    let mut sandbox = Sandbox::new("builtin-repeat-status");
+    sandbox.write("fail-twice", "");
+    let script = "test -s fail-twice && exit 0; echo x >> fail-twice; exit 7";
+    let output = sandbox.run(&format!("repeat 3 sh -c '{}'; echo $?", script));
+    assert_eq!(output.stdout, "0\n");
+    sandbox.write("fail-twice", "");
+    let output = sandbox.run(&format!(
+        "repeat -e 3 sh -c '{}'; echo $?; cat fail-twice",
+        script
+    ));
+    assert_eq!(output.stdout, "7\nx\n");
+    assert_eq!(sandbox.stdout("repeat -e 2 true; echo $?"), "0\n");
+}
+
+#[test]
+fn repeat_errors() {
+    let mut sandbox = Sandbox::new("builtin-repeat-errors");
+    for (line, status, message) in [
+        (
+            "repeat",
+            2,
+            "vssh: repeat: usage: repeat [-e] count command [args...]\n",
+        ),
+        (
+            "repeat 0 echo",
+            1,
+            "vssh: repeat: 0: positive integer expected\n",
+        ),
+        (
+            "repeat -3 echo",
+            1,
+            "vssh: repeat: -3: positive integer expected\n",
+        ),
+        (
+            "repeat x echo",
+            1,
+            "vssh: repeat: x: positive integer expected\n",
+        ),
+        ("repeat 2", 2, "vssh: repeat: command expected\n"),
+    ] {
+        let output = sandbox.run(line);
+        assert_eq!(output.status, status, "{}", line);
+        assert_eq!(output.stderr, message, "{}", line);
+        assert_eq!(output.stdout, "", "{}", line);
+    }
+}
+
+#[test]
+fn listings_quote_alike_and_read_back() {
+    let mut sandbox = Sandbox::new("builtin-quoting");
+    let value = "it's $HOME `x` \\ two  words";
+    let quoted = "'it'\\''s $HOME `x` \\ two  words'";
+    sandbox.run(&format!("v={}", quoted));
+    assert_eq!(sandbox.stdout("printf %s \"$v\""), value);
+    assert_eq!(
+        sandbox.stdout("export v; export -p | grep '^export v='"),
+        format!("export v={}\n", quoted)
+    );
+    let output = vssh(&["-c", "export -p"], &[("_", "/usr/bin/env")], "");
+    assert!(!output.stdout.contains("export _="), "{}", output.stdout);
+    sandbox.run("trap \"$v\" USR1");
+    assert_eq!(
+        sandbox.stdout("trap -p USR1"),
+        format!("trap -- {} USR1\n", quoted)
+    );
+    sandbox.run("trap - USR1");
+    assert_eq!(
+        sandbox.stdout("printf '%q\\n' \"$v\""),
+        format!("{}\n", quoted)
+    );
+    let output = vssh(&["-c", &format!("v={}; set -x; : \"$v\"", quoted)], &[], "");
+    assert_eq!(output.stderr, format!("+ : {}\n", quoted));
+    assert_eq!(
+        sandbox.stdout("printf 'w=%q\\n' \"$v\" > saved; . ./saved; printf %s \"$w\""),
+        value
+    );
+}
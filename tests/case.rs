@@ -0,0 +1,91 @@
+mod common;
+
+use common::{vssh, Sandbox};
+
+fn classify(sandbox: &mut Sandbox, word: &str) -> String {
+    let line = format!(
+        "case {} in a*|*z) echo edge ;; ?) echo one ;; [0-9]*) echo digit ;; *) echo other ;; esac",
+        word
+    );
+    sandbox.stdout(&line).trim_end().to_string()
+}
+
+#[test]
+fn arms_match_glob_patterns() {
+    let mut sandbox = Sandbox::new("case-arms");
+    for (word, arm) in [
+        ("apple", "edge"),
+        ("fizz", "edge"),
+        ("x", "one"),
+        ("42", "digit"),
+        ("hello", "other"),
+        ("''", "other"),
+    ] {
+        assert_eq!(classify(&mut sandbox, word), arm, "{}", word);
+    }
+}
+
+#[test]
+fn status_is_the_arm_or_zero() {
+    let mut sandbox = Sandbox::new("case-status");
+    assert_eq!(sandbox.run("case x in x) false ;; esac").status, 1);
+    assert_eq!(sandbox.run("false; case x in y) false ;; esac").status, 0);
+    assert_eq!(sandbox.run("case x in x) ;; esac").status, 0);
+}
+
+#[test]
+fn word_and_patterns_are_expanded_without_globbing_or_splitting() {
+    let mut sandbox = Sandbox::new("case-expand");
+    sandbox.write("match-me", "");
+    assert_eq!(
+        sandbox.stdout("w='a b'; case $w in 'a b') echo quoted ;; esac"),
+        "quoted\n"
+    );
+    assert_eq!(
+        sandbox.stdout("p='m*'; case match-me in $p) echo pattern ;; esac"),
+        "pattern\n"
+    );
+    assert_eq!(
+        sandbox.stdout("case m* in match-me) echo globbed ;; 'm*') echo literal ;; esac"),
+        "literal\n"
+    );
+    assert_eq!(
+        sandbox.stdout("case '*' in \\*) echo star ;; *) echo any ;; esac"),
+        "star\n"
+    );
+}
+
+#[test]
+fn case_nests_in_functions_and_loops() {
+    let mut sandbox = Sandbox::new("case-nested");
+    assert_eq!(
+        sandbox.stdout(
+            "kind() { case $1 in *.rs) echo rust ;; *) return 3 ;; esac; }; \
+             for f in a.rs b.c; do kind $f || echo \"no $?\"; done"
+        ),
+        "rust\nno 3\n"
+    );
+    assert_eq!(
+        sandbox
+            .stdout("for i in 1 2 3; do case $i in 2) continue ;; 3) break ;; esac; echo $i; done"),
+        "1\n"
+    );
+}
+
+#[test]
+fn scripts_continue_case_across_lines() {
+    let mut sandbox = Sandbox::new("case-lines");
+    let output =
+        sandbox.script("case b in\n  a) echo a\n  ;;\n  b|c)\n    echo bc\n    ;;\nesac\n");
+    assert_eq!(output.stdout, "bc\n");
+}
+
+#[test]
+fn interactive_case_prompts_with_ps2_until_esac() {
+    let output = vssh(
+        &["-i"],
+        &[("PS1", "$ "), ("PS2", "> ")],
+        "case x in\nx) echo matched ;;\nesac\n",
+    );
+    assert_eq!(output.stdout, "$ > > matched\n$ exit\n");
+}
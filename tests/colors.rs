@@ -0,0 +1,101 @@
+mod common;
+
+use common::{vssh, vssh_pty};
+
+// On a terminal the shell goes by the path it was started with.
+fn not_found(code: Option<&str>) -> String {
+    let message = format!(
+        "{}: no-such-command-here: command not found",
+        env!("CARGO_BIN_EXE_vssh")
+    );
+    match code {
+        Some(code) => format!("\x1b[{}m{}\x1b[0m\r\n", code, message),
+        None => format!("\n{}\r\n", message),
+    }
+}
+
+#[test]
+fn terminals_get_colored_output() {
+    let output = vssh_pty(
+        &["-i"],
+        &[("PS1", "")],
+        "no-such-command-here\nVSSH_COLORS='error=35;1'\nno-such-command-here\nexit\n",
+    );
+    for code in ["31", "35;1"] {
+        assert!(
+            output.stdout.contains(&not_found(Some(code))),
+            "{}: {:?}",
+            code,
+            output.stdout
+        );
+    }
+    let output = vssh_pty(&["-i"], &[("PS1", "")], "sleep 0.1 &\nsleep 0.3\n\nexit\n");
+    assert!(
+        output.stdout.contains("\x1b[33m[1]+  Done"),
+        "{:?}",
+        output.stdout
+    );
+}
+
+#[test]
+fn prompt_parts_are_colored_inside_markers() {
+    let output = vssh_pty(
+        &["-i"],
+        &[
+            ("PS1", "\\w\\$ "),
+            ("VSSH_COLORS", "prompt_cwd=34:prompt_symbol=1"),
+        ],
+        "exit\n",
+    );
+    let symbol = match unsafe { libc::geteuid() } {
+        0 => '#',
+        _ => '$',
+    };
+    assert!(
+        output
+            .stdout
+            .contains(&format!("\x1b[34m~\x1b[0m\x1b[1m{}\x1b[0m ", symbol)),
+        "{:?}",
+        output.stdout
+    );
+    assert!(
+        !output.stdout.contains(['\x01', '\x02']),
+        "{:?}",
+        output.stdout
+    );
+}
+
+#[test]
+fn no_color_and_pipes_stay_plain() {
+    let output = vssh_pty(
+        &["-i"],
+        &[("PS1", ""), ("NO_COLOR", "1"), ("VSSH_COLORS", "error=35")],
+        "no-such-command-here\nNO_COLOR=\nno-such-command-here\nexit\n",
+    );
+    assert!(
+        output.stdout.contains(&not_found(None)),
+        "{:?}",
+        output.stdout
+    );
+    assert!(
+        output.stdout.contains(&not_found(Some("35"))),
+        "{:?}",
+        output.stdout
+    );
+    let output = vssh(
+        &["-i"],
+        &[
+            ("PS1", "\\w\\$ "),
+            ("VSSH_COLORS", "prompt_cwd=34:error=35"),
+        ],
+        "no-such-command-here\nsleep 0.1 &\nsleep 0.3\n\n",
+    );
+    assert!(!output.stdout.contains('\x1b'), "{:?}", output.stdout);
+    assert!(!output.stderr.contains('\x1b'), "{:?}", output.stderr);
+    assert!(
+        output.stderr.starts_with(not_found(None).trim()),
+        "{:?}",
+        output.stderr
+    );
+    assert!(output.stderr.contains("[1]+  Done"), "{:?}", output.stderr);
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{vssh, Sandbox};
+
+#[test]
+fn only_trailing_newlines_are_stripped() {
+    let mut sandbox = Sandbox::new("subst-newlines");
+    for (line, shown) in [
+        (
+            "x=$(printf 'a\\n\\nb\\n\\n\\n'); printf '[%s]' \"$x\"",
+            "[a\n\nb]",
+        ),
+        ("x=$(printf '\\n\\n'); printf '[%s]' \"$x\"", "[]"),
+        ("x=$(printf ' a \\n'); printf '[%s]' \"$x\"", "[ a ]"),
+        ("x=$(printf 'a\\r\\n'); printf '[%s]' \"$x\"", "[a\r]"),
+        ("printf '[%s]' $(printf 'a b\\nc\\n\\n')", "[a][b][c]"),
+        ("printf '[%s]' \"$(printf 'a b\\nc\\n\\n')\"", "[a b\nc]"),
+    ] {
+        assert_eq!(sandbox.stdout(line), shown, "{}", line);
+    }
+    sandbox.write("one", "");
+    sandbox.write("two", "");
+    assert_eq!(
+        sandbox.stdout("files=$(ls); printf '<%s>' $files; printf '<%s>' \"$files\""),
+        "<one><two><one\ntwo>"
+    );
+}
+
+#[test]
+fn reading_a_file_does_not_fork() {
+    let mut sandbox = Sandbox::new("subst-file");
+    sandbox.write("text", "a\n\nb\n\n\n");
+    sandbox.run("VSSH_MAX_FORKS=1");
+    let output = sandbox.run(
+        "x=$(< text); x=$(< text); y=$(<text); z=$( < text ); printf '[%s]' \"$x\" \"$y\" \"$z\"",
+    );
+    assert_eq!(output.stdout, "[a\n\nb][a\n\nb][a\n\nb]");
+    assert_eq!(output.stderr, "");
+    let output = sandbox.run("x=$(cat text); x=$(cat text)");
+    assert!(output.stderr.contains("runaway"), "{}", output.stderr);
+    sandbox.run("unset VSSH_MAX_FORKS");
+    assert_eq!(
+        sandbox.stdout("[ \"$(< text)\" = \"$(cat text)\" ] && echo same"),
+        "same\n"
+    );
+    let output = sandbox.run("x=$(< missing); echo \"[$x] $?\"");
+    assert_eq!(output.stdout, "[] 1\n");
+    assert_eq!(output.stderr, "vssh: missing: No such file or directory\n");
+    assert_eq!(sandbox.stdout("printf '[%s]' \"$(< /dev/null)\""), "[]");
+}
+
+#[test]
+fn output_larger_than_a_pipe() {
+    let mut sandbox = Sandbox::new("subst-large");
+    let output = sandbox.run("big=$(head -c 5000000 /dev/zero | tr '\\0' a); echo ${#big}");
+    assert_eq!(output.stdout, "5000000\n");
+    let output = sandbox.run("n=$(seq 1 300000); echo ${#n}; echo \"${n##*$'\\n'}\"");
+    assert_eq!(output.stdout, "1988894\n300000\n");
+    sandbox.write("big", &"line\n".repeat(1 << 20));
+    let output = sandbox.run("x=$(< big); echo ${#x}");
+    assert_eq!(output.stdout, "5242879\n");
+}
+
+#[test]
+fn background_children_do_not_hold_the_capture_open() {
+    // The sleeps close stderr so the harness does not wait for them too;
+    // their stdout is still the capture pipe.
+    let started = Instant::now();
+    let output = vssh(
+        &[
+            "-c",
+            "s=$(sleep 5 2>&- & echo bg); echo \"$s\"; t=`sleep 5 2>&- & true`; echo \"[$t]\"",
+        ],
+        &[],
+        "",
+    );
+    assert_eq!(output.stdout, "bg\n[]\n");
+    assert!(
+        started.elapsed() < Duration::from_secs(4),
+        "{:?}",
+        started.elapsed()
+    );
+    let mut sandbox = Sandbox::new("subst-background");
+    let started = Instant::now();
+    assert_eq!(sandbox.stdout("s=$(sleep 5 & echo bg); echo $s"), "bg\n");
+    assert!(
+        started.elapsed() < Duration::from_secs(4),
+        "{:?}",
+        started.elapsed()
+    );
+}
@@ -0,0 +1,263 @@
+// Shared by the integration suites; each one uses a different subset.
+#![allow(dead_code)]
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::os::fd::OwnedFd;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, MutexGuard};
+
+use vssh::embed::Config;
+use vssh::shell::Shell;
+
+// Embedded shells chdir and redirect fds 1 and 2 for the whole process.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+#[derive(Debug)]
+pub struct Output {
+    pub status: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// An embedded shell running in a scratch directory, with its output
+/// captured per line.
+pub struct Sandbox {
+    pub dir: PathBuf,
+    pub shell: Shell,
+    root: PathBuf,
+    _serial: MutexGuard<'static, ()>,
+}
+
+impl Sandbox {
+    pub fn new(name: &str) -> Self {
+        Self::with_output(name, None, None)
+    }
+
+    /// A sandbox whose shell writes to `stdout` and `stderr` outside of
+    /// `run`, as an embedder's would.
+    pub fn with_output(name: &str, stdout: Option<OwnedFd>, stderr: Option<OwnedFd>) -> Self {
+        let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let root = std::env::temp_dir().join(format!("vssh-test-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&root).ok();
+        let dir = root.join("work");
+        fs::create_dir_all(&dir).unwrap();
+        let shell = Shell::with_config(Config {
+            env: Some(vec![
+                ("PATH".into(), std::env::var("PATH").unwrap_or_default()),
+                ("HOME".into(), dir.display().to_string()),
+            ]),
+            cwd: Some(dir.clone()),
+            stdout,
+            stderr,
+            ..Config::default()
+        })
+        .unwrap();
+        Sandbox {
+            dir,
+            shell,
+            root,
+            _serial: serial,
+        }
+    }
+
+    pub fn run(&mut self, line: &str) -> Output {
+        self.capture(|shell| shell.run_line(line))
+    }
+
+    pub fn script(&mut self, text: &str) -> Output {
+        self.capture(|shell| shell.run_script(text.as_bytes()))
+    }
+
+    /// Output of a line that must succeed.
+    pub fn stdout(&mut self, line: &str) -> String {
+        let output = self.run(line);
+        assert_eq!(output.status, 0, "{}: {:?}", line, output);
+        output.stdout
+    }
+
+    fn capture(&mut self, run: impl FnOnce(&mut Shell) -> vssh::error::Result<i32>) -> Output {
+        let out = self.root.join("stdout");
+        let err = self.root.join("stderr");
+        self.shell.attached = vec![
+            (
+                libc::STDOUT_FILENO,
+                OwnedFd::from(File::create(&out).unwrap()),
+            ),
+            (
+                libc::STDERR_FILENO,
+                OwnedFd::from(File::create(&err).unwrap()),
+            ),
+        ];
+        let result = run(&mut self.shell);
+        self.shell.attached.clear();
+        let mut stderr = fs::read_to_string(&err).unwrap();
+        let status = match result {
+            Ok(status) => status,
+            Err(e) => {
+                stderr.push_str(&format!("{}\n", e));
+                e.status()
+            }
+        };
+        Output {
+            status,
+            stdout: fs::read_to_string(&out).unwrap(),
+            stderr,
+        }
+    }
+
+    pub fn write(&self, name: &str, contents: &str) {
+        let path = self.dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, contents).unwrap();
+    }
+
+    pub fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.dir.join(name)).unwrap()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.root).ok();
+    }
+}
+
+/// Runs the vssh binary with `stdin` as its input, ignoring any rc files.
+pub fn vssh(args: &[&str], env: &[(&str, &str)], stdin: impl AsRef<[u8]>) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_vssh"));
+    command.args(["--norc", "--noprofile"]).args(args);
+    capture(command, &std::env::temp_dir(), env, stdin.as_ref())
+}
+
+/// Like `vssh`, but at home in `home` and reading the rc and env files
+/// found there.
+pub fn vssh_with_rc(
+    home: &Path,
+    args: &[&str],
+    env: &[(&str, &str)],
+    stdin: impl AsRef<[u8]>,
+) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_vssh"));
+    command.args(args);
+    capture(command, home, env, stdin.as_ref())
+}
+
+fn capture(mut command: Command, home: &Path, env: &[(&str, &str)], stdin: &[u8]) -> Output {
+    let mut child = command
+        .env_clear()
+        .env("PATH", std::env::var("PATH").unwrap_or_default())
+        .env("HOME", home)
+        .envs(env.iter().copied())
+        .current_dir(home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    let output = child.wait_with_output().unwrap();
+    Output {
+        status: output.status.code().unwrap_or(-1),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    }
+}
+
+/// Runs vssh on a new pseudo-terminal, typing `input` into it, and
+/// returns everything the shell wrote to the screen.
+pub fn vssh_pty(args: &[&str], env: &[(&str, &str)], input: &str) -> Output {
+    use std::io::Read;
+    use std::os::fd::FromRawFd;
+    use std::os::unix::process::CommandExt;
+    use std::time::{Duration, Instant};
+
+    let (mut master, slave) = unsafe {
+        let (mut master, mut slave) = (0, 0);
+        let result = libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            std::ptr::null(),
+        );
+        assert_eq!(result, 0, "openpty");
+        (File::from_raw_fd(master), OwnedFd::from_raw_fd(slave))
+    };
+    let home = std::env::temp_dir();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_vssh"));
+    command
+        .args(["--norc", "--noprofile"])
+        .args(args)
+        .env_clear()
+        .env("PATH", std::env::var("PATH").unwrap_or_default())
+        .env("HOME", &home)
+        .env("TERM", "dumb")
+        .envs(env.iter().copied())
+        .current_dir(&home)
+        .stdin(slave.try_clone().unwrap())
+        .stdout(slave.try_clone().unwrap())
+        .stderr(slave.try_clone().unwrap());
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            libc::ioctl(0, libc::TIOCSCTTY, 0);
+            Ok(())
+        });
+    }
+    let mut child = command.spawn().unwrap();
+    // The command holds copies of the slave end too.
+    drop((command, slave));
+    let mut reader = master.try_clone().unwrap();
+    let screen = std::thread::spawn(move || {
+        let mut screen = Vec::new();
+        let mut chunk = [0; 4096];
+        // EIO once the last slave descriptor closes.
+        while let Ok(n @ 1..) = reader.read(&mut chunk) {
+            screen.extend_from_slice(&chunk[..n]);
+        }
+        screen
+    });
+    for line in input.split_inclusive('\n') {
+        master.write_all(line.as_bytes()).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if started.elapsed() > Duration::from_secs(10) {
+            child.kill().ok();
+            panic!("vssh did not exit on the terminal");
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    drop(master);
+    Output {
+        status: status.code().unwrap_or(-1),
+        stdout: String::from_utf8_lossy(&screen.join().unwrap()).into_owned(),
+        stderr: String::new(),
+    }
+}
+
+/// The screen without carriage returns, title updates and cursor moves.
+pub fn without_escapes(screen: &str) -> String {
+    let mut out = String::new();
+    let mut chars = screen.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {}
+            '\x1b' => match chars.next() {
+                Some(']') => while chars.next().is_some_and(|c| c != '\x07') {},
+                Some('[') => while chars.next().is_some_and(|c| !c.is_ascii_alphabetic()) {},
+                _ => {}
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::fs;
+
+use common::{vssh, Sandbox};
+
+#[test]
+fn a_deleted_directory_does_not_stop_the_shell() {
+    let mut sandbox = Sandbox::new("cwd-deleted");
+    sandbox.write("a/b/c/file", "");
+    sandbox.run("cd a/b/c");
+    fs::remove_dir_all(sandbox.dir.join("a/b")).unwrap();
+    let output = sandbox.run("echo still here; x=1; echo $((x + 1))");
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, "still here\n2\n");
+    let prompt = sandbox.shell.render_prompt("\\w$ ");
+    assert_eq!(prompt, "~/a/b/c (deleted)$ ");
+    assert_eq!(
+        sandbox.shell.display_cwd(),
+        format!("{}/a/b/c (deleted)", sandbox.dir.display())
+    );
+    for line in ["ls", "sh -c 'echo ran'", "echo x | cat"] {
+        let output = sandbox.run(line);
+        assert_eq!(output.status, 1, "{}", line);
+        assert_eq!(output.stdout, "", "{}", line);
+        assert_eq!(
+            output.stderr, "vssh: cannot access current directory\n",
+            "{}",
+            line
+        );
+    }
+    assert_eq!(
+        sandbox.stdout("cd ..; echo $PWD"),
+        format!("{}/a\n", sandbox.dir.display())
+    );
+    assert_eq!(std::env::current_dir().unwrap(), sandbox.dir.join("a"));
+    assert_eq!(sandbox.stdout("sh -c 'echo ran'"), "ran\n");
+    assert_eq!(sandbox.shell.render_prompt("\\w$ "), "~/a$ ");
+}
+
+#[test]
+fn cd_recovers_to_the_nearest_ancestor_or_home() {
+    let mut sandbox = Sandbox::new("cwd-recover");
+    sandbox.write("a/b/c/file", "");
+    sandbox.run("cd a/b/c");
+    fs::remove_dir_all(sandbox.dir.join("a")).unwrap();
+    assert_eq!(
+        sandbox.stdout("cd ..; echo $PWD"),
+        format!("{}\n", sandbox.dir.display())
+    );
+    sandbox.write("x/y/file", "");
+    sandbox.run("cd x/y");
+    fs::remove_dir_all(sandbox.dir.join("x")).unwrap();
+    assert_eq!(
+        sandbox.stdout("cd; echo $PWD"),
+        format!("{}\n", sandbox.dir.display())
+    );
+    assert_eq!(std::env::current_dir().unwrap(), sandbox.dir);
+}
+
+#[test]
+fn scripts_keep_going_in_a_deleted_directory() {
+    let dir = std::env::temp_dir().join(format!("vssh-cwd-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let script = format!(
+        "cd {}; mkdir -p d; cd d; rmdir ../d; echo ok; ls; echo $?; cd ..; echo $?; ls -d .\n",
+        dir.display()
+    );
+    let output = vssh(&[], &[], script);
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, "ok\n1\n0\n.\n");
+    assert_eq!(output.stderr.lines().count(), 1, "{}", output.stderr);
+    assert!(
+        output
+            .stderr
+            .ends_with(": cannot access current directory\n"),
+        "{}",
+        output.stderr
+    );
+    fs::remove_dir_all(dir).ok();
+}
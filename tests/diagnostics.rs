@@ -0,0 +1,77 @@
+mod common;
+
+use std::fs;
+
+use common::vssh;
+
+const VSSH: &str = env!("CARGO_BIN_EXE_vssh");
+
+#[test]
+fn dollar_zero_follows_the_invocation() {
+    let output = vssh(&["-c", "echo $0"], &[], "");
+    assert_eq!(output.stdout, format!("{}\n", VSSH));
+    let output = vssh(&["-c", "echo $0 $1 $#", "name", "one"], &[], "");
+    assert_eq!(output.stdout, "name one 1\n");
+    let output = vssh(&[], &[], "echo $0\n");
+    assert_eq!(output.stdout, format!("{}\n", VSSH));
+    let script = std::env::temp_dir().join(format!("vssh-test-zero-{}.sh", std::process::id()));
+    fs::write(&script, "echo $0 \"${0##*/}\"\ncd /nope\n").unwrap();
+    let path = script.display().to_string();
+    let output = vssh(&[&path], &[], "");
+    fs::remove_file(&script).ok();
+    let name = path.rsplit('/').next().unwrap();
+    assert_eq!(output.stdout, format!("{} {}\n", path, name));
+    assert_eq!(
+        output.stderr,
+        format!("{}: cd: /nope: No such file or directory\n", path)
+    );
+}
+
+#[test]
+fn diagnostics_name_the_shell_on_stderr() {
+    for line in [
+        "cd /nope",
+        "cd a b",
+        "no-such-command-here",
+        "./no/such/file",
+        "cat < /nope",
+        "fi",
+        "echo $((1/0))",
+        "echo $((1 +))",
+        "echo ${x?unset}",
+        "readonly r=1; r=2",
+        "exit abc",
+        "history -x",
+        "repeat 0 true",
+        "wait %9",
+        "trap x BOGUS",
+        "kill -Z 1",
+        "source /nope",
+        "printf %d x > /dev/null",
+        "set -o bogus",
+        "shopt -s bogus",
+        "hash no-such-command-here",
+        "export 1a=b",
+        "local x",
+        "f() { return x; }; f",
+    ] {
+        let output = vssh(&["-c", line, "named"], &[], "");
+        assert_ne!(output.status, 0, "{}", line);
+        assert_eq!(output.stdout, "", "{}", line);
+        assert!(!output.stderr.is_empty(), "{}", line);
+        for message in output.stderr.lines() {
+            assert!(message.starts_with("named: "), "{}: {}", line, message);
+            assert!(!message.contains("Error"), "{}: {}", line, message);
+        }
+    }
+}
+
+#[test]
+fn interactive_diagnostics_use_the_binary_name() {
+    let output = vssh(&["-i"], &[("PS1", "")], "cd /nope\n");
+    assert_eq!(
+        output.stderr,
+        format!("{}: cd: /nope: No such file or directory\n", VSSH)
+    );
+    assert_eq!(output.stdout, "exit\n");
+}
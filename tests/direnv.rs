@@ -0,0 +1,115 @@
+mod common;
+
+use common::Sandbox;
+
+fn nested(name: &str) -> Sandbox {
+    let mut sandbox = Sandbox::new(name);
+    sandbox.shell.interactive = true;
+    sandbox.write("outer/.vssh_env", "export A=outer\nB=outer-b\n");
+    sandbox.write("outer/inner/.vssh_env", "A=inner\nC=inner-c\nunset D\n");
+    sandbox.write("outer/inner/deeper/file", "");
+    sandbox.write("elsewhere/file", "");
+    sandbox.run("A=orig; D=kept");
+    sandbox
+}
+
+fn values(sandbox: &mut Sandbox) -> String {
+    sandbox.stdout("echo \"${A-unset} ${B-unset} ${C-unset} ${D-unset}\"")
+}
+
+#[test]
+fn untrusted_files_are_refused() {
+    let mut sandbox = nested("direnv-untrusted");
+    let output = sandbox.run("cd outer");
+    assert_eq!(output.status, 0);
+    assert_eq!(
+        output.stderr,
+        format!(
+            "vssh: {}/outer/.vssh_env is not trusted; run `vssh-env allow' to load it\n",
+            sandbox.dir.display()
+        )
+    );
+    assert_eq!(values(&mut sandbox), "orig unset unset kept\n");
+}
+
+#[test]
+fn nested_files_load_and_unwind_in_order() {
+    let mut sandbox = nested("direnv-nested");
+    sandbox.run("cd outer/inner; vssh-env allow; vssh-env allow ..; cd ../..");
+    assert_eq!(values(&mut sandbox), "orig unset unset kept\n");
+    for (dir, expected) in [
+        ("outer", "outer outer-b unset kept\n"),
+        ("outer/inner", "inner outer-b inner-c unset\n"),
+        ("outer/inner/deeper", "inner outer-b inner-c unset\n"),
+        ("outer", "outer outer-b unset kept\n"),
+        ("outer/inner/deeper", "inner outer-b inner-c unset\n"),
+        ("elsewhere", "orig unset unset kept\n"),
+        ("outer/inner", "inner outer-b inner-c unset\n"),
+        (".", "orig unset unset kept\n"),
+    ] {
+        let output = sandbox.run(&format!("cd {}/{}", sandbox.dir.display(), dir));
+        assert_eq!(output.stderr, "", "{}", dir);
+        assert_eq!(values(&mut sandbox), expected, "{}", dir);
+    }
+    assert_eq!(
+        sandbox.stdout("sh -c 'echo ${A-unexported}'"),
+        "unexported\n"
+    );
+}
+
+#[test]
+fn changed_files_need_allowing_again() {
+    let mut sandbox = nested("direnv-changed");
+    sandbox.run("vssh-env allow outer");
+    sandbox.write("outer/.vssh_env", "export A=tampered\n");
+    let output = sandbox.run("cd outer");
+    assert!(
+        output.stderr.ends_with(
+            "/outer/.vssh_env has changed since it was allowed; run `vssh-env allow' to load it\n"
+        ),
+        "{}",
+        output.stderr
+    );
+    assert_eq!(values(&mut sandbox), "orig unset unset kept\n");
+    sandbox.run("vssh-env allow");
+    assert_eq!(values(&mut sandbox), "tampered unset unset kept\n");
+    sandbox.run("cd ..; vssh-env deny outer");
+    let output = sandbox.run("cd outer");
+    assert!(
+        output.stderr.contains("is not trusted"),
+        "{}",
+        output.stderr
+    );
+    assert_eq!(values(&mut sandbox), "orig unset unset kept\n");
+}
+
+#[test]
+fn vssh_env_errors() {
+    let mut sandbox = nested("direnv-errors");
+    for (line, status, message) in [
+        (
+            "vssh-env",
+            2,
+            "vssh: vssh-env: usage: vssh-env allow|deny [dir]\n",
+        ),
+        (
+            "vssh-env trust",
+            2,
+            "vssh: vssh-env: usage: vssh-env allow|deny [dir]\n",
+        ),
+        (
+            "vssh-env allow elsewhere",
+            1,
+            "vssh: vssh-env: elsewhere: no .vssh_env file\n",
+        ),
+        (
+            "vssh-env allow nope",
+            1,
+            "vssh: vssh-env: nope: No such file or directory\n",
+        ),
+    ] {
+        let output = sandbox.run(line);
+        assert_eq!(output.status, status, "{}", line);
+        assert_eq!(output.stderr, message, "{}", line);
+    }
+}
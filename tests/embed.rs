@@ -0,0 +1,130 @@
+mod common;
+
+use std::fs::File;
+use std::io::Read;
+use std::os::fd::OwnedFd;
+
+use nix::unistd::pipe;
+
+use common::Sandbox;
+
+fn drain(read_end: OwnedFd) -> String {
+    let mut text = String::new();
+    File::from(read_end).read_to_string(&mut text).unwrap();
+    text
+}
+
+#[test]
+fn pipeline_into_a_file() {
+    let mut sandbox = Sandbox::new("pipeline");
+    assert_eq!(
+        sandbox
+            .shell
+            .run_line("echo hi | tr a-z A-Z > out")
+            .unwrap(),
+        0
+    );
+    assert_eq!(sandbox.read("out"), "HI\n");
+}
+
+#[test]
+fn attached_output_is_captured() {
+    let (out_read, out_write) = pipe().unwrap();
+    let (err_read, err_write) = pipe().unwrap();
+    let mut sandbox = Sandbox::with_output("capture", Some(out_write), Some(err_write));
+    let status = sandbox
+        .shell
+        .run_line("echo out; printf '%s\\n' err >&2; sh -c 'echo child'")
+        .unwrap();
+    assert_eq!(status, 0);
+    drop(sandbox);
+    assert_eq!(drain(out_read), "out\nchild\n");
+    assert_eq!(drain(err_read), "err\n");
+}
+
+#[test]
+fn statuses_are_returned() {
+    let mut sandbox = Sandbox::new("status");
+    assert_eq!(sandbox.shell.run_line("true").unwrap(), 0);
+    assert_eq!(sandbox.shell.run_line("false").unwrap(), 1);
+    assert_eq!(sandbox.shell.run_line("sh -c 'exit 7'").unwrap(), 7);
+    assert_eq!(sandbox.shell.run_line("false | true").unwrap(), 0);
+    assert_eq!(sandbox.shell.run_line("exit 4").unwrap(), 4);
+    assert_eq!(
+        sandbox
+            .shell
+            .run_line("echo still running > after")
+            .unwrap(),
+        0
+    );
+    assert_eq!(sandbox.read("after"), "still running\n");
+}
+
+#[test]
+fn an_interrupted_line_does_not_cancel_the_next() {
+    let mut sandbox = Sandbox::new("interrupt");
+    // Only an interactive shell takes a child's SIGINT as its own ^C.
+    sandbox.shell.interactive = true;
+    assert_eq!(
+        sandbox
+            .shell
+            .run_line("sh -c 'kill -INT $$'; echo skipped > skipped")
+            .unwrap(),
+        130
+    );
+    assert!(!sandbox.dir.join("skipped").exists());
+    assert_eq!(sandbox.shell.run_line("echo ran > ran").unwrap(), 0);
+    assert_eq!(sandbox.read("ran"), "ran\n");
+    sandbox.shell.run_line("sh -c 'kill -INT $$'").unwrap();
+    assert_eq!(
+        sandbox
+            .shell
+            .run_script(&b"echo ran > script\n"[..])
+            .unwrap(),
+        0
+    );
+    assert_eq!(sandbox.read("script"), "ran\n");
+}
+
+#[test]
+fn syntax_errors_are_errors() {
+    let mut sandbox = Sandbox::new("syntax");
+    assert!(sandbox.shell.run_line("echo (").is_err());
+    assert!(sandbox.shell.run_line("if true; then").is_err());
+}
+
+#[test]
+fn state_persists_between_lines() {
+    let mut sandbox = Sandbox::new("state");
+    sandbox
+        .shell
+        .run_line("greet() { echo \"hello $1\"; }; name=world")
+        .unwrap();
+    sandbox.shell.run_line("mkdir sub; cd sub").unwrap();
+    sandbox
+        .shell
+        .run_line("greet \"$name\" >> log; greet again >> log")
+        .unwrap();
+    assert_eq!(sandbox.read("sub/log"), "hello world\nhello again\n");
+}
+
+#[test]
+fn scripts_span_lines() {
+    let mut sandbox = Sandbox::new("script");
+    let script = "for i in 1 2 3\ndo\n  echo $i\ndone > out\nif [ -s out ]; then\n  wc -l < out > count\nfi\n";
+    assert_eq!(sandbox.shell.run_script(script.as_bytes()).unwrap(), 0);
+    assert_eq!(sandbox.read("out"), "1\n2\n3\n");
+    assert_eq!(sandbox.read("count").trim(), "3");
+}
+
+#[test]
+fn redirections_and_builtins() {
+    let mut sandbox = Sandbox::new("redirect");
+    sandbox
+        .shell
+        .run_line("echo one > f; echo two >> f; cat < f | sort -r > g; printf '%s-' a b > h")
+        .unwrap();
+    assert_eq!(sandbox.read("f"), "one\ntwo\n");
+    assert_eq!(sandbox.read("g"), "two\none\n");
+    assert_eq!(sandbox.read("h"), "a-b-");
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::vssh_with_rc;
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vssh-env-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("env.sh"), "MARK=from-env\n").unwrap();
+    fs::write(dir.join("script"), "echo \"script $MARK\"\n").unwrap();
+    dir
+}
+
+#[test]
+fn vssh_env_reaches_non_interactive_shells() {
+    let dir = scratch("noninteractive");
+    let env = [("VSSH_ENV", "$HOME/env.sh")];
+    let output = vssh_with_rc(&dir, &["-c", "echo \"c $MARK\""], &env, "");
+    assert_eq!(output.stdout, "c from-env\n");
+    let output = vssh_with_rc(&dir, &["script"], &env, "");
+    assert_eq!(output.stdout, "script from-env\n");
+    let output = vssh_with_rc(&dir, &[], &env, "echo \"stdin $MARK\"\n");
+    assert_eq!(output.stdout, "stdin from-env\n");
+    let output = vssh_with_rc(&dir, &["-c", "echo \"c $MARK\""], &[("ENV", "env.sh")], "");
+    assert_eq!(output.stdout, "c \n");
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn env_reaches_interactive_shells() {
+    let dir = scratch("interactive");
+    fs::write(dir.join(".vsshrc"), "MARK=from-rc; RC=$MARK\n").unwrap();
+    let env = [("ENV", "${HOME}/env.sh"), ("PS1", "")];
+    let output = vssh_with_rc(&dir, &["-i"], &env, "echo \"$RC $MARK\"\n");
+    assert_eq!(output.stdout, "from-rc from-env\nexit\n");
+    let output = vssh_with_rc(
+        &dir,
+        &["-i"],
+        &[("VSSH_ENV", "env.sh"), ("PS1", "")],
+        "echo \"[$MARK]\"\n",
+    );
+    assert_eq!(output.stdout, "[from-rc]\nexit\n");
+    let output = vssh_with_rc(&dir, &["-i", "-l"], &env, "echo \"[$MARK]\"\n");
+    assert_eq!(output.stdout, "[from-rc]\nexit\n");
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn norc_and_noprofile_skip_env_files() {
+    let dir = scratch("suppressed");
+    let env = [("ENV", "env.sh"), ("VSSH_ENV", "env.sh"), ("PS1", "")];
+    for flag in ["--norc", "--noprofile"] {
+        let output = vssh_with_rc(&dir, &[flag, "-c", "echo \"[$MARK]\""], &env, "");
+        assert_eq!(output.stdout, "[]\n", "{}", flag);
+        let output = vssh_with_rc(&dir, &[flag, "-i"], &env, "echo \"[$MARK]\"\n");
+        assert_eq!(output.stdout, "[]\nexit\n", "{}", flag);
+    }
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn env_file_failures_are_warnings() {
+    let dir = scratch("failures");
+    fs::write(dir.join("broken.sh"), "MARK=early; if then\n").unwrap();
+    fs::write(
+        dir.join("failing.sh"),
+        "MARK=early; false; no-such-command-here\n",
+    )
+    .unwrap();
+    for (value, warning) in [
+        ("missing.sh", "missing.sh: No such file or directory"),
+        ("broken.sh", "broken.sh: "),
+        (
+            "$(echo env.sh)",
+            "VSSH_ENV: only parameter expansion is performed",
+        ),
+    ] {
+        let output = vssh_with_rc(
+            &dir,
+            &["-c", "echo \"ran [$MARK]\""],
+            &[("VSSH_ENV", value)],
+            "",
+        );
+        assert_eq!(output.status, 0, "{}", value);
+        assert_eq!(output.stdout, "ran []\n", "{}", value);
+        assert!(
+            output.stderr.contains(warning),
+            "{}: {}",
+            value,
+            output.stderr
+        );
+    }
+    let output = vssh_with_rc(
+        &dir,
+        &["-c", "echo \"ran [$MARK]\""],
+        &[("VSSH_ENV", "failing.sh")],
+        "",
+    );
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, "ran [early]\n");
+    assert!(
+        output.stderr.contains("command not found"),
+        "{}",
+        output.stderr
+    );
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn env_files_that_start_vssh_do_not_recurse() {
+    let dir = scratch("recursion");
+    let vssh = env!("CARGO_BIN_EXE_vssh");
+    fs::write(
+        dir.join("env.sh"),
+        format!(
+            "echo loading\n{} -c 'echo \"inner [$MARK]\"'\nMARK=outer\n",
+            vssh
+        ),
+    )
+    .unwrap();
+    let output = vssh_with_rc(
+        &dir,
+        &["-c", "echo \"done $MARK\""],
+        &[("VSSH_ENV", "env.sh")],
+        "",
+    );
+    assert_eq!(output.stdout, "loading\ninner []\ndone outer\n");
+    let output = vssh_with_rc(
+        &dir,
+        &["-c", "echo \"[$VSSH_LOADING_ENV]\""],
+        &[("VSSH_ENV", "env.sh")],
+        "",
+    );
+    assert!(output.stdout.ends_with("[]\n"), "{}", output.stdout);
+    fs::remove_dir_all(dir).ok();
+}
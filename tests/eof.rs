@@ -0,0 +1,86 @@
+mod common;
+
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use common::vssh;
+
+#[test]
+fn piped_scripts_end_with_the_last_status() {
+    for (script, status, stdout) in [
+        ("", 0, ""),
+        ("echo ls", 0, "ls\n"),
+        ("false", 1, ""),
+        ("echo a\nfalse\n", 1, "a\n"),
+        ("false\ntrue\n", 0, ""),
+        ("sh -c 'exit 9'", 9, ""),
+        ("exit 3", 3, ""),
+    ] {
+        let output = vssh(&[], &[], script);
+        assert_eq!(output.status, status, "{:?}", script);
+        assert_eq!(output.stdout, stdout, "{:?}", script);
+    }
+}
+
+#[test]
+fn closed_stdin_terminates() {
+    let started = Instant::now();
+    let status = Command::new(env!("CARGO_BIN_EXE_vssh"))
+        .args(["--norc", "--noprofile"])
+        .stdin(Stdio::null())
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn interactive_eof_prints_exit() {
+    let output = vssh(&["-i"], &[("PS1", "$ ")], "echo hi\n");
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, "$ hi\n$ exit\n");
+    let output = vssh(&["-i"], &[("PS1", "$ ")], "false\n");
+    assert_eq!(output.status, 1);
+}
+
+#[test]
+fn ignoreeof_counts_end_of_file() {
+    let output = vssh(
+        &["-i"],
+        &[("PS1", "$ "), ("IGNOREEOF", "2")],
+        "set -o ignoreeof\n",
+    );
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, "$ $ $ $ exit\n");
+    assert_eq!(
+        output
+            .stderr
+            .matches("Use \"exit\" to leave the shell.")
+            .count(),
+        2
+    );
+    let output = vssh(&["-i"], &[("PS1", "$ ")], "set -o ignoreeof\n");
+    let warnings = output.stderr.matches("Use \"exit\"").count();
+    assert_eq!(warnings, 10, "{}", output.stderr);
+}
+
+#[test]
+fn eof_warns_once_about_stopped_jobs() {
+    let output = vssh(
+        &["-i"],
+        &[("PS1", "")],
+        "set -m\nsleep 100 &\nkill -STOP $!\nsleep 0.2\necho $!\n",
+    );
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stderr.matches("There are stopped jobs.").count(), 1);
+    let pid: i32 = output.stdout.lines().next().unwrap().parse().unwrap();
+    let started = Instant::now();
+    while unsafe { libc::kill(pid, 0) } == 0 {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "{} survived",
+            pid
+        );
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
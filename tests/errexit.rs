@@ -0,0 +1,56 @@
+mod common;
+
+use common::{vssh, Sandbox};
+
+#[test]
+fn failures_exit_the_shell() {
+    for script in [
+        "set -e; echo one; false; echo two",
+        "set -o errexit; echo one; (exit 3); echo two",
+        "set -e; f() { echo one; false; echo two; }; f; echo three",
+        "set -e; echo one; echo > /no/such/dir/file; echo two",
+    ] {
+        let output = vssh(&["-c", script], &[], "");
+        assert_eq!(output.stdout, "one\n", "{}", script);
+        assert_ne!(output.status, 0, "{}", script);
+    }
+    let output = vssh(
+        &["-c", "set -e; trap 'echo bye $?' EXIT; sh -c 'exit 4'"],
+        &[],
+        "",
+    );
+    assert_eq!(output.stdout, "bye 4\n");
+    assert_eq!(output.status, 4);
+    let output = vssh(&[], &[], "set -e\nfalse\necho two\n");
+    assert_eq!(output.stdout, "");
+    assert_eq!(output.status, 1);
+}
+
+#[test]
+fn tested_commands_are_exempt() {
+    let script = "set -e
+if false; then :; fi
+while false; do :; done
+until true; do :; done
+false && echo no
+false || true
+! true
+false | true
+f() { false; echo in-f; }
+f || echo no
+if f; then echo tested; fi
+set +e
+false
+echo done";
+    let output = vssh(&["-c", script], &[], "");
+    assert_eq!(output.stdout, "in-f\nin-f\ntested\ndone\n");
+    assert_eq!(output.status, 0);
+}
+
+#[test]
+fn embedded_shells_stop_the_script() {
+    let mut sandbox = Sandbox::new("errexit-embedded");
+    let output = sandbox.script("set -e\necho one\nfalse\necho two\n");
+    assert_eq!(output.stdout, "one\n");
+    assert_eq!(output.status, 1);
+}
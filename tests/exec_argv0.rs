@@ -0,0 +1,113 @@
+mod common;
+
+use common::vssh;
+
+// cat of its own command line shows the argv the shell built for it.
+fn argv(line: &str) -> Vec<String> {
+    let output = vssh(&["-c", line], &[], "");
+    assert_eq!(output.status, 0, "{}: {:?}", line, output);
+    let mut args: Vec<String> = output.stdout.split('\0').map(str::to_string).collect();
+    assert_eq!(args.pop().as_deref(), Some(""), "{}", line);
+    args
+}
+
+#[test]
+fn argv0_is_the_typed_word_by_default() {
+    assert_eq!(
+        argv("cat /proc/self/cmdline"),
+        ["cat", "/proc/self/cmdline"]
+    );
+    assert_eq!(
+        argv("exec cat /proc/self/cmdline"),
+        ["cat", "/proc/self/cmdline"]
+    );
+    let cat = std::process::Command::new("sh")
+        .args(["-c", "command -v cat"])
+        .output()
+        .unwrap();
+    let cat = String::from_utf8(cat.stdout).unwrap().trim().to_string();
+    assert_eq!(argv(&format!("{} /proc/self/cmdline", cat))[0], cat);
+}
+
+#[test]
+fn exec_presents_a_chosen_argv0() {
+    for (line, argv0) in [
+        ("exec -a shown cat /proc/self/cmdline", "shown"),
+        ("exec -a 'with space' cat /proc/self/cmdline", "with space"),
+        ("exec -l cat /proc/self/cmdline", "-cat"),
+        ("exec -l -a name cat /proc/self/cmdline", "-name"),
+        ("exec -- cat /proc/self/cmdline", "cat"),
+    ] {
+        assert_eq!(argv(line), [argv0, "/proc/self/cmdline"], "{}", line);
+    }
+}
+
+#[test]
+fn exec_replaces_the_shell() {
+    let output = vssh(&["-c", "exec sh -c 'exit 7'; echo not reached"], &[], "");
+    assert_eq!(output.status, 7);
+    assert_eq!(output.stdout, "");
+    let output = vssh(
+        &["-c", "export X=1; exec -c cat /proc/self/environ"],
+        &[],
+        "",
+    );
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, "");
+    let output = vssh(&["-c", "export X=1; exec cat /proc/self/environ"], &[], "");
+    assert!(output.stdout.split('\0').any(|var| var == "X=1"));
+}
+
+#[test]
+fn login_children_see_the_dash() {
+    let vssh_path = env!("CARGO_BIN_EXE_vssh");
+    let line = format!(
+        "exec -l {} --norc --noprofile -c 'echo \"$0\"' name",
+        vssh_path
+    );
+    let output = vssh(&["-c", &line], &[], "");
+    assert_eq!(output.stdout, "name\n");
+    let output = vssh(
+        &["-c", &format!("exec -l {} --norc --noprofile", vssh_path)],
+        &[],
+        "echo \"$0\"\n",
+    );
+    assert_eq!(output.stdout, format!("-{}\n", vssh_path));
+    let output = vssh(
+        &[
+            "-c",
+            &format!("exec -a custom {} --norc --noprofile", vssh_path),
+        ],
+        &[],
+        "echo \"$0\"\n",
+    );
+    assert_eq!(output.stdout, "custom\n");
+}
+
+#[test]
+fn exec_errors() {
+    for (line, status, message) in [
+        ("exec -a", 2, "exec: -a: option requires an argument\n"),
+        ("exec -x true", 2, "exec: -x: invalid option\n"),
+        (
+            "exec no-such-command-here",
+            127,
+            "no-such-command-here: command not found\n",
+        ),
+        (
+            "exec ./no-such-file",
+            127,
+            "./no-such-file: No such file or directory\n",
+        ),
+    ] {
+        let output = vssh(&["-c", line], &[], "");
+        assert_eq!(output.status, status, "{}", line);
+        let first = output.stderr.lines().next().unwrap_or("");
+        assert!(
+            format!("{}\n", first).ends_with(&format!(": {}", message)),
+            "{}: {}",
+            line,
+            output.stderr
+        );
+    }
+}
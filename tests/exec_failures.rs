@@ -0,0 +1,160 @@
+mod common;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use common::{vssh, Sandbox};
+
+fn not_executable(sandbox: &Sandbox, name: &str) {
+    sandbox.write(name, "echo should not run\n");
+    fs::set_permissions(sandbox.dir.join(name), fs::Permissions::from_mode(0o644)).unwrap();
+}
+
+#[test]
+fn missing_commands_exit_127() {
+    let mut sandbox = Sandbox::new("exec-missing");
+    let output = sandbox.run("no-such-command-here arg");
+    assert_eq!(output.status, 127);
+    assert_eq!(
+        output.stderr,
+        "vssh: no-such-command-here: command not found\n"
+    );
+    assert_eq!(output.stdout, "");
+    let output = sandbox.run("./no-such-file");
+    assert_eq!(output.status, 127);
+    assert_eq!(
+        output.stderr,
+        "vssh: ./no-such-file: No such file or directory\n"
+    );
+}
+
+#[test]
+fn unexecutable_files_exit_126() {
+    let mut sandbox = Sandbox::new("exec-denied");
+    not_executable(&sandbox, "script");
+    let output = sandbox.run("./script");
+    assert_eq!(output.status, 126);
+    assert_eq!(output.stderr, "vssh: ./script: Permission denied\n");
+    assert_eq!(output.stdout, "");
+}
+
+#[test]
+fn statuses_reach_lists_and_dollar_question() {
+    let mut sandbox = Sandbox::new("exec-status");
+    not_executable(&sandbox, "script");
+    let output = sandbox.run("no-such-command-here; echo $?; ./script; echo $?");
+    assert_eq!(output.stdout, "127\n126\n");
+    let output = sandbox.run("no-such-command-here && echo and || echo or $?");
+    assert_eq!(output.stdout, "or 127\n");
+    let output = sandbox.run("no-such-command-here | ./script | cat; echo ${PIPESTATUS[@]}");
+    assert_eq!(output.stdout, "127 126 0\n");
+}
+
+#[test]
+fn scripts_exit_with_the_code() {
+    let output = vssh(&["-c", "no-such-command-here"], &[], "");
+    assert_eq!(output.status, 127);
+    assert!(
+        output
+            .stderr
+            .ends_with(": no-such-command-here: command not found\n"),
+        "{}",
+        output.stderr
+    );
+    assert_eq!(output.stdout, "");
+}
+
+const FAILURES: &str = "printf start; no-such-command-here; printf 'end\\n'\n\
+                        cat < missing; echo \"redirect $?\"\n\
+                        no-such-command-here | cat; echo \"pipe ${PIPESTATUS[@]}\"\n\
+                        echo x | cat < missing | cat; echo \"pipe redirect $?\"\n";
+
+const FAILURE_OUTPUT: &str = "startend\nredirect 1\npipe 127 0\npipe redirect 1\n";
+
+fn failure_messages(stderr: &str) -> Vec<&str> {
+    stderr
+        .lines()
+        .filter(|line| !line.starts_with("Process ") && !line.starts_with("Pipeline "))
+        .map(|line| line.rsplit_once(": ").map_or(line, |(_, message)| message))
+        .collect()
+}
+
+#[test]
+fn failed_children_leave_the_shell_intact() {
+    let mut sandbox = Sandbox::new("exec-child");
+    let output = sandbox.script(FAILURES);
+    assert_eq!(output.stdout, FAILURE_OUTPUT);
+    assert_eq!(
+        failure_messages(&output.stderr),
+        [
+            "command not found",
+            "No such file or directory",
+            "command not found",
+            "No such file or directory"
+        ]
+    );
+}
+
+#[test]
+fn failed_children_in_a_script_process() {
+    let output = vssh(&[], &[], FAILURES);
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, FAILURE_OUTPUT);
+    assert_eq!(
+        failure_messages(&output.stderr).len(),
+        4,
+        "{}",
+        output.stderr
+    );
+}
+
+#[test]
+fn failed_children_in_an_interactive_shell() {
+    let output = vssh(&["-i"], &[("PS1", "")], FAILURES);
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, format!("{}exit\n", FAILURE_OUTPUT));
+    assert_eq!(
+        failure_messages(&output.stderr).len(),
+        4,
+        "{}",
+        output.stderr
+    );
+}
+
+fn executable(sandbox: &Sandbox, name: &str, contents: &[u8]) {
+    let path = sandbox.dir.join(name);
+    fs::write(&path, contents).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+fn scripts_without_an_interpreter_run_in_the_shell() {
+    let mut sandbox = Sandbox::new("exec-noexec");
+    executable(&sandbox, "hi", b"echo hi\n");
+    assert_eq!(sandbox.stdout("./hi"), "hi\n");
+    executable(&sandbox, "args", b"echo \"$0\" $# \"$1\"\nexit 4\n");
+    let output = sandbox.run("./args 'a b' c");
+    assert_eq!(output.stdout, "./args 2 a b\n");
+    assert_eq!(output.status, 4);
+    let output = sandbox.run("PATH=.:$PATH; hi | tr a-z A-Z");
+    assert_eq!(output.stdout, "HI\n");
+}
+
+#[test]
+fn binary_files_are_not_parsed() {
+    let mut sandbox = Sandbox::new("exec-binary");
+    executable(&sandbox, "elf", b"\x7fELF\x02\x01\x01\0garbage\0\0\0");
+    executable(&sandbox, "data", b"echo \0\x01\x02 not text\n");
+    for name in ["elf", "data"] {
+        let output = sandbox.run(&format!("./{}", name));
+        assert_eq!(output.status, 126, "{}", name);
+        assert_eq!(
+            output.stderr,
+            format!(
+                "vssh: ./{}: cannot execute binary file: Exec format error\n",
+                name
+            )
+        );
+        assert_eq!(output.stdout, "");
+    }
+}
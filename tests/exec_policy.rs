@@ -0,0 +1,102 @@
+mod common;
+
+use std::process::Command;
+
+use common::vssh;
+
+const POLICY: &str = "VSSH_EXEC_POLICY='cat=oom:300 awk=nice:7 ionice=ionice:idle show=oom:200'\n";
+const NICENESS: &str = "awk '{print $19}' /proc/self/stat";
+const OOM: &str = "cat /proc/self/oom_score_adj";
+
+#[test]
+fn children_report_the_configured_values() {
+    let script = format!(
+        "{}{}\n{}\nionice\n/bin/{}\n{} | cat\n",
+        POLICY, OOM, NICENESS, OOM, OOM
+    );
+    let output = vssh(&[], &[], script);
+    assert_eq!(output.stderr, "");
+    assert_eq!(output.stdout, "300\n7\nidle\n300\n300\n");
+    let output = vssh(&["-c", OOM], &[("VSSH_EXEC_POLICY", "cat=oom:250")], "");
+    assert_eq!(output.stdout, "250\n");
+}
+
+#[test]
+fn the_resolved_command_decides() {
+    let script = format!(
+        "{}show() {{ {}; }}\nshow\npolicy off {}\nnice -n 3 {}\npolicy off show\n",
+        POLICY, OOM, OOM, NICENESS
+    );
+    let output = vssh(&[], &[], script);
+    assert_eq!(output.stdout, "300\n0\n3\n0\n");
+}
+
+#[test]
+fn the_builtin_shows_rules() {
+    let script = format!("{}policy cat show nope\npolicy\n", POLICY);
+    let output = vssh(&[], &[], script);
+    assert_eq!(
+        output.stdout,
+        "cat: oom:300\nshow: oom:200\nnope: none\n\
+         cat=oom:300\nawk=nice:7\nionice=ionice:idle\nshow=oom:200\n"
+    );
+    let output = vssh(
+        &["-c", "policy; echo $?"],
+        &[("VSSH_EXEC_POLICY", "cat=nice:30")],
+        "",
+    );
+    assert_eq!(output.stdout, "cat=\n1\n");
+    assert!(
+        output
+            .stderr
+            .ends_with(": policy: `nice:30': invalid adjustment\n"),
+        "{}",
+        output.stderr
+    );
+    let output = vssh(&["-c", "policy off"], &[], "");
+    assert_eq!(output.status, 2);
+}
+
+#[test]
+fn failures_warn_once_and_still_run() {
+    let vssh_path = env!("CARGO_BIN_EXE_vssh");
+    let script = format!(
+        "VSSH_EXEC_POLICY='cat=nice:-5,oom:-100'; {}; {}; {} | cat",
+        OOM, OOM, OOM
+    );
+    // Root may lower both, so drop to nobody first.
+    let mut command = match unsafe { libc::geteuid() } {
+        0 => {
+            let mut command = Command::new("setpriv");
+            command.args([
+                "--reuid=65534",
+                "--regid=65534",
+                "--clear-groups",
+                vssh_path,
+            ]);
+            command
+        }
+        _ => Command::new(vssh_path),
+    };
+    let output = command
+        .args(["--norc", "--noprofile", "-c", &script])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0\n0\n0\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let warnings: Vec<&str> = stderr.lines().collect();
+    assert_eq!(warnings.len(), 2, "{}", stderr);
+    assert!(
+        warnings[0].ends_with(": warning: cat: cannot set niceness (nice:-5): Permission denied"),
+        "{}",
+        stderr
+    );
+    assert!(
+        warnings[1].ends_with(
+            ": warning: cat: cannot set OOM score adjustment (oom:-100): Permission denied"
+        ),
+        "{}",
+        stderr
+    );
+}
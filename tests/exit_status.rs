@@ -0,0 +1,57 @@
+mod common;
+
+use std::os::unix::process::ExitStatusExt;
+use std::process::Command;
+
+use common::vssh;
+
+fn run(line: &str) -> std::process::ExitStatus {
+    Command::new(env!("CARGO_BIN_EXE_vssh"))
+        .args(["--norc", "--noprofile", "-c", line])
+        .status()
+        .unwrap()
+}
+
+#[test]
+fn signals_to_the_shell_reach_its_parent() {
+    for (line, signal) in [
+        ("kill -INT $$", libc::SIGINT),
+        ("kill -TERM $$", libc::SIGTERM),
+        ("kill -HUP $$; echo not reached", libc::SIGHUP),
+    ] {
+        let status = run(line);
+        assert_eq!(status.signal(), Some(signal), "{}: {:?}", line, status);
+        assert_eq!(status.code(), None, "{}", line);
+    }
+}
+
+#[test]
+fn exit_codes_wrap() {
+    for (line, code) in [
+        ("exit 0", 0),
+        ("exit 255", 255),
+        ("exit 256", 0),
+        ("exit 257", 1),
+        ("exit -1", 255),
+        ("sh -c 'exit 7'", 7),
+        ("sh -c 'kill -9 $$'", 137),
+        ("exit 130", 130),
+    ] {
+        let status = run(line);
+        assert_eq!(status.code(), Some(code), "{}: {:?}", line, status);
+    }
+}
+
+#[test]
+fn signal_statuses_flow_through_the_shell() {
+    let output = vssh(
+        &[],
+        &[],
+        "sh -c 'kill -TERM $$'; echo $?\n\
+         sh -c 'kill -KILL $$' | sh -c 'exit 3'; echo ${PIPESTATUS[@]}\n\
+         if sh -c 'kill -USR1 $$'; then echo yes; else echo \"no $?\"; fi\n\
+         sh -c 'kill -SEGV $$' || echo \"or $?\"\n\
+         (exit 300); echo $?\n",
+    );
+    assert_eq!(output.stdout, "143\n137 3\nno 138\nor 139\n44\n");
+}
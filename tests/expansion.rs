@@ -0,0 +1,185 @@
+mod common;
+
+use common::{vssh, Sandbox};
+
+const SETUP: &str = "g='*.x'; sp='p  q'; empty=; n=5; colons=a:b; set -- 'one two' three";
+
+const FILES: [&str; 7] = ["a.x", "b.x", "B.x", "_.x", "é.x", "sp ace.x", "d1/f"];
+
+// The command line typed, and the fields it must expand to.
+const WORDS: &[(&str, &str)] = &[
+    ("$g", "<B.x><_.x><a.x><b.x><sp ace.x><é.x>"),
+    ("\"$g\"", "<*.x>"),
+    ("'$g'", "<$g>"),
+    ("${g}\"$sp\"", "<*.xp  q>"),
+    ("$sp", "<p><q>"),
+    ("\"$sp\"", "<p  q>"),
+    ("\"${sp}\"$sp", "<p  qp><q>"),
+    ("$empty", "<>"),
+    ("\"$empty\" x", "<><x>"),
+    ("$none x", "<x>"),
+    ("$colons", "<a:b>"),
+    ("*.x", "<B.x><_.x><a.x><b.x><sp ace.x><é.x>"),
+    ("\\*.x", "<*.x>"),
+    ("\"*\".x", "<*.x>"),
+    ("*.nomatch", "<*.nomatch>"),
+    ("{a,b}.x", "<a.x><b.x>"),
+    ("{1..3}", "<1><2><3>"),
+    ("{a..c}{1,2}", "<a1><a2><b1><b2><c1><c2>"),
+    ("'{a,b}'", "<{a,b}>"),
+    ("\\{a,b\\}", "<{a,b}>"),
+    ("~/{a,b}", "<HOME/a><HOME/b>"),
+    ("{~,x}", "<HOME><x>"),
+    ("~nosuchuser", "<~nosuchuser>"),
+    ("x~", "<x~>"),
+    ("\"~\"", "<~>"),
+    ("{$sp,z}", "<p><q><z>"),
+    ("$(echo a b)", "<a><b>"),
+    ("\"$(echo a b)\"", "<a b>"),
+    ("$(echo '*.x')", "<B.x><_.x><a.x><b.x><sp ace.x><é.x>"),
+    ("\"$(echo '*.x')\"", "<*.x>"),
+    ("`echo hi there`", "<hi><there>"),
+    ("a$(echo b c)d", "<ab><cd>"),
+    ("$(printf 'a\\n\\n')", "<a>"),
+    ("${#sp}", "<4>"),
+    ("$((1 + 2))", "<3>"),
+    ("$((n * 2)){x,y}", "<10x><10y>"),
+    ("${none:-d*}", "<d1>"),
+    ("\"${none:-$sp}\"", "<p  q>"),
+    ("${none:-\"$sp\"}", "<p  q>"),
+    ("d{1,2}/", "<d1/><d2/>"),
+    ("\"$@\"", "<one two><three>"),
+    ("$@", "<one><two><three>"),
+    ("\"$*\"", "<one two three>"),
+    ("'a'\"b\"c", "<abc>"),
+    ("a\\ b", "<a b>"),
+    ("\"a\\\"b\"", "<a\"b>"),
+    ("\\$g", "<$g>"),
+];
+
+fn sandbox(name: &str) -> Sandbox {
+    let mut sandbox = Sandbox::new(name);
+    for file in FILES {
+        sandbox.write(file, "");
+    }
+    sandbox.run(SETUP);
+    sandbox
+}
+
+#[test]
+fn words_expand_in_posix_order() {
+    let mut sandbox = sandbox("expand-table");
+    let home = sandbox.dir.display().to_string();
+    for (line, fields) in WORDS {
+        assert_eq!(
+            sandbox.stdout(&format!("printf '<%s>' {}; echo", line)),
+            format!("{}\n", fields.replace("HOME", &home)),
+            "{}",
+            line
+        );
+    }
+}
+
+#[test]
+fn glob_order_ignores_the_locale() {
+    let mut sandbox = sandbox("expand-locale");
+    for locale in ["C", "POSIX", "en_US.UTF-8", "de_DE.UTF-8"] {
+        for variable in ["LC_ALL", "LC_COLLATE", "LANG"] {
+            let output = sandbox.stdout(&format!(
+                "({}={} printf '<%s>' *.x; echo)",
+                variable, locale
+            ));
+            assert_eq!(
+                output, "<B.x><_.x><a.x><b.x><sp ace.x><é.x>\n",
+                "{}={}",
+                variable, locale
+            );
+        }
+    }
+}
+
+#[test]
+fn expand_debug_shows_each_stage() {
+    let mut sandbox = sandbox("expand-debug");
+    let home = sandbox.dir.display().to_string();
+    assert_eq!(
+        sandbox.stdout("expand-debug '~/{a,b}' '$g' '\"$(echo a b)\"'"),
+        format!(
+            "word: ~/{{a,b}}\n\
+             \x20 brace: <~/a> <~/b>\n\
+             \x20 tilde: <'{home}'/a> <'{home}'/b>\n\
+             \x20 substitution: <'{home}'/a> <'{home}'/b>\n\
+             \x20 splitting: <'{home}'/a> <'{home}'/b>\n\
+             \x20 pathname: <'{home}'/a> <'{home}'/b>\n\
+             \x20 quote removal: <{home}/a> <{home}/b>\n\
+             word: $g\n\
+             \x20 brace: <${{g}}>\n\
+             \x20 tilde: <${{g}}>\n\
+             \x20 substitution: <*.x>\n\
+             \x20 splitting: <*.x>\n\
+             \x20 pathname: <'B.x'> <'_.x'> <'a.x'> <'b.x'> <'sp ace.x'> <'é.x'>\n\
+             \x20 quote removal: <B.x> <_.x> <a.x> <b.x> <sp ace.x> <é.x>\n\
+             word: \"$(echo a b)\"\n\
+             \x20 brace: <\"$(echo a b)\">\n\
+             \x20 tilde: <\"$(echo a b)\">\n\
+             \x20 substitution: <'a b'>\n\
+             \x20 splitting: <'a b'>\n\
+             \x20 pathname: <'a b'>\n\
+             \x20 quote removal: <a b>\n",
+            home = home
+        )
+    );
+    let output = sandbox.run("expand-debug");
+    assert_eq!(output.status, 2);
+    assert_eq!(
+        output.stderr,
+        "vssh: expand-debug: usage: expand-debug word ...\n"
+    );
+}
+
+#[test]
+fn assignments_expand_tildes_after_colons() {
+    let mut sandbox = Sandbox::new("expand-assignments");
+    let home = sandbox.dir.display().to_string();
+    for (line, expanded) in [
+        ("a=~:~/w:x~; echo $a", "HOME:HOME/w:x~"),
+        ("export CDPATH=~:~/work; echo $CDPATH", "HOME:HOME/work"),
+        ("readonly r=~/r; echo $r", "HOME/r"),
+        ("echo P=~:~/x", "P=HOME:HOME/x"),
+        ("echo P=\\~:~ 'Q=~' \"R=~\"", "P=~:HOME Q=~ R=~"),
+        ("echo ~:~ 1x=~:~", "~:~ 1x=~:~"),
+        ("b='~:~'; echo $b", "~:~"),
+        ("c=a:~:b=~; echo $c", "a:HOME:b=~"),
+    ] {
+        assert_eq!(
+            sandbox.stdout(line),
+            format!("{}\n", expanded.replace("HOME", &home)),
+            "{}",
+            line
+        );
+    }
+}
+
+#[test]
+fn division_by_zero_abandons_the_command() {
+    let mut sandbox = Sandbox::new("arith-zero");
+    for expr in ["1/0", "5%0", "n/=0"] {
+        let line = format!("n=1; echo $(({})); touch ran", expr);
+        let output = sandbox.run(&line);
+        assert_eq!(output.status, 1, "{}", line);
+        assert_eq!(output.stdout, "", "{}", line);
+        assert!(
+            output.stderr.ends_with(": division by 0\n"),
+            "{}: {}",
+            line,
+            output.stderr
+        );
+        assert!(!sandbox.dir.join("ran").exists(), "{}", line);
+    }
+    assert_eq!(sandbox.stdout("echo next"), "next\n");
+    let output = sandbox.run("((1/0)); echo $?");
+    assert_eq!(output.stdout, "1\n");
+    let output = vssh(&[], &[], "echo $((1/0))\necho next\n");
+    assert_eq!(output.status, 1);
+    assert_eq!(output.stdout, "");
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use common::Sandbox;
+use vssh::embed::Config;
+use vssh::shell::Shell;
+
+fn add(sandbox: &mut Sandbox, lines: &[&str]) {
+    for line in lines {
+        sandbox.shell.add_history(line);
+    }
+}
+
+// `history` output without the five-column numbers.
+fn listed(sandbox: &mut Sandbox) -> Vec<String> {
+    let output = sandbox.stdout("history");
+    output.lines().map(|line| line[7..].to_string()).collect()
+}
+
+#[test]
+fn histsize_bounds_the_list() {
+    let mut sandbox = Sandbox::new("history-size");
+    sandbox.run("HISTSIZE=3");
+    add(&mut sandbox, &["one", "two", "three", "four", "five"]);
+    assert_eq!(
+        sandbox.stdout("history"),
+        "    3  three\n    4  four\n    5  five\n"
+    );
+    assert_eq!(sandbox.stdout("history 2"), "    4  four\n    5  five\n");
+    sandbox.run("HISTSIZE=0");
+    add(&mut sandbox, &["six"]);
+    assert_eq!(sandbox.stdout("history"), "");
+}
+
+#[test]
+fn histcontrol_skips_entries() {
+    for (control, expected) in [
+        ("", vec!["ls", "ls", " secret", "pwd", "ls"]),
+        ("ignoredups", vec!["ls", " secret", "pwd", "ls"]),
+        ("ignorespace", vec!["ls", "ls", "pwd", "ls"]),
+        ("ignoreboth", vec!["ls", "pwd", "ls"]),
+        ("ignorespace:ignoredups", vec!["ls", "pwd", "ls"]),
+        ("erasedups", vec![" secret", "pwd", "ls"]),
+    ] {
+        let mut sandbox = Sandbox::new("history-control");
+        sandbox.run(&format!("HISTCONTROL={}", control));
+        add(&mut sandbox, &["ls", "ls", " secret", "pwd", "ls"]);
+        assert_eq!(listed(&mut sandbox), expected, "{:?}", control);
+    }
+}
+
+#[test]
+fn histignore_patterns() {
+    let mut sandbox = Sandbox::new("history-ignore");
+    sandbox.run("HISTIGNORE='ls:cd *:[bf]g'");
+    add(
+        &mut sandbox,
+        &[
+            "ls", "ls -l", "cd /tmp", "cd", "bg", "fg", "jobs", "echo ls",
+        ],
+    );
+    assert_eq!(listed(&mut sandbox), ["ls -l", "cd", "jobs", "echo ls"]);
+}
+
+#[test]
+fn timestamps_are_comment_lines() {
+    let mut sandbox = Sandbox::new("history-time");
+    sandbox.write(".vssh_history", "#100\nold\n#200\nfirst\\\nsecond\nplain\n");
+    sandbox.run("HISTTIMEFORMAT='%s '; history -r");
+    assert_eq!(
+        sandbox.stdout("history"),
+        "    1  100 old\n    2  200 first\nsecond\n    3  plain\n"
+    );
+    sandbox.run("history -c");
+    add(&mut sandbox, &["new"]);
+    sandbox.run("history -w");
+    let text = sandbox.read(".vssh_history");
+    let (stamp, line) = text.split_once('\n').unwrap();
+    assert_eq!(line, "new\n");
+    let stamp: i64 = stamp.strip_prefix('#').unwrap().parse().unwrap();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    assert!((now - stamp).abs() < 60, "{}", stamp);
+    sandbox.run("unset HISTTIMEFORMAT; history -w");
+    assert_eq!(sandbox.read(".vssh_history"), "new\n");
+}
+
+#[test]
+fn histfilesize_bounds_the_file() {
+    let mut sandbox = Sandbox::new("history-file-size");
+    sandbox.run("HISTFILESIZE=2");
+    add(&mut sandbox, &["one", "two", "three"]);
+    sandbox.run("history -w");
+    assert_eq!(sandbox.read(".vssh_history"), "two\nthree\n");
+    add(&mut sandbox, &["four"]);
+    sandbox.run("history -a");
+    assert_eq!(sandbox.read(".vssh_history"), "three\nfour\n");
+}
+
+fn another_shell(sandbox: &Sandbox) -> Shell {
+    Shell::with_config(Config {
+        env: Some(vec![
+            ("PATH".into(), std::env::var("PATH").unwrap_or_default()),
+            ("HOME".into(), sandbox.dir.display().to_string()),
+        ]),
+        cwd: Some(sandbox.dir.clone()),
+        ..Config::default()
+    })
+    .unwrap()
+}
+
+#[test]
+fn sessions_append_and_reload() {
+    let mut sandbox = Sandbox::new("history-shared");
+    let mut other = another_shell(&sandbox);
+    add(&mut sandbox, &["a1"]);
+    sandbox.run("history -a");
+    std::mem::swap(&mut sandbox.shell, &mut other);
+    add(&mut sandbox, &["b1"]);
+    sandbox.run("history -a");
+    sandbox.run("history -r");
+    assert_eq!(listed(&mut sandbox), ["b1", "a1", "b1"]);
+    std::mem::swap(&mut sandbox.shell, &mut other);
+    add(&mut sandbox, &["a2"]);
+    sandbox.run("history -a");
+    assert_eq!(sandbox.read(".vssh_history"), "a1\nb1\na2\n");
+    sandbox.run("history -c; history -r");
+    assert_eq!(listed(&mut sandbox), ["a1", "b1", "a2"]);
+    assert_eq!(sandbox.run("history -a; history -a").status, 0);
+    assert_eq!(sandbox.read(".vssh_history"), "a1\nb1\na2\n");
+}
+
+#[test]
+fn history_file_errors() {
+    let mut sandbox = Sandbox::new("history-errors");
+    let output = sandbox.run("HISTFILE=missing/file; history -r");
+    assert_eq!(output.status, 1);
+    assert_eq!(
+        output.stderr,
+        "vssh: history: missing/file: No such file or directory\n"
+    );
+    let output = sandbox.run("history -x");
+    assert_eq!(output.status, 2);
+    assert_eq!(sandbox.run("HISTFILE=; history -w").status, 0);
+}
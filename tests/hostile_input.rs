@@ -0,0 +1,73 @@
+mod common;
+
+use common::{vssh, Sandbox};
+
+#[test]
+fn nul_bytes_in_arguments_are_errors() {
+    let mut sandbox = Sandbox::new("hostile-nul");
+    let output = sandbox.run("/bin/echo $'a\\x00b'; echo \"status $?\"");
+    assert_eq!(output.stdout, "status 1\n");
+    assert_eq!(
+        output.stderr,
+        "vssh: /bin/echo: argument contains null byte\n"
+    );
+    let output = sandbox.run("/bin/echo \"$(printf 'a\\0b')\"");
+    assert_eq!(output.status, 1);
+    assert!(output.stderr.contains("argument contains null byte"));
+}
+
+#[test]
+fn invalid_utf8_reaches_commands_untouched() {
+    let output = vssh(
+        &[],
+        &[],
+        b"printf '%s' \xff\xfe | od -An -tx1\n/bin/echo caf\xe9 | od -An -tx1\n",
+    );
+    assert_eq!(output.status, 0, "{}", output.stderr);
+    let lines: Vec<&str> = output.stdout.lines().map(str::trim).collect();
+    assert_eq!(lines, ["ff fe", "63 61 66 e9 0a"]);
+}
+
+#[test]
+fn nul_bytes_in_scripts_do_not_stop_the_shell() {
+    let output = vssh(&[], &[], b"echo a\0b\necho next\n");
+    assert_eq!(output.status, 0, "{}", output.stderr);
+    assert!(output.stdout.ends_with("next\n"), "{:?}", output.stdout);
+}
+
+#[test]
+fn huge_lines_are_rejected_and_skipped() {
+    let mut script = format!("echo {}\n", "x".repeat(5 << 20));
+    script.push_str("echo next\n");
+    let output = vssh(&[], &[], script);
+    assert_eq!(output.stdout, "next\n");
+    assert!(
+        output.stderr.contains("input line too long"),
+        "{}",
+        output.stderr
+    );
+}
+
+#[test]
+fn deep_nesting_is_an_error_not_a_crash() {
+    for script in [
+        "(".repeat(100_000),
+        "$(".repeat(100_000),
+        "if true; then ".repeat(100_000),
+        format!(": {}y{}", "${x:-".repeat(5_000), "}".repeat(5_000)),
+        format!("echo $(({}1{}))", "(".repeat(100_000), ")".repeat(100_000)),
+    ] {
+        let output = vssh(&[], &[], format!("{}\n", script));
+        assert!(
+            output.stderr.contains("nested too deeply"),
+            "{}: {}",
+            &script[..20],
+            output.stderr
+        );
+        assert!(
+            output.status == 1 || output.status == 2,
+            "{}",
+            output.status
+        );
+    }
+}
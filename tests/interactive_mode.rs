@@ -0,0 +1,24 @@
+mod common;
+
+use common::vssh;
+
+#[test]
+fn piped_input_gets_no_prompts_or_chatter() {
+    let input = "echo hi\nsh -c 'exit 3'\nsleep 0.1 &\nwait\nyes | head -1\necho $?\n";
+    let output = vssh(&[], &[("PS1", "$ "), ("PS2", "> ")], input);
+    assert_eq!(output.stdout, "hi\ny\n0\n");
+    assert_eq!(output.stderr, "");
+    let output = vssh(&[], &[("PS1", "$ ")], "if true\nthen echo more\nfi\n");
+    assert_eq!(output.stdout, "more\n");
+}
+
+#[test]
+fn dash_i_forces_interactive_mode() {
+    let output = vssh(&["-i"], &[("PS1", "$ ")], "echo hi\nsh -c 'exit 3'\n");
+    assert_eq!(output.stdout, "$ hi\n$ $ exit\n");
+    assert!(
+        output.stderr.contains("exited with status 3"),
+        "{}",
+        output.stderr
+    );
+}
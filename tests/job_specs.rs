@@ -0,0 +1,174 @@
+mod common;
+
+use common::Sandbox;
+
+const SLEEPERS: &str = "sleep 101 & sleep 102 & sleep 103 &";
+
+fn states(sandbox: &mut Sandbox) -> Vec<String> {
+    sandbox
+        .stdout("jobs")
+        .lines()
+        .map(|line| {
+            line.split_whitespace()
+                .take(3)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+fn clean_up(sandbox: &mut Sandbox) {
+    for job in &sandbox.shell.jobs {
+        for pid in job.pids() {
+            unsafe { libc::kill(pid.as_raw(), libc::SIGKILL) };
+        }
+    }
+    sandbox.run("wait");
+}
+
+#[test]
+fn jobs_marks_current_and_previous() {
+    let mut sandbox = Sandbox::new("jobspec-marks");
+    sandbox.run(SLEEPERS);
+    assert_eq!(
+        states(&mut sandbox),
+        [
+            "[1] Running sleep",
+            "[2]- Running sleep",
+            "[3]+ Running sleep"
+        ]
+    );
+    sandbox.run("kill -STOP %1; sleep 0.2");
+    assert_eq!(
+        states(&mut sandbox),
+        [
+            "[1]+ Stopped sleep",
+            "[2] Running sleep",
+            "[3]- Running sleep"
+        ]
+    );
+    sandbox.run("kill -STOP %2; sleep 0.2");
+    assert_eq!(
+        states(&mut sandbox),
+        [
+            "[1]- Stopped sleep",
+            "[2]+ Stopped sleep",
+            "[3] Running sleep"
+        ]
+    );
+    clean_up(&mut sandbox);
+}
+
+#[test]
+fn kill_takes_every_spec_form() {
+    let mut sandbox = Sandbox::new("jobspec-kill");
+    for (spec, killed) in [
+        ("%1", 1),
+        ("%3", 3),
+        ("%+", 3),
+        ("%%", 3),
+        ("%-", 2),
+        ("'%sleep 101'", 1),
+        ("%?102", 2),
+        ("%?03", 3),
+    ] {
+        sandbox.run(SLEEPERS);
+        let output = sandbox.run(&format!("kill {}; sleep 0.2", spec));
+        assert_eq!(output.status, 0, "{}: {:?}", spec, output);
+        let states = states(&mut sandbox);
+        for (index, state) in states.iter().enumerate() {
+            assert_eq!(
+                state.contains("Running"),
+                index + 1 != killed,
+                "{}: {:?}",
+                spec,
+                states
+            );
+        }
+        clean_up(&mut sandbox);
+    }
+}
+
+#[test]
+fn fg_takes_every_spec_form() {
+    let mut sandbox = Sandbox::new("jobspec-fg");
+    for (spec, status) in [
+        ("", 32),
+        ("%1", 31),
+        ("%2", 32),
+        ("%+", 32),
+        ("%%", 32),
+        ("%-", 31),
+        ("'%sh -c \"sleep 0.5; exit 31'", 31),
+        ("%?32", 32),
+    ] {
+        sandbox.run("sh -c \"sleep 0.5; exit 31\" & sh -c \"sleep 0.5; exit 32\" &");
+        let output = sandbox.run(&format!("fg {}", spec));
+        assert_eq!(output.status, status, "{}: {:?}", spec, output);
+        assert_eq!(
+            output.stdout,
+            format!("sh -c \"sleep 0.5; exit {}\"\n", status),
+            "{}",
+            spec
+        );
+        assert_eq!(sandbox.shell.jobs.len(), 1, "{}", spec);
+        clean_up(&mut sandbox);
+    }
+}
+
+#[test]
+fn unresolved_specs_are_errors() {
+    let mut sandbox = Sandbox::new("jobspec-errors");
+    // wait reports an unknown job the way it reports an unknown pid.
+    let failed = |builtin| if builtin == "wait" { 127 } else { 1 };
+    for builtin in ["fg", "bg", "kill", "wait", "disown"] {
+        let output = sandbox.run(&format!("{} %1", builtin));
+        assert_eq!(output.status, failed(builtin), "{}", builtin);
+        assert_eq!(
+            output.stderr,
+            format!("vssh: {}: %1: no such job\n", builtin)
+        );
+    }
+    let output = sandbox.run("fg");
+    assert_eq!(output.stderr, "vssh: fg: current: no such job\n");
+    sandbox.run(SLEEPERS);
+    for builtin in ["fg", "bg", "kill", "wait", "disown"] {
+        for (spec, message) in [
+            ("%sleep", "ambiguous job spec"),
+            ("%?10", "ambiguous job spec"),
+            ("%vim", "no such job"),
+            ("%?vim", "no such job"),
+            ("%4", "no such job"),
+        ] {
+            let output = sandbox.run(&format!("{} {}", builtin, spec));
+            assert_eq!(output.status, failed(builtin), "{} {}", builtin, spec);
+            assert_eq!(
+                output.stderr,
+                format!("vssh: {}: {}: {}\n", builtin, spec, message)
+            );
+        }
+    }
+    assert_eq!(sandbox.shell.jobs.len(), 3);
+    clean_up(&mut sandbox);
+}
+
+#[test]
+fn jobs_shows_how_each_job_ended() {
+    let mut sandbox = Sandbox::new("jobspec-ended");
+    sandbox.run("sleep 101 & sh -c 'exit 143' & sleep 103 & sleep 104 &");
+    sandbox.run("kill %1; kill -STOP %3; sleep 0.2");
+    let listing = sandbox.stdout("jobs");
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            format!("[1]   {:<24}sleep 101", "Terminated"),
+            format!("[2]   {:<24}sh -c 'exit 143'", "Exit 143"),
+            format!("[3]+  {:<24}sleep 103", "Stopped"),
+            format!("[4]-  {:<24}sleep 104 &", "Running"),
+        ],
+        "{}",
+        listing
+    );
+    clean_up(&mut sandbox);
+}
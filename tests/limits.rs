@@ -0,0 +1,91 @@
+mod common;
+
+use common::{vssh, Sandbox};
+
+// Embedded shells run on the caller's thread, whose stack may end a
+// recursion before the configured limit does; the binary's main thread
+// reaches the limit itself.
+fn assert_limited(stderr: &str, message: &str) {
+    let (prefix, _) = message.rsplit_once('(').unwrap();
+    assert!(
+        stderr.starts_with(prefix) && stderr.ends_with(")\n") && stderr.lines().count() == 1,
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn self_recursive_functions() {
+    let line = "f() { f; }; f; echo \"status $?\"";
+    let message = "vssh: f: maximum function nesting level exceeded (200)\n";
+    let output = vssh(&["-c", line, "vssh"], &[], "");
+    assert_eq!(output.stdout, "status 1\n");
+    assert_eq!(output.stderr, message);
+    let mut sandbox = Sandbox::new("limits-function");
+    let output = sandbox.run(line);
+    assert_eq!(output.stdout, "status 1\n");
+    assert_limited(&output.stderr, message);
+    let output = sandbox.run("FUNCNEST=10; n=0; g() { n=$((n + 1)); g; }; g; echo $n");
+    assert_eq!(output.stdout, "10\n");
+    assert_eq!(
+        output.stderr,
+        "vssh: g: maximum function nesting level exceeded (10)\n"
+    );
+    assert_eq!(sandbox.stdout("echo still here"), "still here\n");
+}
+
+#[test]
+fn mutually_recursive_functions() {
+    let line = "ping() { pong; }; pong() { ping; }; ping; echo $?";
+    let message = "vssh: ping: maximum function nesting level exceeded (200)\n";
+    let output = vssh(&["-c", line, "vssh"], &[], "");
+    assert_eq!(
+        (output.stdout.as_str(), output.stderr.as_str()),
+        ("1\n", message)
+    );
+    let mut sandbox = Sandbox::new("limits-mutual");
+    let output = sandbox.run(line);
+    assert_eq!(output.stdout, "1\n");
+    assert!(output
+        .stderr
+        .contains(": maximum function nesting level exceeded ("));
+    assert_eq!(output.stderr.lines().count(), 1, "{}", output.stderr);
+}
+
+#[test]
+fn self_sourcing_files() {
+    let mut sandbox = Sandbox::new("limits-source");
+    sandbox.write("self.sh", "n=$((n + 1))\nsource ./self.sh\n");
+    let output = sandbox.run("n=0; source ./self.sh; echo \"$? $n\"");
+    assert!(output.stdout.starts_with("1 "), "{}", output.stdout);
+    assert_limited(
+        &output.stderr,
+        "vssh: source: ./self.sh: maximum source nesting level exceeded (100)\n",
+    );
+    let script = sandbox.dir.join("abs.sh").display().to_string();
+    sandbox.write("abs.sh", &format!("n=$((n + 1))\nsource {}\n", script));
+    let line = format!("n=0; source {}; echo \"$? $n\"", script);
+    let output = vssh(&["-c", &line, "vssh"], &[], "");
+    assert_eq!(output.stdout, "1 100\n");
+}
+
+#[test]
+fn nested_command_substitutions() {
+    let nested = |depth| format!("echo {}x{}", "$(echo ".repeat(depth), ")".repeat(depth));
+    let mut sandbox = Sandbox::new("limits-substitution");
+    assert_eq!(sandbox.stdout(&nested(50)), "x\n");
+    let output = sandbox.run(&nested(200));
+    assert_ne!(output.stderr, "");
+    let output = vssh(&["-c", &nested(200), "vssh"], &[], "");
+    assert_eq!(
+        output.stderr,
+        "vssh: maximum command substitution nesting level exceeded (128)\n"
+    );
+    let output = sandbox.run("f() { echo $(f); }; f > /dev/null; echo done");
+    assert_eq!(output.stdout, "done\n");
+    assert!(
+        output.stderr.contains("nesting level exceeded"),
+        "{}",
+        output.stderr
+    );
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use common::{vssh, Sandbox};
+use vssh::embed::Config;
+use vssh::shell::Shell;
+
+fn another_shell(sandbox: &Sandbox) -> Shell {
+    Shell::with_config(Config {
+        env: Some(vec![
+            ("PATH".into(), std::env::var("PATH").unwrap_or_default()),
+            ("HOME".into(), sandbox.dir.display().to_string()),
+        ]),
+        cwd: Some(sandbox.dir.clone()),
+        ..Config::default()
+    })
+    .unwrap()
+}
+
+#[test]
+fn two_shells_race_for_a_lock() {
+    let mut sandbox = Sandbox::new("lock-race");
+    let mut other = another_shell(&sandbox);
+    assert_eq!(
+        sandbox.stdout("exec 9> build.lock; lock -x 9; echo held"),
+        "held\n"
+    );
+    std::mem::swap(&mut sandbox.shell, &mut other);
+    let started = Instant::now();
+    assert_eq!(
+        sandbox.stdout("lock -x -w 0.3 build.lock echo ran; echo $?"),
+        "73\n"
+    );
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert_eq!(
+        sandbox.stdout("lock -s -n build.lock echo ran; echo $?"),
+        "73\n"
+    );
+    std::mem::swap(&mut sandbox.shell, &mut other);
+    assert_eq!(sandbox.stdout("lock -s 9; echo >&9 still open"), "");
+    std::mem::swap(&mut sandbox.shell, &mut other);
+    assert_eq!(
+        sandbox.stdout("lock -s -n build.lock echo shared"),
+        "shared\n"
+    );
+    assert_eq!(
+        sandbox.stdout("lock -x -n build.lock echo ran; echo $?"),
+        "73\n"
+    );
+    std::mem::swap(&mut sandbox.shell, &mut other);
+    sandbox.run("lock -u 9");
+    std::mem::swap(&mut sandbox.shell, &mut other);
+    assert_eq!(sandbox.stdout("lock -x -n build.lock echo ran"), "ran\n");
+    std::mem::swap(&mut sandbox.shell, &mut other);
+    sandbox.run("exec 9>&-");
+    assert_eq!(sandbox.read("build.lock"), "still open\n");
+}
+
+#[test]
+fn file_locks_last_as_long_as_the_command() {
+    let mut sandbox = Sandbox::new("lock-command");
+    let output = sandbox.run(
+        "lock -x build.lock sleep 0.5 & sleep 0.2\n\
+         lock -n build.lock echo ran; echo $?\n\
+         wait; lock -n build.lock echo ran; echo $?",
+    );
+    assert_eq!(output.stdout, "73\nran\n0\n");
+    assert_eq!(
+        sandbox.stdout("lock build.lock sh -c 'exit 5'; echo $?"),
+        "5\n"
+    );
+}
+
+#[test]
+fn processes_take_turns() {
+    let sandbox = Sandbox::new("lock-processes");
+    sandbox.write("count", "0\n");
+    let script = format!(
+        "cd {}; i=0; while [ $i -lt 25 ]; do lock -x count.lock sh -c 'n=$(cat count); sleep 0.005; echo $((n + 1)) > count'; i=$((i + 1)); done\n",
+        sandbox.dir.display()
+    );
+    let children: Vec<_> = (0..2)
+        .map(|_| {
+            let mut child = Command::new(env!("CARGO_BIN_EXE_vssh"))
+                .args(["--norc", "--noprofile"])
+                .stdin(Stdio::piped())
+                .spawn()
+                .unwrap();
+            child
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(script.as_bytes())
+                .unwrap();
+            child
+        })
+        .collect();
+    for mut child in children {
+        assert!(child.wait().unwrap().success());
+    }
+    assert_eq!(sandbox.read("count"), "50\n");
+}
+
+#[test]
+fn interrupts_abort_the_wait() {
+    let sandbox = Sandbox::new("lock-interrupt");
+    let lock = sandbox.dir.join("build.lock").display().to_string();
+    let holder = std::fs::File::create(&lock).unwrap();
+    assert_eq!(
+        unsafe { libc::flock(std::os::fd::AsRawFd::as_raw_fd(&holder), libc::LOCK_EX) },
+        0
+    );
+    let mut child = Command::new(env!("CARGO_BIN_EXE_vssh"))
+        .args(["--norc", "--noprofile", "-i"])
+        .env("PS1", "")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(stdin, "lock -x {} echo ran; echo after", lock).unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+    unsafe { libc::kill(child.id() as i32, libc::SIGINT) };
+    std::thread::sleep(Duration::from_millis(100));
+    writeln!(stdin, "echo status $?").unwrap();
+    drop(stdin);
+    let started = Instant::now();
+    let output = child.wait_with_output().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "status 130\nexit\n"
+    );
+}
+
+#[test]
+fn lock_errors() {
+    let mut sandbox = Sandbox::new("lock-errors");
+    let usage =
+        "vssh: lock: usage: lock [-s|-x|-u] [-n] [-w seconds] (fd | file command [args...])\n";
+    for (line, status, message) in [
+        ("lock", 2, usage.to_string()),
+        ("lock build.lock", 2, usage.to_string()),
+        ("lock -q 9", 2, usage.to_string()),
+        ("lock -w", 2, usage.to_string()),
+        ("lock -u build.lock true", 2, usage.to_string()),
+        (
+            "lock -w soon build.lock true",
+            1,
+            "vssh: lock: soon: invalid timeout\n".to_string(),
+        ),
+        (
+            "lock -w -1 build.lock true",
+            1,
+            "vssh: lock: -1: invalid timeout\n".to_string(),
+        ),
+        (
+            "lock 57",
+            1,
+            format!("vssh: lock: 57: {}\n", nix::errno::Errno::EBADF.desc()),
+        ),
+        (
+            "lock gone/build.lock true",
+            1,
+            "vssh: lock: gone/build.lock: No such file or directory\n".to_string(),
+        ),
+    ] {
+        let output = sandbox.run(line);
+        assert_eq!(output.status, status, "{}", line);
+        assert_eq!(output.stderr, message, "{}", line);
+    }
+    let output = vssh(&["-c", "lock -n /dev/null true; echo $?"], &[], "");
+    assert_eq!(output.stdout, "0\n");
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{vssh, Sandbox};
+
+#[test]
+fn for_iterates_expanded_words() {
+    let mut sandbox = Sandbox::new("for-words");
+    sandbox.write("b.log", "");
+    sandbox.write("a.log", "");
+    sandbox.write("c.txt", "");
+    assert_eq!(
+        sandbox.stdout("x=two; for f in one $x *.log $(echo sub); do echo $f; done"),
+        "one\ntwo\na.log\nb.log\nsub\n"
+    );
+    assert_eq!(sandbox.stdout("for f in a b; do :; done; echo $f"), "b\n");
+}
+
+#[test]
+fn for_status_is_the_last_iteration() {
+    let mut sandbox = Sandbox::new("for-status");
+    assert_eq!(sandbox.run("for i in 1 2; do [ $i = 2 ]; done").status, 0);
+    assert_eq!(sandbox.run("for i in 1 2; do [ $i = 1 ]; done").status, 1);
+    assert_eq!(sandbox.run("false; for i in; do true; done").status, 0);
+    assert_eq!(
+        sandbox
+            .run("empty=; false; for i in $empty; do :; done")
+            .status,
+        0
+    );
+}
+
+#[test]
+fn for_without_in_uses_the_positional_parameters() {
+    let mut sandbox = Sandbox::new("for-positional");
+    assert_eq!(
+        sandbox.stdout("f() { for a; do echo \"<$a>\"; done; }; f x 'y z'"),
+        "<x>\n<y z>\n"
+    );
+}
+
+#[test]
+fn break_and_continue() {
+    let mut sandbox = Sandbox::new("for-break");
+    assert_eq!(
+        sandbox.stdout(
+            "for i in 1 2 3 4; do [ $i = 2 ] && continue; [ $i = 4 ] && break; echo $i; done"
+        ),
+        "1\n3\n"
+    );
+    assert_eq!(
+        sandbox.stdout(
+            "for i in 1 2; do for j in a b c; do [ $j = b ] && continue 2; echo $i$j; done; echo never; done"
+        ),
+        "1a\n2a\n"
+    );
+    assert_eq!(
+        sandbox
+            .stdout("for i in 1 2; do for j in a b; do echo $i$j; break 2; done; done; echo after"),
+        "1a\nafter\n"
+    );
+    assert_eq!(
+        sandbox.stdout("for i in 1 2 3; do while true; do break; done; echo $i; done"),
+        "1\n2\n3\n"
+    );
+}
+
+#[test]
+fn scripts_continue_loops_across_lines() {
+    let mut sandbox = Sandbox::new("for-lines");
+    let output = sandbox
+        .script("for i in 1 2 3\ndo\n  [ $i = 2 ] && continue\n  echo $i\ndone\necho done\n");
+    assert_eq!(output.stdout, "1\n3\ndone\n");
+}
+
+#[test]
+fn interactive_loops_prompt_with_ps2_until_done() {
+    let output = vssh(
+        &["-i"],
+        &[("PS1", "$ "), ("PS2", "> ")],
+        "for i in 1 2\ndo\n  echo $i\ndone\necho after\n",
+    );
+    assert_eq!(output.stdout, "$ > > > 1\n2\n$ after\n$ exit\n");
+}
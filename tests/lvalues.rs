@@ -0,0 +1,115 @@
+use vssh::embed::Config;
+use vssh::shell::Shell;
+
+// lastpipe keeps `... | read` in this shell, so the assignments stick.
+fn shell() -> Shell {
+    let mut shell = Shell::with_config(Config {
+        env: Some(Vec::new()),
+        ..Config::default()
+    })
+    .unwrap();
+    shell.options.set("lastpipe", true);
+    shell
+}
+
+fn elements(shell: &Shell, name: &str) -> Vec<(usize, String)> {
+    shell.vars.elements(name)
+}
+
+fn pairs(expected: &[(usize, &str)]) -> Vec<(usize, String)> {
+    expected.iter().map(|(i, v)| (*i, v.to_string())).collect()
+}
+
+#[test]
+fn printf_v_past_the_end_makes_a_sparse_array() {
+    let mut shell = shell();
+    shell
+        .run_line("arr=(a b); printf -v 'arr[9]' '%s-%d' x 7")
+        .unwrap();
+    assert_eq!(
+        elements(&shell, "arr"),
+        pairs(&[(0, "a"), (1, "b"), (9, "x-7")])
+    );
+}
+
+#[test]
+fn subscripts_are_arithmetic() {
+    let mut shell = shell();
+    shell.run_line("i=2; printf -v 'arr[i * 3 + 1]' v").unwrap();
+    assert_eq!(elements(&shell, "arr"), pairs(&[(7, "v")]));
+}
+
+#[test]
+fn read_into_an_element_keeps_the_others() {
+    let mut shell = shell();
+    shell
+        .run_line("arr=(a b c); echo new | read 'arr[5]'")
+        .unwrap();
+    assert_eq!(
+        elements(&shell, "arr"),
+        pairs(&[(0, "a"), (1, "b"), (2, "c"), (5, "new")])
+    );
+}
+
+#[test]
+fn read_a_replaces_the_array() {
+    let mut shell = shell();
+    shell
+        .run_line("arr=(x x x x); echo 'one  two three' | read -a arr")
+        .unwrap();
+    assert_eq!(
+        elements(&shell, "arr"),
+        pairs(&[(0, "one"), (1, "two"), (2, "three")])
+    );
+}
+
+#[test]
+fn mapfile_origin_leaves_a_gap() {
+    let mut shell = shell();
+    shell
+        .run_line("printf 'p\\nq\\n' | mapfile -t -O 5 lines")
+        .unwrap();
+    assert_eq!(elements(&shell, "lines"), pairs(&[(5, "p"), (6, "q")]));
+}
+
+#[test]
+fn negative_subscripts_count_from_the_end() {
+    let mut shell = shell();
+    shell
+        .run_line("arr=(a b c); printf -v 'arr[-1]' z")
+        .unwrap();
+    assert_eq!(
+        elements(&shell, "arr"),
+        pairs(&[(0, "a"), (1, "b"), (2, "z")])
+    );
+    assert_ne!(
+        shell.run_line("empty=(); printf -v 'empty[-1]' z").unwrap(),
+        0
+    );
+}
+
+#[test]
+fn readonly_targets_are_rejected() {
+    let mut shell = shell();
+    shell.run_line("arr=(a b); s=1; readonly arr s").unwrap();
+    for line in [
+        "s=2",
+        "printf -v s %s 2",
+        "printf -v 'arr[4]' %s 2",
+        "arr[0]=2",
+        "arr=(2)",
+        "echo 2 | read s",
+        "echo 2 | read -a arr",
+        "echo 2 | mapfile arr",
+        "(( s += 1 ))",
+        "for s in 2; do :; done",
+        "unset s",
+        "unset 'arr[1]'",
+        "export s=2",
+        "readonly s=2",
+    ] {
+        assert_eq!(shell.run_line(line).unwrap(), 1, "{}", line);
+    }
+    assert_eq!(shell.vars.get("s"), Some("1"));
+    assert_eq!(elements(&shell, "arr"), pairs(&[(0, "a"), (1, "b")]));
+}
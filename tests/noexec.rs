@@ -0,0 +1,128 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::vssh;
+
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("vssh-test-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        Scratch(dir)
+    }
+
+    fn file(&self, name: &str, contents: &str) -> String {
+        let path = self.0.join(name);
+        fs::write(&path, contents).unwrap();
+        path.display().to_string()
+    }
+
+    fn touched(&self) -> bool {
+        self.0.join("marker").exists()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+#[test]
+fn reports_the_line_of_the_error() {
+    let scratch = Scratch::new("noexec-line");
+    let script = scratch.file(
+        "script.sh",
+        &format!(
+            "touch {}/marker\n\
+             echo start\n\
+             if true; then\n\
+             \x20 echo inside\n\
+             fi\n\
+             # a comment\n\
+             echo done; fi\n\
+             echo after\n",
+            scratch.0.display()
+        ),
+    );
+    let output = vssh(&["-n", &script], &[], "");
+    assert_eq!(output.status, 2);
+    assert_eq!(output.stdout, "");
+    assert_eq!(
+        output.stderr,
+        format!(
+            "vssh: {}: line 7: syntax error near unexpected token `fi'\n",
+            script
+        )
+    );
+    assert!(!scratch.touched());
+}
+
+#[test]
+fn reports_every_error() {
+    let scratch = Scratch::new("noexec-every");
+    let script = scratch.file("script.sh", "ls )\necho ok\n| cat\nfor\n");
+    let output = vssh(&["-n", &script], &[], "");
+    assert_eq!(output.status, 2);
+    let lines: Vec<&str> = output
+        .stderr
+        .lines()
+        .map(|line| line.split(": ").nth(2).unwrap())
+        .collect();
+    assert_eq!(lines, ["line 1", "line 3", "line 4"], "{}", output.stderr);
+}
+
+#[test]
+fn valid_scripts_run_nothing() {
+    let scratch = Scratch::new("noexec-valid");
+    let script = scratch.file(
+        "script.sh",
+        &format!(
+            "touch {0}/marker\necho $(touch {0}/marker)\nx=$((n=1))\nexit 3\n",
+            scratch.0.display()
+        ),
+    );
+    let output = vssh(&["-n", &script], &[], "");
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, "");
+    assert_eq!(output.stderr, "");
+    assert!(!scratch.touched());
+}
+
+#[test]
+fn commands_and_standard_input() {
+    let output = vssh(&["-n", "-c", "echo a; echo b |"], &[], "");
+    assert_eq!(output.status, 2);
+    assert_eq!(
+        output.stderr,
+        "vssh: -c: line 1: syntax error: unexpected end of file\n"
+    );
+    let output = vssh(&["-n"], &[], "echo a\n\nwhile true; do\n");
+    assert_eq!(output.status, 2);
+    assert_eq!(
+        output.stderr,
+        "vssh: stdin: line 4: syntax error: unexpected end of file while looking for `done'\n"
+    );
+    let output = vssh(&["-n"], &[], "echo a\n");
+    assert_eq!((output.status, output.stdout.as_str()), (0, ""));
+}
+
+#[test]
+fn set_n_stops_execution() {
+    let scratch = Scratch::new("noexec-set");
+    let output = vssh(
+        &[],
+        &[],
+        format!(
+            "echo before\nset -n\necho after\ntouch {}/marker\nexit 3\n",
+            scratch.0.display()
+        ),
+    );
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, "before\n");
+    assert!(!scratch.touched());
+}
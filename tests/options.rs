@@ -0,0 +1,137 @@
+mod common;
+
+use common::Sandbox;
+use vssh::options::{Spec, OPTIONS};
+
+// What every listing form says about `spec` when it is `on`.
+fn assert_listed(sandbox: &mut Sandbox, spec: &Spec, on: bool) {
+    let name = spec.name;
+    let (sign, state) = if on { ('-', "on") } else { ('+', "off") };
+    let mut expected = Vec::new();
+    if spec.in_set() {
+        expected.push(("set -o", format!("{:<15}\t{}", name, state)));
+        expected.push(("set +o", format!("set {}o {}", sign, name)));
+        expected.push(("shopt -op", format!("set {}o {}", sign, name)));
+    }
+    if spec.in_shopt() {
+        expected.push(("shopt", format!("{:<15}\t{}", name, state)));
+        let flag = if on { "-s" } else { "-u" };
+        expected.push(("shopt -p", format!("shopt {} {}", flag, name)));
+    }
+    for (line, shown) in expected {
+        let listing = sandbox.stdout(line);
+        assert!(
+            listing.lines().any(|l| l == shown),
+            "{}: {:?} not in\n{}",
+            line,
+            shown,
+            listing
+        );
+    }
+    let query = match spec.in_shopt() {
+        true => format!("shopt -q {}", name),
+        false => format!("shopt -oq {}", name),
+    };
+    let output = sandbox.run(&query);
+    assert_eq!(output.status, if on { 0 } else { 1 }, "{}", query);
+    assert_eq!(output.stdout, "", "{}", query);
+}
+
+fn toggle(sandbox: &mut Sandbox, spec: &Spec, on: &str, off: &str) {
+    for (line, expected) in [(on, true), (off, false), (on, true), (off, false)] {
+        let output = sandbox.run(line);
+        assert_eq!(output.status, 0, "{}: {:?}", line, output);
+        assert_eq!(sandbox.shell.options.get(spec.name), expected, "{}", line);
+        // noexec ends a non-interactive shell's input, so nothing after
+        // it runs, including the command to turn it back off.
+        if spec.name == "noexec" {
+            sandbox.shell.options.set(spec.name, false);
+            return;
+        }
+        assert_listed(sandbox, spec, expected);
+    }
+}
+
+#[test]
+fn every_option_toggles_through_each_interface() {
+    for spec in OPTIONS {
+        let name = spec.name;
+        let mut sandbox = Sandbox::new("options-toggle");
+        assert_eq!(sandbox.shell.options.get(name), spec.default, "{}", name);
+        if spec.name != "noexec" {
+            assert_listed(&mut sandbox, spec, spec.default);
+        }
+        if spec.in_set() {
+            let (on, off) = (format!("set -o {}", name), format!("set +o {}", name));
+            toggle(&mut sandbox, spec, &on, &off);
+            let (on, off) = (format!("shopt -os {}", name), format!("shopt -ou {}", name));
+            toggle(&mut sandbox, spec, &on, &off);
+            if let Some(flag) = spec.flag {
+                let (on, off) = (format!("set -{}", flag), format!("set +{}", flag));
+                toggle(&mut sandbox, spec, &on, &off);
+            }
+        } else {
+            let output = sandbox.run(&format!("set -o {}", name));
+            assert_eq!(output.status, 1, "{}", name);
+            assert_eq!(
+                output.stderr,
+                format!("vssh: set: {}: invalid option name\n", name)
+            );
+        }
+        if spec.in_shopt() {
+            let (on, off) = (format!("shopt -s {}", name), format!("shopt -u {}", name));
+            toggle(&mut sandbox, spec, &on, &off);
+        } else {
+            let output = sandbox.run(&format!("shopt -s {}", name));
+            assert_eq!(output.status, 1, "{}", name);
+            assert_eq!(
+                output.stderr,
+                format!("vssh: shopt: {}: invalid shell option name\n", name)
+            );
+        }
+    }
+}
+
+#[test]
+fn unknown_options_fail() {
+    let mut sandbox = Sandbox::new("options-unknown");
+    for (line, status, message) in [
+        ("set -o bogus", 1, "vssh: set: bogus: invalid option name\n"),
+        ("set +o bogus", 1, "vssh: set: bogus: invalid option name\n"),
+        ("set -Q", 2, "vssh: set: -Q: invalid option\n"),
+        (
+            "shopt -s bogus",
+            1,
+            "vssh: shopt: bogus: invalid shell option name\n",
+        ),
+        (
+            "shopt -q bogus",
+            1,
+            "vssh: shopt: bogus: invalid shell option name\n",
+        ),
+    ] {
+        let output = sandbox.run(line);
+        assert_eq!(output.status, status, "{}", line);
+        assert_eq!(output.stderr, message, "{}", line);
+    }
+}
+
+#[test]
+fn editing_modes_exclude_each_other() {
+    let mut sandbox = Sandbox::new("options-editing");
+    sandbox.run("set -o vi");
+    assert!(sandbox.shell.options.get("vi") && !sandbox.shell.options.get("emacs"));
+    sandbox.run("set -o emacs");
+    assert!(!sandbox.shell.options.get("vi") && sandbox.shell.options.get("emacs"));
+}
+
+#[test]
+fn set_plus_o_output_restores_the_options() {
+    let mut sandbox = Sandbox::new("options-restore");
+    let saved = sandbox.stdout("set -o nounset; set -o xtrace; set +o");
+    sandbox.run("set +o nounset; set +o xtrace; set -o verbose");
+    sandbox.script(&saved);
+    assert!(sandbox.shell.options.get("nounset"));
+    assert!(sandbox.shell.options.get("xtrace"));
+    assert!(!sandbox.shell.options.get("verbose"));
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use common::{vssh, Sandbox};
+
+const STATES: [(&str, &str); 3] = [("set", "v=val"), ("empty", "v="), ("unset", "unset v")];
+
+// Expected expansion for `set`, `empty` and `unset`, or None for an error.
+const MATRIX: [(&str, [Option<&str>; 3]); 8] = [
+    (":-", [Some("val"), Some("d"), Some("d")]),
+    ("-", [Some("val"), Some(""), Some("d")]),
+    (":=", [Some("val"), Some("d"), Some("d")]),
+    ("=", [Some("val"), Some(""), Some("d")]),
+    (":?", [Some("val"), None, None]),
+    ("?", [Some("val"), Some(""), None]),
+    (":+", [Some("d"), Some(""), Some("")]),
+    ("+", [Some("d"), Some("d"), Some("")]),
+];
+
+fn check_matrix(sandbox: &mut Sandbox, setup: &str) {
+    for (op, expected) in MATRIX {
+        for ((state, assign), expected) in STATES.iter().zip(expected) {
+            let line = format!("{}; {}; echo \"<${{v{}d}}>\"", setup, assign, op);
+            let output = sandbox.run(&line);
+            match expected {
+                Some(text) => {
+                    assert_eq!(output.status, 0, "{} {}: {:?}", op, state, output);
+                    assert_eq!(output.stdout, format!("<{}>\n", text), "{} {}", op, state);
+                }
+                None => {
+                    assert_ne!(output.status, 0, "{} {}", op, state);
+                    assert_eq!(output.stdout, "", "{} {}", op, state);
+                    assert_eq!(output.stderr, "vssh: v: d\n", "{} {}", op, state);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn operators_over_set_empty_and_unset() {
+    let mut sandbox = Sandbox::new("param-matrix");
+    check_matrix(&mut sandbox, "set +u");
+}
+
+#[test]
+fn operators_suppress_nounset() {
+    let mut sandbox = Sandbox::new("param-nounset");
+    check_matrix(&mut sandbox, "set -u");
+    let output = sandbox.run("set -u; unset v; echo \"<$v>\"");
+    assert_ne!(output.status, 0);
+    assert_eq!(output.stderr, "vssh: v: unbound variable\n");
+    assert_eq!(sandbox.stdout("set -u; v=; echo \"<$v>\""), "<>\n");
+}
+
+#[test]
+fn assigning_forms_store_the_default() {
+    let mut sandbox = Sandbox::new("param-assign");
+    for (op, assign, stored) in [
+        (":=", "unset v", "d"),
+        (":=", "v=", "d"),
+        ("=", "unset v", "d"),
+        ("=", "v=", ""),
+        (":=", "v=val", "val"),
+    ] {
+        let line = format!("{}; : ${{v{}d}}; echo \"<$v>\"", assign, op);
+        assert_eq!(sandbox.stdout(&line), format!("<{}>\n", stored), "{}", line);
+    }
+    let output = sandbox.run(": ${1:=x}");
+    assert_eq!(output.stderr, "vssh: $1: cannot assign in this way\n");
+}
+
+#[test]
+fn the_right_hand_side_is_expanded() {
+    let mut sandbox = Sandbox::new("param-rhs");
+    assert_eq!(
+        sandbox.stdout("unset v; d=fallback; echo ${v:-$d-$(echo sub)}"),
+        "fallback-sub\n"
+    );
+    assert_eq!(sandbox.stdout("v=1; echo ${v:+\"a  b\"}"), "a  b\n");
+    assert_eq!(
+        sandbox.stdout("unset v; set -- ${v:-a b c}; echo $#"),
+        "3\n"
+    );
+    assert_eq!(
+        sandbox.stdout("unset v; set -- \"${v:-a b c}\"; echo $#"),
+        "1\n"
+    );
+    let output = sandbox.run("unset v; m=custom; echo ${v:?$m message}");
+    assert_eq!(output.stderr, "vssh: v: custom message\n");
+    let output = sandbox.run("unset v; echo ${v:?}");
+    assert_eq!(output.stderr, "vssh: v: parameter null or not set\n");
+}
+
+#[test]
+fn a_failed_check_exits_a_script() {
+    let output = vssh(
+        &[],
+        &[],
+        "echo before\nunset v\necho ${v:?gone}\necho after\n",
+    );
+    assert_ne!(output.status, 0);
+    assert_eq!(output.stdout, "before\n");
+    assert!(output.stderr.contains("v: gone"), "{}", output.stderr);
+}
+
+#[test]
+fn ifs_splits_unquoted_expansions() {
+    let mut sandbox = Sandbox::new("param-ifs");
+    assert_eq!(
+        sandbox
+            .stdout("PATH=/bin:/usr/bin::/opt; IFS=:; for dir in $PATH; do echo \"<$dir>\"; done"),
+        "</bin>\n</usr/bin>\n<>\n</opt>\n"
+    );
+    assert_eq!(sandbox.stdout("v='a  b'; IFS=; set -- $v; echo $#"), "1\n");
+    assert_eq!(
+        sandbox.stdout("empty=; IFS=; set -- $empty; echo $#"),
+        "0\n"
+    );
+    assert_eq!(
+        sandbox.stdout("empty=; IFS=; set -- $empty$empty \"$empty\" x$empty; echo $#"),
+        "2\n"
+    );
+    sandbox.run("unset IFS");
+    assert_eq!(
+        sandbox.stdout("v='a  b'; unset IFS; set -- $v \"$v\"; echo $#"),
+        "3\n"
+    );
+    sandbox.write("x y", "");
+    assert_eq!(
+        sandbox.stdout("unset IFS; v='x*'; set -- $v; echo $#"),
+        "1\n"
+    );
+    assert_eq!(sandbox.stdout("v='x *'; set -- $v; echo $2"), "x y\n");
+}
+
+#[test]
+fn identity_variables_survive_an_empty_environment() {
+    let output = vssh(
+        &["-c", "sh -c 'for v in \"$USER\" \"$LOGNAME\" \"$HOSTNAME\"; do test -n \"$v\" || echo empty; done; echo $SHELL'"],
+        &[],
+        "",
+    );
+    assert_eq!(output.stderr, "");
+    assert_eq!(output.stdout, format!("{}\n", env!("CARGO_BIN_EXE_vssh")));
+    let output = vssh(
+        &["-c", "USER=me; SHELL=/bin/sh; sh -c 'echo $USER $SHELL'"],
+        &[],
+        "",
+    );
+    assert_eq!(output.stdout, "me /bin/sh\n");
+}
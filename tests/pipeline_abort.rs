@@ -0,0 +1,60 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use vssh::embed::Config;
+use vssh::error::ShellError;
+use vssh::shell::Shell;
+
+fn running(pid: &str) -> bool {
+    fs::read_to_string(format!("/proc/{}/stat", pid))
+        .map(|stat| !stat.contains(") Z "))
+        .unwrap_or(false)
+}
+
+#[test]
+fn fork_failure_kills_the_whole_pipeline_group() {
+    let dir = std::env::temp_dir().join(format!("vssh-abort-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let pidfile = dir.join("pid");
+    let mut shell = Shell::with_config(Config {
+        cwd: Some(dir.clone()),
+        ..Config::default()
+    })
+    .unwrap();
+    shell.options.set("monitor", true);
+    let watched = pidfile.clone();
+    let mut forks = 0;
+    shell.fork_hook = Some(Box::new(move |_| {
+        forks += 1;
+        if forks < 2 {
+            return Ok(());
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !fs::read_to_string(&watched).is_ok_and(|pid| pid.ends_with('\n')) {
+            assert!(Instant::now() < deadline, "first segment never started");
+            thread::sleep(Duration::from_millis(10));
+        }
+        Err(ShellError::Internal(
+            "fork: Resource temporarily unavailable".to_string(),
+        ))
+    }));
+
+    let started = Instant::now();
+    let status = shell.run_line("sh -c 'sleep 30 & echo $! > pid; wait' | cat");
+    assert!(!matches!(status, Ok(0)), "{:?}", status);
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    let grandchild = fs::read_to_string(&pidfile).unwrap();
+    let grandchild = grandchild.trim();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while running(grandchild) && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(
+        !running(grandchild),
+        "sleep {} outlived the pipeline",
+        grandchild
+    );
+    fs::remove_dir_all(&dir).ok();
+}
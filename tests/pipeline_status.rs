@@ -0,0 +1,70 @@
+mod common;
+
+use common::vssh;
+
+// "Pipeline 12 | 13 exited..." with the pids replaced by `N`.
+fn without_pids(stderr: &str) -> String {
+    stderr
+        .lines()
+        .map(|line| {
+            let (kind, rest) = line.split_once(' ').unwrap();
+            let at = rest.find(|c: char| c.is_ascii_alphabetic()).unwrap();
+            let (pids, report) = rest.split_at(at);
+            let pids: Vec<&str> = pids.split(" | ").map(|_| "N").collect();
+            format!("{} {} {}\n", kind, pids.join(" | "), report)
+        })
+        .collect()
+}
+
+#[test]
+fn closed_pipes_are_not_failures() {
+    let output = vssh(&[], &[], "yes | head -3\necho ${PIPESTATUS[@]}\n");
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, "y\ny\ny\n141 0\n");
+    assert_eq!(output.stderr, "");
+    let output = vssh(&["-c", "yes | head -3"], &[], "");
+    assert_eq!((output.status, output.stderr.as_str()), (0, ""));
+}
+
+#[test]
+fn interactive_pipelines_report_once() {
+    let output = vssh(&["-i"], &[("PS1", "")], "yes | head -3\n");
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, "y\ny\ny\nexit\n");
+    assert_eq!(
+        without_pids(&output.stderr),
+        "Pipeline N | N exited with status 0\n"
+    );
+    for (line, report) in [
+        (
+            "true | true | false",
+            "Pipeline N | N | N exited with status 1\n",
+        ),
+        (
+            "sh -c 'kill -TERM $$' | true | true",
+            "Pipeline N | N | N exited with status 0 (segment 1 killed by signal SIGTERM)\n",
+        ),
+        (
+            "true | sh -c 'kill -TERM $$'",
+            "Pipeline N | N was killed by signal SIGTERM, core dumped: false\n",
+        ),
+    ] {
+        let output = vssh(&["-i"], &[("PS1", "")], format!("{}\n", line));
+        assert_eq!(without_pids(&output.stderr), report, "{}", line);
+    }
+}
+
+#[test]
+fn verbose_status_reports_each_segment() {
+    let output = vssh(
+        &["-i"],
+        &[("PS1", "")],
+        "set -o verbose-status\nyes | head -1\n",
+    );
+    assert_eq!(output.stdout, "y\nexit\n");
+    assert_eq!(
+        without_pids(&output.stderr),
+        "Process N was killed by signal SIGPIPE, core dumped: false\n\
+         Process N exited with status 0\n"
+    );
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{vssh, Sandbox};
+
+const PS1: &str = "\\?\\$ ";
+
+fn prompt_after(sandbox: &mut Sandbox, line: &str) -> String {
+    sandbox.run(line);
+    sandbox.shell.render_prompt(PS1)
+}
+
+#[test]
+fn failures_show_their_status() {
+    let mut sandbox = Sandbox::new("prompt-status");
+    let symbol = sandbox.shell.render_prompt("\\$");
+    for (line, segment) in [
+        ("true", ""),
+        ("false", "[1]"),
+        ("(exit 3)", "[3]"),
+        ("sh -c 'exit 130'", "[130]"),
+        ("sh -c 'kill -INT $$'", "[SIGINT]"),
+        ("sh -c 'kill -TERM $$'", "[SIGTERM]"),
+        ("true | false", "[1]"),
+        ("false | true", ""),
+        ("false; true", ""),
+        ("true; false", "[1]"),
+        ("false || true", ""),
+        ("no-such-command-here", "[127]"),
+        ("echo ok", ""),
+    ] {
+        assert_eq!(
+            prompt_after(&mut sandbox, line),
+            format!("{}{} ", segment, symbol),
+            "{}",
+            line
+        );
+    }
+}
+
+#[test]
+fn later_shell_work_keeps_the_status() {
+    let mut sandbox = Sandbox::new("prompt-status-kept");
+    let symbol = sandbox.shell.render_prompt("\\$");
+    sandbox.run("trap 'false' DEBUG");
+    assert_eq!(prompt_after(&mut sandbox, "true"), format!("{} ", symbol));
+    sandbox.run("trap - DEBUG; trap 'true' ERR");
+    assert_eq!(
+        prompt_after(&mut sandbox, "false"),
+        format!("[1]{} ", symbol)
+    );
+    sandbox.run("trap - ERR");
+    sandbox.run("(sleep 0.1; exit 4) &");
+    assert_eq!(
+        prompt_after(&mut sandbox, "sh -c 'exit 2'"),
+        format!("[2]{} ", symbol)
+    );
+    std::thread::sleep(Duration::from_millis(300));
+    sandbox.shell.notify_jobs();
+    assert_eq!(sandbox.shell.render_prompt(PS1), format!("[2]{} ", symbol));
+    assert_eq!(
+        sandbox.shell.render_prompt("\\w \\?\\$ "),
+        format!("~ [2]{} ", symbol)
+    );
+}
+
+#[test]
+fn the_default_prompt_shows_the_status() {
+    let home = std::env::temp_dir().display().to_string();
+    let output = vssh(&["-i"], &[], "false\nsh -c 'kill -INT $$'\ntrue\n");
+    assert_eq!(
+        output.stdout,
+        format!(
+            "{home}$ {home} [1]$ {home} [SIGINT]$ {home}$ exit\n",
+            home = home
+        )
+    );
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use common::Sandbox;
+
+// Every stage would leave a marker file if it were spawned.
+fn assert_nothing_ran(sandbox: &mut Sandbox, line: &str, message: &str) {
+    let output = sandbox.run(&format!("{}; echo $?", line));
+    assert_eq!(output.stdout, "1\n", "{}", line);
+    assert_eq!(output.stderr, format!("vssh: {}\n", message), "{}", line);
+    for marker in ["first", "last"] {
+        assert!(
+            !sandbox.dir.join(marker).exists(),
+            "{}: {} ran",
+            line,
+            marker
+        );
+    }
+}
+
+#[test]
+fn missing_input_file() {
+    let mut sandbox = Sandbox::new("redirect-missing");
+    assert_nothing_ran(
+        &mut sandbox,
+        "sort < missing.txt | touch last",
+        "missing.txt: No such file or directory",
+    );
+    assert_nothing_ran(
+        &mut sandbox,
+        "touch first | sort < missing.txt",
+        "missing.txt: No such file or directory",
+    );
+    assert_nothing_ran(
+        &mut sandbox,
+        "touch first | cat < missing.txt | touch last",
+        "missing.txt: No such file or directory",
+    );
+}
+
+#[test]
+fn output_directory_missing_or_unwritable() {
+    let mut sandbox = Sandbox::new("redirect-unwritable");
+    assert_nothing_ran(
+        &mut sandbox,
+        "touch first | echo x > gone/out",
+        "gone/out: No such file or directory",
+    );
+    sandbox.write("plain", "");
+    assert_nothing_ran(
+        &mut sandbox,
+        "echo x > plain/out | touch last",
+        "plain/out: Not a directory",
+    );
+    // root writes through directory permissions.
+    if unsafe { libc::geteuid() } != 0 {
+        fs::create_dir(sandbox.dir.join("locked")).unwrap();
+        fs::set_permissions(
+            sandbox.dir.join("locked"),
+            fs::Permissions::from_mode(0o555),
+        )
+        .unwrap();
+        assert_nothing_ran(
+            &mut sandbox,
+            "touch first | echo x > locked/out | touch last",
+            "locked/out: Permission denied",
+        );
+    }
+}
+
+#[test]
+fn output_target_is_a_directory() {
+    let mut sandbox = Sandbox::new("redirect-directory");
+    fs::create_dir(sandbox.dir.join("dir")).unwrap();
+    assert_nothing_ran(&mut sandbox, "echo x > dir", "dir: Is a directory");
+    assert_nothing_ran(
+        &mut sandbox,
+        "touch first | echo x >> dir | touch last",
+        "dir: Is a directory",
+    );
+}
+
+#[test]
+fn opened_files_reach_the_right_stage() {
+    let mut sandbox = Sandbox::new("redirect-stages");
+    sandbox.write("in", "b\na\n");
+    let output = sandbox.run("sort < in | tr a-z A-Z > out 2> err; cat out");
+    assert_eq!(output.stdout, "A\nB\n");
+    assert_eq!(sandbox.read("err"), "");
+}
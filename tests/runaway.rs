@@ -0,0 +1,77 @@
+use std::fs::File;
+use std::io::Read;
+use std::os::fd::OwnedFd;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use nix::sys::signal::{kill, Signal};
+use nix::sys::wait::waitpid;
+use nix::unistd::pipe;
+
+use vssh::embed::Config;
+use vssh::shell::Shell;
+
+// Attached stderr is dup2()ed over the process's fd 2 while a line runs.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn run(limits: &[(&str, &str)], line: &str) -> (Shell, OwnedFd, Duration) {
+    let (read_end, write_end) = pipe().unwrap();
+    let mut shell = Shell::with_config(Config {
+        stderr: Some(write_end),
+        ..Config::default()
+    })
+    .unwrap();
+    for (name, value) in limits {
+        shell.vars.set(name, *value);
+    }
+    let started = Instant::now();
+    let result = shell.run_line(line);
+    let elapsed = started.elapsed();
+    assert!(result.is_ok_and(|status| status != 0));
+    shell.attached.clear();
+    (shell, read_end, elapsed)
+}
+
+fn drain(read_end: OwnedFd) -> String {
+    let mut text = String::new();
+    File::from(read_end).read_to_string(&mut text).unwrap();
+    text
+}
+
+#[test]
+fn background_loop_is_halted_and_blames_the_job() {
+    let _serial = SERIAL.lock().unwrap();
+    let (shell, stderr, elapsed) = run(
+        &[("VSSH_MAX_JOBS", "5")],
+        "i=0; while /usr/bin/test $i -lt 100; do sleep 10 & i=$((i + 1)); done; echo never",
+    );
+    for job in &shell.jobs {
+        for pid in job.pids() {
+            let _ = kill(pid, Signal::SIGKILL);
+            let _ = waitpid(pid, None);
+        }
+    }
+    let stderr = drain(stderr);
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    assert!(
+        stderr.contains("runaway process creation in `sleep 10'"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("halted"), "{}", stderr);
+    assert_eq!(shell.vars.get("i"), Some("5"));
+}
+
+#[test]
+fn fork_storm_is_halted() {
+    let _serial = SERIAL.lock().unwrap();
+    let (shell, stderr, elapsed) = run(&[("VSSH_MAX_FORKS", "20")], "while :; do /bin/true; done");
+    let stderr = drain(stderr);
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    assert!(
+        stderr.contains("runaway process creation in `/bin/true'"),
+        "{}",
+        stderr
+    );
+    assert!(shell.jobs.is_empty());
+}
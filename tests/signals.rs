@@ -0,0 +1,208 @@
+mod common;
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use nix::sys::signal::{kill, Signal};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+
+use common::vssh;
+use vssh::embed::Config;
+use vssh::shell::Shell;
+
+// Signal dispositions are process-wide, so these tests take turns.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+// Without job control, background jobs ignore SIGINT as POSIX requires.
+fn monitored() -> Shell {
+    let mut shell = Shell::with_config(Config::default()).unwrap();
+    shell.options.set("monitor", true);
+    shell
+}
+
+fn background(shell: &mut Shell, line: &str) -> Pid {
+    assert_eq!(shell.run_line(line).unwrap(), 0);
+    shell.last_background.unwrap()
+}
+
+#[test]
+fn background_sleep_dies_on_sigint_and_sigterm() {
+    let _serial = SERIAL.lock().unwrap();
+    let mut shell = monitored();
+    for signal in [Signal::SIGINT, Signal::SIGTERM] {
+        let pid = background(&mut shell, "sleep 100 &");
+        thread::sleep(Duration::from_millis(50));
+        kill(pid, signal).unwrap();
+        assert_eq!(
+            waitpid(pid, None).unwrap(),
+            WaitStatus::Signaled(pid, signal, false)
+        );
+    }
+}
+
+#[test]
+fn ignored_traps_are_inherited() {
+    let _serial = SERIAL.lock().unwrap();
+    let mut shell = monitored();
+    let pid = background(&mut shell, "trap '' INT; sleep 100 &");
+    thread::sleep(Duration::from_millis(50));
+    kill(pid, Signal::SIGINT).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(
+        waitpid(pid, Some(WaitPidFlag::WNOHANG)).unwrap(),
+        WaitStatus::StillAlive
+    );
+    kill(pid, Signal::SIGTERM).unwrap();
+    assert_eq!(
+        waitpid(pid, None).unwrap(),
+        WaitStatus::Signaled(pid, Signal::SIGTERM, false)
+    );
+
+    let pid = background(&mut shell, "trap - INT; sleep 100 &");
+    thread::sleep(Duration::from_millis(50));
+    kill(pid, Signal::SIGINT).unwrap();
+    assert_eq!(
+        waitpid(pid, None).unwrap(),
+        WaitStatus::Signaled(pid, Signal::SIGINT, false)
+    );
+}
+
+#[test]
+fn spawned_and_forked_children_keep_ignores() {
+    for mode in ["", "set -o spawn; "] {
+        for args in [&["-c"][..], &["-i", "-c"]] {
+            let line = format!(
+                "trap '' INT; {}sh -c 'kill -INT $$'; echo survived $?",
+                mode
+            );
+            let output = vssh(&[args, &[line.as_str()]].concat(), &[], "");
+            assert_eq!(output.status, 0, "{:?} {}", args, line);
+            assert_eq!(output.stdout, "survived 0\n", "{:?} {}", args, line);
+        }
+    }
+    let output = vssh(
+        &["-c", "set -o spawn; sh -c 'kill -TERM $$'; echo $?"],
+        &[],
+        "",
+    );
+    assert_eq!(output.stdout, "143\n");
+}
+
+#[test]
+fn scripts_outlive_children_killed_by_sigint() {
+    let output = vssh(&["-c", "sh -c 'kill -INT $$'; echo after $?"], &[], "");
+    assert_eq!((output.status, output.stdout.as_str()), (0, "after 130\n"));
+    let output = vssh(
+        &[
+            "-c",
+            "for i in 1 2 3; do sh -c 'kill -INT $$'; echo $i; done",
+        ],
+        &[],
+        "",
+    );
+    assert_eq!(output.stdout, "1\n2\n3\n");
+    let output = vssh(
+        &["-i"],
+        &[("PS1", "")],
+        "for i in 1 2 3; do sh -c 'kill -INT $$'; echo $i; done\necho next\n",
+    );
+    assert_eq!(output.stdout, "next\nexit\n");
+}
+
+#[test]
+fn trapped_signals_run_their_handlers() {
+    let output = vssh(
+        &[
+            "-c",
+            "trap 'echo caught INT $?' INT; trap 'echo caught USR1' USR1\n\
+             kill -INT $$; echo after\n\
+             false; kill -USR1 $$; echo status $?\n\
+             sh -c 'sleep 0.1; kill -TERM $PPID' & trap 'echo term' TERM; sleep 0.4; echo slept\n\
+             trap - USR1; trap '' TERM; kill -TERM $$; echo still here",
+        ],
+        &[],
+        "",
+    );
+    assert_eq!(output.status, 0, "{}", output.stderr);
+    assert_eq!(
+        output.stdout,
+        "caught INT 0\nafter\ncaught USR1\nstatus 0\nterm\nslept\nstill here\n"
+    );
+    let output = vssh(
+        &[
+            "-c",
+            "trap 'echo caught' USR1; trap - USR1; kill -USR1 $$; echo not reached",
+        ],
+        &[],
+        "",
+    );
+    assert_eq!(output.stdout, "");
+    assert_eq!(output.status, -1);
+    let output = vssh(&["-c", "trap 'echo no' KILL; echo $?"], &[], "");
+    assert_eq!(output.stdout, "1\n");
+    assert!(output.stderr.contains("trap: KILL: "), "{}", output.stderr);
+}
+
+#[test]
+fn children_do_not_inherit_handlers() {
+    let output = vssh(
+        &[
+            "-c",
+            "trap 'echo parent' TERM; sh -c 'kill -TERM $$'; echo $?; (sh -c 'kill -TERM $PPID'; echo not reached); echo $?",
+        ],
+        &[],
+        "",
+    );
+    assert_eq!(output.stdout, "143\n143\n");
+}
+
+#[test]
+fn err_fires_once_where_the_command_failed() {
+    let output = vssh(
+        &[
+            "-c",
+            "trap 'echo err $?' ERR\n\
+             f() { false; echo in f; false; }\n\
+             f; echo after\n\
+             g() { false; true; }; g\n\
+             if f; then :; fi; false || true; ! false\n\
+             set -E; f",
+        ],
+        &[],
+        "",
+    );
+    assert_eq!(
+        output.stdout,
+        "in f\nerr 1\nafter\nin f\nerr 1\nin f\nerr 1\nerr 1\n"
+    );
+}
+
+#[test]
+fn exit_trap_runs_once_on_exit() {
+    let mut shell = Shell::with_config(Config::default()).unwrap();
+    shell.run_line("n=0; trap 'n=$((n + 1))' EXIT").unwrap();
+    assert_eq!(shell.vars.get("n"), Some("0"));
+    assert_eq!(shell.run_line("exit 3").unwrap(), 3);
+    assert_eq!(shell.vars.get("n"), Some("1"));
+    shell.run_line("exit").unwrap();
+    assert_eq!(shell.vars.get("n"), Some("1"));
+}
+
+#[test]
+fn interactive_shells_ignore_sigquit() {
+    let output = vssh(
+        &["-i"],
+        &[("PS1", "")],
+        "kill -QUIT $$; echo alive\n\
+         sh -c 'kill -QUIT $$'; echo $?\n\
+         set -o spawn; sh -c 'kill -QUIT $$'; echo $?\n",
+    );
+    assert_eq!(output.stdout, "alive\n131\n131\nexit\n");
+    assert_eq!(output.status, 0);
+    // A non-interactive shell keeps the default and dies of it.
+    let output = vssh(&["-c", "kill -QUIT $$; echo alive"], &[], "");
+    assert_eq!(output.stdout, "");
+    assert_ne!(output.status, 0);
+}
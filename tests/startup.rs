@@ -0,0 +1,65 @@
+mod common;
+
+use common::{vssh, Sandbox};
+
+fn phases(stderr: &str) -> Vec<(&str, &str)> {
+    let mut lines = stderr.lines();
+    assert_eq!(lines.next(), Some("startup profile:"), "{}", stderr);
+    lines
+        .take_while(|line| line.starts_with("  "))
+        .map(|line| line.trim().split_once("  ").unwrap())
+        .map(|(phase, time)| (phase, time.trim()))
+        .collect()
+}
+
+#[test]
+fn profile_breaks_down_interactive_startup() {
+    let output = vssh(&["-i", "--profile-startup"], &[("PS1", "")], "echo hi\n");
+    assert_eq!(output.stdout, "hi\nexit\n");
+    let phases = phases(&output.stderr);
+    let names: Vec<&str> = phases.iter().map(|(phase, _)| *phase).collect();
+    assert_eq!(
+        names,
+        [
+            "shell",
+            "prompt setup",
+            "history",
+            "completion cache",
+            "directory env",
+            "total"
+        ]
+    );
+    for (phase, time) in phases {
+        match phase {
+            "history" | "completion cache" => assert_eq!(time, "deferred"),
+            _ => assert!(time.ends_with('s'), "{} {}", phase, time),
+        }
+    }
+}
+
+#[test]
+fn profile_of_a_command_string() {
+    let output = vssh(&["--profile-startup", "-c", "echo x"], &[], "");
+    assert_eq!(output.stdout, "x\n");
+    let names: Vec<&str> = phases(&output.stderr).iter().map(|(p, _)| *p).collect();
+    assert_eq!(names, ["shell", "total"]);
+    let output = vssh(&["-c", "echo x"], &[], "");
+    assert_eq!(output.stderr, "");
+}
+
+#[test]
+fn history_loads_on_first_use() {
+    let sandbox = Sandbox::new("startup-history");
+    sandbox.write("history", "old one\nold two\n");
+    let file = sandbox.dir.join("history").display().to_string();
+    let env = [("PS1", ""), ("HISTFILE", file.as_str())];
+    let output = vssh(&["-i"], &env, "echo new\nhistory\n");
+    assert_eq!(
+        output.stdout,
+        "new\n    1  old one\n    2  old two\n    3  echo new\n    4  history\nexit\n"
+    );
+    sandbox.write("history", "old one\nold two\n");
+    let output = vssh(&["-i"], &env, "echo new\n");
+    assert_eq!(output.stdout, "new\nexit\n");
+    assert_eq!(sandbox.read("history"), "old one\nold two\necho new\n");
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::{vssh, Sandbox};
+
+#[test]
+fn syntax_errors_run_nothing() {
+    let mut sandbox = Sandbox::new("syntax-nothing");
+    for line in [
+        "touch ran | | wc",
+        "| touch ran",
+        "touch ran; ; true",
+        "touch ran && && true",
+        "touch ran | && true",
+        "touch ran & &",
+    ] {
+        let output = sandbox.run(line);
+        assert_eq!(output.status, 2, "{}", line);
+        assert!(
+            output.stderr.contains("syntax error near unexpected token"),
+            "{}: {}",
+            line,
+            output.stderr
+        );
+        assert!(!sandbox.dir.join("ran").exists(), "{}", line);
+    }
+}
+
+#[test]
+fn interactive_errors_set_the_status_and_continue() {
+    let output = vssh(&["-i"], &[("PS1", "$ ")], "ls | | wc\necho $?\n");
+    assert_eq!(output.stdout, "$ $ 2\n$ exit\n");
+    assert!(output
+        .stderr
+        .contains("syntax error near unexpected token `|'"));
+}
+
+#[test]
+fn a_trailing_pipe_prompts_for_more() {
+    let output = vssh(
+        &["-i"],
+        &[("PS1", "$ "), ("PS2", "> ")],
+        "echo hi |\ntr a-z A-Z\n",
+    );
+    assert_eq!(output.stdout, "$ > HI\n$ exit\n");
+    assert!(!output.stderr.contains("syntax error"), "{}", output.stderr);
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use common::vssh_pty;
+
+fn flags(modes: &str) -> Vec<&str> {
+    modes
+        .split([' ', ';', '\n'])
+        .filter(|word| ["echo", "-echo", "icanon", "-icanon", "isig", "-isig"].contains(word))
+        .collect()
+}
+
+#[test]
+fn foreground_commands_cannot_keep_the_terminal_mangled() {
+    let dir = std::env::temp_dir().join(format!("vssh-test-termios-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let before = dir.join("before");
+    let after_exit = dir.join("exit");
+    let after_signal = dir.join("signal");
+    let input = format!(
+        "stty -a > {}\nstty raw -echo\nstty -a > {}\nsh -c 'stty -icanon -isig; kill -9 $$'\nstty -a > {}\nexit\n",
+        before.display(),
+        after_exit.display(),
+        after_signal.display(),
+    );
+    let output = vssh_pty(&["-i"], &[("PS1", "$ ")], &input);
+    assert_eq!(output.status, 0, "{}", output.stdout);
+    let before = std::fs::read_to_string(before).unwrap();
+    assert_eq!(flags(&before), ["isig", "icanon", "echo"]);
+    for file in [after_exit, after_signal] {
+        let modes = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(flags(&modes), flags(&before), "{}", file.display());
+    }
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,271 @@
+mod common;
+
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use common::{vssh, Sandbox};
+
+#[derive(Debug, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(fields) => fields.get(key).unwrap_or(&Json::Null),
+            _ => &Json::Null,
+        }
+    }
+
+    fn number(&self) -> f64 {
+        match self {
+            Json::Number(n) => *n,
+            other => panic!("not a number: {:?}", other),
+        }
+    }
+
+    fn str(&self) -> &str {
+        match self {
+            Json::String(s) => s,
+            other => panic!("not a string: {:?}", other),
+        }
+    }
+
+    fn strings(&self) -> Vec<&str> {
+        match self {
+            Json::Array(items) => items.iter().map(Json::str).collect(),
+            other => panic!("not an array: {:?}", other),
+        }
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Json {
+    while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+    match chars.next().expect("value expected") {
+        '{' => {
+            let mut fields = BTreeMap::new();
+            if chars.next_if_eq(&'}').is_some() {
+                return Json::Object(fields);
+            }
+            loop {
+                let Json::String(key) = parse_value(chars) else {
+                    panic!("key expected");
+                };
+                assert_eq!(chars.next(), Some(':'));
+                fields.insert(key, parse_value(chars));
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => return Json::Object(fields),
+                    other => panic!("unexpected {:?} in object", other),
+                }
+            }
+        }
+        '[' => {
+            let mut items = Vec::new();
+            if chars.next_if_eq(&']').is_some() {
+                return Json::Array(items);
+            }
+            loop {
+                items.push(parse_value(chars));
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => return Json::Array(items),
+                    other => panic!("unexpected {:?} in array", other),
+                }
+            }
+        }
+        '"' => {
+            let mut text = String::new();
+            loop {
+                match chars.next().expect("unterminated string") {
+                    '"' => return Json::String(text),
+                    '\\' => match chars.next().expect("unterminated escape") {
+                        'n' => text.push('\n'),
+                        'r' => text.push('\r'),
+                        't' => text.push('\t'),
+                        'u' => {
+                            let hex: String = chars.by_ref().take(4).collect();
+                            let code = u32::from_str_radix(&hex, 16).unwrap();
+                            text.push(char::from_u32(code).unwrap());
+                        }
+                        c => text.push(c),
+                    },
+                    c => {
+                        assert!(c >= ' ', "raw control character in string");
+                        text.push(c);
+                    }
+                }
+            }
+        }
+        c @ ('t' | 'f' | 'n') => {
+            let mut word = String::from(c);
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "true" => Json::Bool(true),
+                "false" => Json::Bool(false),
+                "null" => Json::Null,
+                other => panic!("unknown literal {:?}", other),
+            }
+        }
+        c => {
+            let mut number = String::from(c);
+            while let Some(c) = chars.next_if(|c| "+-.eE".contains(*c) || c.is_ascii_digit()) {
+                number.push(c);
+            }
+            Json::Number(number.parse().unwrap())
+        }
+    }
+}
+
+/// Every line of a trace file, each of which must be one whole object.
+fn records(text: &str) -> Vec<Json> {
+    text.lines()
+        .map(|line| {
+            let mut chars = line.chars().peekable();
+            let value = parse_value(&mut chars);
+            assert_eq!(chars.next(), None, "trailing data in {}", line);
+            assert!(matches!(value, Json::Object(_)), "{}", line);
+            value
+        })
+        .collect()
+}
+
+fn events<'a>(records: &'a [Json], event: &str) -> Vec<&'a Json> {
+    records
+        .iter()
+        .filter(|record| record.get("event").str() == event)
+        .collect()
+}
+
+#[test]
+fn pipelines_record_argv_and_statuses() {
+    let mut sandbox = Sandbox::new("trace-pipeline");
+    let path = sandbox.dir.join("trace.json").display().to_string();
+    sandbox.run(&format!("VSSH_TRACE_FILE={}", path));
+    let output = sandbox.run("echo 'a \"b\"' $HOME | tr a-z A-Z > out | sh -c 'exit 3'");
+    assert_eq!(output.status, 3);
+    let records = records(&sandbox.read("trace.json"));
+    let starts = events(&records, "start");
+    let ends = events(&records, "end");
+    assert_eq!(starts.len(), 3, "{:?}", records);
+    assert_eq!(ends.len(), 3, "{:?}", records);
+    let home = sandbox.dir.display().to_string();
+    assert_eq!(starts[0].get("argv").strings(), ["echo", "a \"b\"", &home]);
+    assert_eq!(starts[1].get("argv").strings(), ["tr", "a-z", "A-Z"]);
+    assert_eq!(starts[2].get("argv").strings(), ["sh", "-c", "exit 3"]);
+    let redirect = match starts[1].get("redirects") {
+        Json::Array(redirects) => &redirects[0],
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(redirect.get("fd").number(), 1.0);
+    assert_eq!(redirect.get("op").str(), ">");
+    assert_eq!(redirect.get("target").str(), "out");
+    let pipeline = starts[0].get("pipeline");
+    for (start, status) in starts.iter().zip([0.0, 0.0, 3.0]) {
+        assert_eq!(start.get("pipeline"), pipeline);
+        assert_eq!(start.get("background"), &Json::Bool(false));
+        assert_eq!(start.get("cwd").str(), home);
+        let id = start.get("id");
+        let end = ends.iter().find(|end| end.get("id") == id).unwrap();
+        assert_eq!(end.get("status").number(), status, "{:?}", id);
+        assert!(end.get("duration").number() >= 0.0);
+    }
+    let ids: Vec<f64> = starts
+        .iter()
+        .map(|start| start.get("id").number())
+        .collect();
+    assert!(ids.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", ids);
+}
+
+#[test]
+fn lists_and_background_jobs_get_their_own_pipelines() {
+    let mut sandbox = Sandbox::new("trace-lists");
+    let path = sandbox.dir.join("trace.json").display().to_string();
+    sandbox.run(&format!("VSSH_TRACE_FILE={}", path));
+    sandbox.run("true; false; sleep 0 & wait");
+    let records = records(&sandbox.read("trace.json"));
+    let starts = events(&records, "start");
+    let argv: Vec<&str> = starts.iter().map(|s| s.get("argv").strings()[0]).collect();
+    assert_eq!(argv, ["true", "false", "sleep", "wait"]);
+    let pipelines: Vec<f64> = starts.iter().map(|s| s.get("pipeline").number()).collect();
+    assert!(
+        pipelines.windows(2).all(|pair| pair[0] < pair[1]),
+        "{:?}",
+        pipelines
+    );
+    let background: Vec<&Json> = starts.iter().map(|s| s.get("background")).collect();
+    assert_eq!(
+        background,
+        [
+            &Json::Bool(false),
+            &Json::Bool(false),
+            &Json::Bool(true),
+            &Json::Bool(false)
+        ]
+    );
+    let statuses: Vec<f64> = events(&records, "end")
+        .iter()
+        .filter(|end| end.get("id") != starts[2].get("id"))
+        .map(|end| end.get("status").number())
+        .collect();
+    assert_eq!(statuses, [0.0, 1.0, 0.0]);
+}
+
+#[test]
+fn shells_share_a_trace_file() {
+    let sandbox = Sandbox::new("trace-shared");
+    let path = sandbox.dir.join("trace.json").display().to_string();
+    sandbox.write("trace.json", "");
+    for line in ["echo one", "echo two | cat"] {
+        let output = vssh(&["--trace-json", &path, "-c", line], &[], "");
+        assert_eq!(output.status, 0);
+    }
+    let output = vssh(&["-c", "echo three"], &[("VSSH_TRACE_FILE", &path)], "");
+    assert_eq!(output.stdout, "three\n");
+    let records = records(&sandbox.read("trace.json"));
+    let argv: Vec<Vec<&str>> = events(&records, "start")
+        .iter()
+        .map(|start| start.get("argv").strings())
+        .collect();
+    assert_eq!(
+        argv,
+        [
+            vec!["echo", "one"],
+            vec!["echo", "two"],
+            vec!["cat"],
+            vec!["echo", "three"]
+        ]
+    );
+    assert_eq!(events(&records, "end").len(), 4);
+}
+
+#[test]
+fn unwritable_trace_files_do_not_stop_commands() {
+    let output = vssh(
+        &[
+            "--trace-json",
+            "/nonexistent/trace.json",
+            "-c",
+            "echo ran; false; echo $?",
+        ],
+        &[],
+        "",
+    );
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, "ran\n1\n");
+    assert_eq!(
+        output.stderr.matches("tracing disabled").count(),
+        1,
+        "{}",
+        output.stderr
+    );
+}
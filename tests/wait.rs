@@ -0,0 +1,58 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::Sandbox;
+
+#[test]
+fn wait_n_drains_jobs_one_at_a_time() {
+    let mut sandbox = Sandbox::new("wait-drain");
+    let started = Instant::now();
+    let output = sandbox.run(
+        "for i in 1 2 3 4 5; do sleep 0.$i & done\n\
+         n=0; while wait -n; do n=$((n + 1)); done; echo $n",
+    );
+    assert_eq!(output.stdout, "5\n");
+    assert!(started.elapsed() >= Duration::from_millis(500));
+    assert!(sandbox.shell.jobs.is_empty());
+    let output = sandbox.run("wait -n");
+    assert_eq!(output.status, 127);
+}
+
+#[test]
+fn wait_n_returns_the_first_status() {
+    let mut sandbox = Sandbox::new("wait-first");
+    let output =
+        sandbox.run("sleep 0.4 & sh -c 'sleep 0.1; exit 9' & wait -n; echo $?; wait -n; echo $?");
+    assert_eq!(output.stdout, "9\n0\n");
+    let output = sandbox.run("(exit 5) & sleep 0.2; wait -n; echo $?");
+    assert_eq!(output.stdout, "5\n");
+}
+
+#[test]
+fn several_operands_return_the_last_status() {
+    let mut sandbox = Sandbox::new("wait-operands");
+    sandbox.run("sh -c 'sleep 0.2; exit 3' & a=$!; sh -c 'exit 4' & b=$!");
+    assert_eq!(sandbox.stdout("wait $a $b; echo $?"), "4\n");
+    sandbox.run("sh -c 'exit 3' & a=$!; sh -c 'sleep 0.2; exit 4' & b=$!");
+    assert_eq!(sandbox.stdout("wait $b $a; echo $?"), "3\n");
+    sandbox.run("sh -c 'exit 6' & sleep 0.1 & sh -c 'exit 7' &");
+    assert_eq!(sandbox.stdout("wait %1 %3 %2; echo $?"), "0\n");
+    assert!(sandbox.shell.jobs.is_empty());
+}
+
+#[test]
+fn finished_jobs_keep_their_status_until_waited_for() {
+    let mut sandbox = Sandbox::new("wait-finished");
+    sandbox.run("sh -c 'exit 3' & a=$!; sh -c 'exit 4' & b=$!; sleep 0.3");
+    assert_eq!(sandbox.shell.jobs.len(), 2);
+    assert_eq!(sandbox.stdout("wait $a; echo $?"), "3\n");
+    assert_eq!(sandbox.stdout("wait %2; echo $?"), "4\n");
+    let output = sandbox.run("wait $a; echo $?");
+    assert_eq!(output.stdout, "127\n");
+    assert!(
+        output.stderr.ends_with("is not a child of this shell\n"),
+        "{}",
+        output.stderr
+    );
+}